@@ -0,0 +1,6 @@
+fn main() {
+    // Firmware only treats an image as a driver if the PE header says so
+    if std::env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("uefi") {
+        println!("cargo:rustc-link-arg-bin=uefi-tpm2-driver=/subsystem:efi_boot_service_driver");
+    }
+}
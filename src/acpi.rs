@@ -0,0 +1,200 @@
+//! Just enough ACPI to find the `TPM2` table, which says how the firmware talks to the TPM. The
+//! tables are found through the RSDP in the system table's configuration table, preferring the
+//! XSDT (64-bit addresses) over the RSDT.
+
+use core::{fmt, slice};
+
+use uefi::{system, table::cfg::ConfigTableEntry};
+
+use crate::tpm::marshal::Reader;
+
+/// Signature, length, revision, checksum, OEM ID, OEM table ID, OEM revision, creator ID and
+/// creator revision
+const HEADER_SIZE: usize = 36;
+
+/// # Safety
+///
+/// `address` has to point at an ACPI table
+unsafe fn table_at(address: u64) -> Option<&'static [u8]> {
+    let address = address as usize as *const u8;
+    if address.is_null() {
+        return None;
+    }
+    // SAFETY: every table starts with the header, and its length covers the whole table
+    let header = unsafe { slice::from_raw_parts(address, HEADER_SIZE) };
+    let length = u32::from_le_bytes(header[4..8].try_into().unwrap()) as usize;
+    if length < HEADER_SIZE {
+        return None;
+    }
+    Some(unsafe { slice::from_raw_parts(address, length) })
+}
+
+fn rsdp() -> Option<(bool, *const u8)> {
+    system::with_config_table(|entries| {
+        let find = |guid| {
+            entries
+                .iter()
+                .find(|entry| entry.guid == guid)
+                .map(|entry| entry.address as *const u8)
+        };
+        find(ConfigTableEntry::ACPI2_GUID)
+            .map(|address| (true, address))
+            .or_else(|| find(ConfigTableEntry::ACPI_GUID).map(|address| (false, address)))
+    })
+}
+
+/// The first table with `signature`, including its header
+pub fn find_table(signature: &[u8; 4]) -> Option<&'static [u8]> {
+    let (acpi2, rsdp) = rsdp()?;
+    // SAFETY: the firmware put the RSDP there. Revision 2 and later are 36 bytes.
+    let rsdp = unsafe { slice::from_raw_parts(rsdp, if acpi2 { 36 } else { 20 }) };
+    if &rsdp[..8] != b"RSD PTR " {
+        return None;
+    }
+    let mut reader = Reader::new(&rsdp[16..]);
+    let rsdt_address = reader.u32_le().ok()?;
+    let xsdt_address = if acpi2 && rsdp[15] >= 2 {
+        reader.u32_le().ok()?;
+        reader.u64_le().ok()?
+    } else {
+        0
+    };
+    // SAFETY: the RSDP points at the root tables, and they point at the rest
+    let (root, entry_size) = match unsafe { table_at(xsdt_address) } {
+        Some(xsdt) => (xsdt, 8),
+        None => (unsafe { table_at(rsdt_address as u64) }?, 4),
+    };
+    let mut entries = Reader::new(&root[HEADER_SIZE..]);
+    while !entries.is_empty() {
+        let address = if entry_size == 8 {
+            entries.u64_le().ok()?
+        } else {
+            entries.u32_le().ok()? as u64
+        };
+        if let Some(table) = unsafe { table_at(address) }
+            && &table[..4] == signature
+        {
+            return Some(table);
+        }
+    }
+    None
+}
+
+/// How the firmware starts a command on the TPM
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StartMethod(pub u32);
+
+impl StartMethod {
+    pub const ACPI: Self = Self(2);
+    /// `TPM_TIS`/FIFO over memory-mapped I/O, which is how discrete TPMs on LPC or SPI are used
+    pub const FIFO: Self = Self(6);
+    pub const CRB: Self = Self(7);
+    pub const CRB_WITH_ACPI: Self = Self(8);
+    pub const CRB_WITH_ARM_SMC: Self = Self(11);
+    pub const CRB_WITH_ARM_FFA: Self = Self(15);
+
+    /// The Command Response Buffer interface, which firmware TPMs use
+    pub fn is_crb(self) -> bool {
+        matches!(
+            self,
+            Self::CRB | Self::CRB_WITH_ACPI | Self::CRB_WITH_ARM_SMC | Self::CRB_WITH_ARM_FFA
+        )
+    }
+}
+
+impl fmt::Display for StartMethod {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::ACPI => f.write_str("ACPI start"),
+            Self::FIFO => f.write_str("FIFO (TIS)"),
+            Self::CRB => f.write_str("CRB"),
+            Self::CRB_WITH_ACPI => f.write_str("CRB with ACPI start"),
+            Self::CRB_WITH_ARM_SMC => f.write_str("CRB with Arm SMC"),
+            Self::CRB_WITH_ARM_FFA => f.write_str("CRB with Arm FF-A"),
+            Self(other) => write!(f, "unknown ({other})"),
+        }
+    }
+}
+
+/// The fields of the `TPM2` table we use
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Tpm2Table {
+    /// 0 for client platforms, 1 for servers
+    pub platform_class: u16,
+    /// The CRB control area, or the FIFO registers
+    pub control_area: u64,
+    pub start_method: StartMethod,
+}
+
+impl Tpm2Table {
+    pub fn parse(table: &[u8]) -> Option<Self> {
+        if table.get(..4)? != b"TPM2" {
+            return None;
+        }
+        let mut reader = Reader::new(table.get(HEADER_SIZE..)?);
+        let platform_class = reader.u16_le().ok()?;
+        let _reserved = reader.u16_le().ok()?;
+        Some(Self {
+            platform_class,
+            control_area: reader.u64_le().ok()?,
+            start_method: StartMethod(reader.u32_le().ok()?),
+        })
+    }
+
+    /// The running system's table, if the firmware has one
+    pub fn find() -> Option<Self> {
+        Self::parse(find_table(b"TPM2")?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A revision 4 `TPM2` table of a firmware TPM, with a CRB control area and a log area
+    const FIRMWARE_TPM2_TABLE: [u8; 76] = [
+        0x54, 0x50, 0x4d, 0x32, 0x4c, 0x00, 0x00, 0x00, 0x04, 0x3b, 0x49, 0x4e, 0x54, 0x45, 0x4c,
+        0x20, 0x45, 0x44, 0x4b, 0x32, 0x20, 0x20, 0x20, 0x20, 0x02, 0x00, 0x00, 0x00, 0x49, 0x4e,
+        0x54, 0x4c, 0x25, 0x09, 0x20, 0x20, 0x00, 0x00, 0x00, 0x00, 0x40, 0x00, 0xd4, 0xfe, 0x00,
+        0x00, 0x00, 0x00, 0x07, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0xf0, 0x7f, 0x00, 0x00, 0x00,
+        0x00,
+    ];
+
+    #[test]
+    fn tpm2_table_parses() {
+        let table = Tpm2Table::parse(&FIRMWARE_TPM2_TABLE).unwrap();
+        assert_eq!(
+            table,
+            Tpm2Table {
+                platform_class: 0,
+                control_area: 0xfed40040,
+                start_method: StartMethod::CRB,
+            }
+        );
+        assert!(table.start_method.is_crb());
+    }
+
+    #[test]
+    fn other_tables_are_not_tpm2() {
+        let mut table = FIRMWARE_TPM2_TABLE;
+        table[..4].copy_from_slice(b"TCPA");
+        assert_eq!(Tpm2Table::parse(&table), None);
+    }
+
+    #[test]
+    fn truncated_tpm2_table_is_rejected() {
+        assert_eq!(
+            Tpm2Table::parse(&FIRMWARE_TPM2_TABLE[..HEADER_SIZE + 8]),
+            None
+        );
+        assert_eq!(Tpm2Table::parse(b"TPM2"), None);
+    }
+
+    #[test]
+    fn start_methods_are_named() {
+        assert_eq!(format!("{}", StartMethod::FIFO), "FIFO (TIS)");
+        assert_eq!(format!("{}", StartMethod(3)), "unknown (3)");
+        assert!(!StartMethod::FIFO.is_crb());
+    }
+}
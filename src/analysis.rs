@@ -0,0 +1,941 @@
+//! The passes that only need the event log. The app runs them on the firmware's log before
+//! comparing with the live TPM, and [`analyze_log_file`] runs them on a log from anywhere.
+
+use alloc::{format, string::String, vec::Vec};
+
+use hex_slice::AsHex;
+use sha1::{Digest, Sha1};
+use uefi::proto::tcg::{
+    AlgorithmId, EventType, PcrIndex,
+    v2::{EventLog, HashLogExtendEventFlags, PcrEventInputs},
+};
+
+use crate::{
+    authenticode::authenticode_digest,
+    ct::ct_eq,
+    event_log::{
+        parser::{LogEvent, NO_PCR, parse_event_log},
+        variable::VariableData,
+    },
+    findings::{Findings, codes},
+    measure::measure_data,
+    quirks::{FirmwareInfo, find_quirk},
+    tpm::{
+        TcgTransport, TpmError, TpmTransport, alg,
+        pcr::{DEBUG_PCR, DRTM_PCRS, TpmDigest, pcr_read, pcr_read_single, pcr_reset},
+        pcr_selection::{PcrSelection, PcrSelectionList},
+    },
+};
+
+/// PCRs in a PC Client TPM
+pub const PCR_COUNT: usize = 24;
+
+/// The SHA-1 bank as it should be after extending every event in the log
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Sha1Replay {
+    pub pcrs: [[u8; 20]; PCR_COUNT],
+    /// PCRs with at least one event
+    pub extended: [bool; PCR_COUNT],
+}
+
+/// Runs every pass that doesn't need a TPM and replays the SHA-1 bank. `firmware` is used to
+/// recognize known firmware bugs.
+pub fn analyze_events(
+    events: &[LogEvent<'_>],
+    firmware: &FirmwareInfo,
+    findings: &mut Findings,
+) -> Sha1Replay {
+    #[cfg(feature = "decoders")]
+    {
+        let mut cmdline_analysis = crate::event_log::cmdline::CmdlineAnalysis::default();
+        for (event_index, event) in events.iter().enumerate() {
+            cmdline_analysis.add_event(
+                event_index,
+                event.pcr_index,
+                event.event_type,
+                event.event_data,
+            );
+        }
+        cmdline_analysis.report(findings);
+    }
+    check_pcr_indices(events, findings);
+    check_digest_sizes(events, findings);
+    check_separators(events, findings);
+    check_actions(events, findings);
+    check_event_digests(events, firmware, findings);
+    replay_sha1(events, findings)
+}
+
+/// The part of the event data that the digest of an event of this type is over, for the types
+/// where that's defined
+fn digested_data<'a>(event: &LogEvent<'a>) -> Option<&'a [u8]> {
+    match event.event_type {
+        EventType::SEPARATOR
+        | EventType::EFI_ACTION
+        | EventType::CRTM_VERSION
+        | EventType::EFI_VARIABLE_DRIVER_CONFIG
+        | EventType::EFI_VARIABLE_BOOT2
+        | EventType::EFI_VARIABLE_AUTHORITY => Some(event.event_data),
+        // Only the variable's contents, which is why EV_EFI_VARIABLE_BOOT2 was added
+        EventType::EFI_VARIABLE_BOOT => Some(VariableData::parse(event.event_data)?.data),
+        _ => None,
+    }
+}
+
+/// Checks that every event is for a PCR the TPM has. [`NO_PCR`] is fine for `EV_NO_ACTION` events,
+/// which aren't extended anyway, but on any other event it means the event went nowhere.
+fn check_pcr_indices(events: &[LogEvent<'_>], findings: &mut Findings) {
+    for (event_index, event) in events.iter().enumerate() {
+        let message = match event.pcr_index {
+            NO_PCR if event.event_type == EventType::NO_ACTION => continue,
+            NO_PCR => format!(
+                "{:?} event has the no-PCR index {NO_PCR:#x}, so it wasn't extended anywhere",
+                event.event_type
+            ),
+            pcr_index if pcr_index as usize >= PCR_COUNT => {
+                format!("PCR index {pcr_index} out of range")
+            }
+            _ => continue,
+        };
+        findings.add(
+            &codes::LOG_MALFORMED_EVENT,
+            // Not a PCR to list it under
+            Some(event.pcr_index).filter(|pcr_index| *pcr_index != NO_PCR),
+            Some(event_index),
+            message,
+        );
+    }
+}
+
+/// Checks that every digest is the size of its algorithm's digests. One that isn't means the log
+/// is corrupt, or its digest sizes were read wrong and everything after is misparsed.
+fn check_digest_sizes(events: &[LogEvent<'_>], findings: &mut Findings) {
+    for (event_index, event) in events.iter().enumerate() {
+        for (algorithm, digest) in &event.digests {
+            match alg::digest_size(*algorithm) {
+                Some(expected) if expected != digest.len() => findings.add(
+                    &codes::LOG_MALFORMED_EVENT,
+                    Some(event.pcr_index),
+                    Some(event_index),
+                    format!(
+                        "{} digest is {} bytes instead of {expected}",
+                        alg::name(*algorithm).unwrap_or("unknown"),
+                        digest.len()
+                    ),
+                ),
+                _ => {}
+            }
+        }
+    }
+}
+
+/// The PCRs the firmware owns, which it ends with an `EV_SEPARATOR` before running a boot option
+pub const FIRMWARE_PCRS: core::ops::RangeInclusive<u32> = 0..=7;
+
+/// Events only the platform firmware measures, before it hands over to the boot options. Boot
+/// applications, `EV_EFI_ACTION`s, GPTs and the authorities used to verify the OS loader are
+/// measured after the separator on every machine, so they aren't in here.
+fn is_firmware_event(event_type: EventType) -> bool {
+    matches!(
+        event_type,
+        EventType::POST_CODE
+            | EventType::CRTM_CONTENTS
+            | EventType::CRTM_VERSION
+            | EventType::CPU_MICROCODE
+            | EventType::PLATFORM_CONFIG_FLAGS
+            | EventType::TABLE_OF_DEVICES
+            | EventType::NONHOST_CODE
+            | EventType::NONHOST_CONFIG
+            | EventType::NONHOST_INFO
+            | EventType::EFI_VARIABLE_DRIVER_CONFIG
+            | EventType::EFI_PLATFORM_FIRMWARE_BLOB
+            | EventType::EFI_PLATFORM_FIRMWARE_BLOB2
+            | EventType::EFI_HANDOFF_TABLES
+            | EventType::EFI_HANDOFF_TABLES2
+            | EventType::EFI_HCRTM_EVENT
+    )
+}
+
+/// Checks that each of the [`FIRMWARE_PCRS`] has a separator, and that the firmware didn't
+/// measure anything into it after that. Either means the log was tampered with, or something
+/// measured past the boundary between the firmware and the OS.
+fn check_separators(events: &[LogEvent<'_>], findings: &mut Findings) {
+    let mut separated = [false; PCR_COUNT];
+    for (event_index, event) in events.iter().enumerate() {
+        if !FIRMWARE_PCRS.contains(&event.pcr_index) {
+            continue;
+        }
+        let pcr = event.pcr_index as usize;
+        if event.event_type == EventType::SEPARATOR {
+            separated[pcr] = true;
+        } else if separated[pcr] && is_firmware_event(event.event_type) {
+            findings.add(
+                &codes::LOG_EVENT_AFTER_SEPARATOR,
+                Some(event.pcr_index),
+                Some(event_index),
+                format!("{:?} event after the separator", event.event_type),
+            );
+        }
+    }
+    for pcr_index in FIRMWARE_PCRS.filter(|pcr_index| !separated[*pcr_index as usize]) {
+        findings.add(
+            &codes::LOG_SEPARATOR_MISSING,
+            Some(pcr_index),
+            None,
+            "no EV_SEPARATOR event".into(),
+        );
+    }
+}
+
+/// `EV_EFI_ACTION` strings that mean the platform is less protected than it looks, with what
+/// each one means. The PC Client firmware profile has the firmware measure these into PCR 7.
+pub const SECURITY_ACTIONS: &[(&str, &str)] = &[
+    (
+        "UEFI Debug Mode",
+        "the firmware is in debug mode, so its protections can be bypassed",
+    ),
+    (
+        "DMA Protection Disabled",
+        "DMA protection is disabled, so devices can read and write memory before the OS sets up the IOMMU",
+    ),
+];
+
+/// The text of an `EV_EFI_ACTION`. It's ASCII without a null terminator, but some firmware adds
+/// one anyway.
+pub fn action_text(event_data: &[u8]) -> &[u8] {
+    let len = event_data
+        .iter()
+        .rposition(|byte| *byte != 0)
+        .map_or(0, |i| i + 1);
+    &event_data[..len]
+}
+
+/// Reports the actions in [`SECURITY_ACTIONS`]
+fn check_actions(events: &[LogEvent<'_>], findings: &mut Findings) {
+    for (event_index, event) in events.iter().enumerate() {
+        if event.event_type != EventType::EFI_ACTION {
+            continue;
+        }
+        let text = action_text(event.event_data);
+        if let Some((action, meaning)) = SECURITY_ACTIONS
+            .iter()
+            .find(|(action, _)| action.as_bytes() == text)
+        {
+            findings.add(
+                &codes::EVENT_SECURITY_ACTION,
+                Some(event.pcr_index),
+                Some(event_index),
+                format!("\"{action}\": {meaning}"),
+            );
+        }
+    }
+}
+
+/// Checks that the SHA-1 digest of each event is the hash of its data
+fn check_event_digests(events: &[LogEvent<'_>], firmware: &FirmwareInfo, findings: &mut Findings) {
+    for (event_index, event) in events.iter().enumerate() {
+        let (Some(data), Some(digest)) = (digested_data(event), event.digest(AlgorithmId::SHA1))
+        else {
+            continue;
+        };
+        if ct_eq(&Sha1::digest(data), digest) {
+            continue;
+        }
+        match find_quirk(firmware, event.event_type) {
+            Some(quirk) => findings.add(
+                &codes::EVENT_DIGEST_KNOWN_QUIRK,
+                Some(event.pcr_index),
+                Some(event_index),
+                format!(
+                    "known issue on this firmware, measured value differs from logged data: {} ({}, see {})",
+                    quirk.id, quirk.description, quirk.reference
+                ),
+            ),
+            None => findings.add(
+                &codes::EVENT_DIGEST_MISMATCH,
+                Some(event.pcr_index),
+                Some(event_index),
+                format!("{:?} digest isn't the SHA1 of its data", event.event_type),
+            ),
+        }
+    }
+}
+
+fn replay_sha1(events: &[LogEvent<'_>], findings: &mut Findings) -> Sha1Replay {
+    let mut replay = Sha1Replay::default();
+    for (event_index, event) in events.iter().enumerate() {
+        // These are informational and never extended
+        if event.event_type == EventType::NO_ACTION {
+            continue;
+        }
+        let pcr_index = event.pcr_index as usize;
+        // Reported by `check_pcr_indices`
+        if pcr_index >= PCR_COUNT {
+            continue;
+        }
+        let Some(digest) = event.digest(AlgorithmId::SHA1) else {
+            findings.add(
+                &codes::LOG_MALFORMED_EVENT,
+                Some(event.pcr_index),
+                Some(event_index),
+                "no SHA1 digest, so the SHA1 bank can't be replayed".into(),
+            );
+            continue;
+        };
+        let mut pcr = TpmDigest::sha1(replay.pcrs[pcr_index]);
+        let extended =
+            TpmDigest::try_from((AlgorithmId::SHA1, digest)).and_then(|digest| pcr.extend(&digest));
+        if let Err(e) = extended {
+            findings.add(
+                &codes::LOG_MALFORMED_EVENT,
+                Some(event.pcr_index),
+                Some(event_index),
+                format!("can't extend the SHA1 bank: {e}"),
+            );
+            continue;
+        }
+        replay.pcrs[pcr_index].copy_from_slice(pcr.digest.as_slice());
+        replay.extended[pcr_index] = true;
+    }
+    replay
+}
+
+/// Reads the SHA-1 bank from the TPM and compares it with the replay
+pub fn compare_sha1_pcrs(tcg: &mut dyn TpmTransport, replay: &Sha1Replay, findings: &mut Findings) {
+    for i in 0..PCR_COUNT {
+        let pcr_index = Some(i as u32);
+        let pcr = match pcr_read_single(tcg, AlgorithmId::SHA1, i as u32) {
+            Ok(Some(pcr)) => pcr,
+            Ok(None) => {
+                findings.add(
+                    &codes::REPLAY_UNAVAILABLE,
+                    pcr_index,
+                    None,
+                    "not in the SHA1 bank".into(),
+                );
+                continue;
+            }
+            Err(e) => {
+                findings.add(
+                    &codes::REPLAY_UNAVAILABLE,
+                    pcr_index,
+                    None,
+                    format!("PCR_Read failed: {e}"),
+                );
+                continue;
+            }
+        };
+        let pcr_value = pcr.digest.as_slice();
+        if pcr_value.iter().all(|byte| *byte == u8::MAX) && DRTM_PCRS.contains(&(i as u32)) {
+            findings.add(
+                &codes::REPLAY_UNAVAILABLE,
+                pcr_index,
+                None,
+                "all ones, so there was no dynamic launch since the TPM was reset".into(),
+            );
+        } else if ct_eq(pcr_value, &replay.pcrs[i]) {
+            let pcr_value = pcr_value.plain_hex(false);
+            findings.add(
+                &codes::REPLAY_MATCH,
+                pcr_index,
+                None,
+                format!("{pcr_value:x} matches event log"),
+            );
+        } else {
+            let pcr_value = pcr_value.plain_hex(false);
+            findings.add(
+                &codes::REPLAY_MISMATCH,
+                pcr_index,
+                None,
+                format!("{pcr_value:x} does not match event log!"),
+            );
+        };
+    }
+}
+
+/// PCR 16 is reserved for debugging, so measuring into it doesn't disturb anything sealed to the
+/// boot
+pub const IMAGE_MEASUREMENT_PCR: u32 = DEBUG_PCR;
+
+/// Has the firmware measure `image` with `PE_COFF_IMAGE`, so it computes the Authenticode hash
+/// itself, and checks the digest it logged against [`authenticode_digest`]. `description` becomes
+/// the event data.
+pub fn measure_image(
+    tcg: &mut dyn TcgTransport,
+    image: &[u8],
+    description: &[u8],
+    findings: &mut Findings,
+) {
+    let pcr_index = Some(IMAGE_MEASUREMENT_PCR);
+    let expected = match authenticode_digest::<Sha1>(image) {
+        Ok(expected) => expected,
+        Err(e) => {
+            findings.add(
+                &codes::IMAGE_HASH_UNCHECKED,
+                pcr_index,
+                None,
+                format!("{e}"),
+            );
+            return;
+        }
+    };
+    let result =
+        PcrEventInputs::new_in_box(PcrIndex(IMAGE_MEASUREMENT_PCR), EventType::IPL, description)
+            .and_then(|event| {
+                tcg.protocol().hash_log_extend_event(
+                    HashLogExtendEventFlags::PE_COFF_IMAGE,
+                    image,
+                    &event,
+                )
+            });
+    if let Err(e) = result {
+        findings.add(
+            &codes::IMAGE_HASH_UNCHECKED,
+            pcr_index,
+            None,
+            format!("HashLogExtendEvent failed: {e}"),
+        );
+        return;
+    }
+    // Our event is the newest one in the log
+    let event_log = match tcg.protocol().get_event_log_v2() {
+        Ok(event_log) => event_log,
+        Err(e) => {
+            findings.add(
+                &codes::IMAGE_HASH_UNCHECKED,
+                pcr_index,
+                None,
+                format!("failed to get the event log: {e}"),
+            );
+            return;
+        }
+    };
+    let Some(event) = event_log.iter().last() else {
+        findings.add(
+            &codes::IMAGE_HASH_UNCHECKED,
+            pcr_index,
+            None,
+            "the event log is empty".into(),
+        );
+        return;
+    };
+    let event = LogEvent::from(&event);
+    let logged = event
+        .digest(AlgorithmId::SHA1)
+        .filter(|_| event.pcr_index == IMAGE_MEASUREMENT_PCR && event.event_data == description);
+    let Some(logged) = logged else {
+        findings.add(
+            &codes::IMAGE_HASH_UNCHECKED,
+            pcr_index,
+            None,
+            "the event wasn't logged, or has no SHA1 digest".into(),
+        );
+        return;
+    };
+    let matches = ct_eq(logged, &expected);
+    let expected = expected.plain_hex(false);
+    if matches {
+        findings.add(
+            &codes::IMAGE_HASH_MATCH,
+            pcr_index,
+            None,
+            format!("Authenticode SHA1 is {expected:x}"),
+        );
+    } else {
+        let logged = logged.plain_hex(false);
+        findings.add(
+            &codes::IMAGE_HASH_MISMATCH,
+            pcr_index,
+            None,
+            format!("firmware logged {logged:x}, we computed {expected:x}"),
+        );
+    }
+}
+
+/// The event [`scratch_pcr_self_check`] logs
+const SELF_CHECK_EVENT: &[u8] = b"uefi-tpm2 scratch PCR self-check";
+
+/// The SHA-1 bank's [`DEBUG_PCR`]
+fn read_debug_pcr(tcg: &mut dyn TcgTransport) -> Result<[u8; 20], String> {
+    match pcr_read_single(tcg, AlgorithmId::SHA1, DEBUG_PCR) {
+        Ok(Some(pcr)) => pcr
+            .digest
+            .as_slice()
+            .try_into()
+            .map_err(|_| "the SHA1 bank returned a digest of the wrong size".into()),
+        Ok(None) => Err("there's no SHA1 bank".into()),
+        Err(e) => Err(format!("PCR_Read failed: {e}")),
+    }
+}
+
+/// Checks that [`DEBUG_PCR`] works as a scratch PCR: resets it, has the firmware extend and log an
+/// event into it, checks the PCR against the digest in the re-fetched log, then resets it again
+/// and checks it's back to zeros. The PCR is left reset, but the event stays in the log.
+pub fn scratch_pcr_self_check(tcg: &mut dyn TcgTransport, findings: &mut Findings) {
+    if let Err(message) = run_scratch_pcr_self_check(tcg, findings) {
+        findings.add(
+            &codes::SCRATCH_PCR_UNCHECKED,
+            Some(DEBUG_PCR),
+            None,
+            message,
+        );
+    }
+}
+
+fn run_scratch_pcr_self_check(
+    tcg: &mut dyn TcgTransport,
+    findings: &mut Findings,
+) -> Result<(), String> {
+    let pcr_index = Some(DEBUG_PCR);
+    pcr_reset(tcg, DEBUG_PCR).map_err(|e| format!("PCR_Reset failed: {e}"))?;
+    let reset = read_debug_pcr(tcg)?;
+    if reset != [0; 20] {
+        findings.add(
+            &codes::SCRATCH_PCR_NOT_RESET,
+            pcr_index,
+            None,
+            format!("{:x} before extending", reset.plain_hex(false)),
+        );
+        return Ok(());
+    }
+    measure_data(
+        tcg.protocol(),
+        DEBUG_PCR,
+        EventType::EFI_ACTION,
+        SELF_CHECK_EVENT,
+    )
+    .map_err(|e| format!("HashLogExtendEvent failed: {e}"))?;
+    // Our event is the newest one in the log
+    let event_log = tcg
+        .protocol()
+        .get_event_log_v2()
+        .map_err(|e| format!("failed to get the event log: {e}"))?;
+    let logged = event_log.iter().last().and_then(|event| {
+        let event = LogEvent::from(&event);
+        event
+            .digest(AlgorithmId::SHA1)
+            .filter(|_| event.pcr_index == DEBUG_PCR && event.event_data == SELF_CHECK_EVENT)
+            .and_then(|digest| <[u8; 20]>::try_from(digest).ok())
+    });
+    let logged = logged.ok_or("the event wasn't logged, or has no SHA1 digest")?;
+    let mut expected = TpmDigest::sha1([0; 20]);
+    expected
+        .extend(&TpmDigest::sha1(logged))
+        .map_err(|e| format!("{e}"))?;
+    let expected = expected.digest.as_slice();
+    let extended = read_debug_pcr(tcg)?;
+    let matches = extended == expected;
+    if !matches {
+        findings.add(
+            &codes::SCRATCH_PCR_MISMATCH,
+            pcr_index,
+            None,
+            format!(
+                "PCR is {:x}, the log says {:x}",
+                extended.plain_hex(false),
+                expected.plain_hex(false)
+            ),
+        );
+    }
+    pcr_reset(tcg, DEBUG_PCR).map_err(|e| format!("PCR_Reset failed after extending: {e}"))?;
+    let reset = read_debug_pcr(tcg)?;
+    if reset != [0; 20] {
+        findings.add(
+            &codes::SCRATCH_PCR_NOT_RESET,
+            pcr_index,
+            None,
+            format!("{:x} after extending", reset.plain_hex(false)),
+        );
+    } else if matches {
+        findings.add(
+            &codes::SCRATCH_PCR_OK,
+            pcr_index,
+            None,
+            "extended, logged and reset".into(),
+        );
+    }
+    Ok(())
+}
+
+/// How each PCR in a bank compares with a replay of the event log
+#[derive(Debug, Clone, Default)]
+pub struct ConsistencyResult {
+    /// PCRs that have the value the log says they should
+    pub consistent: Vec<u32>,
+    /// PCRs that don't, with the replayed value and then the TPM's
+    pub inconsistent: Vec<(u32, TpmDigest, TpmDigest)>,
+    /// How many events the log has if it's truncated, in which case nothing was compared
+    pub truncated: Option<usize>,
+}
+
+impl ConsistencyResult {
+    /// The result for a log that was truncated after `index` events
+    pub fn truncated_at(index: usize) -> Self {
+        Self {
+            truncated: Some(index),
+            ..Self::default()
+        }
+    }
+}
+
+/// Reads every PCR in the `algorithm` bank and compares it with a replay of `event_log`. PCRs the
+/// TPM doesn't have in that bank are in neither list. It's
+/// [`TpmError::UnsupportedAlgorithm`] if we can't hash with `algorithm` or an event has no digest
+/// for it.
+///
+/// A truncated log is missing its last events, so the replay would be wrong. Nothing is compared
+/// then, and the result only says where the log stops.
+pub fn pcr_bank_is_consistent(
+    tcg: &mut dyn TpmTransport,
+    algorithm: AlgorithmId,
+    event_log: &EventLog<'_>,
+) -> Result<ConsistencyResult, TpmError> {
+    let events = event_log
+        .iter()
+        .map(|event| LogEvent::from(&event))
+        .collect::<Vec<_>>();
+    if event_log.is_truncated() {
+        return Ok(ConsistencyResult::truncated_at(events.len()));
+    }
+    let zero = TpmDigest::zero(algorithm).ok_or(TpmError::UnsupportedAlgorithm(algorithm))?;
+    let mut replayed = [zero; PCR_COUNT];
+    for event in &events {
+        // These are informational and never extended
+        if event.event_type == EventType::NO_ACTION {
+            continue;
+        }
+        // No TPM has it, so it can't be compared anyway
+        let Some(pcr) = replayed.get_mut(event.pcr_index as usize) else {
+            continue;
+        };
+        let digest = event
+            .digest(algorithm)
+            .ok_or(TpmError::UnsupportedAlgorithm(algorithm))?;
+        pcr.extend(&TpmDigest::try_from((algorithm, digest))?)?;
+    }
+    let mut result = ConsistencyResult::default();
+    // `PCR_Read` stops after 8 digests
+    for first in (0..PCR_COUNT as u32).step_by(8) {
+        let mut selection = PcrSelectionList::new();
+        // A new list always has room
+        let _ = selection.push(
+            (first..first + 8).try_fold(PcrSelection::new(algorithm), PcrSelection::with_pcr)?,
+        );
+        let read = pcr_read(tcg, &selection)?;
+        let Some(bank) = read
+            .selection
+            .as_slice()
+            .first()
+            .filter(|bank| bank.hash == algorithm)
+        else {
+            continue;
+        };
+        for (pcr_index, actual) in bank.pcrs().zip(&read.digests) {
+            let expected = replayed[pcr_index as usize];
+            if ct_eq(expected.digest.as_slice(), actual.as_slice()) {
+                result.consistent.push(pcr_index);
+            } else {
+                result.inconsistent.push((
+                    pcr_index,
+                    expected,
+                    TpmDigest {
+                        algorithm,
+                        digest: *actual,
+                    },
+                ));
+            }
+        }
+    }
+    Ok(result)
+}
+
+/// Where the firmware measures the boot applications it loads
+pub const BOOT_MANAGER_CODE_PCR: u32 = 4;
+
+/// Whether the firmware measured an image with this SHA-256 Authenticode hash into PCR 4 as an
+/// `EV_EFI_BOOT_SERVICES_APPLICATION` this boot. An application can check itself with the hash of
+/// its own file.
+pub fn verify_uefi_image_digest(event_log: &EventLog<'_>, image_sha256: &[u8; 32]) -> bool {
+    event_log.iter().any(|event| {
+        let event = LogEvent::from(&event);
+        event.pcr_index == BOOT_MANAGER_CODE_PCR
+            && event.event_type == EventType::EFI_BOOT_SERVICES_APPLICATION
+            && event
+                .digest(AlgorithmId::SHA256)
+                .is_some_and(|digest| ct_eq(digest, image_sha256))
+    })
+}
+
+/// Analyzes a log copied from somewhere else, like Linux's `binary_bios_measurements`. Checks
+/// against the live TPM are reported as skipped.
+pub fn analyze_log_file(bytes: &[u8]) -> Findings {
+    let mut findings = Findings::default();
+    let events = match parse_event_log(bytes) {
+        Ok(events) => events,
+        Err(e) => {
+            findings.add(&codes::LOG_UNPARSABLE, None, None, format!("{e}"));
+            return findings;
+        }
+    };
+    // Nothing is known about the machine the log came from
+    let replay = analyze_events(&events, &FirmwareInfo::default(), &mut findings);
+    report_replay_skipped(&replay, &mut findings);
+    findings
+}
+
+/// Instead of [`compare_sha1_pcrs`] when there's no TPM to compare with, records the replayed
+/// value of each PCR
+pub fn report_replay_skipped(replay: &Sha1Replay, findings: &mut Findings) {
+    for (i, pcr) in replay.pcrs.iter().enumerate() {
+        if replay.extended[i] {
+            let pcr = pcr.plain_hex(false);
+            findings.add(
+                &codes::REPLAY_SKIPPED,
+                Some(i as u32),
+                None,
+                format!("replayed SHA1 value is {pcr:x}"),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::findings::Severity;
+
+    /// A `TCG_PCR_EVENT` with its SHA-1 digest over all of `data`
+    fn sha1_event(pcr_index: u32, event_type: EventType, data: &[u8]) -> Vec<u8> {
+        let mut event = Vec::new();
+        event.extend_from_slice(&pcr_index.to_le_bytes());
+        event.extend_from_slice(&event_type.0.to_le_bytes());
+        event.extend_from_slice(&Sha1::digest(data));
+        event.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        event.extend_from_slice(data);
+        event
+    }
+
+    /// An event as the parser returns it, without digests
+    fn event(pcr_index: u32, event_type: EventType, event_data: &[u8]) -> LogEvent<'_> {
+        LogEvent {
+            pcr_index,
+            event_type,
+            digests: Vec::new(),
+            event_data,
+        }
+    }
+
+    /// The code, PCR and event of each finding, in the order they were added
+    fn found(findings: &Findings) -> Vec<(&'static str, Option<u32>, Option<usize>)> {
+        findings
+            .iter()
+            .map(|finding| (finding.code.code(), finding.pcr_index, finding.event_index))
+            .collect()
+    }
+
+    /// A CRTM version in PCR 0, then a separator in each firmware PCR
+    fn crafted_log() -> Vec<u8> {
+        let mut log = sha1_event(0, EventType::CRTM_VERSION, b"1.0\0");
+        for pcr_index in 0..8 {
+            log.extend(sha1_event(pcr_index, EventType::SEPARATOR, &[0; 4]));
+        }
+        log
+    }
+
+    #[test]
+    fn analyze_log_file_replays_a_crafted_log() {
+        let findings = analyze_log_file(&crafted_log());
+        assert_eq!(findings.worst_severity(), Some(Severity::Info));
+        let skipped = findings
+            .iter()
+            .filter(|finding| finding.code.code() == codes::REPLAY_SKIPPED.code())
+            .map(|finding| finding.pcr_index)
+            .collect::<Vec<_>>();
+        assert_eq!(skipped, (0..8).map(Some).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn analyze_log_file_reports_a_bad_digest() {
+        let mut log = crafted_log();
+        // The first byte of the CRTM version's digest
+        log[8] ^= 1;
+        let findings = analyze_log_file(&log);
+        assert!(
+            findings
+                .iter()
+                .any(|finding| finding.code.severity() > Severity::Info
+                    && finding.event_index == Some(0))
+        );
+    }
+
+    #[test]
+    fn analyze_log_file_reports_an_unparsable_log() {
+        let findings = analyze_log_file(&crafted_log()[..10]);
+        let codes = findings
+            .iter()
+            .map(|finding| finding.code.code())
+            .collect::<Vec<_>>();
+        assert_eq!(codes, ["LOG-003"]);
+    }
+
+    #[test]
+    fn security_actions_are_reported() {
+        let events = [
+            event(7, EventType::EFI_ACTION, b"UEFI Debug Mode"),
+            // Some firmware null-terminates it
+            event(7, EventType::EFI_ACTION, b"DMA Protection Disabled\0"),
+            event(
+                4,
+                EventType::EFI_ACTION,
+                b"Calling EFI Application from Boot Option",
+            ),
+        ];
+        let mut findings = Findings::default();
+        check_actions(&events, &mut findings);
+        assert_eq!(
+            found(&findings),
+            [("EVT-003", Some(7), Some(0)), ("EVT-003", Some(7), Some(1))]
+        );
+    }
+
+    #[test]
+    fn actions_are_matched_whole() {
+        let events = [
+            event(7, EventType::EFI_ACTION, b"UEFI Debug Mode Off"),
+            event(7, EventType::EFI_ACTION, b"uefi debug mode"),
+            // The right text, but not an action
+            event(7, EventType::IPL, b"UEFI Debug Mode"),
+        ];
+        let mut findings = Findings::default();
+        check_actions(&events, &mut findings);
+        assert_eq!(found(&findings), []);
+    }
+
+    #[test]
+    fn action_text_strips_trailing_nulls() {
+        assert_eq!(
+            action_text(b"Exit Boot Services Invocation\0\0"),
+            b"Exit Boot Services Invocation"
+        );
+        assert_eq!(action_text(b"\0"), b"");
+        assert_eq!(action_text(b""), b"");
+    }
+
+    #[test]
+    fn every_firmware_pcr_needs_a_separator() {
+        // PCR 3 has none, and PCR 8 isn't the firmware's
+        let events = [0, 1, 2, 4, 5, 6, 7]
+            .into_iter()
+            .map(|pcr_index| event(pcr_index, EventType::SEPARATOR, &[0; 4]))
+            .collect::<Vec<_>>();
+        let mut findings = Findings::default();
+        check_separators(&events, &mut findings);
+        assert_eq!(found(&findings), [("LOG-008", Some(3), None)]);
+    }
+
+    #[test]
+    fn firmware_events_after_the_separator_are_reported() {
+        let mut events = (0..8)
+            .map(|pcr_index| event(pcr_index, EventType::SEPARATOR, &[0; 4]))
+            .collect::<Vec<_>>();
+        events.push(event(0, EventType::POST_CODE, b""));
+        // Boot applications are measured after the separator on every machine
+        events.push(event(4, EventType::EFI_BOOT_SERVICES_APPLICATION, b""));
+        // A separator in another PCR doesn't end this one
+        events.insert(0, event(2, EventType::EFI_PLATFORM_FIRMWARE_BLOB, b""));
+        let mut findings = Findings::default();
+        check_separators(&events, &mut findings);
+        assert_eq!(found(&findings), [("LOG-009", Some(0), Some(9))]);
+    }
+
+    #[test]
+    fn no_pcr_is_fine_on_no_action_events() {
+        let events = [event(NO_PCR, EventType::NO_ACTION, b"")];
+        let mut findings = Findings::default();
+        check_pcr_indices(&events, &mut findings);
+        assert_eq!(found(&findings), []);
+    }
+
+    #[test]
+    fn no_pcr_on_an_extended_event_is_malformed() {
+        let events = [
+            event(0, EventType::POST_CODE, b""),
+            event(
+                NO_PCR,
+                EventType::EFI_ACTION,
+                b"Exit Boot Services Invocation",
+            ),
+        ];
+        let mut findings = Findings::default();
+        check_pcr_indices(&events, &mut findings);
+        assert_eq!(found(&findings), [("LOG-002", None, Some(1))]);
+    }
+
+    #[test]
+    fn out_of_range_pcr_is_reported_once() {
+        let mut log = crafted_log();
+        log.extend(sha1_event(24, EventType::EFI_ACTION, b"action"));
+        let findings = analyze_log_file(&log);
+        let malformed = found(&findings)
+            .into_iter()
+            .filter(|(code, ..)| *code == "LOG-002")
+            .collect::<Vec<_>>();
+        assert_eq!(malformed, [("LOG-002", Some(24), Some(9))]);
+    }
+
+    #[test]
+    fn digests_of_the_wrong_size_are_malformed() {
+        let mut events = [
+            event(0, EventType::CRTM_VERSION, b"1.0\0"),
+            event(4, EventType::EFI_BOOT_SERVICES_APPLICATION, b""),
+        ];
+        events[0].digests = Vec::from([(AlgorithmId::SHA1, &[0x11; 20][..])]);
+        events[1].digests = Vec::from([
+            (AlgorithmId::SHA1, &[0x11; 20][..]),
+            (AlgorithmId::SHA256, &[0x22; 20][..]),
+            // Sizes of unknown algorithms can't be checked
+            (alg::TPM_ALG_NULL, &[][..]),
+        ]);
+        let mut findings = Findings::default();
+        check_digest_sizes(&events, &mut findings);
+        assert_eq!(found(&findings), [("LOG-002", Some(4), Some(1))]);
+    }
+
+    #[test]
+    fn event_digests_are_checked_against_known_quirks() {
+        let data = b"BootOrder";
+        let digest = Sha1::digest(data);
+        let wrong = [0; 20];
+        let with_digest = |pcr_index, event_type, digest| LogEvent {
+            digests: [(AlgorithmId::SHA1, digest)].into(),
+            ..event(pcr_index, event_type, data)
+        };
+        let events = [
+            with_digest(1, EventType::EFI_VARIABLE_BOOT2, &digest[..]),
+            with_digest(1, EventType::EFI_VARIABLE_BOOT2, &wrong[..]),
+            with_digest(7, EventType::SEPARATOR, &wrong[..]),
+            // Not checked, since its data isn't what's hashed
+            with_digest(4, EventType::EFI_BOOT_SERVICES_APPLICATION, &wrong[..]),
+        ];
+
+        let mut findings = Findings::default();
+        check_event_digests(&events, &FirmwareInfo::default(), &mut findings);
+        assert_eq!(
+            found(&findings),
+            [("EVT-001", Some(1), Some(1)), ("EVT-001", Some(7), Some(2))]
+        );
+
+        let edk2 = FirmwareInfo {
+            vendor: Some("EDK II".into()),
+            ..Default::default()
+        };
+        let mut findings = Findings::default();
+        check_event_digests(&events, &edk2, &mut findings);
+        assert_eq!(
+            found(&findings),
+            [("EVT-002", Some(1), Some(1)), ("EVT-001", Some(7), Some(2))]
+        );
+    }
+}
@@ -0,0 +1,219 @@
+//! What a verifier does with a quote and the event log it came with: replay the log, and check
+//! that the PCR values it gives are the ones the TPM signed. The signature itself has to have
+//! been checked already; this only ties the log to the quote.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use hex_slice::AsHex;
+use uefi::proto::tcg::{AlgorithmId, EventType};
+
+use crate::{
+    analysis::PCR_COUNT,
+    event_log::parser::LogEvent,
+    tpm::{TpmError, attest::QuoteInfo, pcr::TpmDigest},
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum QuoteCheckError {
+    /// The log replays to different PCR values than the quote was over. The quote only holds
+    /// their digest, so which PCR differs can't be told from it.
+    Mismatch {
+        expected: Vec<u8>,
+        got: Vec<u8>,
+    },
+    /// An event that's extended into a quoted PCR has no digest for the quoted bank
+    MissingDigest {
+        event_index: usize,
+        pcr_index: u32,
+        algorithm: AlgorithmId,
+    },
+    Tpm(TpmError),
+}
+
+impl From<TpmError> for QuoteCheckError {
+    fn from(error: TpmError) -> Self {
+        Self::Tpm(error)
+    }
+}
+
+impl fmt::Display for QuoteCheckError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Mismatch { expected, got } => write!(
+                f,
+                "the log replays to a PCR digest of {:x}, but the quote has {:x}",
+                expected.plain_hex(false),
+                got.plain_hex(false)
+            ),
+            Self::MissingDigest {
+                event_index,
+                pcr_index,
+                algorithm,
+            } => write!(
+                f,
+                "event {event_index} in PCR {pcr_index} has no {algorithm:?} digest"
+            ),
+            Self::Tpm(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+/// Replays `pcr_index` in the `algorithm` bank
+fn replay_pcr(
+    events: &[LogEvent<'_>],
+    algorithm: AlgorithmId,
+    pcr_index: u32,
+) -> Result<TpmDigest, QuoteCheckError> {
+    let mut pcr = TpmDigest::zero(algorithm).ok_or(TpmError::UnsupportedAlgorithm(algorithm))?;
+    for (event_index, event) in events.iter().enumerate() {
+        // These are informational and never extended
+        if event.pcr_index != pcr_index || event.event_type == EventType::NO_ACTION {
+            continue;
+        }
+        let digest = event
+            .digest(algorithm)
+            .ok_or(QuoteCheckError::MissingDigest {
+                event_index,
+                pcr_index,
+                algorithm,
+            })?;
+        let digest = TpmDigest::try_from((algorithm, digest))?;
+        pcr.extend(&digest)?;
+    }
+    Ok(pcr)
+}
+
+/// Replays the PCRs `quote` selects and checks that their digest, hashed with `algorithm` (the
+/// signing scheme's hash), is the `pcrDigest` in the quote
+pub fn verify_against_quote(
+    events: &[LogEvent<'_>],
+    quote: &QuoteInfo,
+    algorithm: AlgorithmId,
+) -> Result<(), QuoteCheckError> {
+    // Bank by bank, then by increasing PCR index, like the TPM concatenates them
+    let mut pcrs = Vec::new();
+    for selection in quote.pcr_select.as_slice() {
+        for pcr_index in selection.pcrs().filter(|pcr| (*pcr as usize) < PCR_COUNT) {
+            pcrs.push(replay_pcr(events, selection.hash, pcr_index)?);
+        }
+    }
+    let parts = pcrs
+        .iter()
+        .map(|pcr| pcr.digest.as_slice())
+        .collect::<Vec<_>>();
+    let expected = TpmDigest::hash(algorithm, &parts)?;
+    if expected.digest != quote.pcr_digest {
+        return Err(QuoteCheckError::Mismatch {
+            expected: expected.digest.as_slice().into(),
+            got: quote.pcr_digest.as_slice().into(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use sha1::{Digest, Sha1};
+
+    use super::*;
+    use crate::tpm::{
+        pcr_selection::{PcrSelection, PcrSelectionList},
+        tpm2b::Tpm2b,
+    };
+
+    /// An event with only a SHA-1 digest
+    fn event(pcr_index: u32, event_type: EventType, digest: &[u8]) -> LogEvent<'_> {
+        LogEvent {
+            pcr_index,
+            event_type,
+            digests: vec![(AlgorithmId::SHA1, digest)],
+            event_data: &[],
+        }
+    }
+
+    /// Two events in PCR 0, one in PCR 1, and a `NO_ACTION` event that isn't extended
+    fn synthetic_log() -> Vec<LogEvent<'static>> {
+        vec![
+            event(0, EventType::NO_ACTION, &[0xff; 20]),
+            event(0, EventType::POST_CODE, &[1; 20]),
+            event(1, EventType::EFI_HANDOFF_TABLES, &[3; 20]),
+            event(0, EventType::SEPARATOR, &[2; 20]),
+        ]
+    }
+
+    /// A quote over PCRs 0 and 1 in the SHA-1 bank
+    fn quote(pcr_digest: &[u8]) -> QuoteInfo {
+        let mut pcr_select = PcrSelectionList::new();
+        pcr_select
+            .push(
+                PcrSelection::new(AlgorithmId::SHA1)
+                    .with_pcr(0)
+                    .unwrap()
+                    .with_pcr(1)
+                    .unwrap(),
+            )
+            .unwrap();
+        QuoteInfo {
+            pcr_select,
+            pcr_digest: Tpm2b::new(pcr_digest).unwrap(),
+        }
+    }
+
+    /// The `pcrDigest` the TPM would sign for [`synthetic_log`], worked out independently
+    fn expected_pcr_digest() -> Vec<u8> {
+        let extend = |pcr: &[u8], digest: &[u8]| {
+            Sha1::new()
+                .chain_update(pcr)
+                .chain_update(digest)
+                .finalize()
+        };
+        let pcr0 = extend(&extend(&[0; 20], &[1; 20]), &[2; 20]);
+        let pcr1 = extend(&[0; 20], &[3; 20]);
+        Sha1::new()
+            .chain_update(pcr0)
+            .chain_update(pcr1)
+            .finalize()
+            .to_vec()
+    }
+
+    #[test]
+    fn log_matches_its_quote() {
+        let quote = quote(&expected_pcr_digest());
+        assert_eq!(
+            verify_against_quote(&synthetic_log(), &quote, AlgorithmId::SHA1),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn tampered_log_is_a_mismatch() {
+        let quote = quote(&expected_pcr_digest());
+        let mut events = synthetic_log();
+        events[3].digests[0].1 = &[4; 20];
+        let Err(QuoteCheckError::Mismatch { expected, got }) =
+            verify_against_quote(&events, &quote, AlgorithmId::SHA1)
+        else {
+            panic!("expected a mismatch");
+        };
+        assert_ne!(expected, got);
+        assert_eq!(got, expected_pcr_digest());
+    }
+
+    #[test]
+    fn event_without_the_quoted_bank_is_reported() {
+        let quote = quote(&expected_pcr_digest());
+        let mut events = synthetic_log();
+        events[2].digests[0].0 = AlgorithmId::SHA256;
+        assert_eq!(
+            verify_against_quote(&events, &quote, AlgorithmId::SHA1),
+            Err(QuoteCheckError::MissingDigest {
+                event_index: 2,
+                pcr_index: 1,
+                algorithm: AlgorithmId::SHA1,
+            })
+        );
+    }
+}
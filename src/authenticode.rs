@@ -0,0 +1,120 @@
+//! The Authenticode hash of a PE/COFF image, which is what firmware measures for every EFI binary
+//! it loads. Checksum and certificate table are left out so signing doesn't change the hash.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use sha1::Digest;
+
+const PE32_MAGIC: u16 = 0x10B;
+const PE32_PLUS_MAGIC: u16 = 0x20B;
+/// `IMAGE_DIRECTORY_ENTRY_SECURITY`
+const CERTIFICATE_TABLE_INDEX: u32 = 4;
+const SECTION_HEADER_SIZE: usize = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PeError {
+    /// No `MZ` or `PE\0\0` signature
+    NotPe,
+    Truncated,
+    /// A header field points outside the image
+    Malformed,
+}
+
+impl fmt::Display for PeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotPe => f.write_str("not a PE image"),
+            Self::Truncated => f.write_str("PE image is truncated"),
+            Self::Malformed => f.write_str("PE image has a header field out of range"),
+        }
+    }
+}
+
+fn u16_at(image: &[u8], offset: usize) -> Result<u16, PeError> {
+    let bytes = image.get(offset..offset + 2).ok_or(PeError::Truncated)?;
+    Ok(u16::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn u32_at(image: &[u8], offset: usize) -> Result<u32, PeError> {
+    let bytes = image.get(offset..offset + 4).ok_or(PeError::Truncated)?;
+    Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+}
+
+fn range(image: &[u8], start: usize, end: usize) -> Result<&[u8], PeError> {
+    image.get(start..end).ok_or(PeError::Malformed)
+}
+
+/// Hashes `image` as it is in the file (not as loaded into memory), following "Windows
+/// Authenticode Portable Executable Signature Format"
+pub fn authenticode_digest<D: Digest>(image: &[u8]) -> Result<Vec<u8>, PeError> {
+    if image.get(..2) != Some(b"MZ") {
+        return Err(PeError::NotPe);
+    }
+    let pe_offset = u32_at(image, 0x3C)? as usize;
+    if image.get(pe_offset..pe_offset + 4) != Some(b"PE\0\0") {
+        return Err(PeError::NotPe);
+    }
+    let coff_offset = pe_offset + 4;
+    let number_of_sections = u16_at(image, coff_offset + 2)? as usize;
+    let size_of_optional_header = u16_at(image, coff_offset + 16)? as usize;
+    let optional_offset = coff_offset + 20;
+    let directories_offset = match u16_at(image, optional_offset)? {
+        PE32_MAGIC => optional_offset + 92,
+        PE32_PLUS_MAGIC => optional_offset + 108,
+        _ => return Err(PeError::NotPe),
+    };
+    let checksum_offset = optional_offset + 64;
+    let size_of_headers = u32_at(image, optional_offset + 60)? as usize;
+    let number_of_directories = u32_at(image, directories_offset)?;
+    let certificate_entry_offset = directories_offset + 4 + CERTIFICATE_TABLE_INDEX as usize * 8;
+    let certificate_table_size = if number_of_directories > CERTIFICATE_TABLE_INDEX {
+        u32_at(image, certificate_entry_offset + 4)? as usize
+    } else {
+        0
+    };
+
+    let mut hasher = D::new();
+    hasher.update(range(image, 0, checksum_offset)?);
+    if number_of_directories > CERTIFICATE_TABLE_INDEX {
+        hasher.update(range(image, checksum_offset + 4, certificate_entry_offset)?);
+        hasher.update(range(image, certificate_entry_offset + 8, size_of_headers)?);
+    } else {
+        hasher.update(range(image, checksum_offset + 4, size_of_headers)?);
+    }
+
+    // (PointerToRawData, SizeOfRawData) in file order
+    let section_table_offset = optional_offset + size_of_optional_header;
+    let mut sections = (0..number_of_sections)
+        .map(|i| {
+            let header = section_table_offset + i * SECTION_HEADER_SIZE;
+            Ok((
+                u32_at(image, header + 20)? as usize,
+                u32_at(image, header + 16)? as usize,
+            ))
+        })
+        .collect::<Result<Vec<_>, PeError>>()?;
+    sections.sort_unstable();
+    let mut bytes_hashed = size_of_headers;
+    for (pointer_to_raw_data, size_of_raw_data) in sections {
+        if size_of_raw_data == 0 {
+            continue;
+        }
+        hasher.update(range(
+            image,
+            pointer_to_raw_data,
+            pointer_to_raw_data + size_of_raw_data,
+        )?);
+        bytes_hashed += size_of_raw_data;
+    }
+
+    // Anything after the sections except the certificate table, which is always at the end
+    let end = image
+        .len()
+        .checked_sub(certificate_table_size)
+        .ok_or(PeError::Malformed)?;
+    if end > bytes_hashed {
+        hasher.update(range(image, bytes_hashed, end)?);
+    }
+    Ok(hasher.finalize().to_vec())
+}
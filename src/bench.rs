@@ -0,0 +1,136 @@
+//! Times many runs of a few commands, so changes to how commands are built and submitted can be
+//! measured instead of guessed. Runs against whatever [`TpmTransport`] it's given: the firmware's
+//! TPM, or a simulator behind the TCG protocol.
+
+use alloc::vec::Vec;
+
+use log::info;
+use uefi::proto::tcg::AlgorithmId;
+
+use crate::{
+    timer::TickTimer,
+    tpm::{CommandCode, TpmError, TpmTransport, pcr::pcr_read_single, random::get_random},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BenchResult {
+    pub command: CommandCode,
+    pub iterations: usize,
+    pub failures: usize,
+    /// Including the runs that failed
+    pub total_us: u64,
+}
+
+impl BenchResult {
+    pub fn mean_us(&self) -> Option<u64> {
+        (self.iterations != 0).then(|| self.total_us / self.iterations as u64)
+    }
+
+    pub fn per_second(&self) -> Option<u64> {
+        (self.total_us != 0).then(|| self.iterations as u64 * 1_000_000 / self.total_us)
+    }
+}
+
+fn bench_command(
+    timer: &TickTimer,
+    command: CommandCode,
+    iterations: usize,
+    mut run: impl FnMut() -> Result<(), TpmError>,
+) -> BenchResult {
+    let mut failures = 0;
+    let ((), total_us) = timer.time(|| {
+        for _ in 0..iterations {
+            if run().is_err() {
+                failures += 1;
+            }
+        }
+    });
+    BenchResult {
+        command,
+        iterations,
+        failures,
+        total_us,
+    }
+}
+
+/// Runs `GetRandom` and `PCR_Read` `iterations` times each. `None` if there's no timer.
+pub fn run(tcg: &mut dyn TpmTransport, iterations: usize) -> Option<Vec<BenchResult>> {
+    let timer = TickTimer::calibrate()?;
+    Some(Vec::from([
+        bench_command(&timer, CommandCode::GET_RANDOM, iterations, || {
+            get_random(tcg, &mut [0; 32]).map(|_| ())
+        }),
+        bench_command(&timer, CommandCode::PCR_READ, iterations, || {
+            pcr_read_single(tcg, AlgorithmId::SHA256, 0).map(|_| ())
+        }),
+    ]))
+}
+
+/// One row per command
+pub fn log_table(results: &[BenchResult]) {
+    info!(
+        "{:<16} {:>10} {:>8} {:>12} {:>10}",
+        "command", "iterations", "failures", "mean (us)", "per second"
+    );
+    for result in results {
+        info!(
+            "{:<16} {:>10} {:>8} {:>12} {:>10}",
+            result.command,
+            result.iterations,
+            result.failures,
+            result.mean_us().unwrap_or_default(),
+            result.per_second().unwrap_or_default()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tpm::mock::MockTransport;
+
+    #[test]
+    fn harness_runs_a_few_iterations() {
+        // Answers every command with 32 random bytes, which PCR_Read can't parse
+        let mut parameters = Vec::from([0x00, 0x20]);
+        parameters.extend_from_slice(&[0x5a; 32]);
+        let mut tcg = MockTransport::success(&parameters);
+        let timer = TickTimer::with_ticks_per_us(1);
+        let results = [
+            bench_command(&timer, CommandCode::GET_RANDOM, 3, || {
+                get_random(&mut tcg, &mut [0; 32]).map(|_| ())
+            }),
+            bench_command(&timer, CommandCode::PCR_READ, 2, || {
+                pcr_read_single(&mut tcg, AlgorithmId::SHA256, 0).map(|_| ())
+            }),
+        ];
+        assert_eq!(tcg.commands.len(), 5);
+        assert_eq!(
+            results.map(|result| (result.command, result.iterations, result.failures)),
+            [
+                (CommandCode::GET_RANDOM, 3, 0),
+                (CommandCode::PCR_READ, 2, 2),
+            ]
+        );
+        log_table(&results);
+    }
+
+    #[test]
+    fn mean_and_throughput() {
+        let result = BenchResult {
+            command: CommandCode::GET_RANDOM,
+            iterations: 4,
+            failures: 0,
+            total_us: 2_000,
+        };
+        assert_eq!(result.mean_us(), Some(500));
+        assert_eq!(result.per_second(), Some(2_000));
+        let nothing = BenchResult {
+            iterations: 0,
+            total_us: 0,
+            ..result
+        };
+        assert_eq!(nothing.mean_us(), None);
+        assert_eq!(nothing.per_second(), None);
+    }
+}
@@ -0,0 +1,57 @@
+//! Runs the verification without any UI and leaves the verdict in a UEFI variable, for loading
+//! with `Driver####` or including in a firmware volume. Build it with `--no-default-features` to
+//! leave out the decoders it doesn't need.
+
+#![no_main]
+#![no_std]
+
+extern crate alloc;
+
+use alloc::string::ToString;
+
+use uefi::{Identify, boot::SearchType, prelude::*, proto::tcg::v2::Tcg};
+use uefi_tpm2::{
+    analysis,
+    event_log::parser::collect_events,
+    findings::{Findings, codes},
+    quirks::FirmwareInfo,
+    verdict::{Verdict, save_verdict},
+};
+
+#[entry]
+fn main() -> Status {
+    // No `uefi::helpers::init`, so nothing is logged
+    let Ok(handles) = boot::locate_handle_buffer(SearchType::ByProtocol(&Tcg::GUID)) else {
+        return Status::NOT_FOUND;
+    };
+    let Ok(mut tcg) = boot::open_protocol_exclusive::<Tcg>(handles[0]) else {
+        return Status::ACCESS_DENIED;
+    };
+    let mut firmware = FirmwareInfo {
+        vendor: Some(system::firmware_vendor().to_string()),
+        revision: Some(system::firmware_revision()),
+        ..Default::default()
+    };
+    let _ = firmware.read_tpm(&mut tcg);
+    let mut findings = Findings::default();
+    let event_log = match tcg.get_event_log_v2() {
+        Ok(event_log) => event_log,
+        Err(e) => return e.status(),
+    };
+    if event_log.is_truncated() {
+        findings.add(
+            &codes::LOG_TRUNCATED,
+            None,
+            None,
+            "the firmware ran out of space for events".into(),
+        );
+    } else {
+        let events = collect_events(&event_log);
+        let replay = analysis::analyze_events(&events, &firmware, &mut findings);
+        analysis::compare_sha1_pcrs(&mut tcg, &replay, &mut findings);
+    }
+    match save_verdict(&Verdict::from_findings(&findings)) {
+        Ok(()) => Status::SUCCESS,
+        Err(e) => e.status(),
+    }
+}
@@ -0,0 +1,161 @@
+//! The X.509 certificates measured into PCR 7, for opening in real tooling: the contents of
+//! `PK`, `KEK`, `db` and `dbx` as measured at boot, and each certificate an
+//! `EV_EFI_VARIABLE_AUTHORITY` event says verified an image.
+//!
+//! Each certificate is written once as `<SHA-1 fingerprint>.der`, so one that's in several lists
+//! doesn't overwrite or collide with itself. The index file has a line per place it was found.
+
+use alloc::{format, string::String, vec::Vec};
+use core::fmt;
+
+use hex_slice::AsHex;
+use sha1::{Digest, Sha1};
+use uefi::{
+    CString16,
+    fs::{self, FileSystem, PathBuf},
+    proto::tcg::EventType,
+};
+
+use crate::event_log::{
+    parser::LogEvent,
+    signature_list::{SignatureLists, is_signature_database},
+    variable::VariableData,
+};
+
+/// Where a certificate was found
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CertSource {
+    pub event_index: usize,
+    pub event_type: EventType,
+    pub variable: String,
+    /// The list and the signature in it. `None` for `EV_EFI_VARIABLE_AUTHORITY`, which has a
+    /// single signature.
+    pub position: Option<(usize, usize)>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ExtractedCert<'a> {
+    pub sha1: [u8; 20],
+    pub der: &'a [u8],
+    pub sources: Vec<CertSource>,
+}
+
+impl ExtractedCert<'_> {
+    pub fn file_name(&self) -> String {
+        format!("{:x}.der", self.sha1.plain_hex(false))
+    }
+}
+
+fn add<'a>(certs: &mut Vec<ExtractedCert<'a>>, der: &'a [u8], source: CertSource) {
+    let mut sha1 = [0; 20];
+    sha1.copy_from_slice(&Sha1::digest(der));
+    match certs.iter_mut().find(|cert| cert.sha1 == sha1) {
+        Some(cert) => cert.sources.push(source),
+        None => certs.push(ExtractedCert {
+            sha1,
+            der,
+            sources: Vec::from([source]),
+        }),
+    }
+}
+
+/// Every distinct certificate in the log, in the order they were first found
+pub fn extract_certificates<'a>(events: &[LogEvent<'a>]) -> Vec<ExtractedCert<'a>> {
+    let mut certs = Vec::new();
+    for (event_index, event) in events.iter().enumerate() {
+        let Some(variable) = VariableData::parse(event.event_data) else {
+            continue;
+        };
+        let source = |position| CertSource {
+            event_index,
+            event_type: event.event_type,
+            variable: variable.name(),
+            position,
+        };
+        match event.event_type {
+            EventType::EFI_VARIABLE_DRIVER_CONFIG if is_signature_database(&variable) => {
+                for entry in SignatureLists::new(variable.data).filter(|entry| entry.is_x509()) {
+                    add(
+                        &mut certs,
+                        entry.data,
+                        source(Some((entry.list_index, entry.entry_index))),
+                    );
+                }
+            }
+            // An `EFI_SIGNATURE_DATA` from `db`, without the list around it. Its type isn't
+            // logged, but a DER certificate starts with a SEQUENCE where a hash has no such rule.
+            EventType::EFI_VARIABLE_AUTHORITY => {
+                if let Some(der) = variable
+                    .data
+                    .get(16..)
+                    .filter(|der| der.first() == Some(&0x30))
+                {
+                    add(&mut certs, der, source(None));
+                }
+            }
+            _ => {}
+        }
+    }
+    certs
+}
+
+/// One line per source: the file, the event, the variable and where in it
+pub fn index_file(certs: &[ExtractedCert<'_>]) -> String {
+    let mut index = String::new();
+    for cert in certs {
+        for source in &cert.sources {
+            index += &format!(
+                "{} event {} {:?} {}",
+                cert.file_name(),
+                source.event_index,
+                source.event_type,
+                source.variable
+            );
+            if let Some((list_index, entry_index)) = source.position {
+                index += &format!(" list {list_index} signature {entry_index}");
+            }
+            index.push('\n');
+        }
+    }
+    index
+}
+
+#[derive(Debug)]
+pub enum ExportError {
+    InvalidPath(String),
+    Write(String, fs::Error),
+}
+
+impl fmt::Display for ExportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidPath(path) => write!(f, "invalid path {path:?}"),
+            Self::Write(path, e) => write!(f, "failed to write {path}: {e}"),
+        }
+    }
+}
+
+/// Writes each certificate and `index.txt` into `directory`, creating it if needed
+pub fn export_certificates(
+    file_system: &mut FileSystem,
+    directory: &str,
+    certs: &[ExtractedCert<'_>],
+) -> Result<(), ExportError> {
+    let path = |path: String| match CString16::try_from(path.as_str()) {
+        Ok(path) => Ok(PathBuf::from(path)),
+        Err(_) => Err(ExportError::InvalidPath(path)),
+    };
+    file_system
+        .create_dir_all(path(directory.into())?)
+        .map_err(|e| ExportError::Write(directory.into(), e))?;
+    let mut write = |name: &str, content: &[u8]| {
+        let file = format!("{directory}\\{name}");
+        file_system
+            .write(path(file.clone())?, content)
+            .map_err(|e| ExportError::Write(file, e))
+    };
+    for cert in certs {
+        write(&cert.file_name(), cert.der)?;
+    }
+    write("index.txt", index_file(certs).as_bytes())
+}
@@ -0,0 +1,114 @@
+//! What was being done when an error happened, so an error from deep in a flow doesn't reach the
+//! user as a bare response code. Each layer names its phase on the way out:
+//!
+//! ```text
+//! identify → read the firmware version → TPM2_GetCapability() → TPM_RC_VALUE (parameter 1)
+//! ```
+//!
+//! The command and handle come from [`TpmError::Response`], which the command wrappers fill in,
+//! and the stage from the pipeline.
+
+use alloc::{borrow::Cow, vec::Vec};
+use core::fmt;
+
+use crate::tpm::TpmError;
+
+/// An error with the phases it passed through, innermost first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContextError {
+    pub error: TpmError,
+    phases: Vec<Cow<'static, str>>,
+}
+
+impl ContextError {
+    /// Outermost first, the order they're printed in
+    pub fn phases(&self) -> impl Iterator<Item = &str> {
+        self.phases.iter().rev().map(|phase| phase.as_ref())
+    }
+}
+
+impl From<TpmError> for ContextError {
+    fn from(error: TpmError) -> Self {
+        Self {
+            error,
+            phases: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Display for ContextError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for phase in self.phases() {
+            write!(f, "{phase} → ")?;
+        }
+        write!(f, "{}", self.error)
+    }
+}
+
+pub trait Context<T> {
+    /// Names the phase the error happened in
+    fn context(self, phase: impl Into<Cow<'static, str>>) -> Result<T, ContextError>;
+}
+
+impl<T, E: Into<ContextError>> Context<T> for Result<T, E> {
+    fn context(self, phase: impl Into<Cow<'static, str>>) -> Result<T, ContextError> {
+        self.map_err(|error| {
+            let mut error = error.into();
+            error.phases.push(phase.into());
+            error
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tpm::{
+        ResponseCode, ResponsePosition, TpmHandle, TpmTransport, mock::MockTransport,
+        object::read_public,
+    };
+
+    const SRK: TpmHandle = 0x81000001;
+
+    /// Reads the SRK's public area, like a stage deep in a flow would
+    fn read_srk(tcg: &mut dyn TpmTransport) -> Result<(), ContextError> {
+        read_public(tcg, SRK).context("read the SRK")?;
+        Ok(())
+    }
+
+    fn provision(tcg: &mut dyn TpmTransport) -> Result<(), ContextError> {
+        read_srk(tcg).context(alloc::format!("check handle {SRK:#x}"))
+    }
+
+    #[test]
+    fn context_is_kept_across_layers() {
+        // `TPM_RC_HANDLE` for handle 1
+        let mut tcg = MockTransport::new(MockTransport::response_bytes(ResponseCode(0x18b), &[]));
+        let error = provision(&mut tcg).context("provision").unwrap_err();
+        assert_eq!(
+            error.phases().collect::<Vec<_>>(),
+            ["provision", "check handle 0x81000001", "read the SRK"]
+        );
+        let TpmError::Response { code, .. } = error.error else {
+            panic!("expected a response code, got {:?}", error.error);
+        };
+        assert_eq!(code.position(), Some(ResponsePosition::Handle(1)));
+        assert_eq!(
+            alloc::format!("{error}"),
+            "provision → check handle 0x81000001 → read the SRK → \
+             TPM2_ReadPublic(handle 0x81000001) → TPM_RC_HANDLE (handle 1)"
+        );
+    }
+
+    #[test]
+    fn errors_without_context_print_alone() {
+        let error = ContextError::from(TpmError::Malformed);
+        assert_eq!(error.phases().count(), 0);
+        assert_eq!(alloc::format!("{error}"), "malformed response");
+    }
+
+    #[test]
+    fn success_passes_through() {
+        assert_eq!(Ok::<_, TpmError>(5).context("unused"), Ok(5));
+    }
+}
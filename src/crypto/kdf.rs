@@ -0,0 +1,318 @@
+//! The key derivation functions from TPM 2.0 Part 1, section 11.4.10
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use hmac::{Mac, SimpleHmac};
+use sha1::Sha1;
+use sha2::{
+    Sha256, Sha384, Sha512,
+    digest::{Digest, core_api::BlockSizeUser},
+};
+use uefi::proto::tcg::AlgorithmId;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KdfError {
+    UnsupportedHash(AlgorithmId),
+    /// Asked for 0 bits
+    EmptyOutput,
+}
+
+impl fmt::Display for KdfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedHash(algorithm) => {
+                write!(f, "unsupported hash algorithm {:#06x}", algorithm.0)
+            }
+            Self::EmptyOutput => f.write_str("KDF output must be at least 1 bit"),
+        }
+    }
+}
+
+/// KDFa, the HMAC-based counter mode KDF from SP 800-108. Each block is
+/// `HMAC(key, counter || label || 0x00 || contextU || contextV || bits)`.
+///
+/// `label` is a string like `b"ATH"` and gets its terminating zero added here, unless it already
+/// has one. If `bits` isn't a multiple of 8, the extra high bits of the first byte are cleared.
+pub fn kdfa(
+    hash_alg: AlgorithmId,
+    key: &[u8],
+    label: &[u8],
+    context_u: &[u8],
+    context_v: &[u8],
+    bits: u32,
+) -> Result<Vec<u8>, KdfError> {
+    match hash_alg {
+        AlgorithmId::SHA1 => kdfa_with::<Sha1>(key, label, context_u, context_v, bits),
+        AlgorithmId::SHA256 => kdfa_with::<Sha256>(key, label, context_u, context_v, bits),
+        AlgorithmId::SHA384 => kdfa_with::<Sha384>(key, label, context_u, context_v, bits),
+        AlgorithmId::SHA512 => kdfa_with::<Sha512>(key, label, context_u, context_v, bits),
+        _ => Err(KdfError::UnsupportedHash(hash_alg)),
+    }
+}
+
+fn kdfa_with<D: Digest + BlockSizeUser>(
+    key: &[u8],
+    label: &[u8],
+    context_u: &[u8],
+    context_v: &[u8],
+    bits: u32,
+) -> Result<Vec<u8>, KdfError> {
+    if bits == 0 {
+        return Err(KdfError::EmptyOutput);
+    }
+    let label = label.strip_suffix(&[0]).unwrap_or(label);
+    let len = bits.div_ceil(8) as usize;
+    let mut output = Vec::with_capacity(len);
+    let mut counter = 1u32;
+    while output.len() < len {
+        // HMAC takes keys of any length
+        let mut mac = SimpleHmac::<D>::new_from_slice(key).unwrap();
+        mac.update(&counter.to_be_bytes());
+        mac.update(label);
+        mac.update(&[0]);
+        mac.update(context_u);
+        mac.update(context_v);
+        mac.update(&bits.to_be_bytes());
+        let block = mac.finalize().into_bytes();
+        let needed = len - output.len();
+        output.extend_from_slice(&block[..needed.min(block.len())]);
+        counter += 1;
+    }
+    mask_high_bits(&mut output, bits);
+    Ok(output)
+}
+
+/// KDFe, the hash-based KDF from SP 800-56A used to turn an ECDH shared secret into a key. Each
+/// block is `H(counter || z || use || 0x00 || partyUInfo || partyVInfo)`. Unlike KDFa there is no
+/// HMAC, the counter comes before the secret, and the output length isn't hashed.
+///
+/// `z` is the x coordinate of the shared point. `label` (`Use`) is terminated like in [`kdfa`].
+pub fn kdfe(
+    hash_alg: AlgorithmId,
+    z: &[u8],
+    label: &[u8],
+    party_u_info: &[u8],
+    party_v_info: &[u8],
+    bits: u32,
+) -> Result<Vec<u8>, KdfError> {
+    match hash_alg {
+        AlgorithmId::SHA1 => kdfe_with::<Sha1>(z, label, party_u_info, party_v_info, bits),
+        AlgorithmId::SHA256 => kdfe_with::<Sha256>(z, label, party_u_info, party_v_info, bits),
+        AlgorithmId::SHA384 => kdfe_with::<Sha384>(z, label, party_u_info, party_v_info, bits),
+        AlgorithmId::SHA512 => kdfe_with::<Sha512>(z, label, party_u_info, party_v_info, bits),
+        _ => Err(KdfError::UnsupportedHash(hash_alg)),
+    }
+}
+
+/// Another name for [`kdfe`]
+pub fn kdfb(
+    hash_alg: AlgorithmId,
+    key: &[u8],
+    label: &[u8],
+    context_u: &[u8],
+    context_v: &[u8],
+    bits: u32,
+) -> Result<Vec<u8>, KdfError> {
+    kdfe(hash_alg, key, label, context_u, context_v, bits)
+}
+
+fn kdfe_with<D: Digest>(
+    z: &[u8],
+    label: &[u8],
+    party_u_info: &[u8],
+    party_v_info: &[u8],
+    bits: u32,
+) -> Result<Vec<u8>, KdfError> {
+    if bits == 0 {
+        return Err(KdfError::EmptyOutput);
+    }
+    let label = label.strip_suffix(&[0]).unwrap_or(label);
+    let len = bits.div_ceil(8) as usize;
+    let mut output = Vec::with_capacity(len);
+    let mut counter = 1u32;
+    while output.len() < len {
+        let mut hasher = D::new();
+        hasher.update(counter.to_be_bytes());
+        hasher.update(z);
+        hasher.update(label);
+        hasher.update([0]);
+        hasher.update(party_u_info);
+        hasher.update(party_v_info);
+        let block = hasher.finalize();
+        let needed = len - output.len();
+        output.extend_from_slice(&block[..needed.min(block.len())]);
+        counter += 1;
+    }
+    mask_high_bits(&mut output, bits);
+    Ok(output)
+}
+
+/// The KDFs produce whole bytes, with the unused bits at the start of the output cleared
+fn mask_high_bits(output: &mut [u8], bits: u32) {
+    if !bits.is_multiple_of(8) {
+        output[0] &= (1 << (bits % 8)) - 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The shared secret for the KDFe vectors, which were computed with a separate implementation
+    /// of the formula in Part 1, section 11.4.10.3
+    const Z: [u8; 32] = {
+        let mut z = [0; 32];
+        let mut i = 0;
+        while i < z.len() {
+            z[i] = i as u8;
+            i += 1;
+        }
+        z
+    };
+
+    /// The KDFa vectors were computed the same way, from section 11.4.10.2
+    const KEY: [u8; 32] = Z;
+
+    #[test]
+    fn kdfa_session_key() {
+        let key = kdfa(
+            AlgorithmId::SHA256,
+            &KEY,
+            b"ATH",
+            &[0x11; 16],
+            &[0x22; 16],
+            256,
+        )
+        .unwrap();
+        assert_eq!(
+            key,
+            [
+                0xb9, 0x31, 0x02, 0xad, 0x8d, 0x3b, 0x1d, 0xd4, 0x3b, 0xce, 0x22, 0xe7, 0xde, 0xe8,
+                0x1a, 0xbc, 0xf3, 0x58, 0x30, 0x6c, 0xe7, 0x39, 0x47, 0x44, 0x9b, 0x4f, 0x21, 0xbc,
+                0xa7, 0x83, 0xaf, 0x41,
+            ]
+        );
+    }
+
+    #[test]
+    fn kdfa_takes_more_blocks() {
+        let key = kdfa(AlgorithmId::SHA256, &KEY, b"CFB\0", b"name", &[], 384).unwrap();
+        assert_eq!(
+            key,
+            [
+                0x3b, 0x98, 0xff, 0x90, 0x0d, 0xe1, 0x47, 0xf9, 0x91, 0x7a, 0x91, 0x8b, 0x95, 0x62,
+                0xf2, 0x52, 0xcc, 0x2e, 0x99, 0x9b, 0x74, 0xb3, 0x0f, 0x67, 0xd8, 0x63, 0xf8, 0x5d,
+                0x84, 0xda, 0x7f, 0x29, 0x9d, 0x69, 0x09, 0x2f, 0xf7, 0x99, 0x86, 0xd5, 0x65, 0x99,
+                0x0d, 0x49, 0x38, 0xcc, 0x16, 0x3c,
+            ]
+        );
+    }
+
+    #[test]
+    fn kdfa_sha1_storage_key() {
+        let key = kdfa(AlgorithmId::SHA1, &KEY, b"STORAGE", b"name", &[], 128).unwrap();
+        assert_eq!(
+            key,
+            [
+                0x99, 0xb3, 0x1e, 0x48, 0xb3, 0x46, 0xb2, 0x80, 0xfd, 0xfb, 0xe0, 0xfa, 0xfc, 0x98,
+                0x5d, 0xbf,
+            ]
+        );
+    }
+
+    #[test]
+    fn kdfa_clears_high_bits() {
+        // The length is hashed, so unlike KDFe this isn't a prefix of the 256-bit output
+        let key = kdfa(
+            AlgorithmId::SHA256,
+            &KEY,
+            b"ATH",
+            &[0x11; 16],
+            &[0x22; 16],
+            12,
+        )
+        .unwrap();
+        assert_eq!(key, [0x07, 0xe0]);
+    }
+
+    #[test]
+    fn kdfe_sha256() {
+        let key = kdfe(
+            AlgorithmId::SHA256,
+            &Z,
+            b"SECRET",
+            &[0x11; 32],
+            &[0x22; 32],
+            256,
+        )
+        .unwrap();
+        assert_eq!(
+            key,
+            [
+                0xc2, 0x1b, 0xe0, 0x73, 0x52, 0x12, 0x59, 0x77, 0x3c, 0xab, 0x94, 0x0e, 0x5c, 0xbd,
+                0x0a, 0x66, 0x10, 0x5a, 0x8d, 0xa9, 0x39, 0xb0, 0xb3, 0xae, 0x13, 0xb7, 0xd7, 0xa3,
+                0x75, 0x55, 0xb3, 0xe3,
+            ]
+        );
+    }
+
+    #[test]
+    fn kdfe_sha1_with_empty_party_info() {
+        let key = kdfe(AlgorithmId::SHA1, &Z, b"SECRET\0", &[], &[], 160).unwrap();
+        assert_eq!(
+            key,
+            [
+                0x2c, 0x0b, 0x27, 0x52, 0xa7, 0x84, 0x72, 0x3d, 0xd1, 0x81, 0x60, 0xf8, 0x24, 0x1b,
+                0x92, 0x64, 0x1f, 0xe5, 0x74, 0xc8,
+            ]
+        );
+    }
+
+    #[test]
+    fn kdfe_takes_more_blocks_and_clears_high_bits() {
+        let short = kdfe(
+            AlgorithmId::SHA256,
+            &Z,
+            b"SECRET",
+            &[0x11; 32],
+            &[0x22; 32],
+            256,
+        )
+        .unwrap();
+        let long = kdfe(
+            AlgorithmId::SHA256,
+            &Z,
+            b"SECRET",
+            &[0x11; 32],
+            &[0x22; 32],
+            300,
+        )
+        .unwrap();
+        assert_eq!(long.len(), 38);
+        // The length isn't hashed, so the first block is the same apart from the cleared bits
+        assert_eq!(long[0], short[0] & 0x0F);
+        assert_eq!(long[1..32], short[1..]);
+        assert_eq!(long[32..], [0x45, 0x3c, 0x92, 0xde, 0x4e, 0x6a]);
+    }
+
+    #[test]
+    fn kdfb_is_kdfe() {
+        assert_eq!(
+            kdfb(AlgorithmId::SHA384, &Z, b"SECRET", b"u", b"v", 384),
+            kdfe(AlgorithmId::SHA384, &Z, b"SECRET", b"u", b"v", 384)
+        );
+    }
+
+    #[test]
+    fn kdfe_rejects_bad_arguments() {
+        assert_eq!(
+            kdfe(AlgorithmId::SHA256, &Z, b"SECRET", &[], &[], 0),
+            Err(KdfError::EmptyOutput)
+        );
+        assert_eq!(
+            kdfe(AlgorithmId::SM3_256, &Z, b"SECRET", &[], &[], 256),
+            Err(KdfError::UnsupportedHash(AlgorithmId::SM3_256))
+        );
+    }
+}
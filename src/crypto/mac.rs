@@ -0,0 +1,101 @@
+//! HMACs over session parameters, like the `hmac` field of an authorization
+
+use hmac::{Mac, SimpleHmac};
+use sha2::Sha256;
+
+/// HMAC-SHA256 from RFC 2104. Keys longer than a block are hashed first, like HMAC always does.
+pub fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+    // HMAC takes keys of any length
+    let mut mac = SimpleHmac::<Sha256>::new_from_slice(key).unwrap();
+    mac.update(data);
+    let mut tag = [0; 32];
+    tag.copy_from_slice(&mac.finalize().into_bytes());
+    tag
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The HMAC-SHA256 results of the RFC 4231 test cases, leaving out the truncated test case 5
+
+    #[test]
+    fn rfc4231_test_case_1() {
+        assert_eq!(
+            hmac_sha256(&[0x0b; 20], b"Hi There"),
+            [
+                0xb0, 0x34, 0x4c, 0x61, 0xd8, 0xdb, 0x38, 0x53, 0x5c, 0xa8, 0xaf, 0xce, 0xaf, 0x0b,
+                0xf1, 0x2b, 0x88, 0x1d, 0xc2, 0x00, 0xc9, 0x83, 0x3d, 0xa7, 0x26, 0xe9, 0x37, 0x6c,
+                0x2e, 0x32, 0xcf, 0xf7,
+            ]
+        );
+    }
+
+    #[test]
+    fn rfc4231_test_case_2() {
+        assert_eq!(
+            hmac_sha256(b"Jefe", b"what do ya want for nothing?"),
+            [
+                0x5b, 0xdc, 0xc1, 0x46, 0xbf, 0x60, 0x75, 0x4e, 0x6a, 0x04, 0x24, 0x26, 0x08, 0x95,
+                0x75, 0xc7, 0x5a, 0x00, 0x3f, 0x08, 0x9d, 0x27, 0x39, 0x83, 0x9d, 0xec, 0x58, 0xb9,
+                0x64, 0xec, 0x38, 0x43,
+            ]
+        );
+    }
+
+    #[test]
+    fn rfc4231_test_case_3() {
+        assert_eq!(
+            hmac_sha256(&[0xaa; 20], &[0xdd; 50]),
+            [
+                0x77, 0x3e, 0xa9, 0x1e, 0x36, 0x80, 0x0e, 0x46, 0x85, 0x4d, 0xb8, 0xeb, 0xd0, 0x91,
+                0x81, 0xa7, 0x29, 0x59, 0x09, 0x8b, 0x3e, 0xf8, 0xc1, 0x22, 0xd9, 0x63, 0x55, 0x14,
+                0xce, 0xd5, 0x65, 0xfe,
+            ]
+        );
+    }
+
+    #[test]
+    fn rfc4231_test_case_4() {
+        let key = core::array::from_fn::<u8, 25, _>(|i| i as u8 + 1);
+        assert_eq!(
+            hmac_sha256(&key, &[0xcd; 50]),
+            [
+                0x82, 0x55, 0x8a, 0x38, 0x9a, 0x44, 0x3c, 0x0e, 0xa4, 0xcc, 0x81, 0x98, 0x99, 0xf2,
+                0x08, 0x3a, 0x85, 0xf0, 0xfa, 0xa3, 0xe5, 0x78, 0xf8, 0x07, 0x7a, 0x2e, 0x3f, 0xf4,
+                0x67, 0x29, 0x66, 0x5b,
+            ]
+        );
+    }
+
+    #[test]
+    fn rfc4231_test_case_6() {
+        assert_eq!(
+            hmac_sha256(
+                &[0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First"
+            ),
+            [
+                0x60, 0xe4, 0x31, 0x59, 0x1e, 0xe0, 0xb6, 0x7f, 0x0d, 0x8a, 0x26, 0xaa, 0xcb, 0xf5,
+                0xb7, 0x7f, 0x8e, 0x0b, 0xc6, 0x21, 0x37, 0x28, 0xc5, 0x14, 0x05, 0x46, 0x04, 0x0f,
+                0x0e, 0xe3, 0x7f, 0x54,
+            ]
+        );
+    }
+
+    #[test]
+    fn rfc4231_test_case_7() {
+        assert_eq!(
+            hmac_sha256(
+                &[0xaa; 131],
+                b"This is a test using a larger than block-size key and a larger than block-size \
+                  data. The key needs to be hashed before being used by the HMAC algorithm."
+            ),
+            [
+                0x9b, 0x09, 0xff, 0xa7, 0x1b, 0x94, 0x2f, 0xcb, 0x27, 0x63, 0x5f, 0xbc, 0xd5, 0xb0,
+                0xe9, 0x44, 0xbf, 0xdc, 0x63, 0x64, 0x4f, 0x07, 0x13, 0x93, 0x8a, 0x7f, 0x51, 0x53,
+                0x5c, 0x3a, 0x35, 0xe2,
+            ]
+        );
+    }
+}
@@ -0,0 +1,5 @@
+//! Cryptography done on our side of the TPM, to set up and check sessions
+
+pub mod kdf;
+pub mod mac;
+pub mod session;
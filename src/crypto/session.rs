@@ -0,0 +1,271 @@
+//! Salted sessions, from TPM 2.0 Part 1, section 19.6. The salt is a secret only we and the TPM
+//! know, since it's encrypted to a key only the TPM can decrypt with, so someone watching the bus
+//! can't work out the session key even when the bound object's auth value is weak or empty.
+
+use alloc::{vec, vec::Vec};
+use core::fmt;
+
+use rsa::BigUint;
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha384, Sha512};
+use uefi::proto::tcg::AlgorithmId;
+
+use super::kdf::{KdfError, kdfa, kdfe};
+use crate::tpm::{
+    alg,
+    public::{PublicId, PublicParameters, TpmtPublic},
+};
+
+/// The OAEP label and KDFe `Use` for a salt
+pub const SECRET_LABEL: &[u8] = b"SECRET";
+
+/// The KDFa label for a session key
+pub const SESSION_KEY_LABEL: &[u8] = b"ATH";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaltError {
+    UnsupportedHash(AlgorithmId),
+    /// Salts can only be encrypted to RSA keys here
+    NotRsa,
+    /// The salt doesn't fit in an OAEP block for this modulus and hash
+    SaltTooLong,
+    /// The OAEP seed isn't the hash's digest size
+    SeedSize {
+        expected: usize,
+        actual: usize,
+    },
+}
+
+impl fmt::Display for SaltError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedHash(algorithm) => {
+                write!(f, "unsupported hash algorithm {:#06x}", algorithm.0)
+            }
+            Self::NotRsa => f.write_str("the salt can only be encrypted to an RSA key"),
+            Self::SaltTooLong => f.write_str("the salt is too long for the key"),
+            Self::SeedSize { expected, actual } => {
+                write!(f, "OAEP seed is {actual} bytes instead of {expected}")
+            }
+        }
+    }
+}
+
+/// The session key: `KDFa(sessionAlg, bind.authValue || salt, "ATH", nonceTPM, nonceCaller,
+/// digest bits)`. Empty for a session that's neither bound nor salted, which has no key.
+pub fn session_key(
+    session_alg: AlgorithmId,
+    bind_auth: &[u8],
+    salt: &[u8],
+    nonce_tpm: &[u8],
+    nonce_caller: &[u8],
+) -> Result<Vec<u8>, KdfError> {
+    if bind_auth.is_empty() && salt.is_empty() {
+        return Ok(Vec::new());
+    }
+    let bits = alg::digest_size(session_alg).ok_or(KdfError::UnsupportedHash(session_alg))? * 8;
+    let key = [bind_auth, salt].concat();
+    kdfa(
+        session_alg,
+        &key,
+        SESSION_KEY_LABEL,
+        nonce_tpm,
+        nonce_caller,
+        bits as u32,
+    )
+}
+
+/// The salt for an ECC `tpmKey`: `KDFe(nameAlg, Z, "SECRET", QeU.x, QsV.x, digest bits)`, where
+/// `z_x` is the x coordinate of our ephemeral private key times the TPM key's public point,
+/// `ephemeral_x` is the x coordinate of our ephemeral public key (sent as the encrypted salt) and
+/// `tpm_key_x` is the TPM key's
+pub fn ecc_salt(
+    name_alg: AlgorithmId,
+    z_x: &[u8],
+    ephemeral_x: &[u8],
+    tpm_key_x: &[u8],
+) -> Result<Vec<u8>, KdfError> {
+    let bits = alg::digest_size(name_alg).ok_or(KdfError::UnsupportedHash(name_alg))? * 8;
+    kdfe(
+        name_alg,
+        z_x,
+        SECRET_LABEL,
+        ephemeral_x,
+        tpm_key_x,
+        bits as u32,
+    )
+}
+
+/// The `encryptedSalt` for an RSA `tpmKey`: `salt` encrypted with RSA-OAEP, the key's name
+/// algorithm and the label `SECRET`. `seed` is OAEP's random seed, as long as a digest, which
+/// should come from a good source like `TPM2_GetRandom`.
+pub fn rsa_encrypt_salt(
+    tpm_key: &TpmtPublic,
+    salt: &[u8],
+    seed: &[u8],
+) -> Result<Vec<u8>, SaltError> {
+    let (PublicParameters::Rsa { exponent, .. }, PublicId::Rsa(modulus)) =
+        (tpm_key.parameters, &tpm_key.unique)
+    else {
+        return Err(SaltError::NotRsa);
+    };
+    let encoded = match tpm_key.name_alg {
+        AlgorithmId::SHA1 => oaep_encode::<Sha1>(modulus.as_slice().len(), salt, seed),
+        AlgorithmId::SHA256 => oaep_encode::<Sha256>(modulus.as_slice().len(), salt, seed),
+        AlgorithmId::SHA384 => oaep_encode::<Sha384>(modulus.as_slice().len(), salt, seed),
+        AlgorithmId::SHA512 => oaep_encode::<Sha512>(modulus.as_slice().len(), salt, seed),
+        algorithm => Err(SaltError::UnsupportedHash(algorithm)),
+    }?;
+    // 0 means the default
+    let exponent = if exponent == 0 { 65537 } else { exponent };
+    let encrypted = BigUint::from_bytes_be(&encoded)
+        .modpow(
+            &exponent.into(),
+            &BigUint::from_bytes_be(modulus.as_slice()),
+        )
+        .to_bytes_be();
+    // Left-padded to the modulus size
+    let mut block = vec![0; encoded.len() - encrypted.len()];
+    block.extend_from_slice(&encrypted);
+    Ok(block)
+}
+
+/// EME-OAEP encoding from RFC 8017, section 7.1.1, into a `k`-byte block. The TPM's label
+/// includes its terminating zero.
+fn oaep_encode<D: Digest>(k: usize, message: &[u8], seed: &[u8]) -> Result<Vec<u8>, SaltError> {
+    let h_len = <D as Digest>::output_size();
+    if seed.len() != h_len {
+        return Err(SaltError::SeedSize {
+            expected: h_len,
+            actual: seed.len(),
+        });
+    }
+    if k < 2 * h_len + 2 || message.len() > k - 2 * h_len - 2 {
+        return Err(SaltError::SaltTooLong);
+    }
+    // lHash || PS || 0x01 || M
+    let mut db = D::new_with_prefix(SECRET_LABEL)
+        .chain_update([0])
+        .finalize()
+        .to_vec();
+    db.resize(k - h_len - 1 - message.len() - 1, 0);
+    db.push(0x01);
+    db.extend_from_slice(message);
+    mgf1_xor::<D>(seed, &mut db);
+    let mut masked_seed = seed.to_vec();
+    mgf1_xor::<D>(&db, &mut masked_seed);
+    let mut encoded = Vec::with_capacity(k);
+    encoded.push(0);
+    encoded.extend_from_slice(&masked_seed);
+    encoded.extend_from_slice(&db);
+    Ok(encoded)
+}
+
+/// XORs `out` with MGF1 of `seed`
+fn mgf1_xor<D: Digest>(seed: &[u8], out: &mut [u8]) {
+    let h_len = <D as Digest>::output_size();
+    for (counter, chunk) in out.chunks_mut(h_len).enumerate() {
+        let mask = D::new_with_prefix(seed)
+            .chain_update((counter as u32).to_be_bytes())
+            .finalize();
+        for (byte, mask) in chunk.iter_mut().zip(mask.iter()) {
+            *byte ^= mask;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tpm::{srk::srk_template_rsa2048, tpm2b::Tpm2b};
+
+    /// The modulus of an RSA 1024 test key, whose private key the vectors were checked against by
+    /// decrypting them and decoding the OAEP block separately
+    const MODULUS: [u8; 128] = [
+        0xe4, 0xeb, 0x0c, 0x49, 0xc0, 0xb9, 0x0c, 0x34, 0xff, 0x22, 0x54, 0x08, 0xf9, 0x65, 0x09,
+        0xca, 0x04, 0xfa, 0xe2, 0x6c, 0x6d, 0x5c, 0x8f, 0x1d, 0x7e, 0x39, 0x5f, 0x0f, 0xc0, 0x0a,
+        0x59, 0xfd, 0x6e, 0x74, 0x9a, 0xc6, 0xbd, 0x1d, 0xda, 0xa7, 0x7f, 0x43, 0x0d, 0x74, 0x91,
+        0xed, 0x5d, 0xb3, 0x6c, 0x6b, 0xe7, 0x46, 0xef, 0xe2, 0x35, 0x8c, 0x61, 0xb7, 0xd1, 0x0d,
+        0xd0, 0x85, 0xe6, 0xca, 0xb3, 0x08, 0x32, 0x0a, 0x46, 0x73, 0x2c, 0xfc, 0x41, 0x10, 0xf1,
+        0xc0, 0xe4, 0x62, 0xe5, 0x59, 0x50, 0x5b, 0x0d, 0x5f, 0xbc, 0x6b, 0xd7, 0x3c, 0xf1, 0x5b,
+        0x84, 0x5b, 0xb6, 0x62, 0x69, 0x05, 0x73, 0x0c, 0x15, 0x0a, 0x42, 0x39, 0xaa, 0xaa, 0xf6,
+        0xfc, 0xb2, 0x83, 0x09, 0x56, 0x94, 0x0c, 0x90, 0xda, 0xa2, 0xaf, 0x6f, 0x7f, 0xca, 0x4f,
+        0x92, 0xdb, 0xbe, 0x28, 0x2f, 0x42, 0xca, 0xcf,
+    ];
+
+    fn tpm_key() -> TpmtPublic {
+        TpmtPublic {
+            unique: PublicId::Rsa(Tpm2b::new(&MODULUS).unwrap()),
+            ..srk_template_rsa2048()
+        }
+    }
+
+    #[test]
+    fn rsa_salt_is_oaep_encrypted() {
+        let seed = core::array::from_fn::<u8, 32, _>(|i| 0x40 + i as u8);
+        let encrypted = rsa_encrypt_salt(&tpm_key(), &[0x33; 32], &seed).unwrap();
+        assert_eq!(
+            encrypted,
+            [
+                0x4f, 0x93, 0x4d, 0x1a, 0xce, 0x97, 0x92, 0xe5, 0x43, 0x42, 0x83, 0xfa, 0x3b, 0x7f,
+                0x75, 0x6e, 0x8e, 0xd3, 0xae, 0xb8, 0x45, 0x0c, 0x53, 0x6d, 0xf9, 0xa2, 0x05, 0x76,
+                0x1c, 0x65, 0xb4, 0xe9, 0x41, 0xa2, 0xde, 0xdc, 0x28, 0xd3, 0xd9, 0x45, 0x84, 0xbc,
+                0x6e, 0x85, 0x5e, 0x91, 0x6c, 0x98, 0xb9, 0xf9, 0xd2, 0x48, 0x3e, 0x17, 0xb1, 0x4f,
+                0xbd, 0x11, 0x44, 0x1c, 0xc7, 0xa5, 0xae, 0xa2, 0x30, 0x99, 0xef, 0xf4, 0x94, 0xf9,
+                0x9f, 0xe1, 0x9b, 0x41, 0x6b, 0x1a, 0x85, 0x98, 0xd4, 0xee, 0x82, 0x79, 0xe4, 0x76,
+                0xd9, 0x5a, 0x04, 0x54, 0xe3, 0x6a, 0xd9, 0x64, 0x1d, 0x1d, 0x09, 0x71, 0x5f, 0x22,
+                0xbf, 0x7f, 0x05, 0xff, 0x30, 0x45, 0x7d, 0x31, 0x12, 0x8f, 0xfc, 0x75, 0xdc, 0x79,
+                0x2e, 0xb6, 0x3d, 0x4b, 0xde, 0x4f, 0xf5, 0x4a, 0xf9, 0xfd, 0xb6, 0xca, 0x50, 0x8e,
+                0x0d, 0xc5,
+            ]
+        );
+    }
+
+    #[test]
+    fn rsa_salt_needs_a_digest_sized_seed() {
+        assert_eq!(
+            rsa_encrypt_salt(&tpm_key(), &[0x33; 32], &[0; 20]),
+            Err(SaltError::SeedSize {
+                expected: 32,
+                actual: 20,
+            })
+        );
+    }
+
+    #[test]
+    fn rsa_salt_has_to_fit_the_key() {
+        // 128 - 2 * 32 - 2 bytes is the most a 1024-bit key takes with SHA-256
+        assert_eq!(
+            rsa_encrypt_salt(&tpm_key(), &[0x33; 63], &[0; 32]),
+            Err(SaltError::SaltTooLong)
+        );
+    }
+
+    #[test]
+    fn session_key_from_bind_auth_and_salt() {
+        let key = session_key(
+            AlgorithmId::SHA256,
+            b"auth",
+            &[0x33; 32],
+            &[0x11; 16],
+            &[0x22; 16],
+        )
+        .unwrap();
+        assert_eq!(
+            key,
+            [
+                0xfb, 0x24, 0xc6, 0xa4, 0x06, 0x32, 0x40, 0x9a, 0x2b, 0xd7, 0x97, 0xf9, 0x62, 0x57,
+                0x35, 0x24, 0x74, 0x05, 0xb9, 0xb2, 0x85, 0xc5, 0xc3, 0x68, 0x2f, 0x9b, 0xc7, 0xb3,
+                0x1a, 0x2f, 0x79, 0xfd,
+            ]
+        );
+    }
+
+    #[test]
+    fn unbound_unsalted_session_has_no_key() {
+        assert_eq!(
+            session_key(AlgorithmId::SHA256, &[], &[], &[0x11; 16], &[0x22; 16]),
+            Ok(Vec::new())
+        );
+    }
+}
@@ -0,0 +1,63 @@
+//! Constant-time comparisons, so checking a digest or secret doesn't leak how much of it matched
+
+/// Compares every byte, no matter where the first difference is. Only the lengths are compared
+/// in variable time, since those aren't secret.
+pub fn ct_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    // Keep the optimizer from turning the fold back into an early exit
+    core::hint::black_box(difference(a.iter().zip(b))) == 0
+}
+
+/// The OR of every pair's XOR, which is zero only if every pair is equal
+fn difference<'a>(pairs: impl Iterator<Item = (&'a u8, &'a u8)>) -> u8 {
+    pairs.fold(0, |difference, (a, b)| difference | (a ^ b))
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+
+    use super::*;
+
+    #[test]
+    fn compares_contents_and_lengths() {
+        assert!(ct_eq(b"", b""));
+        assert!(ct_eq(b"digest", b"digest"));
+        assert!(!ct_eq(b"digest", b"digesT"));
+        assert!(!ct_eq(b"digest", b"Digest"));
+        assert!(!ct_eq(b"digest", b"diges"));
+        assert!(!ct_eq(b"", b"d"));
+        // Differences that cancel out in a sum or XOR of all bytes
+        assert!(!ct_eq(&[1, 2], &[2, 1]));
+        assert!(!ct_eq(&[0x0F, 0xF0], &[0xF0, 0x0F]));
+    }
+
+    #[test]
+    fn every_difference_is_found() {
+        let a = [0x5A; 32];
+        for i in 0..a.len() {
+            for bit in 0..8 {
+                let mut b = a;
+                b[i] ^= 1 << bit;
+                assert!(!ct_eq(&a, &b), "byte {i} bit {bit}");
+            }
+        }
+    }
+
+    #[test]
+    fn examines_every_byte() {
+        let a = [0; 64];
+        let mut b = [0; 64];
+        // Differs at the very first byte, where an early exit would stop
+        b[0] = 1;
+        let examined = Cell::new(0);
+        let pairs = a
+            .iter()
+            .zip(&b)
+            .inspect(|_| examined.set(examined.get() + 1));
+        assert_ne!(difference(pairs), 0);
+        assert_eq!(examined.get(), a.len());
+    }
+}
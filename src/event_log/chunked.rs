@@ -0,0 +1,269 @@
+//! Copying the firmware's event log out a chunk at a time, into a buffer of the caller's choosing,
+//! for platforms whose log is too big to copy or parse in one go. Every chunk holds whole
+//! `TCG_PCR_EVENT2`s, so [`parse_event_chunk`](super::parser::parse_event_chunk) can parse each
+//! one on its own and the buffer can be reused for the next.
+
+use core::fmt;
+
+use uefi::proto::tcg::{AlgorithmId, v2::EventLog};
+
+use super::parser::{LogEvent, MAX_EVENTS};
+use crate::tpm::alg;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkError {
+    /// The event doesn't fit in the buffer on its own
+    EventTooLarge { event_index: usize, size: usize },
+    /// The event has a digest [`parse_event_chunk`](super::parser::parse_event_chunk) couldn't
+    /// find the end of
+    UnsupportedDigest {
+        event_index: usize,
+        algorithm: AlgorithmId,
+    },
+}
+
+impl fmt::Display for ChunkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::EventTooLarge { event_index, size } => write!(
+                f,
+                "event #{event_index} is {size} bytes, more than the chunk buffer holds"
+            ),
+            Self::UnsupportedDigest {
+                event_index,
+                algorithm,
+            } => write!(
+                f,
+                "event #{event_index} has a digest of unsupported algorithm {:#06x}",
+                algorithm.0
+            ),
+        }
+    }
+}
+
+/// How many bytes `event` takes up as a `TCG_PCR_EVENT2`, after checking every digest is one
+/// the chunk parser knows the size of
+fn event_size(event_index: usize, event: &LogEvent<'_>) -> Result<usize, ChunkError> {
+    let mut size = 4 + 4 + 4;
+    for (algorithm, digest) in &event.digests {
+        let algorithm = *algorithm;
+        if alg::digest_size(algorithm) != Some(digest.len()) {
+            return Err(ChunkError::UnsupportedDigest {
+                event_index,
+                algorithm,
+            });
+        }
+        size += 2 + digest.len();
+    }
+    Ok(size + 4 + event.event_data.len())
+}
+
+/// Writes `event` as a `TCG_PCR_EVENT2` to the start of `out`, which [`event_size`] said is
+/// big enough
+fn write_event(event: &LogEvent<'_>, out: &mut [u8]) {
+    let mut position = 0;
+    let mut put = |bytes: &[u8]| {
+        out[position..position + bytes.len()].copy_from_slice(bytes);
+        position += bytes.len();
+    };
+    put(&event.pcr_index.to_le_bytes());
+    put(&event.event_type.0.to_le_bytes());
+    put(&(event.digests.len() as u32).to_le_bytes());
+    for (algorithm, digest) in &event.digests {
+        put(&algorithm.0.to_le_bytes());
+        put(digest);
+    }
+    put(&(event.event_data.len() as u32).to_le_bytes());
+    put(event.event_data);
+}
+
+/// Copies the events of `event_log` into `buffer`, calling `on_chunk` with the part of it that's
+/// filled whenever the next event doesn't fit, and once more at the end. Returns how many events
+/// were copied. Stops after [`MAX_EVENTS`], like
+/// [`collect_events`](super::parser::collect_events).
+pub fn copy_event_log_chunks(
+    event_log: &EventLog<'_>,
+    buffer: &mut [u8],
+    on_chunk: impl FnMut(&[u8]),
+) -> Result<usize, ChunkError> {
+    copy_events_in_chunks(
+        event_log.iter().map(|event| LogEvent::from(&event)),
+        buffer,
+        on_chunk,
+    )
+}
+
+/// [`copy_event_log_chunks`] for events from anywhere, like a log file parsed with
+/// [`parse_event_log`](super::parser::parse_event_log)
+pub fn copy_events_in_chunks<'a>(
+    events: impl IntoIterator<Item = LogEvent<'a>>,
+    buffer: &mut [u8],
+    mut on_chunk: impl FnMut(&[u8]),
+) -> Result<usize, ChunkError> {
+    let mut len = 0;
+    let mut copied = 0;
+    for (event_index, event) in events.into_iter().enumerate().take(MAX_EVENTS) {
+        let size = event_size(event_index, &event)?;
+        if size > buffer.len() {
+            return Err(ChunkError::EventTooLarge { event_index, size });
+        }
+        if len + size > buffer.len() {
+            on_chunk(&buffer[..len]);
+            len = 0;
+        }
+        write_event(&event, &mut buffer[len..]);
+        len += size;
+        copied += 1;
+    }
+    if len > 0 {
+        on_chunk(&buffer[..len]);
+    }
+    Ok(copied)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use uefi::proto::tcg::EventType;
+
+    use super::*;
+    use crate::event_log::parser::{SPEC_ID_EVENT03_SIGNATURE, parse_event_chunk, parse_event_log};
+
+    /// A `TCG_PCR_EVENT2` with made-up SHA-1 and SHA-256 digests
+    fn agile_event(pcr_index: u32, event_type: EventType, data: &[u8]) -> Vec<u8> {
+        let mut event = Vec::new();
+        event.extend_from_slice(&pcr_index.to_le_bytes());
+        event.extend_from_slice(&event_type.0.to_le_bytes());
+        event.extend_from_slice(&2u32.to_le_bytes());
+        event.extend_from_slice(&AlgorithmId::SHA1.0.to_le_bytes());
+        event.extend_from_slice(&[pcr_index as u8; 20]);
+        event.extend_from_slice(&AlgorithmId::SHA256.0.to_le_bytes());
+        event.extend_from_slice(&[!pcr_index as u8; 32]);
+        event.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        event.extend_from_slice(data);
+        event
+    }
+
+    /// The `Spec ID Event03` event for SHA-1 and SHA-256, then `events`
+    fn agile_log(events: &[Vec<u8>]) -> Vec<u8> {
+        let mut spec_id = Vec::from(SPEC_ID_EVENT03_SIGNATURE);
+        // platformClass, specVersionMinor, specVersionMajor, specErrata, uintnSize
+        spec_id.extend_from_slice(&[0, 0, 0, 0, 0, 2, 0, 2]);
+        spec_id.extend_from_slice(&2u32.to_le_bytes());
+        for (algorithm, size) in [(AlgorithmId::SHA1, 20u16), (AlgorithmId::SHA256, 32)] {
+            spec_id.extend_from_slice(&algorithm.0.to_le_bytes());
+            spec_id.extend_from_slice(&size.to_le_bytes());
+        }
+        // vendorInfoSize
+        spec_id.push(0);
+        let mut log = Vec::new();
+        log.extend_from_slice(&0u32.to_le_bytes());
+        log.extend_from_slice(&EventType::NO_ACTION.0.to_le_bytes());
+        log.extend_from_slice(&[0; 20]);
+        log.extend_from_slice(&(spec_id.len() as u32).to_le_bytes());
+        log.extend(spec_id);
+        for event in events {
+            log.extend_from_slice(event);
+        }
+        log
+    }
+
+    fn events() -> Vec<Vec<u8>> {
+        Vec::from([
+            agile_event(0, EventType::CRTM_VERSION, b"1.0\0"),
+            agile_event(7, EventType::EFI_VARIABLE_DRIVER_CONFIG, &[0x5A; 100]),
+            agile_event(
+                4,
+                EventType::EFI_ACTION,
+                b"Calling EFI Application from Boot Option",
+            ),
+            agile_event(4, EventType::SEPARATOR, &[0; 4]),
+            agile_event(9, EventType::IPL, &[]),
+        ])
+    }
+
+    type Summary<'a> = (u32, EventType, Vec<(AlgorithmId, &'a [u8])>, &'a [u8]);
+
+    fn summarize<'a>(events: &[LogEvent<'a>]) -> Vec<Summary<'a>> {
+        events
+            .iter()
+            .map(|event| {
+                (
+                    event.pcr_index,
+                    event.event_type,
+                    event.digests.clone(),
+                    event.event_data,
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn chunks_parse_back_to_the_same_events() {
+        let log = agile_log(&events());
+        let parsed = parse_event_log(&log).unwrap();
+        let mut buffer = [0; 200];
+        let mut chunks = Vec::new();
+        let copied = copy_events_in_chunks(parsed.iter().cloned(), &mut buffer, |chunk| {
+            chunks.push(Vec::from(chunk))
+        })
+        .unwrap();
+        assert_eq!(copied, 5);
+        assert!(chunks.len() > 1);
+        let mut from_chunks = Vec::new();
+        for chunk in &chunks {
+            from_chunks.extend(summarize(&parse_event_chunk(chunk).unwrap()));
+        }
+        assert_eq!(from_chunks, summarize(&parsed));
+    }
+
+    #[test]
+    fn one_chunk_is_the_log_without_the_spec_id_event() {
+        let events = events();
+        let log = agile_log(&events);
+        let parsed = parse_event_log(&log).unwrap();
+        let mut buffer = [0; 1024];
+        let mut chunks = Vec::new();
+        copy_events_in_chunks(parsed, &mut buffer, |chunk| chunks.push(Vec::from(chunk))).unwrap();
+        assert_eq!(chunks, [events.concat()]);
+    }
+
+    #[test]
+    fn an_event_bigger_than_the_buffer_fails() {
+        let log = agile_log(&events());
+        let parsed = parse_event_log(&log).unwrap();
+        let mut buffer = [0; 120];
+        let result = copy_events_in_chunks(parsed, &mut buffer, |_| {});
+        // 16 bytes of header, 2 + 20 and 2 + 32 of digests, and 100 of data
+        assert_eq!(
+            result,
+            Err(ChunkError::EventTooLarge {
+                event_index: 1,
+                size: 172
+            })
+        );
+    }
+
+    #[test]
+    fn digests_the_chunk_parser_cant_size_fail() {
+        let unknown = [(AlgorithmId(0x0099), &[0; 16][..])];
+        let truncated = [(AlgorithmId::SHA1, &[0; 19][..])];
+        for digests in [&unknown, &truncated] {
+            let event = LogEvent {
+                pcr_index: 0,
+                event_type: EventType::POST_CODE,
+                digests: Vec::from(*digests),
+                event_data: &[],
+            };
+            let mut buffer = [0; 64];
+            assert_eq!(
+                copy_events_in_chunks([event], &mut buffer, |_| {}),
+                Err(ChunkError::UnsupportedDigest {
+                    event_index: 0,
+                    algorithm: digests[0].0,
+                })
+            );
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! Finds kernel command lines in `Boot####` variables (measured into PCR 1) and compares them to
+//! the command lines the kernel or its stub measured into PCR 9 and 12
+
+use alloc::{format, string::String, vec::Vec};
+
+use uefi::proto::tcg::EventType;
+
+use super::{
+    load_option::{LoadOption, OptionalData},
+    variable::VariableData,
+};
+use crate::{
+    findings::{Findings, codes},
+    tpm::marshal::Reader,
+    ucs2,
+};
+
+/// The `taggedEventID` Linux's EFI stub uses when it measures its load options into PCR 9
+pub const LINUX_LOAD_OPTIONS_EVENT_TAG_ID: u32 = 0x8F3B22ED;
+
+#[derive(Debug, Clone)]
+pub struct BootEntry {
+    pub event_index: usize,
+    pub number: u16,
+    pub description: String,
+    pub optional_data: OptionalData,
+}
+
+#[derive(Debug, Clone)]
+pub struct MeasuredCmdline {
+    pub event_index: usize,
+    pub pcr_index: u32,
+    pub cmdline: String,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CmdlineAnalysis {
+    pub boot_entries: Vec<BootEntry>,
+    pub measured: Vec<MeasuredCmdline>,
+    /// Boot variable events that couldn't be decoded
+    pub malformed: Vec<(usize, String)>,
+}
+
+impl CmdlineAnalysis {
+    pub fn add_event(
+        &mut self,
+        event_index: usize,
+        pcr_index: u32,
+        event_type: EventType,
+        event_data: &[u8],
+    ) {
+        match (pcr_index, event_type) {
+            (1, EventType::EFI_VARIABLE_BOOT | EventType::EFI_VARIABLE_BOOT2) => {
+                let Some(variable) = VariableData::parse(event_data) else {
+                    self.malformed.push((
+                        event_index,
+                        "boot variable is not a UEFI_VARIABLE_DATA".into(),
+                    ));
+                    return;
+                };
+                let Some(number) = variable.boot_option_number() else {
+                    return;
+                };
+                let Some(load_option) = LoadOption::parse(variable.data) else {
+                    self.malformed.push((
+                        event_index,
+                        format!("Boot{number:04X} is not a valid EFI_LOAD_OPTION"),
+                    ));
+                    return;
+                };
+                self.boot_entries.push(BootEntry {
+                    event_index,
+                    number,
+                    description: load_option.description(),
+                    optional_data: load_option.optional_data_text(),
+                });
+            }
+            (9, EventType::EVENT_TAG) => {
+                let mut reader = Reader::new(event_data);
+                let (Ok(LINUX_LOAD_OPTIONS_EVENT_TAG_ID), Ok(size)) =
+                    (reader.u32_le(), reader.u32_le())
+                else {
+                    return;
+                };
+                if let Ok(data) = reader.bytes(size as usize) {
+                    self.measured.push(MeasuredCmdline {
+                        event_index,
+                        pcr_index,
+                        cmdline: ucs2::decode_lossy(data),
+                    });
+                }
+            }
+            (12, EventType::IPL) if ucs2::is_text(event_data) => {
+                self.measured.push(MeasuredCmdline {
+                    event_index,
+                    pcr_index,
+                    cmdline: ucs2::decode_lossy(event_data),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    pub fn report(&self, findings: &mut Findings) {
+        for (event_index, message) in &self.malformed {
+            findings.add(
+                &codes::LOG_MALFORMED_EVENT,
+                Some(1),
+                Some(*event_index),
+                message.clone(),
+            );
+        }
+        for entry in &self.boot_entries {
+            log::debug!(
+                "Boot{:04X} {:?}: optional data {}",
+                entry.number,
+                entry.description,
+                entry.optional_data
+            );
+            if entry.optional_data != OptionalData::Empty {
+                findings.add(
+                    &codes::CMDLINE_IN_BOOT_ENTRY,
+                    Some(1),
+                    Some(entry.event_index),
+                    format!(
+                        "Boot{:04X} has optional data {}, so PCR 1 will change if this command line changes",
+                        entry.number, entry.optional_data
+                    ),
+                );
+            }
+        }
+        for measured in &self.measured {
+            let matching_entry = self.boot_entries.iter().find(|entry| {
+                entry
+                    .optional_data
+                    .text()
+                    .is_some_and(|text| text.trim() == measured.cmdline.trim())
+            });
+            match matching_entry {
+                Some(entry) => findings.add(
+                    &codes::CMDLINE_MATCH,
+                    Some(measured.pcr_index),
+                    Some(measured.event_index),
+                    format!("command line matches Boot{:04X}", entry.number),
+                ),
+                None => findings.add(
+                    &codes::CMDLINE_MISMATCH,
+                    Some(measured.pcr_index),
+                    Some(measured.event_index),
+                    format!(
+                        "command line doesn't match any boot entry: {:?}",
+                        measured.cmdline
+                    ),
+                ),
+            }
+        }
+    }
+}
@@ -0,0 +1,260 @@
+//! Device paths as text, for the ones in event data like `UEFI_IMAGE_LOAD_EVENT`. These are only
+//! bytes, maybe describing a device that's gone, so the firmware's `DevicePathToText` can't be
+//! used on them. The common nodes are rendered like it would, anything else as
+//! `Path(type,subtype)`:
+//!
+//! ```text
+//! PciRoot(0x0)/Pci(0x1D,0x0)/HD(1,GPT,...)/\EFI\BOOT\BOOTX64.EFI
+//! ```
+
+use alloc::string::String;
+use core::fmt::Write;
+
+use hex_slice::AsHex;
+use uefi::Guid;
+
+use crate::{tpm::marshal::Reader, ucs2};
+
+/// The most text a device path is rendered to. Longer ones end in `...`.
+pub const MAX_DEVICE_PATH_TEXT: usize = 512;
+
+/// More nodes than any real device path has
+const MAX_NODES: usize = 64;
+
+const HARDWARE: u8 = 0x01;
+const ACPI: u8 = 0x02;
+const MESSAGING: u8 = 0x03;
+const MEDIA: u8 = 0x04;
+const END: u8 = 0x7F;
+
+/// `EFI_END_INSTANCE_DEVICE_PATH`, between the paths of a multi-instance device path
+const END_INSTANCE: u8 = 0x01;
+
+/// `EISA_PNP_ID(0x0A03)` and `EISA_PNP_ID(0x0A08)`, the PCI and PCI Express root bridges
+const PNP0A03: u32 = 0x0A0341D0;
+const PNP0A08: u32 = 0x0A0841D0;
+
+fn guid(data: &[u8]) -> Option<Guid> {
+    Some(Guid::from_bytes(data.get(..16)?.try_into().unwrap()))
+}
+
+/// Writes one node. `None` if it's too short for its type.
+fn render_node(node_type: u8, subtype: u8, data: &[u8], out: &mut String) -> Option<()> {
+    let mut reader = Reader::new(data);
+    match (node_type, subtype) {
+        (HARDWARE, 0x01) => {
+            let function = reader.u8().ok()?;
+            let device = reader.u8().ok()?;
+            write!(out, "Pci({device:#X},{function:#X})").ok()
+        }
+        (ACPI, 0x01) => {
+            let hid = reader.u32_le().ok()?;
+            let uid = reader.u32_le().ok()?;
+            match hid {
+                PNP0A03 => write!(out, "PciRoot({uid:#X})"),
+                PNP0A08 => write!(out, "PcieRoot({uid:#X})"),
+                _ => write!(out, "Acpi({hid:#010X},{uid:#X})"),
+            }
+            .ok()
+        }
+        (MESSAGING, 0x05) => {
+            let parent_port = reader.u8().ok()?;
+            let interface = reader.u8().ok()?;
+            write!(out, "USB({parent_port:#X},{interface:#X})").ok()
+        }
+        (MESSAGING, 0x0B) => {
+            let address = reader.bytes(32).ok()?;
+            let if_type = reader.u8().ok()?;
+            // Ethernet and IEEE 802 addresses are 6 bytes, padded with zeros
+            let len = if if_type <= 1 { 6 } else { 32 };
+            write!(
+                out,
+                "MAC({:02x},{if_type:#X})",
+                address[..len].plain_hex(false)
+            )
+            .ok()
+        }
+        (MEDIA, 0x01) => {
+            let partition = reader.u32_le().ok()?;
+            let start = reader.u64_le().ok()?;
+            let size = reader.u64_le().ok()?;
+            let signature = reader.bytes(16).ok()?;
+            let _format = reader.u8().ok()?;
+            match reader.u8().ok()? {
+                1 => write!(
+                    out,
+                    "HD({partition},MBR,{:#010X},{start:#X},{size:#X})",
+                    u32::from_le_bytes(signature[..4].try_into().unwrap())
+                ),
+                2 => write!(
+                    out,
+                    "HD({partition},GPT,{},{start:#X},{size:#X})",
+                    guid(signature)?
+                ),
+                _ => write!(out, "HD({partition},{start:#X},{size:#X})"),
+            }
+            .ok()
+        }
+        (MEDIA, 0x04) => {
+            out.push_str(&ucs2::decode_lossy(data));
+            Some(())
+        }
+        // The PI spec's firmware file and volume, where firmware drivers are loaded from
+        (MEDIA, 0x06) => write!(out, "FvFile({})", guid(data)?).ok(),
+        (MEDIA, 0x07) => write!(out, "Fv({})", guid(data)?).ok(),
+        _ => write!(out, "Path({node_type},{subtype})").ok(),
+    }
+}
+
+/// Renders the device path `path`, node by node, onto `out`. Stops at the end node, at a node
+/// whose length doesn't fit, or at [`MAX_DEVICE_PATH_TEXT`].
+pub fn render_device_path(path: &[u8], out: &mut String) {
+    let start = out.len();
+    let mut reader = Reader::new(path);
+    let mut separator = "";
+    for _ in 0..MAX_NODES {
+        let (Ok(node_type), Ok(subtype), Ok(length)) = (reader.u8(), reader.u8(), reader.u16_le())
+        else {
+            return;
+        };
+        let Some(data) = (length as usize)
+            .checked_sub(4)
+            .and_then(|len| reader.bytes(len).ok())
+        else {
+            out.push_str(separator);
+            out.push_str("<malformed>");
+            return;
+        };
+        if node_type == END {
+            if subtype != END_INSTANCE {
+                return;
+            }
+            separator = ",";
+            continue;
+        }
+        out.push_str(separator);
+        let node_start = out.len();
+        if render_node(node_type, subtype, data, out).is_none() {
+            out.truncate(node_start);
+            out.push_str("<malformed>");
+            return;
+        }
+        if out.len() - start > MAX_DEVICE_PATH_TEXT {
+            let mut end = start + MAX_DEVICE_PATH_TEXT;
+            while !out.is_char_boundary(end) {
+                end -= 1;
+            }
+            out.truncate(end);
+            out.push_str("...");
+            return;
+        }
+        separator = "/";
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use uefi::guid;
+
+    use super::*;
+
+    fn node(node_type: u8, subtype: u8, data: &[u8]) -> Vec<u8> {
+        let mut node = Vec::from([node_type, subtype]);
+        node.extend_from_slice(&(4 + data.len() as u16).to_le_bytes());
+        node.extend_from_slice(data);
+        node
+    }
+
+    fn end() -> Vec<u8> {
+        node(END, 0xFF, &[])
+    }
+
+    fn file_path(path: &str) -> Vec<u8> {
+        let data = path
+            .encode_utf16()
+            .chain([0])
+            .flat_map(u16::to_le_bytes)
+            .collect::<Vec<_>>();
+        node(MEDIA, 0x04, &data)
+    }
+
+    fn render(path: &[u8]) -> String {
+        let mut text = String::new();
+        render_device_path(path, &mut text);
+        text
+    }
+
+    #[test]
+    fn boot_loader_path() {
+        let partition = guid!("f0e1d2c3-b4a5-4697-8879-6a5b4c3d2e1f");
+        let mut hd = Vec::new();
+        hd.extend_from_slice(&1u32.to_le_bytes());
+        hd.extend_from_slice(&0x800u64.to_le_bytes());
+        hd.extend_from_slice(&0x100000u64.to_le_bytes());
+        hd.extend_from_slice(&partition.to_bytes());
+        // GPT partition format and signature type
+        hd.extend_from_slice(&[2, 2]);
+        let path = [
+            node(ACPI, 0x01, &[0xD0, 0x41, 0x03, 0x0A, 0, 0, 0, 0]),
+            node(HARDWARE, 0x01, &[0x00, 0x1D]),
+            node(MEDIA, 0x01, &hd),
+            file_path("\\EFI\\BOOT\\BOOTX64.EFI"),
+            end(),
+        ]
+        .concat();
+        assert_eq!(
+            render(&path),
+            "PciRoot(0x0)/Pci(0x1D,0x0)/\
+             HD(1,GPT,f0e1d2c3-b4a5-4697-8879-6a5b4c3d2e1f,0x800,0x100000)/\
+             \\EFI\\BOOT\\BOOTX64.EFI"
+        );
+    }
+
+    #[test]
+    fn mac_address_is_six_bytes_for_ethernet() {
+        let mut mac = Vec::from([0x00, 0x11, 0x22, 0xaa, 0xbb, 0xcc]);
+        mac.resize(32, 0);
+        mac.push(1);
+        let path = [node(MESSAGING, 0x0B, &mac), end()].concat();
+        assert_eq!(render(&path), "MAC(001122aabbcc,0x1)");
+    }
+
+    #[test]
+    fn unknown_nodes_are_named_by_type() {
+        let path = [node(0x05, 0x01, &[0; 4]), end()].concat();
+        assert_eq!(render(&path), "Path(5,1)");
+    }
+
+    #[test]
+    fn instances_are_separated_by_commas() {
+        let path = [
+            node(MESSAGING, 0x05, &[1, 0]),
+            node(END, END_INSTANCE, &[]),
+            node(MESSAGING, 0x05, &[2, 0]),
+            end(),
+        ]
+        .concat();
+        assert_eq!(render(&path), "USB(0x1,0x0),USB(0x2,0x0)");
+    }
+
+    #[test]
+    fn short_nodes_are_malformed() {
+        // A PCI node without its device
+        let path = [node(MESSAGING, 0x05, &[1, 0]), node(HARDWARE, 0x01, &[0])].concat();
+        assert_eq!(render(&path), "USB(0x1,0x0)/<malformed>");
+        // A node longer than the path
+        let mut path = node(MESSAGING, 0x05, &[1, 0]);
+        path[2] = 0x40;
+        assert_eq!(render(&path), "<malformed>");
+    }
+
+    #[test]
+    fn long_paths_are_cut_off() {
+        let path = [file_path(&"a".repeat(600)), end()].concat();
+        let text = render(&path);
+        assert_eq!(text.len(), MAX_DEVICE_PATH_TEXT + 3);
+        assert!(text.ends_with("a..."));
+    }
+}
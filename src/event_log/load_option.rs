@@ -0,0 +1,91 @@
+use alloc::string::String;
+use core::fmt;
+
+use crate::ucs2;
+
+/// `EFI_LOAD_OPTION`, the contents of a `Boot####` variable
+#[derive(Debug, Clone, Copy)]
+pub struct LoadOption<'a> {
+    pub attributes: u32,
+    /// UCS-2 including the null terminator
+    pub description: &'a [u8],
+    pub file_path_list: &'a [u8],
+    /// Whatever the creator of the boot entry wanted to pass to the image, often a kernel command
+    /// line
+    pub optional_data: &'a [u8],
+}
+
+impl<'a> LoadOption<'a> {
+    pub const ACTIVE: u32 = 0x00000001;
+
+    pub fn parse(data: &'a [u8]) -> Option<Self> {
+        let attributes = u32::from_le_bytes(data.get(..4)?.try_into().unwrap());
+        let file_path_list_length = u16::from_le_bytes(data.get(4..6)?.try_into().unwrap());
+        let rest = &data[6..];
+        let (description, rest) = rest.split_at(ucs2::terminated_len(rest)?);
+        let (file_path_list, optional_data) =
+            rest.split_at_checked(file_path_list_length as usize)?;
+        Some(Self {
+            attributes,
+            description,
+            file_path_list,
+            optional_data,
+        })
+    }
+
+    pub fn description(&self) -> String {
+        ucs2::decode_lossy(self.description)
+    }
+
+    pub fn optional_data_text(&self) -> OptionalData {
+        OptionalData::decode(self.optional_data)
+    }
+}
+
+/// A best-effort interpretation of a load option's optional data
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptionalData {
+    Empty,
+    Ucs2(String),
+    Ascii(String),
+    Binary(usize),
+}
+
+impl OptionalData {
+    pub fn decode(data: &[u8]) -> Self {
+        if data.is_empty() {
+            Self::Empty
+        } else if ucs2::is_text(data) {
+            Self::Ucs2(ucs2::decode_lossy(data))
+        } else if let Some(text) = ascii_text(data) {
+            Self::Ascii(text)
+        } else {
+            Self::Binary(data.len())
+        }
+    }
+
+    pub fn text(&self) -> Option<&str> {
+        match self {
+            Self::Ucs2(text) | Self::Ascii(text) => Some(text),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for OptionalData {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => f.write_str("(none)"),
+            Self::Ucs2(text) => write!(f, "{text:?} (UCS-2)"),
+            Self::Ascii(text) => write!(f, "{text:?} (ASCII)"),
+            Self::Binary(len) => write!(f, "{len} bytes of binary data"),
+        }
+    }
+}
+
+fn ascii_text(data: &[u8]) -> Option<String> {
+    let data = data.strip_suffix(&[0]).unwrap_or(data);
+    data.iter()
+        .all(|byte| byte.is_ascii_graphic() || *byte == b' ')
+        .then(|| data.iter().map(|byte| *byte as char).collect())
+}
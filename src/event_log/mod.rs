@@ -0,0 +1,12 @@
+pub mod chunked;
+#[cfg(feature = "decoders")]
+pub mod cmdline;
+#[cfg(feature = "decoders")]
+pub mod device_path;
+#[cfg(feature = "decoders")]
+pub mod load_option;
+pub mod parser;
+pub mod signature_list;
+#[cfg(feature = "decoders")]
+pub mod sp800_155;
+pub mod variable;
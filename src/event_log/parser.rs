@@ -0,0 +1,381 @@
+//! Parses an event log from bytes, like Linux's `binary_bios_measurements`, so the analysis can
+//! run on a log copied from another machine. Events from the firmware's log are converted to the
+//! same [`LogEvent`] so both go through the same passes.
+
+use alloc::vec::Vec;
+
+use uefi::proto::tcg::{
+    AlgorithmId, EventType,
+    v2::{EventLog, PcrEvent},
+};
+
+use crate::tpm::{TpmError, alg, marshal::Reader};
+
+/// The signature of the `TCG_EfiSpecIDEvent` at the start of a crypto agile log
+pub const SPEC_ID_EVENT03_SIGNATURE: [u8; 16] = *b"Spec ID Event03\0";
+
+/// The `pcrIndex` some logs give events that aren't extended into any PCR, instead of a real one
+pub const NO_PCR: u32 = 0xFFFF_FFFF;
+
+/// Far more events than any firmware logs. A crafted log can't make us iterate past this.
+pub const MAX_EVENTS: usize = 100_000;
+
+/// The smallest `TCG_PCR_EVENT`: the PCR index, event type, SHA-1 digest and event size
+const SHA1_EVENT_HEADER_SIZE: usize = 4 + 4 + 20 + 4;
+/// The smallest `TCG_PCR_EVENT2`: the PCR index, event type, digest count and event size
+const CRYPTO_AGILE_EVENT_HEADER_SIZE: usize = 4 + 4 + 4 + 4;
+
+/// A `TCG_PCR_EVENT2`, or a `TCG_PCR_EVENT` with its SHA-1 digest
+#[derive(Debug, Clone)]
+pub struct LogEvent<'a> {
+    pub pcr_index: u32,
+    pub event_type: EventType,
+    pub digests: Vec<(AlgorithmId, &'a [u8])>,
+    pub event_data: &'a [u8],
+}
+
+impl<'a> LogEvent<'a> {
+    pub fn digest(&self, algorithm: AlgorithmId) -> Option<&'a [u8]> {
+        self.digests
+            .iter()
+            .find(|(digest_algorithm, _)| *digest_algorithm == algorithm)
+            .map(|(_, digest)| *digest)
+    }
+}
+
+impl<'a> From<&PcrEvent<'a>> for LogEvent<'a> {
+    fn from(event: &PcrEvent<'a>) -> Self {
+        Self {
+            pcr_index: event.pcr_index().0,
+            event_type: event.event_type(),
+            digests: event.digests().into_iter().collect(),
+            event_data: event.event_data(),
+        }
+    }
+}
+
+/// Parses a whole log. A crypto agile log starts with a SHA-1 format `Spec ID Event03` event
+/// giving the digest sizes, which is left out of the result like the firmware's log does.
+/// Otherwise every event is in the SHA-1 format.
+pub fn parse_event_log(bytes: &[u8]) -> Result<Vec<LogEvent<'_>>, TpmError> {
+    let mut reader = Reader::new(bytes);
+    let first = read_sha1_event(&mut reader)?;
+    let mut events = Vec::new();
+    match spec_id_digest_sizes(&first)? {
+        Some(digest_sizes) => read_events(
+            &mut reader,
+            &mut events,
+            CRYPTO_AGILE_EVENT_HEADER_SIZE,
+            |reader| read_crypto_agile_event(reader, &digest_sizes),
+        )?,
+        None => {
+            events.push(first);
+            read_events(&mut reader, &mut events, SHA1_EVENT_HEADER_SIZE, |reader| {
+                Ok(read_sha1_event(reader)?)
+            })?;
+        }
+    }
+    Ok(events)
+}
+
+/// Parses one chunk [`copy_event_log_chunks`] copied out of the firmware's log: whole
+/// `TCG_PCR_EVENT2`s, with digests only for the algorithms in [`alg::HASH_ALGORITHMS`], so their
+/// sizes are known without the spec ID event.
+///
+/// [`copy_event_log_chunks`]: super::chunked::copy_event_log_chunks
+pub fn parse_event_chunk(chunk: &[u8]) -> Result<Vec<LogEvent<'_>>, TpmError> {
+    let digest_sizes = alg::HASH_ALGORITHMS
+        .into_iter()
+        .filter_map(|algorithm| Some((algorithm, alg::digest_size(algorithm)? as u16)))
+        .collect::<Vec<_>>();
+    let mut events = Vec::new();
+    read_events(
+        &mut Reader::new(chunk),
+        &mut events,
+        CRYPTO_AGILE_EVENT_HEADER_SIZE,
+        |reader| read_crypto_agile_event(reader, &digest_sizes),
+    )?;
+    Ok(events)
+}
+
+/// Why an event couldn't be read
+enum ReadEventError {
+    /// The event claims more digests than the log has algorithms. Its digest count is corrupt, so
+    /// where it ends, and where the next event starts, can't be known.
+    DigestCount {
+        count: u32,
+        algorithms: usize,
+    },
+    Tpm(TpmError),
+}
+
+impl From<TpmError> for ReadEventError {
+    fn from(error: TpmError) -> Self {
+        Self::Tpm(error)
+    }
+}
+
+/// Reads events until the end of the log. Stops early, with an error logged, after
+/// [`MAX_EVENTS`], at an event with an impossible digest count, or if an event was read without
+/// moving past at least `header_size` bytes, so no log can keep us here forever or have us read
+/// garbage as events.
+fn read_events<'a>(
+    reader: &mut Reader<'a>,
+    events: &mut Vec<LogEvent<'a>>,
+    header_size: usize,
+    mut read_event: impl FnMut(&mut Reader<'a>) -> Result<LogEvent<'a>, ReadEventError>,
+) -> Result<(), TpmError> {
+    while !reader.is_empty() {
+        if events.len() >= MAX_EVENTS {
+            log::error!(
+                "Stopped reading the event log after {MAX_EVENTS} events, with {} bytes left",
+                reader.remaining().len()
+            );
+            break;
+        }
+        let start = reader.position();
+        let event = match read_event(reader) {
+            Ok(event) => event,
+            Err(ReadEventError::DigestCount { count, algorithms }) => {
+                log::error!(
+                    "Stopped reading the event log at offset {start}: the event there claims {count} digests, but the log only has {algorithms} algorithms"
+                );
+                break;
+            }
+            Err(ReadEventError::Tpm(e)) => return Err(e),
+        };
+        if reader.position() < start + header_size {
+            log::error!(
+                "Stopped reading the event log at offset {start}: the event there took up {} bytes",
+                reader.position() - start
+            );
+            break;
+        }
+        events.push(event);
+    }
+    Ok(())
+}
+
+/// The firmware's log, as [`LogEvent`]s. Stops after [`MAX_EVENTS`], with an error logged.
+pub fn collect_events<'a>(event_log: &EventLog<'a>) -> Vec<LogEvent<'a>> {
+    let mut events = Vec::new();
+    for event in event_log.iter() {
+        if events.len() >= MAX_EVENTS {
+            log::error!("Stopped reading the event log after {MAX_EVENTS} events");
+            break;
+        }
+        events.push(LogEvent::from(&event));
+    }
+    events
+}
+
+/// `TCG_PCR_EVENT`
+fn read_sha1_event<'a>(reader: &mut Reader<'a>) -> Result<LogEvent<'a>, TpmError> {
+    let pcr_index = reader.u32_le()?;
+    let event_type = EventType(reader.u32_le()?);
+    let digest = reader.bytes(20)?;
+    let event_size = reader.u32_le()?;
+    Ok(LogEvent {
+        pcr_index,
+        event_type,
+        digests: Vec::from([(AlgorithmId::SHA1, digest)]),
+        event_data: reader.bytes(event_size as usize)?,
+    })
+}
+
+/// `TCG_PCR_EVENT2`. Digests don't have their size, so it comes from the spec ID event.
+fn read_crypto_agile_event<'a>(
+    reader: &mut Reader<'a>,
+    digest_sizes: &[(AlgorithmId, u16)],
+) -> Result<LogEvent<'a>, ReadEventError> {
+    let pcr_index = reader.u32_le()?;
+    let event_type = EventType(reader.u32_le()?);
+    let count = reader.u32_le()?;
+    // Each algorithm's digest is in an event at most once
+    if count as usize > digest_sizes.len() {
+        return Err(ReadEventError::DigestCount {
+            count,
+            algorithms: digest_sizes.len(),
+        });
+    }
+    let mut digests = Vec::new();
+    for _ in 0..count {
+        let algorithm = AlgorithmId(reader.u16_le()?);
+        let (_, size) = digest_sizes
+            .iter()
+            .find(|(spec_algorithm, _)| *spec_algorithm == algorithm)
+            .ok_or(TpmError::Malformed)?;
+        digests.push((algorithm, reader.bytes(*size as usize)?));
+    }
+    let event_size = reader.u32_le()?;
+    Ok(LogEvent {
+        pcr_index,
+        event_type,
+        digests,
+        event_data: reader.bytes(event_size as usize)?,
+    })
+}
+
+/// The `digestSizes` of a `TCG_EfiSpecIDEvent`, or `None` if `event` isn't one
+fn spec_id_digest_sizes(event: &LogEvent<'_>) -> Result<Option<Vec<(AlgorithmId, u16)>>, TpmError> {
+    if event.pcr_index != 0
+        || event.event_type != EventType::NO_ACTION
+        || !event.event_data.starts_with(&SPEC_ID_EVENT03_SIGNATURE)
+    {
+        return Ok(None);
+    }
+    let mut reader = Reader::new(&event.event_data[SPEC_ID_EVENT03_SIGNATURE.len()..]);
+    // platformClass, specVersionMinor, specVersionMajor, specErrata, uintnSize
+    reader.bytes(4 + 1 + 1 + 1 + 1)?;
+    let count = reader.u32_le()?;
+    let mut digest_sizes = Vec::new();
+    for _ in 0..count {
+        digest_sizes.push((AlgorithmId(reader.u16_le()?), reader.u16_le()?));
+    }
+    Ok(Some(digest_sizes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A `TCG_PCR_EVENT2` with SHA-1 and SHA-256 digests
+    fn agile_event(pcr_index: u32, event_type: EventType, data: &[u8]) -> Vec<u8> {
+        let mut event = Vec::new();
+        event.extend_from_slice(&pcr_index.to_le_bytes());
+        event.extend_from_slice(&event_type.0.to_le_bytes());
+        event.extend_from_slice(&2u32.to_le_bytes());
+        event.extend_from_slice(&AlgorithmId::SHA1.0.to_le_bytes());
+        event.extend_from_slice(&[0x11; 20]);
+        event.extend_from_slice(&AlgorithmId::SHA256.0.to_le_bytes());
+        event.extend_from_slice(&[0x22; 32]);
+        event.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        event.extend_from_slice(data);
+        event
+    }
+
+    /// The `Spec ID Event03` event for SHA-1 and SHA-256, then `events`
+    fn agile_log(events: &[Vec<u8>]) -> Vec<u8> {
+        let mut spec_id = Vec::from(SPEC_ID_EVENT03_SIGNATURE);
+        // platformClass, specVersionMinor, specVersionMajor, specErrata, uintnSize
+        spec_id.extend_from_slice(&[0, 0, 0, 0, 0, 2, 0, 2]);
+        spec_id.extend_from_slice(&2u32.to_le_bytes());
+        for (algorithm, size) in [(AlgorithmId::SHA1, 20u16), (AlgorithmId::SHA256, 32)] {
+            spec_id.extend_from_slice(&algorithm.0.to_le_bytes());
+            spec_id.extend_from_slice(&size.to_le_bytes());
+        }
+        // vendorInfoSize
+        spec_id.push(0);
+        let mut log = Vec::new();
+        log.extend_from_slice(&0u32.to_le_bytes());
+        log.extend_from_slice(&EventType::NO_ACTION.0.to_le_bytes());
+        log.extend_from_slice(&[0; 20]);
+        log.extend_from_slice(&(spec_id.len() as u32).to_le_bytes());
+        log.extend(spec_id);
+        for event in events {
+            log.extend_from_slice(event);
+        }
+        log
+    }
+
+    #[test]
+    fn crypto_agile_log_leaves_out_the_spec_id_event() {
+        let log = agile_log(&[
+            agile_event(0, EventType::CRTM_VERSION, b"1.0\0"),
+            agile_event(7, EventType::SEPARATOR, &[0; 4]),
+        ]);
+        let events = parse_event_log(&log).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].pcr_index, 7);
+        assert_eq!(events[1].event_type, EventType::SEPARATOR);
+        assert_eq!(events[1].digest(AlgorithmId::SHA256), Some(&[0x22; 32][..]));
+        assert_eq!(events[1].event_data, [0; 4]);
+    }
+
+    #[test]
+    fn impossible_digest_count_stops_the_log() {
+        let mut corrupt = agile_event(4, EventType::EFI_ACTION, b"action");
+        // More digests than the log has algorithms
+        corrupt[8..12].copy_from_slice(&3u32.to_le_bytes());
+        let log = agile_log(&[
+            agile_event(0, EventType::CRTM_VERSION, b"1.0\0"),
+            corrupt,
+            agile_event(7, EventType::SEPARATOR, &[0; 4]),
+        ]);
+        let events = parse_event_log(&log).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].event_type, EventType::CRTM_VERSION);
+    }
+
+    #[test]
+    fn truncated_event_fails_the_log() {
+        let mut log = agile_log(&[agile_event(0, EventType::CRTM_VERSION, b"1.0\0")]);
+        log.truncate(log.len() - 1);
+        assert!(matches!(
+            parse_event_log(&log),
+            Err(TpmError::UnexpectedEnd)
+        ));
+    }
+
+    fn empty_event<'a>() -> LogEvent<'a> {
+        LogEvent {
+            pcr_index: 0,
+            event_type: EventType::NO_ACTION,
+            digests: Vec::new(),
+            event_data: &[],
+        }
+    }
+
+    #[test]
+    fn zero_advance_event_stops_the_loop() {
+        let log = [0; 64];
+        let mut events = Vec::new();
+        let mut calls = 0;
+        read_events(
+            &mut Reader::new(&log),
+            &mut events,
+            CRYPTO_AGILE_EVENT_HEADER_SIZE,
+            |_| {
+                calls += 1;
+                Ok(empty_event())
+            },
+        )
+        .unwrap();
+        assert_eq!(calls, 1);
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn event_shorter_than_a_header_stops_the_loop() {
+        let log = [0; 64];
+        let mut events = Vec::new();
+        read_events(
+            &mut Reader::new(&log),
+            &mut events,
+            SHA1_EVENT_HEADER_SIZE,
+            |reader| {
+                reader.bytes(CRYPTO_AGILE_EVENT_HEADER_SIZE)?;
+                Ok(empty_event())
+            },
+        )
+        .unwrap();
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn events_stop_at_the_cap() {
+        let log = alloc::vec![0; (MAX_EVENTS + 3) * CRYPTO_AGILE_EVENT_HEADER_SIZE];
+        let mut events = Vec::new();
+        read_events(
+            &mut Reader::new(&log),
+            &mut events,
+            CRYPTO_AGILE_EVENT_HEADER_SIZE,
+            |reader| {
+                reader.bytes(CRYPTO_AGILE_EVENT_HEADER_SIZE)?;
+                Ok(empty_event())
+            },
+        )
+        .unwrap();
+        assert_eq!(events.len(), MAX_EVENTS);
+    }
+}
@@ -0,0 +1,287 @@
+//! `EFI_SIGNATURE_LIST`s, which is what `PK`, `KEK`, `db` and `dbx` hold: certificates or hashes,
+//! grouped into lists of one type
+
+use core::ops::Range;
+
+use uefi::{Guid, guid, runtime::VariableVendor};
+
+use super::variable::VariableData;
+use crate::tpm::marshal::Reader;
+
+/// `EFI_CERT_X509_GUID`: each signature is a DER X.509 certificate
+pub const EFI_CERT_X509_GUID: Guid = guid!("a5c059a1-94e4-4aa7-87b5-ab155c2bf072");
+/// `EFI_CERT_SHA256_GUID`: each signature is the SHA-256 Authenticode hash of an image
+pub const EFI_CERT_SHA256_GUID: Guid = guid!("c1c41626-504c-4092-aca9-41f936934328");
+pub const EFI_CERT_SHA1_GUID: Guid = guid!("826ca512-cf10-4ac9-b187-be01496631bd");
+pub const EFI_CERT_SHA384_GUID: Guid = guid!("ff3e5307-9fd0-48c9-85f1-8ad56c701e01");
+pub const EFI_CERT_SHA512_GUID: Guid = guid!("093e0fae-a6c4-4f50-9f1b-d41e2b89c19a");
+/// `EFI_CERT_X509_SHA256_GUID`: each signature is the SHA-256 of a certificate's
+/// `TBSCertificate`, followed by when it was revoked
+pub const EFI_CERT_X509_SHA256_GUID: Guid = guid!("3bd2a492-96c0-4079-b420-fcf98ef103ed");
+/// `EFI_CERT_RSA2048_GUID`: each signature is a bare RSA-2048 modulus
+pub const EFI_CERT_RSA2048_GUID: Guid = guid!("3c5766e8-269c-4e34-aa14-ed776e85b3b6");
+
+/// The `SignatureType`s we know, with their names
+const SIGNATURE_TYPES: [(Guid, &str); 7] = [
+    (EFI_CERT_X509_GUID, "X509"),
+    (EFI_CERT_SHA256_GUID, "SHA256"),
+    (EFI_CERT_SHA1_GUID, "SHA1"),
+    (EFI_CERT_SHA384_GUID, "SHA384"),
+    (EFI_CERT_SHA512_GUID, "SHA512"),
+    (EFI_CERT_X509_SHA256_GUID, "X509_SHA256"),
+    (EFI_CERT_RSA2048_GUID, "RSA2048"),
+];
+
+/// The name of a `SignatureType`, like `EFI_CERT_SHA256_GUID`'s `SHA256`
+pub fn signature_type_name(signature_type: Guid) -> Option<&'static str> {
+    SIGNATURE_TYPES
+        .iter()
+        .find(|(guid, _)| *guid == signature_type)
+        .map(|(_, name)| *name)
+}
+
+/// `PK`, `KEK`, `db` or `dbx`, the variables that hold signature lists
+pub fn is_signature_database(variable: &VariableData<'_>) -> bool {
+    (variable.vendor == VariableVendor::GLOBAL_VARIABLE.0
+        && (variable.name_eq("PK") || variable.name_eq("KEK")))
+        || (variable.vendor == VariableVendor::IMAGE_SECURITY_DATABASE.0
+            && (variable.name_eq("db") || variable.name_eq("dbx")))
+}
+
+/// `EFI_SIGNATURE_DATA`
+#[derive(Debug, Clone)]
+pub struct SignatureEntry<'a> {
+    /// The `SignatureType` of the list it's in
+    pub signature_type: Guid,
+    pub owner: Guid,
+    pub data: &'a [u8],
+    /// Where `data` is in the variable's data
+    pub range: Range<usize>,
+    /// Which list in the variable, and which signature in that list
+    pub list_index: usize,
+    pub entry_index: usize,
+}
+
+impl SignatureEntry<'_> {
+    pub fn is_x509(&self) -> bool {
+        self.signature_type == EFI_CERT_X509_GUID
+    }
+
+    /// Whether `data` is a hash of an image, rather than a key or a certificate
+    pub fn is_hash(&self) -> bool {
+        [
+            EFI_CERT_SHA256_GUID,
+            EFI_CERT_SHA1_GUID,
+            EFI_CERT_SHA384_GUID,
+            EFI_CERT_SHA512_GUID,
+        ]
+        .contains(&self.signature_type)
+    }
+}
+
+/// The list [`SignatureLists`] is in the middle of
+#[derive(Debug, Clone, Copy)]
+struct CurrentList {
+    signature_type: Guid,
+    signature_size: usize,
+    /// Where the next signature starts
+    next: usize,
+    end: usize,
+}
+
+/// Iterates over the signatures in a variable's data. Stops at the first malformed list, which
+/// [`is_malformed`](Self::is_malformed) says afterwards.
+#[derive(Debug, Clone)]
+pub struct SignatureLists<'a> {
+    data: &'a [u8],
+    malformed: bool,
+    /// Where the next list starts
+    list_offset: usize,
+    list_index: usize,
+    list: Option<CurrentList>,
+    entry_index: usize,
+}
+
+impl<'a> SignatureLists<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            malformed: false,
+            list_offset: 0,
+            list_index: 0,
+            list: None,
+            entry_index: 0,
+        }
+    }
+
+    /// Whether a list header was truncated or had sizes that don't fit in the data, or a list had
+    /// bytes left over after its last whole signature. Every signature before that was returned.
+    pub fn is_malformed(&self) -> bool {
+        self.malformed
+    }
+
+    /// Reads the header of the list at `list_offset`
+    fn read_list(&self) -> Option<CurrentList> {
+        let mut reader = Reader::new(self.data.get(self.list_offset..)?);
+        let signature_type = Guid::from_bytes(reader.array().ok()?);
+        let list_size = reader.u32_le().ok()? as usize;
+        let header_size = reader.u32_le().ok()? as usize;
+        let signature_size = reader.u32_le().ok()? as usize;
+        let next = self.list_offset + 28usize.checked_add(header_size)?;
+        let end = self.list_offset.checked_add(list_size)?;
+        // `SignatureOwner` is part of every signature
+        if signature_size < 16 || next > end || end > self.data.len() {
+            return None;
+        }
+        Some(CurrentList {
+            signature_type,
+            signature_size,
+            next,
+            end,
+        })
+    }
+}
+
+impl<'a> Iterator for SignatureLists<'a> {
+    type Item = SignatureEntry<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let list = match self.list {
+                Some(list) => list,
+                None if self.list_offset < self.data.len() => {
+                    let Some(list) = self.read_list() else {
+                        self.list_offset = self.data.len();
+                        self.malformed = true;
+                        return None;
+                    };
+                    self.entry_index = 0;
+                    list
+                }
+                None => return None,
+            };
+            let end = list.next + list.signature_size;
+            if end > list.end {
+                self.malformed |= list.next != list.end;
+                self.list = None;
+                self.list_offset = list.end;
+                self.list_index += 1;
+                continue;
+            }
+            self.list = Some(CurrentList { next: end, ..list });
+            let owner = Guid::from_bytes(self.data[list.next..list.next + 16].try_into().unwrap());
+            let range = list.next + 16..end;
+            let entry = SignatureEntry {
+                signature_type: list.signature_type,
+                owner,
+                data: &self.data[range.clone()],
+                range,
+                list_index: self.list_index,
+                entry_index: self.entry_index,
+            };
+            self.entry_index += 1;
+            return Some(entry);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    const OWNER: Guid = guid!("77fa9abd-0359-4d32-bd60-28f4e78f784b");
+
+    /// An `EFI_SIGNATURE_LIST` of `signatures`, which all have to be the same size
+    fn signature_list(signature_type: Guid, signatures: &[&[u8]]) -> Vec<u8> {
+        let signature_size = 16 + signatures[0].len();
+        let mut list = Vec::from(signature_type.to_bytes());
+        list.extend_from_slice(&((28 + signature_size * signatures.len()) as u32).to_le_bytes());
+        // SignatureHeaderSize
+        list.extend_from_slice(&0u32.to_le_bytes());
+        list.extend_from_slice(&(signature_size as u32).to_le_bytes());
+        for signature in signatures {
+            list.extend_from_slice(&OWNER.to_bytes());
+            list.extend_from_slice(signature);
+        }
+        list
+    }
+
+    #[test]
+    fn signatures_are_read_from_every_list() {
+        let mut data = signature_list(EFI_CERT_SHA256_GUID, &[&[0x11; 32], &[0x22; 32]]);
+        data.extend(signature_list(EFI_CERT_X509_GUID, &[b"certificate"]));
+        let mut lists = SignatureLists::new(&data);
+        let entries = lists
+            .by_ref()
+            .map(|entry| {
+                (
+                    entry.signature_type,
+                    entry.owner,
+                    entry.data.to_vec(),
+                    entry.range,
+                    entry.list_index,
+                    entry.entry_index,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert!(!lists.is_malformed());
+        assert_eq!(
+            entries,
+            [
+                (
+                    EFI_CERT_SHA256_GUID,
+                    OWNER,
+                    [0x11; 32].to_vec(),
+                    44..76,
+                    0,
+                    0
+                ),
+                (
+                    EFI_CERT_SHA256_GUID,
+                    OWNER,
+                    [0x22; 32].to_vec(),
+                    92..124,
+                    0,
+                    1
+                ),
+                (
+                    EFI_CERT_X509_GUID,
+                    OWNER,
+                    b"certificate".to_vec(),
+                    168..179,
+                    1,
+                    0
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn leftover_bytes_in_a_list_are_malformed() {
+        let mut data = signature_list(EFI_CERT_SHA256_GUID, &[&[0x11; 32]]);
+        // The list claims a byte more than its signatures
+        data[16..20].copy_from_slice(&77u32.to_le_bytes());
+        data.push(0);
+        let mut lists = SignatureLists::new(&data);
+        assert_eq!(lists.by_ref().count(), 1);
+        assert!(lists.is_malformed());
+    }
+
+    #[test]
+    fn oversized_list_is_malformed() {
+        let mut data = signature_list(EFI_CERT_SHA256_GUID, &[&[0x11; 32]]);
+        data.truncate(data.len() - 1);
+        let mut lists = SignatureLists::new(&data);
+        assert_eq!(lists.by_ref().count(), 0);
+        assert!(lists.is_malformed());
+    }
+
+    #[test]
+    fn signature_types_have_names() {
+        assert_eq!(signature_type_name(EFI_CERT_X509_GUID), Some("X509"));
+        assert_eq!(signature_type_name(EFI_CERT_SHA256_GUID), Some("SHA256"));
+        assert_eq!(signature_type_name(OWNER), None);
+    }
+}
@@ -0,0 +1,225 @@
+//! The `EV_NO_ACTION` event some firmware logs to say which reference integrity manifest (RIM)
+//! describes its measurements, from section 10.4.5.2 of the PC Client Platform Firmware Profile
+
+use alloc::string::String;
+use core::fmt;
+
+use uefi::Guid;
+
+use crate::tpm::marshal::Reader;
+
+/// `TCG_Sp800_155_PlatformId_Event2`
+pub const SP800_155_EVENT_SIGNATURE: [u8; 16] = *b"SP800-155 Event\0";
+/// `TCG_Sp800_155_PlatformId_Event3`, which adds where to find the RIM and platform certificate
+pub const SP800_155_EVENT3_SIGNATURE: [u8; 16] = *b"SP800-155 Event3";
+
+/// Where a [`Sp800_155Event`] says something can be downloaded or found
+#[derive(Debug, Clone, Copy)]
+pub struct Locator<'a> {
+    /// `TCG_LOCATOR_TYPE_*`, like 1 for a URI or 3 for a device path
+    pub locator_type: u32,
+    pub locator: &'a [u8],
+}
+
+/// `TCG_Sp800_155_PlatformId_Event2` or `3`. The strings are whatever the firmware put there,
+/// usually ASCII.
+#[derive(Debug, Clone, Copy)]
+pub struct Sp800_155Event<'a> {
+    /// The IANA Private Enterprise Number of the platform manufacturer
+    pub platform_manufacturer_id: u32,
+    pub reference_manifest_guid: Guid,
+    pub platform_manufacturer: &'a [u8],
+    pub platform_model: &'a [u8],
+    pub platform_version: &'a [u8],
+    pub firmware_manufacturer: &'a [u8],
+    pub firmware_manufacturer_id: u32,
+    pub firmware_version: &'a [u8],
+    /// Only in the v3 layout
+    pub rim_locator: Option<Locator<'a>>,
+    /// Only in the v3 layout
+    pub platform_cert_locator: Option<Locator<'a>>,
+}
+
+impl<'a> Sp800_155Event<'a> {
+    /// `None` if `event_data` isn't an SP800-155 event, or is truncated
+    pub fn parse(event_data: &'a [u8]) -> Option<Self> {
+        let mut reader = Reader::new(event_data);
+        // The signatures only differ in the last byte, which is the layout version
+        let is_v3 = match reader.array::<16>().ok()? {
+            SP800_155_EVENT_SIGNATURE => false,
+            SP800_155_EVENT3_SIGNATURE => true,
+            _ => return None,
+        };
+        let platform_manufacturer_id = reader.u32_le().ok()?;
+        let reference_manifest_guid = Guid::from_bytes(reader.array().ok()?);
+        let platform_manufacturer = read_string(&mut reader)?;
+        let platform_model = read_string(&mut reader)?;
+        let platform_version = read_string(&mut reader)?;
+        let firmware_manufacturer = read_string(&mut reader)?;
+        let firmware_manufacturer_id = reader.u32_le().ok()?;
+        let firmware_version = read_string(&mut reader)?;
+        let (rim_locator, platform_cert_locator) = if is_v3 {
+            (
+                Some(read_locator(&mut reader)?),
+                Some(read_locator(&mut reader)?),
+            )
+        } else {
+            (None, None)
+        };
+        Some(Self {
+            platform_manufacturer_id,
+            reference_manifest_guid,
+            platform_manufacturer,
+            platform_model,
+            platform_version,
+            firmware_manufacturer,
+            firmware_manufacturer_id,
+            firmware_version,
+            rim_locator,
+            platform_cert_locator,
+        })
+    }
+}
+
+/// A string with a one byte size
+fn read_string<'a>(reader: &mut Reader<'a>) -> Option<&'a [u8]> {
+    let len = reader.u8().ok()?;
+    reader.bytes(len as usize).ok()
+}
+
+fn read_locator<'a>(reader: &mut Reader<'a>) -> Option<Locator<'a>> {
+    let locator_type = reader.u32_le().ok()?;
+    let len = reader.u32_le().ok()?;
+    let locator = reader.bytes(len as usize).ok()?;
+    Some(Locator {
+        locator_type,
+        locator,
+    })
+}
+
+/// Decodes a string field, dropping the null terminator some firmware includes
+pub fn text(bytes: &[u8]) -> String {
+    let bytes = bytes.split(|byte| *byte == 0).next().unwrap_or_default();
+    String::from_utf8_lossy(bytes).into()
+}
+
+impl fmt::Display for Sp800_155Event<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} version {} (PEN {}), firmware {} version {} (PEN {}), RIM {}",
+            text(self.platform_manufacturer),
+            text(self.platform_model),
+            text(self.platform_version),
+            self.platform_manufacturer_id,
+            text(self.firmware_manufacturer),
+            text(self.firmware_version),
+            self.firmware_manufacturer_id,
+            self.reference_manifest_guid,
+        )?;
+        if let Some(locator) = self.rim_locator {
+            write!(
+                f,
+                ", RIM locator type {}: {:?}",
+                locator.locator_type,
+                text(locator.locator)
+            )?;
+        }
+        if let Some(locator) = self.platform_cert_locator {
+            write!(
+                f,
+                ", platform certificate locator type {}: {:?}",
+                locator.locator_type,
+                text(locator.locator)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{format, vec::Vec};
+
+    use uefi::guid;
+
+    use super::*;
+
+    const RIM_GUID: Guid = guid!("01234567-89ab-cdef-0123-456789abcdef");
+
+    /// A `TCG_Sp800_155_PlatformId_Event2` body like a vendor's, with null terminated strings
+    fn event2() -> Vec<u8> {
+        let mut event = Vec::from(SP800_155_EVENT_SIGNATURE);
+        event.extend_from_slice(&343u32.to_le_bytes());
+        event.extend_from_slice(&RIM_GUID.to_bytes());
+        for string in [&b"Acme\0"[..], b"Model 7\0", b"1.2\0", b"Acme BIOS\0"] {
+            event.push(string.len() as u8);
+            event.extend_from_slice(string);
+        }
+        event.extend_from_slice(&343u32.to_le_bytes());
+        event.push(3);
+        event.extend_from_slice(b"4.5");
+        event
+    }
+
+    /// The same as [`event2`] in the v3 layout, with a URI for the RIM and no platform certificate
+    fn event3() -> Vec<u8> {
+        let mut event = event2();
+        event[..16].copy_from_slice(&SP800_155_EVENT3_SIGNATURE);
+        let uri = b"https://example.com/rim";
+        event.extend_from_slice(&1u32.to_le_bytes());
+        event.extend_from_slice(&(uri.len() as u32).to_le_bytes());
+        event.extend_from_slice(uri);
+        event.extend_from_slice(&0u32.to_le_bytes());
+        event.extend_from_slice(&0u32.to_le_bytes());
+        event
+    }
+
+    #[test]
+    fn v2_event_parses() {
+        let event = event2();
+        let parsed = Sp800_155Event::parse(&event).unwrap();
+        assert_eq!(parsed.platform_manufacturer_id, 343);
+        assert_eq!(parsed.reference_manifest_guid, RIM_GUID);
+        assert_eq!(parsed.platform_model, b"Model 7\0");
+        assert_eq!(parsed.firmware_version, b"4.5");
+        assert!(parsed.rim_locator.is_none());
+        assert!(parsed.platform_cert_locator.is_none());
+        assert_eq!(
+            format!("{parsed}"),
+            "Acme Model 7 version 1.2 (PEN 343), firmware Acme BIOS version 4.5 (PEN 343), \
+             RIM 01234567-89ab-cdef-0123-456789abcdef"
+        );
+    }
+
+    #[test]
+    fn v3_event_has_locators() {
+        let event = event3();
+        let parsed = Sp800_155Event::parse(&event).unwrap();
+        let rim_locator = parsed.rim_locator.unwrap();
+        assert_eq!(rim_locator.locator_type, 1);
+        assert_eq!(rim_locator.locator, b"https://example.com/rim");
+        assert_eq!(parsed.platform_cert_locator.unwrap().locator, b"");
+        assert!(format!("{parsed}").ends_with(
+            ", RIM locator type 1: \"https://example.com/rim\", \
+             platform certificate locator type 0: \"\""
+        ));
+    }
+
+    #[test]
+    fn other_no_action_events_are_not_sp800_155() {
+        let mut event = event2();
+        event[..16].copy_from_slice(b"StartupLocality\0");
+        assert!(Sp800_155Event::parse(&event).is_none());
+    }
+
+    #[test]
+    fn truncated_events_are_rejected() {
+        let event = event2();
+        assert!(Sp800_155Event::parse(&event[..event.len() - 1]).is_none());
+        // A v2 body can't pass for v3
+        let mut event = event2();
+        event[..16].copy_from_slice(&SP800_155_EVENT3_SIGNATURE);
+        assert!(Sp800_155Event::parse(&event).is_none());
+    }
+}
@@ -0,0 +1,46 @@
+use alloc::string::String;
+
+use uefi::Guid;
+
+use crate::{tpm::marshal::Reader, ucs2};
+
+/// `UEFI_VARIABLE_DATA`, the event data of `EV_EFI_VARIABLE_*` events
+#[derive(Debug, Clone, Copy)]
+pub struct VariableData<'a> {
+    pub vendor: Guid,
+    /// UCS-2, not null-terminated
+    pub name: &'a [u8],
+    pub data: &'a [u8],
+}
+
+impl<'a> VariableData<'a> {
+    pub fn parse(event_data: &'a [u8]) -> Option<Self> {
+        let mut reader = Reader::new(event_data);
+        let vendor = Guid::from_bytes(reader.array().ok()?);
+        let name_length = reader.u64_le().ok()?;
+        let data_length = reader.u64_le().ok()?;
+        let name = reader
+            .bytes(usize::try_from(name_length).ok()?.checked_mul(2)?)
+            .ok()?;
+        let data = reader.bytes(usize::try_from(data_length).ok()?).ok()?;
+        Some(Self { vendor, name, data })
+    }
+
+    pub fn name(&self) -> String {
+        ucs2::decode_lossy(self.name)
+    }
+
+    pub fn name_eq(&self, name: &str) -> bool {
+        ucs2::units(self.name).eq(name.encode_utf16())
+    }
+
+    /// For `Boot####`, the `####`
+    pub fn boot_option_number(&self) -> Option<u16> {
+        let name = self.name();
+        let number = name.strip_prefix("Boot")?;
+        if number.len() != 4 {
+            return None;
+        }
+        u16::from_str_radix(number, 16).ok()
+    }
+}
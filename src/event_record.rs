@@ -0,0 +1,71 @@
+//! Events we extend into PCRs ourselves, kept in a non-volatile UEFI variable so an OS loader can
+//! cross-reference them with the firmware's event log. Each call appends one record.
+//!
+//! All integers are big-endian, like [`crate::verdict`]. Each record:
+//!
+//! | Size | Field |
+//! |------|-------|
+//! | 4 | PCR index |
+//! | 4 | Event type |
+//! | 2 | Number of digests |
+//! | 2 + 2 + n | Each digest: `TPM_ALG_ID`, size, digest |
+//! | 4 + n | Size of the event data, followed by the event data |
+
+use alloc::vec::Vec;
+
+use uefi::{
+    CStr16, cstr16,
+    proto::tcg::{AlgorithmId, EventType, PcrIndex},
+    runtime::{self, VariableAttributes},
+};
+
+use crate::{
+    tpm::{TpmError, TpmTransport, pcr::pcr_extend},
+    verdict::VERDICT_VARIABLE_VENDOR,
+};
+
+/// Under the same vendor GUID as the verdict
+pub const EVENT_RECORD_VARIABLE_NAME: &CStr16 = cstr16!("TpmEventLog");
+
+pub fn serialize_record(
+    pcr_index: PcrIndex,
+    event_type: EventType,
+    event_data: &[u8],
+    digests: &[(AlgorithmId, &[u8])],
+) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(&pcr_index.0.to_be_bytes());
+    data.extend_from_slice(&event_type.0.to_be_bytes());
+    data.extend_from_slice(&(digests.len() as u16).to_be_bytes());
+    for (algorithm, digest) in digests {
+        data.extend_from_slice(&algorithm.0.to_be_bytes());
+        data.extend_from_slice(&(digest.len() as u16).to_be_bytes());
+        data.extend_from_slice(digest);
+    }
+    data.extend_from_slice(&(event_data.len() as u32).to_be_bytes());
+    data.extend_from_slice(event_data);
+    data
+}
+
+/// Extends `digests` (one per bank) into `pcr_index` with `TPM2_PCR_Extend`, then appends the
+/// event to [`EVENT_RECORD_VARIABLE_NAME`]. The variable is only written once the extend
+/// succeeded, so it never has events the PCRs don't.
+pub fn extend_and_record_event(
+    tcg: &mut dyn TpmTransport,
+    pcr_index: PcrIndex,
+    event_type: EventType,
+    event_data: &[u8],
+    digests: &[(AlgorithmId, &[u8])],
+) -> Result<(), TpmError> {
+    pcr_extend(tcg, pcr_index.0, digests)?;
+    runtime::set_variable(
+        EVENT_RECORD_VARIABLE_NAME,
+        &VERDICT_VARIABLE_VENDOR,
+        VariableAttributes::NON_VOLATILE
+            | VariableAttributes::BOOTSERVICE_ACCESS
+            | VariableAttributes::RUNTIME_ACCESS
+            | VariableAttributes::APPEND_WRITE,
+        &serialize_record(pcr_index, event_type, event_data, digests),
+    )?;
+    Ok(())
+}
@@ -0,0 +1,442 @@
+//! A [`MeasurementReport`] as a binary blob, for a remote verifier to read next to a quote. Only
+//! what a verifier decides with is kept: the Secure Boot state, the images loaded, the PCR banks
+//! and how the replay went. The events behind the findings are in the log it has anyway.
+//!
+//! [`MeasurementReport::encode`] writes into the caller's buffer without allocating, so it works
+//! wherever the report was made. [`decode_report`] reads it back, on the host or anywhere else.
+//!
+//! All integers are big-endian, like [`crate::verdict`]:
+//!
+//! | Size | Field |
+//! |------|-------|
+//! | 4 | Magic, `MRPT` in ASCII |
+//! | 1 | Format version, currently 1 |
+//! | 1 | Secure Boot: 0 off, 1 on, `0xFF` unknown |
+//! | 1 | Boot mode: 0 setup, 1 audit, 2 user, 3 deployed, `0xFF` unknown |
+//! | 1 | Flags: bit 0 the event log was read, 1 it's truncated, 2 the PCRs were compared, 3 they all matched |
+//! | 2 | Number of PCR banks |
+//! | 2 | Number of image loads |
+//!
+//! Followed by each PCR bank:
+//!
+//! | Size | Field |
+//! |------|-------|
+//! | 2 | `TPM_ALG_ID` |
+//! | 1 + 4n | Number of PCRs allocated, followed by their indexes |
+//!
+//! Then each image load:
+//!
+//! | Size | Field |
+//! |------|-------|
+//! | 4 | Index of the event in the log |
+//! | 4 | PCR index |
+//! | 4 | Event type |
+//! | 8 | `ImageLengthInMemory`, or `0xFFFFFFFFFFFFFFFF` if unknown |
+//! | 2 + 2 + n | `TPM_ALG_ID` (`TPM_ALG_NULL` if there's no digest), size, digest |
+//! | 2 + n | Length of the device path text, followed by the text in UTF-8. Empty if unknown. |
+
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+use uefi::proto::tcg::{AlgorithmId, EventType};
+
+use crate::{
+    report::{BootMode, ImageLoad, MeasurementReport, PcrBank},
+    tpm::{
+        TpmError,
+        alg::TPM_ALG_NULL,
+        marshal::{Reader, Writer},
+        pcr::TpmDigest,
+    },
+};
+
+pub const MAGIC: [u8; 4] = *b"MRPT";
+pub const VERSION: u8 = 1;
+
+const UNKNOWN: u8 = 0xFF;
+const NO_IMAGE_LENGTH: u64 = u64::MAX;
+
+const FLAG_EVENT_LOG_READ: u8 = 1 << 0;
+const FLAG_TRUNCATED: u8 = 1 << 1;
+const FLAG_PCRS_COMPARED: u8 = 1 << 2;
+const FLAG_REPLAY_OK: u8 = 1 << 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    Truncated,
+    /// A field has a value the format doesn't allow
+    Invalid,
+    TrailingData,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadMagic => f.write_str("not a measurement report"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported measurement report version {version}")
+            }
+            Self::Truncated => f.write_str("measurement report is truncated"),
+            Self::Invalid => f.write_str("measurement report has an invalid field"),
+            Self::TrailingData => f.write_str("unexpected data after measurement report"),
+        }
+    }
+}
+
+impl From<TpmError> for ParseError {
+    fn from(_: TpmError) -> Self {
+        // The only error `Reader` has for running out
+        Self::Truncated
+    }
+}
+
+fn boot_mode_byte(boot_mode: Option<BootMode>) -> u8 {
+    match boot_mode {
+        Some(BootMode::Setup) => 0,
+        Some(BootMode::Audit) => 1,
+        Some(BootMode::User) => 2,
+        Some(BootMode::Deployed) => 3,
+        None => UNKNOWN,
+    }
+}
+
+/// As many as fit in the count's `u16`
+fn counted<T>(items: &[T]) -> &[T] {
+    &items[..items.len().min(u16::MAX as usize)]
+}
+
+/// The device path text, cut on a character to fit the length's `u16`
+fn device_path_bytes(image: &ImageLoad) -> &[u8] {
+    let device_path = image.device_path.as_deref().unwrap_or_default();
+    let mut len = device_path.len().min(u16::MAX as usize);
+    while !device_path.is_char_boundary(len) {
+        len -= 1;
+    }
+    &device_path.as_bytes()[..len]
+}
+
+impl MeasurementReport {
+    /// Writes the report to `out` and returns how many bytes that took, or `None` if `out` is too
+    /// small. [`encoded_len`](Self::encoded_len) says how big it has to be.
+    pub fn encode(&self, out: &mut [u8]) -> Option<usize> {
+        let mut writer = Writer::new(out);
+        self.write(&mut writer).ok()?;
+        Some(writer.len())
+    }
+
+    /// How many bytes [`encode`](Self::encode) writes
+    pub fn encoded_len(&self) -> usize {
+        let banks = counted(&self.pcr_banks)
+            .iter()
+            .map(|bank| 3 + 4 * bank.pcrs.len().min(u8::MAX as usize))
+            .sum::<usize>();
+        let images = counted(&self.loaded_images)
+            .iter()
+            .map(|image| {
+                let digest = image
+                    .digest
+                    .map_or(0, |digest| digest.digest.as_slice().len());
+                24 + digest + 2 + device_path_bytes(image).len()
+            })
+            .sum::<usize>();
+        12 + banks + images
+    }
+
+    fn write(&self, writer: &mut Writer<'_>) -> Result<(), TpmError> {
+        writer.bytes(&MAGIC)?;
+        writer.u8(VERSION)?;
+        writer.u8(self.secure_boot.map_or(UNKNOWN, u8::from))?;
+        writer.u8(boot_mode_byte(self.boot_mode))?;
+        let flags = [
+            (self.event_log_read, FLAG_EVENT_LOG_READ),
+            (self.truncated, FLAG_TRUNCATED),
+            (self.pcrs_compared, FLAG_PCRS_COMPARED),
+            (self.replay_ok, FLAG_REPLAY_OK),
+        ]
+        .into_iter()
+        .filter(|(set, _)| *set)
+        .fold(0, |flags, (_, flag)| flags | flag);
+        writer.u8(flags)?;
+        let banks = counted(&self.pcr_banks);
+        let images = counted(&self.loaded_images);
+        writer.u16(banks.len() as u16)?;
+        writer.u16(images.len() as u16)?;
+        for bank in banks {
+            let pcrs = &bank.pcrs[..bank.pcrs.len().min(u8::MAX as usize)];
+            writer.u16(bank.algorithm.0)?;
+            writer.u8(pcrs.len() as u8)?;
+            for pcr_index in pcrs {
+                writer.u32(*pcr_index)?;
+            }
+        }
+        for image in images {
+            writer.u32(image.event_index as u32)?;
+            writer.u32(image.pcr_index)?;
+            writer.u32(image.event_type.0)?;
+            writer.u64(image.image_length.unwrap_or(NO_IMAGE_LENGTH))?;
+            match &image.digest {
+                Some(digest) => {
+                    writer.u16(digest.algorithm.0)?;
+                    writer.tpm2b(digest.digest.as_slice())?;
+                }
+                None => {
+                    writer.u16(TPM_ALG_NULL.0)?;
+                    writer.tpm2b(&[])?;
+                }
+            }
+            writer.tpm2b(device_path_bytes(image))?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads a report [`MeasurementReport::encode`] wrote. Everything the format leaves out, like the
+/// findings and the variables, is empty.
+pub fn decode_report(data: &[u8]) -> Result<MeasurementReport, ParseError> {
+    let mut reader = Reader::new(data);
+    if reader.array::<4>()? != MAGIC {
+        return Err(ParseError::BadMagic);
+    }
+    let version = reader.u8()?;
+    if version != VERSION {
+        return Err(ParseError::UnsupportedVersion(version));
+    }
+    let secure_boot = match reader.u8()? {
+        0 => Some(false),
+        1 => Some(true),
+        UNKNOWN => None,
+        _ => return Err(ParseError::Invalid),
+    };
+    let boot_mode = match reader.u8()? {
+        0 => Some(BootMode::Setup),
+        1 => Some(BootMode::Audit),
+        2 => Some(BootMode::User),
+        3 => Some(BootMode::Deployed),
+        UNKNOWN => None,
+        _ => return Err(ParseError::Invalid),
+    };
+    let flags = reader.u8()?;
+    let bank_count = reader.u16()?;
+    let image_count = reader.u16()?;
+    let mut pcr_banks = Vec::new();
+    for _ in 0..bank_count {
+        let algorithm = AlgorithmId(reader.u16()?);
+        let pcrs = (0..reader.u8()?)
+            .map(|_| reader.u32())
+            .collect::<Result<_, _>>()?;
+        pcr_banks.push(PcrBank { algorithm, pcrs });
+    }
+    let mut loaded_images = Vec::new();
+    for _ in 0..image_count {
+        let event_index = reader.u32()? as usize;
+        let pcr_index = reader.u32()?;
+        let event_type = EventType(reader.u32()?);
+        let image_length = Some(reader.u64()?).filter(|len| *len != NO_IMAGE_LENGTH);
+        let algorithm = AlgorithmId(reader.u16()?);
+        let digest = reader.tpm2b()?;
+        let digest = if algorithm == TPM_ALG_NULL {
+            None
+        } else {
+            Some(TpmDigest::try_from((algorithm, digest)).map_err(|_| ParseError::Invalid)?)
+        };
+        let device_path = core::str::from_utf8(reader.tpm2b()?).map_err(|_| ParseError::Invalid)?;
+        loaded_images.push(ImageLoad {
+            event_index,
+            pcr_index,
+            event_type,
+            image_length,
+            digest,
+            device_path: (!device_path.is_empty()).then(|| String::from(device_path)),
+        });
+    }
+    if !reader.is_empty() {
+        return Err(ParseError::TrailingData);
+    }
+    Ok(MeasurementReport {
+        secure_boot,
+        boot_mode,
+        loaded_images,
+        pcr_banks,
+        event_log_read: flags & FLAG_EVENT_LOG_READ != 0,
+        truncated: flags & FLAG_TRUNCATED != 0,
+        pcrs_compared: flags & FLAG_PCRS_COMPARED != 0,
+        replay_ok: flags & FLAG_REPLAY_OK != 0,
+        ..Default::default()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec;
+
+    use super::*;
+
+    fn report() -> MeasurementReport {
+        MeasurementReport {
+            secure_boot: Some(true),
+            boot_mode: Some(BootMode::Deployed),
+            loaded_images: Vec::from([
+                ImageLoad {
+                    event_index: 12,
+                    pcr_index: 4,
+                    event_type: EventType::EFI_BOOT_SERVICES_APPLICATION,
+                    image_length: Some(0x1234),
+                    digest: Some(TpmDigest::sha1([0xAB; 20])),
+                    device_path: Some(String::from("HD(1,GPT)/\\EFI\\BOOT\\BOOTX64.EFI")),
+                },
+                ImageLoad {
+                    event_index: 13,
+                    pcr_index: 2,
+                    event_type: EventType::EFI_BOOT_SERVICES_DRIVER,
+                    image_length: None,
+                    digest: None,
+                    device_path: None,
+                },
+            ]),
+            pcr_banks: Vec::from([
+                PcrBank {
+                    algorithm: AlgorithmId::SHA1,
+                    pcrs: (0..24).collect(),
+                },
+                PcrBank {
+                    algorithm: AlgorithmId::SHA256,
+                    pcrs: Vec::new(),
+                },
+            ]),
+            event_log_read: true,
+            pcrs_compared: true,
+            ..Default::default()
+        }
+    }
+
+    fn encode(report: &MeasurementReport) -> Vec<u8> {
+        let mut out = vec![0; report.encoded_len()];
+        assert_eq!(report.encode(&mut out), Some(out.len()));
+        out
+    }
+
+    fn image_fields(
+        image: &ImageLoad,
+    ) -> (
+        usize,
+        u32,
+        EventType,
+        Option<u64>,
+        Option<TpmDigest>,
+        Option<&str>,
+    ) {
+        (
+            image.event_index,
+            image.pcr_index,
+            image.event_type,
+            image.image_length,
+            image.digest,
+            image.device_path.as_deref(),
+        )
+    }
+
+    #[test]
+    fn an_empty_report_is_the_header() {
+        assert_eq!(
+            encode(&MeasurementReport::default()),
+            [b'M', b'R', b'P', b'T', 1, 0xFF, 0xFF, 0, 0, 0, 0, 0]
+        );
+    }
+
+    #[test]
+    fn reports_round_trip() {
+        let report = report();
+        let decoded = decode_report(&encode(&report)).unwrap();
+        assert_eq!(decoded.secure_boot, Some(true));
+        assert_eq!(decoded.boot_mode, Some(BootMode::Deployed));
+        assert!(decoded.event_log_read && decoded.pcrs_compared);
+        assert!(!decoded.truncated && !decoded.replay_ok);
+        let banks = |report: &MeasurementReport| {
+            report
+                .pcr_banks
+                .iter()
+                .map(|bank| (bank.algorithm, bank.pcrs.clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(banks(&decoded), banks(&report));
+        assert_eq!(
+            decoded
+                .loaded_images
+                .iter()
+                .map(image_fields)
+                .collect::<Vec<_>>(),
+            report
+                .loaded_images
+                .iter()
+                .map(image_fields)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn encode_needs_the_whole_length() {
+        let report = report();
+        let mut out = vec![0; report.encoded_len() - 1];
+        assert_eq!(report.encode(&mut out), None);
+    }
+
+    #[test]
+    fn device_paths_are_cut_on_a_character() {
+        let mut report = MeasurementReport::default();
+        report.loaded_images.push(ImageLoad {
+            event_index: 0,
+            pcr_index: 4,
+            event_type: EventType::EFI_BOOT_SERVICES_APPLICATION,
+            image_length: None,
+            digest: None,
+            // The last 'é' straddles the u16 limit
+            device_path: Some("é".repeat(u16::MAX as usize / 2 + 1)),
+        });
+        let decoded = decode_report(&encode(&report)).unwrap();
+        let device_path = decoded.loaded_images[0].device_path.as_deref().unwrap();
+        assert_eq!(device_path.len(), u16::MAX as usize - 1);
+    }
+
+    #[test]
+    fn malformed_reports_are_rejected() {
+        let encoded = encode(&report());
+        let with = |offset: usize, byte: u8| {
+            let mut bytes = encoded.clone();
+            bytes[offset] = byte;
+            decode_report(&bytes).err()
+        };
+        assert_eq!(with(0, b'X'), Some(ParseError::BadMagic));
+        assert_eq!(with(4, 2), Some(ParseError::UnsupportedVersion(2)));
+        assert_eq!(with(5, 2), Some(ParseError::Invalid));
+        assert_eq!(with(6, 4), Some(ParseError::Invalid));
+        assert_eq!(
+            decode_report(&encoded[..encoded.len() - 1]).err(),
+            Some(ParseError::Truncated)
+        );
+        let mut trailing = encoded.clone();
+        trailing.push(0);
+        assert_eq!(
+            decode_report(&trailing).err(),
+            Some(ParseError::TrailingData)
+        );
+    }
+
+    #[test]
+    fn digests_of_the_wrong_size_are_invalid() {
+        let mut report = MeasurementReport::default();
+        report.loaded_images.push(ImageLoad {
+            event_index: 0,
+            pcr_index: 4,
+            event_type: EventType::EFI_BOOT_SERVICES_APPLICATION,
+            image_length: None,
+            digest: Some(TpmDigest::new(AlgorithmId::SHA256, &[0; 20]).unwrap()),
+            device_path: None,
+        });
+        assert_eq!(
+            decode_report(&encode(&report)).err(),
+            Some(ParseError::Invalid)
+        );
+    }
+}
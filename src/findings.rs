@@ -0,0 +1,398 @@
+//! Everything the analysis has to say is reported as a [`Finding`] with a stable code, so tools
+//! parsing the output don't break when the wording of a message changes.
+//!
+//! Codes are never reused or renumbered. The registry:
+//!
+//! | Code | Severity | Meaning |
+//! |------|----------|---------|
+//! | LOG-001 | Error | The event log is truncated, so nothing in it can be verified |
+//! | LOG-002 | Warning | An event's data doesn't match the structure its type requires |
+//! | LOG-003 | Error | An event log file couldn't be parsed |
+//! | LOG-004 | Info | The 1.2 and crypto agile logs have the same events |
+//! | LOG-005 | Warning | An event is in only one of the 1.2 and crypto agile logs |
+//! | LOG-006 | Warning | A PCR replays to different values from the 1.2 and crypto agile logs |
+//! | LOG-007 | Info | The 1.2 and crypto agile logs couldn't be compared |
+//! | LOG-008 | Warning | A firmware PCR (0 to 7) has no separator event |
+//! | LOG-009 | Error | A firmware event was measured into a PCR after its separator |
+//! | RPL-001 | Info | A PCR matches the value replayed from the event log |
+//! | RPL-002 | Info | A PCR is not available in the bank being replayed |
+//! | RPL-003 | Error | A PCR doesn't match the value replayed from the event log |
+//! | RPL-004 | Info | A PCR was replayed but there is no live TPM to compare it with |
+//! | CMD-001 | Info | A boot entry has optional data, so PCR 1 depends on its command line |
+//! | CMD-002 | Info | A command line measured by the OS loader matches a boot entry |
+//! | CMD-003 | Warning | A command line measured by the OS loader doesn't match any boot entry |
+//! | EVT-001 | Warning | An event's digest doesn't match its event data |
+//! | EVT-002 | Info | An event's digest doesn't match its event data, which is a known issue with this firmware |
+//! | EVT-003 | Warning | The firmware logged an action that weakens the platform's security, like disabling DMA protection |
+//! | IMG-001 | Info | The firmware's Authenticode hash of a measured image matches ours |
+//! | IMG-002 | Error | The firmware's Authenticode hash of a measured image doesn't match ours |
+//! | IMG-003 | Warning | A measured image couldn't be cross-checked |
+//! | SCR-001 | Info | The scratch PCR was extended, logged and reset as expected |
+//! | SCR-002 | Error | The scratch PCR doesn't match the event it was extended with in the log |
+//! | SCR-003 | Error | The scratch PCR isn't zeros after it was reset |
+//! | SCR-004 | Warning | The scratch PCR self-check couldn't run |
+
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Error => "error",
+        })
+    }
+}
+
+/// An entry in the registry. These can only be created here, so every finding uses a registered
+/// code.
+#[derive(Debug, PartialEq, Eq)]
+pub struct FindingCode {
+    code: &'static str,
+    severity: Severity,
+    summary: &'static str,
+}
+
+impl FindingCode {
+    const fn new(code: &'static str, severity: Severity, summary: &'static str) -> Self {
+        Self {
+            code,
+            severity,
+            summary,
+        }
+    }
+
+    pub fn code(&self) -> &'static str {
+        self.code
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.severity
+    }
+
+    pub fn summary(&self) -> &'static str {
+        self.summary
+    }
+}
+
+pub mod codes {
+    use super::{FindingCode, Severity::*};
+
+    pub static LOG_TRUNCATED: FindingCode =
+        FindingCode::new("LOG-001", Error, "event log is truncated");
+    pub static LOG_MALFORMED_EVENT: FindingCode =
+        FindingCode::new("LOG-002", Warning, "malformed event data");
+    pub static LOG_UNPARSABLE: FindingCode =
+        FindingCode::new("LOG-003", Error, "event log file can't be parsed");
+    pub static LOG_FORMATS_AGREE: FindingCode =
+        FindingCode::new("LOG-004", Info, "1.2 and crypto agile logs agree");
+    pub static LOG_FORMATS_EVENT_MISSING: FindingCode = FindingCode::new(
+        "LOG-005",
+        Warning,
+        "event missing from the 1.2 or crypto agile log",
+    );
+    pub static LOG_FORMATS_REPLAY_MISMATCH: FindingCode = FindingCode::new(
+        "LOG-006",
+        Warning,
+        "1.2 and crypto agile logs replay differently",
+    );
+    pub static LOG_FORMATS_UNCOMPARED: FindingCode = FindingCode::new(
+        "LOG-007",
+        Info,
+        "1.2 and crypto agile logs can't be compared",
+    );
+    pub static LOG_SEPARATOR_MISSING: FindingCode =
+        FindingCode::new("LOG-008", Warning, "firmware PCR has no separator");
+    pub static LOG_EVENT_AFTER_SEPARATOR: FindingCode =
+        FindingCode::new("LOG-009", Error, "firmware event after the separator");
+    pub static REPLAY_MATCH: FindingCode =
+        FindingCode::new("RPL-001", Info, "PCR matches event log");
+    pub static REPLAY_UNAVAILABLE: FindingCode =
+        FindingCode::new("RPL-002", Info, "PCR unavailable");
+    pub static REPLAY_MISMATCH: FindingCode =
+        FindingCode::new("RPL-003", Error, "PCR does not match event log");
+    pub static REPLAY_SKIPPED: FindingCode =
+        FindingCode::new("RPL-004", Info, "PCR not compared without a live TPM");
+    pub static CMDLINE_IN_BOOT_ENTRY: FindingCode =
+        FindingCode::new("CMD-001", Info, "boot entry has a command line");
+    pub static CMDLINE_MATCH: FindingCode = FindingCode::new(
+        "CMD-002",
+        Info,
+        "measured command line matches a boot entry",
+    );
+    pub static CMDLINE_MISMATCH: FindingCode = FindingCode::new(
+        "CMD-003",
+        Warning,
+        "measured command line doesn't match any boot entry",
+    );
+    pub static EVENT_DIGEST_MISMATCH: FindingCode =
+        FindingCode::new("EVT-001", Warning, "event digest doesn't match event data");
+    pub static EVENT_DIGEST_KNOWN_QUIRK: FindingCode = FindingCode::new(
+        "EVT-002",
+        Info,
+        "event digest doesn't match event data (known firmware issue)",
+    );
+    pub static EVENT_SECURITY_ACTION: FindingCode = FindingCode::new(
+        "EVT-003",
+        Warning,
+        "firmware logged a security-relevant action",
+    );
+    pub static IMAGE_HASH_MATCH: FindingCode =
+        FindingCode::new("IMG-001", Info, "firmware's image hash matches ours");
+    pub static IMAGE_HASH_MISMATCH: FindingCode =
+        FindingCode::new("IMG-002", Error, "firmware's image hash doesn't match ours");
+    pub static IMAGE_HASH_UNCHECKED: FindingCode = FindingCode::new(
+        "IMG-003",
+        Warning,
+        "measured image couldn't be cross-checked",
+    );
+    pub static SCRATCH_PCR_OK: FindingCode = FindingCode::new(
+        "SCR-001",
+        Info,
+        "scratch PCR extended, logged and reset as expected",
+    );
+    pub static SCRATCH_PCR_MISMATCH: FindingCode =
+        FindingCode::new("SCR-002", Error, "scratch PCR doesn't match the event log");
+    pub static SCRATCH_PCR_NOT_RESET: FindingCode =
+        FindingCode::new("SCR-003", Error, "scratch PCR isn't zeros after a reset");
+    pub static SCRATCH_PCR_UNCHECKED: FindingCode =
+        FindingCode::new("SCR-004", Warning, "scratch PCR self-check couldn't run");
+}
+
+/// Every registered code
+pub static REGISTRY: &[&FindingCode] = &[
+    &codes::LOG_TRUNCATED,
+    &codes::LOG_MALFORMED_EVENT,
+    &codes::LOG_UNPARSABLE,
+    &codes::LOG_FORMATS_AGREE,
+    &codes::LOG_FORMATS_EVENT_MISSING,
+    &codes::LOG_FORMATS_REPLAY_MISMATCH,
+    &codes::LOG_FORMATS_UNCOMPARED,
+    &codes::LOG_SEPARATOR_MISSING,
+    &codes::LOG_EVENT_AFTER_SEPARATOR,
+    &codes::REPLAY_MATCH,
+    &codes::REPLAY_UNAVAILABLE,
+    &codes::REPLAY_MISMATCH,
+    &codes::REPLAY_SKIPPED,
+    &codes::CMDLINE_IN_BOOT_ENTRY,
+    &codes::CMDLINE_MATCH,
+    &codes::CMDLINE_MISMATCH,
+    &codes::EVENT_DIGEST_MISMATCH,
+    &codes::EVENT_DIGEST_KNOWN_QUIRK,
+    &codes::EVENT_SECURITY_ACTION,
+    &codes::IMAGE_HASH_MATCH,
+    &codes::IMAGE_HASH_MISMATCH,
+    &codes::IMAGE_HASH_UNCHECKED,
+    &codes::SCRATCH_PCR_OK,
+    &codes::SCRATCH_PCR_MISMATCH,
+    &codes::SCRATCH_PCR_NOT_RESET,
+    &codes::SCRATCH_PCR_UNCHECKED,
+];
+
+/// The registered code with this name
+pub fn lookup(code: &str) -> Option<&'static FindingCode> {
+    REGISTRY
+        .iter()
+        .copied()
+        .find(|registered| registered.code == code)
+}
+
+const fn str_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+const fn codes_are_unique(registry: &[&FindingCode]) -> bool {
+    let mut i = 0;
+    while i < registry.len() {
+        let mut j = i + 1;
+        while j < registry.len() {
+            if str_eq(registry[i].code, registry[j].code) {
+                return false;
+            }
+            j += 1;
+        }
+        i += 1;
+    }
+    true
+}
+
+const _: () = assert!(codes_are_unique(REGISTRY), "finding codes must be unique");
+
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub code: &'static FindingCode,
+    pub pcr_index: Option<u32>,
+    /// The position of the event in the event log
+    pub event_index: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}]", self.code.code)?;
+        if let Some(pcr_index) = self.pcr_index {
+            write!(f, " PCR {pcr_index}")?;
+        }
+        if let Some(event_index) = self.event_index {
+            write!(f, " event #{event_index}")?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Where every pass puts its findings
+#[derive(Debug, Clone, Default)]
+pub struct Findings(Vec<Finding>);
+
+impl Findings {
+    pub fn add(
+        &mut self,
+        code: &'static FindingCode,
+        pcr_index: Option<u32>,
+        event_index: Option<usize>,
+        message: String,
+    ) {
+        self.0.push(Finding {
+            code,
+            pcr_index,
+            event_index,
+            message,
+        });
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Finding> {
+        self.0.iter()
+    }
+
+    pub fn worst_severity(&self) -> Option<Severity> {
+        self.0.iter().map(|finding| finding.code.severity).max()
+    }
+
+    /// The log output backend
+    pub fn log(&self) {
+        for finding in &self.0 {
+            match finding.code.severity {
+                Severity::Info => log::info!("{finding}"),
+                Severity::Warning => log::warn!("{finding}"),
+                Severity::Error => log::error!("{finding}"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{collections::BTreeSet, vec::Vec};
+
+    use sha1::{Digest, Sha1};
+    use uefi::proto::tcg::{AlgorithmId, EventType};
+
+    use super::*;
+    use crate::{
+        analysis::{analyze_events, analyze_log_file, report_replay_skipped},
+        event_log::parser::LogEvent,
+        quirks::FirmwareInfo,
+    };
+
+    fn event<'a>(
+        pcr_index: u32,
+        event_type: EventType,
+        sha1: &'a [u8],
+        event_data: &'a [u8],
+    ) -> LogEvent<'a> {
+        LogEvent {
+            pcr_index,
+            event_type,
+            digests: Vec::from([(AlgorithmId::SHA1, sha1)]),
+            event_data,
+        }
+    }
+
+    /// Every code the passes emitted for `findings`, checking each is the registered one
+    fn registered_codes(findings: &Findings) -> BTreeSet<&'static str> {
+        findings
+            .iter()
+            .map(|finding| {
+                let registered = lookup(finding.code.code());
+                assert!(
+                    registered.is_some_and(|registered| core::ptr::eq(registered, finding.code)),
+                    "{} isn't in the registry",
+                    finding.code.code()
+                );
+                finding.code.code()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn passes_only_emit_registered_codes() {
+        let crtm = b"1.0\0";
+        let crtm_digest = Sha1::digest(crtm);
+        let wrong = [0x5a; 20];
+        let events = [
+            event(0, EventType::CRTM_VERSION, &crtm_digest, crtm),
+            // No TPM has PCR 30
+            event(30, EventType::EFI_ACTION, &wrong, b"action"),
+            event(7, EventType::EFI_ACTION, &wrong, b"DMA Protection Disabled"),
+            event(1, EventType::EFI_VARIABLE_BOOT2, &wrong, b"BootOrder"),
+            // A short SHA-1 digest
+            event(4, EventType::SEPARATOR, &wrong[..19], &[0; 4]),
+            event(7, EventType::SEPARATOR, &wrong, &[0; 4]),
+            // After PCR 7's separator
+            event(7, EventType::EFI_VARIABLE_DRIVER_CONFIG, &wrong, &[]),
+        ];
+        let firmware = FirmwareInfo {
+            vendor: Some("EDK II".into()),
+            ..Default::default()
+        };
+        let mut findings = Findings::default();
+        let replay = analyze_events(&events, &firmware, &mut findings);
+        report_replay_skipped(&replay, &mut findings);
+        let codes = registered_codes(&findings);
+        for code in [
+            "LOG-002", "LOG-008", "LOG-009", "EVT-001", "EVT-002", "EVT-003", "RPL-004",
+        ] {
+            assert!(codes.contains(code), "{code} wasn't emitted: {codes:?}");
+        }
+    }
+
+    #[test]
+    fn unparsable_log_emits_a_registered_code() {
+        assert_eq!(
+            registered_codes(&analyze_log_file(&[0; 10])),
+            BTreeSet::from(["LOG-003"])
+        );
+    }
+
+    #[test]
+    fn registry_is_documented() {
+        let doc = include_str!("findings.rs");
+        for code in REGISTRY {
+            let row = format!("//! | {} | {:?} | ", code.code(), code.severity());
+            assert!(
+                doc.contains(&row),
+                "{} isn't in the module doc",
+                code.code()
+            );
+        }
+    }
+}
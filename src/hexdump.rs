@@ -0,0 +1,77 @@
+//! Bytes laid out like `xxd` does, for tracing commands and responses:
+//!
+//! ```text
+//! 00000000: 8001 0000 000c 0000 017b 0020            .........{.
+//! ```
+
+use core::fmt;
+
+const BYTES_PER_LINE: usize = 16;
+
+/// Writes `bytes` as lines of an 8-digit hex offset, 16 bytes in hex in pairs, and those bytes as
+/// ASCII with `.` for anything unprintable. Lines are separated by newlines, with none at the end.
+pub fn hexdump(bytes: &[u8], out: &mut impl fmt::Write) -> fmt::Result {
+    for (i, line) in bytes.chunks(BYTES_PER_LINE).enumerate() {
+        if i != 0 {
+            out.write_char('\n')?;
+        }
+        write!(out, "{:08x}:", i * BYTES_PER_LINE)?;
+        for column in 0..BYTES_PER_LINE {
+            if column % 2 == 0 {
+                out.write_char(' ')?;
+            }
+            match line.get(column) {
+                Some(byte) => write!(out, "{byte:02x}")?,
+                None => out.write_str("  ")?,
+            }
+        }
+        out.write_str("  ")?;
+        for byte in line {
+            out.write_char(if byte.is_ascii_graphic() || *byte == b' ' {
+                *byte as char
+            } else {
+                '.'
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// [`hexdump`] as a `Display`, for log messages
+#[derive(Debug, Clone, Copy)]
+pub struct HexDump<'a>(pub &'a [u8]);
+
+impl fmt::Display for HexDump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        hexdump(self.0, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{format, string::String};
+
+    use super::*;
+
+    #[test]
+    fn twenty_bytes_like_xxd() {
+        let bytes = b"\x80\x01\x00\x00\x00\x0c\x00\x00\x01\x7buefi-tpm2!";
+        let mut out = String::new();
+        hexdump(bytes, &mut out).unwrap();
+        assert_eq!(
+            out,
+            "00000000: 8001 0000 000c 0000 017b 7565 6669 2d74  .........{uefi-t\n\
+             00000010: 706d 3221                                pm2!"
+        );
+        assert_eq!(format!("{}", HexDump(bytes)), out);
+    }
+
+    #[test]
+    fn odd_length_and_empty() {
+        assert_eq!(
+            format!("{}", HexDump(b"abc")),
+            "00000000: 6162 63                                  abc"
+        );
+        assert_eq!(format!("{}", HexDump(&[])), "");
+    }
+}
@@ -0,0 +1,244 @@
+//! How the firmware talks to the TPM and how long that takes, for working out why commands are
+//! slow or hang. The interface comes from the ACPI `TPM2` table's start method and the
+//! manufacturer the TCG protocol reports, and a few commands that don't change anything are
+//! timed against the durations the PC Client Platform TPM Profile expects of them.
+
+use alloc::{format, string::String, vec::Vec};
+
+use log::info;
+use uefi::proto::tcg::AlgorithmId;
+
+use crate::{
+    acpi::{StartMethod, Tpm2Table},
+    timer::TickTimer,
+    tpm::{
+        CommandCode, TcgTransport, TpmError,
+        capability::{TPM_PT_MANUFACTURER, TaggedProperty, get_tpm_properties},
+        pcr::pcr_read_single,
+        random::get_random,
+    },
+};
+
+/// `TPM2_DURATION_SHORT`, `_MEDIUM` and `_LONG`
+pub const DURATION_SHORT_US: u64 = 20_000;
+pub const DURATION_MEDIUM_US: u64 = 750_000;
+pub const DURATION_LONG_US: u64 = 2_000_000;
+
+/// How many times each command is timed
+const SAMPLES: usize = 5;
+
+/// Manufacturers whose TPMs are firmware running on the CPU or chipset
+const FIRMWARE_TPM_MANUFACTURERS: [&[u8; 4]; 4] = [b"AMD ", b"INTC", b"MSFT", b"QCOM"];
+
+/// `TPM_PT_MANUFACTURER`, or the protocol's `ManufacturerID`, as text
+pub fn manufacturer_name(manufacturer_id: u32) -> String {
+    manufacturer_id
+        .to_be_bytes()
+        .iter()
+        .filter(|byte| **byte != 0)
+        .map(|byte| char::from(*byte))
+        .collect::<String>()
+        .trim_end()
+        .into()
+}
+
+pub fn is_firmware_tpm(manufacturer_id: u32) -> bool {
+    FIRMWARE_TPM_MANUFACTURERS.contains(&&manufacturer_id.to_be_bytes())
+}
+
+#[derive(Debug, Clone)]
+pub struct CommandTiming {
+    pub command: CommandCode,
+    /// The longest the command should take
+    pub expected_us: u64,
+    /// One per successful run
+    pub samples_us: Vec<u64>,
+}
+
+impl CommandTiming {
+    /// The median
+    pub fn typical_us(&self) -> Option<u64> {
+        let mut samples = self.samples_us.clone();
+        samples.sort_unstable();
+        samples.get(samples.len() / 2).copied()
+    }
+
+    pub fn max_us(&self) -> Option<u64> {
+        self.samples_us.iter().copied().max()
+    }
+
+    pub fn is_slow(&self) -> bool {
+        self.max_us().is_some_and(|max| max > self.expected_us)
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct InterfaceReport {
+    /// `None` if there's no ACPI `TPM2` table
+    pub tpm2_table: Option<Tpm2Table>,
+    /// From the TCG protocol's capability
+    pub manufacturer_id: Option<u32>,
+    /// Empty if there's no timer to time them with
+    pub timings: Vec<CommandTiming>,
+}
+
+impl InterfaceReport {
+    /// Some command took longer than the platform profile allows
+    pub fn slow(&self) -> bool {
+        self.timings.iter().any(CommandTiming::is_slow)
+    }
+
+    /// Like `CRB, firmware TPM`
+    pub fn interface(&self) -> String {
+        let start_method = self.tpm2_table.map(|table| table.start_method);
+        let kind = match (start_method, self.manufacturer_id) {
+            (_, Some(manufacturer_id)) if is_firmware_tpm(manufacturer_id) => "firmware TPM",
+            (Some(StartMethod::FIFO), _) => "discrete TPM on LPC or SPI",
+            (Some(start_method), _) if start_method.is_crb() => "CRB TPM from an unknown vendor",
+            _ => "unknown kind of TPM",
+        };
+        match start_method {
+            Some(start_method) => format!("{start_method}, {kind}"),
+            None => format!("no ACPI TPM2 table, {kind}"),
+        }
+    }
+
+    pub fn log(&self) {
+        info!("TPM interface: {}", self.interface());
+        if let Some(table) = self.tpm2_table {
+            // Absent for ACPI start, where the firmware's ACPI methods do it all
+            if table.control_area != 0 {
+                info!(
+                    "TPM {} at {:#x}",
+                    if table.start_method == StartMethod::FIFO {
+                        "registers"
+                    } else {
+                        "control area"
+                    },
+                    table.control_area
+                );
+            }
+        }
+        if let Some(manufacturer_id) = self.manufacturer_id {
+            info!("TPM manufacturer: {}", manufacturer_name(manufacturer_id));
+        }
+        if self.timings.is_empty() {
+            info!("No timer to time TPM commands with");
+        }
+        for timing in &self.timings {
+            let (Some(typical_us), Some(max_us)) = (timing.typical_us(), timing.max_us()) else {
+                log::warn!("{} failed every time", timing.command);
+                continue;
+            };
+            let message = format!(
+                "{}: typically {typical_us} us, at most {max_us} us (expected under {} ms)",
+                timing.command,
+                timing.expected_us / 1000
+            );
+            if timing.is_slow() {
+                log::warn!("{message}");
+            } else {
+                info!("{message}");
+            }
+        }
+        if self.slow() {
+            log::warn!("The TPM is slower than the platform profile allows");
+        }
+    }
+}
+
+fn time_command(
+    timer: &TickTimer,
+    command: CommandCode,
+    expected_us: u64,
+    mut run: impl FnMut() -> Result<(), TpmError>,
+) -> CommandTiming {
+    let mut samples_us = Vec::with_capacity(SAMPLES);
+    for _ in 0..SAMPLES {
+        match timer.time(&mut run) {
+            (Ok(()), elapsed_us) => samples_us.push(elapsed_us),
+            (Err(e), _) => log::debug!("{command} failed while timing it: {e}"),
+        }
+    }
+    CommandTiming {
+        command,
+        expected_us,
+        samples_us,
+    }
+}
+
+pub fn interface_report(tcg: &mut dyn TcgTransport) -> InterfaceReport {
+    let mut report = InterfaceReport {
+        tpm2_table: Tpm2Table::find(),
+        manufacturer_id: tcg
+            .protocol()
+            .get_capability()
+            .ok()
+            .map(|capability| capability.manufacturer_id),
+        ..Default::default()
+    };
+    let Some(timer) = TickTimer::calibrate() else {
+        return report;
+    };
+    // Linux's classification of each command
+    report.timings.push(time_command(
+        &timer,
+        CommandCode::GET_RANDOM,
+        DURATION_LONG_US,
+        || get_random(tcg, &mut [0; 16]).map(|_| ()),
+    ));
+    report.timings.push(time_command(
+        &timer,
+        CommandCode::GET_CAPABILITY,
+        DURATION_MEDIUM_US,
+        || {
+            get_tpm_properties(tcg, TPM_PT_MANUFACTURER, &mut [TaggedProperty::default()])
+                .map(|_| ())
+        },
+    ));
+    report.timings.push(time_command(
+        &timer,
+        CommandCode::PCR_READ,
+        DURATION_SHORT_US,
+        || pcr_read_single(tcg, AlgorithmId::SHA1, 0).map(|_| ()),
+    ));
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interface_uses_the_tpm2_table() {
+        let mut report = InterfaceReport {
+            tpm2_table: Some(Tpm2Table {
+                platform_class: 0,
+                control_area: 0xfed40000,
+                start_method: StartMethod::FIFO,
+            }),
+            ..Default::default()
+        };
+        assert_eq!(report.interface(), "FIFO (TIS), discrete TPM on LPC or SPI");
+        report.tpm2_table = None;
+        assert_eq!(
+            report.interface(),
+            "no ACPI TPM2 table, unknown kind of TPM"
+        );
+    }
+
+    #[test]
+    fn manufacturer_decides_firmware_tpms() {
+        let report = InterfaceReport {
+            tpm2_table: Some(Tpm2Table {
+                platform_class: 0,
+                control_area: 0xfed40040,
+                start_method: StartMethod::CRB,
+            }),
+            manufacturer_id: Some(u32::from_be_bytes(*b"INTC")),
+            ..Default::default()
+        };
+        assert_eq!(report.interface(), "CRB, firmware TPM");
+        assert_eq!(manufacturer_name(u32::from_be_bytes(*b"IBM\0")), "IBM");
+    }
+}
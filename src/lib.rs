@@ -0,0 +1,41 @@
+#![cfg_attr(not(test), no_std)]
+
+extern crate alloc;
+
+pub mod acpi;
+pub mod analysis;
+pub mod attestation;
+pub mod authenticode;
+#[cfg(feature = "bench")]
+pub mod bench;
+pub mod cert_export;
+pub mod context;
+#[cfg(feature = "crypto")]
+pub mod crypto;
+pub mod ct;
+pub mod event_log;
+pub mod event_record;
+pub mod evidence;
+pub mod findings;
+pub mod hexdump;
+pub mod interface;
+pub mod log_diff;
+pub mod log_formats;
+pub mod measure;
+pub mod nv_tool;
+pub mod options;
+pub mod panic;
+#[cfg(feature = "pem")]
+pub mod pem;
+pub mod pipeline;
+pub mod protocol;
+pub mod quirks;
+#[cfg(feature = "repl")]
+pub mod repl;
+pub mod report;
+pub mod sealed_blob;
+pub mod timer;
+pub mod tpm;
+pub mod ucs2;
+pub mod verdict;
+pub mod volume;
@@ -0,0 +1,227 @@
+//! What changed between two event logs, like the logs of the boots before and after a firmware
+//! update, for working out why PCRs changed. Events are matched by PCR, type and digest, wherever
+//! they are in the log, so events that only moved aren't reported.
+
+use alloc::{vec, vec::Vec};
+use core::fmt;
+
+use hex_slice::AsHex;
+use uefi::proto::tcg::{AlgorithmId, EventType};
+
+use crate::event_log::parser::LogEvent;
+
+/// Whether two events extended the same thing into the same PCR. Digests are compared in the
+/// banks both logs have, so a SHA-1 log can be compared with a crypto-agile one.
+fn same_event(a: &LogEvent<'_>, b: &LogEvent<'_>) -> bool {
+    let mut compared = false;
+    for (algorithm, digest) in &a.digests {
+        if let Some(other) = b.digest(*algorithm) {
+            if other != *digest {
+                return false;
+            }
+            compared = true;
+        }
+    }
+    compared && a.pcr_index == b.pcr_index && a.event_type == b.event_type
+}
+
+/// An event that's only in one of the logs
+#[derive(Debug, Clone, Copy)]
+pub struct DiffEvent<'a> {
+    /// Its index in the log it's from
+    pub event_index: usize,
+    pub event: &'a LogEvent<'a>,
+}
+
+impl fmt::Display for DiffEvent<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{} PCR {} {:?}",
+            self.event_index, self.event.pcr_index, self.event.event_type
+        )?;
+        // The strongest digest is enough to tell them apart
+        let digest = [AlgorithmId::SHA256, AlgorithmId::SHA1]
+            .into_iter()
+            .find_map(|algorithm| Some((algorithm, self.event.digest(algorithm)?)));
+        if let Some((algorithm, digest)) = digest {
+            write!(f, " {algorithm:?} {:x}", digest.plain_hex(false))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct LogDiff<'a> {
+    /// In the new log but not the old one
+    pub added: Vec<DiffEvent<'a>>,
+    /// In the old log but not the new one
+    pub removed: Vec<DiffEvent<'a>>,
+}
+
+impl LogDiff<'_> {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty()
+    }
+}
+
+impl fmt::Display for LogDiff<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return f.write_str("the logs have the same events");
+        }
+        let mut lines = self
+            .removed
+            .iter()
+            .map(|event| ('-', event))
+            .chain(self.added.iter().map(|event| ('+', event)));
+        if let Some((sign, event)) = lines.next() {
+            write!(f, "{sign} {event}")?;
+        }
+        for (sign, event) in lines {
+            write!(f, "\n{sign} {event}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The events only one of the logs has. An event that's in one log more times than in the other
+/// is reported once per extra copy.
+pub fn diff_logs<'a>(old: &'a [LogEvent<'a>], new: &'a [LogEvent<'a>]) -> LogDiff<'a> {
+    // Skip informational events, which aren't extended
+    let extended = |event: &LogEvent<'_>| event.event_type != EventType::NO_ACTION;
+    let mut matched = vec![false; old.len()];
+    let mut diff = LogDiff::default();
+    for (event_index, event) in new.iter().enumerate().filter(|(_, e)| extended(e)) {
+        match old
+            .iter()
+            .enumerate()
+            .position(|(i, old_event)| !matched[i] && same_event(old_event, event))
+        {
+            Some(i) => matched[i] = true,
+            None => diff.added.push(DiffEvent { event_index, event }),
+        }
+    }
+    for (event_index, event) in old.iter().enumerate() {
+        if !matched[event_index] && extended(event) {
+            diff.removed.push(DiffEvent { event_index, event });
+        }
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event<'a>(
+        pcr_index: u32,
+        event_type: EventType,
+        digests: &[(AlgorithmId, &'a [u8])],
+    ) -> LogEvent<'a> {
+        LogEvent {
+            pcr_index,
+            event_type,
+            digests: digests.to_vec(),
+            event_data: &[],
+        }
+    }
+
+    #[test]
+    fn moved_events_are_the_same() {
+        let old = [
+            event(
+                0,
+                EventType::CRTM_VERSION,
+                &[(AlgorithmId::SHA1, &[0x11; 20])],
+            ),
+            event(7, EventType::SEPARATOR, &[(AlgorithmId::SHA1, &[0x22; 20])]),
+        ];
+        let new = [old[1].clone(), old[0].clone()];
+        let diff = diff_logs(&old, &new);
+        assert!(diff.is_empty());
+        assert_eq!(diff.to_string(), "the logs have the same events");
+    }
+
+    #[test]
+    fn changed_digest_is_removed_and_added() {
+        let old = [
+            event(
+                0,
+                EventType::CRTM_VERSION,
+                &[(AlgorithmId::SHA1, &[0x11; 20])],
+            ),
+            event(0, EventType::POST_CODE, &[(AlgorithmId::SHA1, &[0xaa; 20])]),
+        ];
+        let new = [
+            event(
+                0,
+                EventType::CRTM_VERSION,
+                &[(AlgorithmId::SHA1, &[0x11; 20])],
+            ),
+            event(0, EventType::POST_CODE, &[(AlgorithmId::SHA1, &[0xbb; 20])]),
+        ];
+        let diff = diff_logs(&old, &new);
+        assert_eq!(
+            diff.to_string(),
+            format!(
+                "- #1 PCR 0 {:?} {:?} {}\n+ #1 PCR 0 {:?} {:?} {}",
+                EventType::POST_CODE,
+                AlgorithmId::SHA1,
+                "aa".repeat(20),
+                EventType::POST_CODE,
+                AlgorithmId::SHA1,
+                "bb".repeat(20),
+            )
+        );
+    }
+
+    #[test]
+    fn extra_copies_are_reported_once_each() {
+        let action = event(
+            4,
+            EventType::EFI_ACTION,
+            &[(AlgorithmId::SHA1, &[0x11; 20])],
+        );
+        let old = [action.clone()];
+        let new = [action.clone(), action.clone(), action];
+        let diff = diff_logs(&old, &new);
+        assert!(diff.removed.is_empty());
+        assert_eq!(
+            diff.added
+                .iter()
+                .map(|event| event.event_index)
+                .collect::<Vec<_>>(),
+            [1, 2]
+        );
+    }
+
+    #[test]
+    fn sha1_log_matches_crypto_agile_log_on_sha1() {
+        let old = [event(
+            4,
+            EventType::EFI_BOOT_SERVICES_APPLICATION,
+            &[(AlgorithmId::SHA1, &[0x11; 20])],
+        )];
+        let new = [event(
+            4,
+            EventType::EFI_BOOT_SERVICES_APPLICATION,
+            &[
+                (AlgorithmId::SHA1, &[0x11; 20]),
+                (AlgorithmId::SHA256, &[0x22; 32]),
+            ],
+        )];
+        assert!(diff_logs(&old, &new).is_empty());
+    }
+
+    #[test]
+    fn no_action_events_are_left_out() {
+        let old = [];
+        let new = [event(
+            0,
+            EventType::NO_ACTION,
+            &[(AlgorithmId::SHA1, &[0; 20])],
+        )];
+        assert!(diff_logs(&old, &new).is_empty());
+    }
+}
@@ -0,0 +1,222 @@
+//! Firmware that supports both the TCG 1.2 (SHA-1 only) and the crypto agile log format keeps
+//! two logs of the same measurements, and they can disagree, for example when only one of them
+//! ran out of space. This compares them event by event.
+//!
+//! The records have different layouts, so events are matched on what both have: the PCR, the
+//! event type and the SHA-1 digest.
+
+use alloc::{format, vec::Vec};
+
+use hex_slice::AsHex;
+use uefi::proto::tcg::{AlgorithmId, EventType, v1};
+
+use crate::{
+    analysis::PCR_COUNT,
+    event_log::parser::LogEvent,
+    findings::{Findings, codes},
+    tpm::pcr::TpmDigest,
+};
+
+/// What both log formats record about an event
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sha1LogEntry {
+    pub pcr_index: u32,
+    pub event_type: EventType,
+    pub digest: [u8; 20],
+}
+
+impl From<&v1::PcrEvent> for Sha1LogEntry {
+    fn from(event: &v1::PcrEvent) -> Self {
+        Self {
+            pcr_index: event.pcr_index().0,
+            event_type: event.event_type(),
+            digest: event.digest(),
+        }
+    }
+}
+
+impl Sha1LogEntry {
+    /// `None` if the event has no SHA-1 digest
+    pub fn from_log_event(event: &LogEvent<'_>) -> Option<Self> {
+        Some(Self {
+            pcr_index: event.pcr_index,
+            event_type: event.event_type,
+            digest: event.digest(AlgorithmId::SHA1)?.try_into().ok()?,
+        })
+    }
+}
+
+/// Indexes into one log of the events extended into `pcr_index`
+fn events_in_pcr(log: &[Sha1LogEntry], pcr_index: u32) -> Vec<usize> {
+    (0..log.len())
+        .filter(|i| log[*i].pcr_index == pcr_index && log[*i].event_type != EventType::NO_ACTION)
+        .collect()
+}
+
+fn replay(log: &[Sha1LogEntry], events: &[usize]) -> [u8; 20] {
+    let mut pcr = TpmDigest::sha1([0; 20]);
+    for i in events {
+        // Both are SHA-1 digests, so this can't fail
+        let _ = pcr.extend(&TpmDigest::sha1(log[*i].digest));
+    }
+    // A SHA-1 digest is 20 bytes
+    pcr.digest.as_slice().try_into().unwrap()
+}
+
+/// Replays every PCR from both logs, and reports the events that are only in one of them.
+/// `sha1_log` is the TCG 1.2 log and `crypto_agile_log` the TCG 2.0 one. Event indexes in the
+/// findings are into the crypto agile log, or the 1.2 log for events only it has.
+pub fn compare_log_formats(
+    sha1_log: &[Sha1LogEntry],
+    crypto_agile_log: &[LogEvent<'_>],
+    findings: &mut Findings,
+) {
+    let Some(crypto_agile_log) = crypto_agile_log
+        .iter()
+        .map(Sha1LogEntry::from_log_event)
+        .collect::<Option<Vec<_>>>()
+    else {
+        findings.add(
+            &codes::LOG_FORMATS_UNCOMPARED,
+            None,
+            None,
+            "the crypto agile log doesn't have SHA1 digests for every event".into(),
+        );
+        return;
+    };
+    let mut agree = true;
+    for pcr_index in 0..PCR_COUNT as u32 {
+        let old = events_in_pcr(sha1_log, pcr_index);
+        let new = events_in_pcr(&crypto_agile_log, pcr_index);
+        let (old_value, new_value) = (replay(sha1_log, &old), replay(&crypto_agile_log, &new));
+        if old_value == new_value {
+            continue;
+        }
+        agree = false;
+        findings.add(
+            &codes::LOG_FORMATS_REPLAY_MISMATCH,
+            Some(pcr_index),
+            None,
+            format!(
+                "1.2 log replays to {:x}, crypto agile log to {:x}",
+                old_value.plain_hex(false),
+                new_value.plain_hex(false)
+            ),
+        );
+        // Walk both in order, matching each 1.2 event with the next equal crypto agile event.
+        // Crypto agile events skipped over on the way are the ones missing from the 1.2 log.
+        let mut cursor = 0;
+        for &old_index in &old {
+            let entry = &sha1_log[old_index];
+            match new[cursor..]
+                .iter()
+                .position(|new_index| crypto_agile_log[*new_index] == *entry)
+            {
+                Some(offset) => {
+                    for &new_index in &new[cursor..cursor + offset] {
+                        report_missing(findings, &crypto_agile_log[new_index], new_index, "1.2");
+                    }
+                    cursor += offset + 1;
+                }
+                None => report_missing(findings, entry, old_index, "crypto agile"),
+            }
+        }
+        for &new_index in &new[cursor..] {
+            report_missing(findings, &crypto_agile_log[new_index], new_index, "1.2");
+        }
+    }
+    if agree {
+        findings.add(
+            &codes::LOG_FORMATS_AGREE,
+            None,
+            None,
+            format!(
+                "{} events in the 1.2 log, {} in the crypto agile log",
+                sha1_log.len(),
+                crypto_agile_log.len()
+            ),
+        );
+    }
+}
+
+fn report_missing(findings: &mut Findings, entry: &Sha1LogEntry, index: usize, missing_from: &str) {
+    findings.add(
+        &codes::LOG_FORMATS_EVENT_MISSING,
+        Some(entry.pcr_index),
+        Some(index),
+        format!(
+            "{:?} {:x} is missing from the {missing_from} log",
+            entry.event_type,
+            entry.digest.plain_hex(false)
+        ),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(pcr_index: u32, event_type: EventType, digest: u8) -> Sha1LogEntry {
+        Sha1LogEntry {
+            pcr_index,
+            event_type,
+            digest: [digest; 20],
+        }
+    }
+
+    /// `entry` as the crypto agile log has it
+    fn event(entry: &Sha1LogEntry) -> LogEvent<'_> {
+        LogEvent {
+            pcr_index: entry.pcr_index,
+            event_type: entry.event_type,
+            digests: Vec::from([(AlgorithmId::SHA1, &entry.digest[..])]),
+            event_data: &[],
+        }
+    }
+
+    fn codes(findings: &Findings) -> Vec<&'static str> {
+        findings.iter().map(|finding| finding.code.code()).collect()
+    }
+
+    #[test]
+    fn replay_extends_in_order() {
+        let log = [
+            entry(0, EventType::POST_CODE, 0x11),
+            entry(0, EventType::SEPARATOR, 0x22),
+        ];
+        let mut expected = TpmDigest::sha1([0; 20]);
+        expected.extend(&TpmDigest::sha1([0x11; 20])).unwrap();
+        expected.extend(&TpmDigest::sha1([0x22; 20])).unwrap();
+        assert_eq!(replay(&log, &[0, 1]), expected.digest.as_slice());
+        assert_eq!(replay(&log, &[]), [0; 20]);
+    }
+
+    #[test]
+    fn identical_logs_agree() {
+        let log = [
+            entry(0, EventType::NO_ACTION, 0x00),
+            entry(0, EventType::POST_CODE, 0x11),
+            entry(7, EventType::SEPARATOR, 0x22),
+        ];
+        let events = log.iter().map(event).collect::<Vec<_>>();
+        let mut findings = Findings::default();
+        compare_log_formats(&log, &events, &mut findings);
+        assert_eq!(codes(&findings), ["LOG-004"]);
+    }
+
+    #[test]
+    fn event_only_in_the_crypto_agile_log_is_reported() {
+        let crypto_agile = [
+            entry(0, EventType::POST_CODE, 0x11),
+            entry(0, EventType::POST_CODE, 0x33),
+            entry(0, EventType::SEPARATOR, 0x22),
+        ];
+        let sha1_log = [crypto_agile[0], crypto_agile[2]];
+        let events = crypto_agile.iter().map(event).collect::<Vec<_>>();
+        let mut findings = Findings::default();
+        compare_log_formats(&sha1_log, &events, &mut findings);
+        assert_eq!(codes(&findings), ["LOG-006", "LOG-005"]);
+        let missing = findings.iter().nth(1).unwrap();
+        assert_eq!((missing.pcr_index, missing.event_index), (Some(0), Some(1)));
+    }
+}
@@ -1,132 +1,733 @@
 #![no_main]
 #![no_std]
 
-use ez_tpm::{CreatePrimary, GetRandom, PcrRead, uefi::submit_command};
-use hex_slice::AsHex;
+extern crate alloc;
+
+use alloc::{
+    boxed::Box,
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
 use log::info;
-use sha1::{Digest, Sha1};
 use uefi::{
-    Identify,
-    boot::SearchType,
+    CString16,
+    boot::ScopedProtocol,
+    fs::{FileSystem, PathBuf},
     prelude::*,
-    proto::tcg::{AlgorithmId, EventType, v2::Tcg},
+    proto::{loaded_image::LoadedImage, tcg::v2::Tcg},
+};
+use uefi_tpm2::{
+    analysis, cert_export,
+    context::Context,
+    event_log::parser::{collect_events, parse_event_log},
+    findings::Findings,
+    interface, log_diff,
+    nv_tool::{self, NvCommand, NvToolError},
+    options::Options,
+    pipeline::{OnFailure, Pipeline, Stage, StageError, StageResult, StageSummary},
+    protocol::{TcgAccess, open_tcg, open_tcg_exclusive},
+    quirks::FirmwareInfo,
+    report,
+    tpm::{
+        CommandCode, LoggingTransport, TPM_RH_OWNER, TcgTransport,
+        auth::{AuthCommand, Password},
+        capability::{
+            CommandSet, TpmSpecVersion, get_persistent_slot_count, is_storage_hierarchy_enabled,
+            read_tpm_unique_id,
+        },
+        command_limit::{CommandLimit, DEFAULT_MAX_COMMANDS},
+        lockout,
+        nv::TpmNvIndex,
+        object::{create_primary, flush_context, list_persistent_objects},
+        pcr::pcr_reset,
+        provision::provision,
+        random::{DrainError, drain_entropy_to},
+        self_test::selftest_and_report,
+        srk::srk_template_rsa2048,
+    },
+    verdict::{self, deserialize_verdict},
+    volume::{self, VolumeError},
 };
 
 #[entry]
 fn main() -> Status {
     uefi::helpers::init().unwrap();
-    let protocol = *boot::locate_handle_buffer(SearchType::ByProtocol(&Tcg::GUID))
-        .unwrap()
-        .first()
-        .unwrap();
-    info!("Protocol: {protocol:#?}");
-    let mut tcg = boot::open_protocol_exclusive::<Tcg>(protocol).unwrap();
-    let event_log = tcg.get_event_log_v2().unwrap();
-    if event_log.is_truncated() {
-        panic!(
-            "Event log is truncated, which means it ran out space. So we can't verify any of the events in the event log!"
+    // Closed before anything else opens the loaded image, like `get_image_file_system` does
+    let (
+        force_auth,
+        log_file,
+        show_verdict,
+        measure_image,
+        volume,
+        stages,
+        export_certs,
+        nv,
+        diff_logs,
+        repl,
+        max_commands,
+    ) = {
+        let loaded_image =
+            boot::open_protocol_exclusive::<LoadedImage>(boot::image_handle()).unwrap();
+        let options = Options::new(loaded_image.load_options_as_bytes().unwrap_or_default());
+        (
+            options.has_flag("--force-auth"),
+            options
+                .value("--log-file")
+                .map(|path| path.chars().collect::<String>()),
+            options.has_flag("--show-verdict"),
+            options
+                .value("--measure-image")
+                .map(|path| path.chars().collect::<String>()),
+            options
+                .value("--volume")
+                .map(|selector| selector.chars().collect::<String>()),
+            options
+                .value("--stages")
+                .map(|stages| stages.chars().collect::<String>()),
+            options
+                .value("--export-certs")
+                .map(|directory| directory.chars().collect::<String>()),
+            options.has_flag("--nv").then(|| {
+                options
+                    .rest("--nv")
+                    .map(|arg| arg.chars().collect::<String>())
+                    .collect::<Vec<_>>()
+            }),
+            options.has_flag("--diff-logs").then(|| {
+                options
+                    .rest("--diff-logs")
+                    .map(|arg| arg.chars().collect::<String>())
+                    .collect::<Vec<_>>()
+            }),
+            options.has_flag("--repl"),
+            options
+                .value("--max-commands")
+                .map(|max| max.chars().collect::<String>()),
         )
+    };
+    lockout::set_force_auth(force_auth);
+    let max_commands = match max_commands.map(|max| max.parse().map_err(|_| max)) {
+        None => DEFAULT_MAX_COMMANDS,
+        Some(Ok(max)) => max,
+        Some(Err(max)) => {
+            log::error!("--max-commands takes a number of commands, not {max:?}");
+            return Status::INVALID_PARAMETER;
+        }
+    };
+    if let Some(args) = nv {
+        return nv_command(volume.as_deref(), &args, max_commands);
+    }
+    if let Some(path) = log_file {
+        return analyze_log_file(volume.as_deref(), &path);
+    }
+    if let Some(paths) = diff_logs {
+        return diff_log_files(volume.as_deref(), &paths);
     }
-    // Dump events
-    for event in event_log.iter() {
-        let event_type = event.event_type();
-        let pcr_index = event.pcr_index();
-        log::debug!("{event_type:?} {pcr_index:?}");
-        for (algorithm, _data) in event.digests() {
-            log::debug!("  {algorithm:?}");
+    if show_verdict {
+        return show_driver_verdict();
+    }
+    if let Some(path) = measure_image {
+        return measure_image_file(volume.as_deref(), &path, max_commands);
+    }
+    if let Some(directory) = export_certs {
+        return export_measured_certificates(volume.as_deref(), &directory, max_commands);
+    }
+    if repl {
+        return repl_command(max_commands);
+    }
+    let (tcg, access) = match open_tcg() {
+        Ok(opened) => opened,
+        Err(e) => {
+            log::error!("{e}");
+            return Status::ACCESS_DENIED;
         }
+    };
+    info!("Opened the TCG2 protocol with {access} access");
+    if !access.can_submit_commands() {
+        log::warn!(
+            "Another driver or app has the TCG2 protocol open, so only the event log is analyzed"
+        );
     }
+    let mut app = App {
+        tcg: wrap_tcg(tcg, max_commands),
+        access,
+        commands: None,
+        firmware: FirmwareInfo {
+            vendor: Some(system::firmware_vendor().to_string()),
+            revision: Some(system::firmware_revision()),
+            ..Default::default()
+        },
+    };
+    let stages = stages.unwrap_or_else(|| DEFAULT_STAGES.into());
+    let requested = stages.split(',').map(str::trim).collect::<Vec<_>>();
+    let outcomes = match pipeline().run(&mut app, &requested) {
+        Ok(outcomes) => outcomes,
+        Err(e) => {
+            log::error!("{e}. Stages: {:?}", pipeline().names().collect::<Vec<_>>());
+            return Status::INVALID_PARAMETER;
+        }
+    };
+    for outcome in &outcomes {
+        outcome.findings.log();
+        let elapsed = outcome
+            .elapsed_us
+            .map(|elapsed_us| format!(" in {} ms", elapsed_us / 1000))
+            .unwrap_or_default();
+        match &outcome.result {
+            StageResult::Succeeded => info!("Stage {} succeeded{elapsed}", outcome.name),
+            // Reads as a trace, from the stage down to what failed
+            StageResult::Failed(e) => log::error!("Stage failed{elapsed}: {} → {e}", outcome.name),
+            StageResult::Skipped => log::warn!("Stage {} skipped", outcome.name),
+        }
+    }
+    info!("Stages {}", StageSummary::new(&outcomes));
+
+    loop {
+        boot::stall(3_000_000);
+    }
+    // Status::SUCCESS
+}
+
+/// The TPM behind what every path sends its commands through: each one traced, and at most
+/// `max_commands` of them
+fn wrap_tcg<T>(tcg: T, max_commands: u32) -> CommandLimit<LoggingTransport<T>> {
+    CommandLimit::new(LoggingTransport { inner: tcg }, max_commands)
+}
 
-    // Analyze events
-    for event in event_log.iter() {
-        let pcr_index = event.pcr_index();
-        match event.event_type() {
-            EventType::CRTM_VERSION => {
-                log::debug!("Core Root of Trust for Measurement (CRTM) Version");
+/// What the stages share
+struct App {
+    /// Traces every command and response at trace level, and stops sending commands after
+    /// `--max-commands`
+    tcg: CommandLimit<LoggingTransport<ScopedProtocol<Tcg>>>,
+    access: TcgAccess,
+    /// `None` if we couldn't find out, in which case we try every command anyway
+    commands: Option<CommandSet>,
+    firmware: FirmwareInfo,
+}
+
+impl App {
+    /// For stages that send the TPM commands
+    fn require_exclusive(&self) -> Result<(), StageError> {
+        if self.access.can_submit_commands() {
+            Ok(())
+        } else {
+            Err(StageError(
+                "sending commands needs exclusive access to the TCG2 protocol".into(),
+            ))
+        }
+    }
+}
+
+/// What runs without `--stages`
+const DEFAULT_STAGES: &str = "self-test,verify,random,create-primary";
+
+/// How many bytes the `random` stage draws
+const RANDOM_BYTES: usize = 16;
+
+/// How many times the `bench` stage runs each command
+#[cfg(feature = "bench")]
+const BENCH_ITERATIONS: usize = 100;
+
+fn pipeline() -> Pipeline<App> {
+    let mut pipeline = Pipeline::<App>::default();
+    // Before anything trusts what the TPM computes
+    pipeline.register(Stage {
+        name: "self-test",
+        requires: &[],
+        on_failure: OnFailure::Continue,
+        run: |app, _findings| {
+            app.require_exclusive()?;
+            let report = selftest_and_report(&mut app.tcg).context("run the self test")?;
+            info!("TPM self test {report}");
+            if !report.passed() {
+                return Err(StageError(format!("the TPM's self test {report}")));
+            }
+            Ok(())
+        },
+    });
+    pipeline.register(Stage {
+        name: "lockout",
+        requires: &[],
+        on_failure: OnFailure::Continue,
+        run: |app, _findings| {
+            app.require_exclusive()?;
+            let status = lockout::refresh(&mut app.tcg).context("read the lockout state")?;
+            info!("Dictionary-attack lockout: {status}");
+            Ok(())
+        },
+    });
+    pipeline.register(Stage {
+        name: "identify",
+        requires: &[],
+        on_failure: OnFailure::Continue,
+        run: |app, _findings| {
+            // The firmware fields are all the other stages need
+            if !app.access.can_submit_commands() {
+                return Ok(());
+            }
+            app.commands = CommandSet::read(&mut app.tcg)
+                .inspect_err(|e| log::warn!("Failed to list the TPM's commands: {e}"))
+                .ok();
+            match is_storage_hierarchy_enabled(&mut app.tcg) {
+                Ok(true) => {}
+                Ok(false) => log::warn!(
+                    "The storage hierarchy is disabled until the next reboot, so commands using the owner hierarchy will fail with TPM_RC_HIERARCHY. The firmware or bootloader disabled it."
+                ),
+                Err(e) => log::warn!("Failed to check if the storage hierarchy is enabled: {e}"),
             }
-            EventType::EFI_PLATFORM_FIRMWARE_BLOB => {
-                if pcr_index.0 == 0 {
-                    log::debug!("Part of Firmware");
-                } else {
-                    log::debug!("Part of Firmware (but {pcr_index:?} for some reason)");
+            match TpmSpecVersion::read(&mut app.tcg) {
+                Ok(version) => info!("{version}"),
+                Err(e) => log::warn!("Failed to read the TPM's specification version: {e}"),
+            }
+            match read_tpm_unique_id(&mut app.tcg) {
+                Ok(id) => info!("TPM model ID: {id}"),
+                Err(e) => log::warn!("Failed to read the TPM's model ID: {e}"),
+            }
+            match get_persistent_slot_count(&mut app.tcg) {
+                Ok((used, available)) => {
+                    info!("Persistent objects: {used}, room for {available} more")
                 }
+                Err(e) => log::warn!("Failed to count the persistent objects: {e}"),
             }
-            EventType::EFI_VARIABLE_DRIVER_CONFIG => {
-                log::debug!("measure configuration for EFI Variables");
-            }
-            EventType::SEPARATOR => {
-                log::debug!("Separator (end of code controlling the computer) {pcr_index:?}");
-            }
-            EventType::EFI_BOOT_SERVICES_APPLICATION => {
-                let sha1 = event
-                    .digests()
-                    .into_iter()
-                    .find_map(|(algorithm, digest)| {
-                        if algorithm == AlgorithmId::SHA1 {
-                            Some(digest.plain_hex(false))
-                        } else {
-                            None
-                        }
-                    })
-                    .unwrap();
-                log::debug!("UEFI image loaded. sha1: {sha1:x}");
-            }
-            EventType::EFI_ACTION => {
-                // let action = str::from_utf8(event.event_data()).unwrap();
-                // log::debug!("UEFI action: {action:?}");
-            }
-            event_type => {
-                log::debug!("Unknown({event_type:?}) {pcr_index:?}");
-            }
-        }
-    }
-
-    // Replay events
-    // For now we will choose SHA1 to replay
-    let mut expected_sha1_pcr_values = [Default::default(); 24];
-    for event in event_log.iter() {
-        let digest = event
-            .digests()
-            .into_iter()
-            .find_map(|(algorithm, digest)| {
-                if algorithm == AlgorithmId::SHA1 {
-                    Some(digest)
-                } else {
-                    None
+            match list_persistent_objects(&mut app.tcg) {
+                Ok(objects) => {
+                    for object in objects {
+                        info!("Persistent object {object}");
+                    }
                 }
+                Err(e) => log::warn!("Failed to list the persistent objects: {e}"),
+            }
+            Ok(app
+                .firmware
+                .read_tpm(&mut app.tcg)
+                .context("read the firmware version")?)
+        },
+    });
+    pipeline.register(Stage {
+        name: "verify",
+        requires: &["identify"],
+        // The command stages don't need the event log, so firmware with a broken one can still
+        // run them
+        on_failure: OnFailure::Continue,
+        run: |app, findings| {
+            let mut report = report::analyze(&mut app.tcg, &app.firmware, app.access);
+            // Logged with the stage's outcome instead
+            *findings = core::mem::take(&mut report.findings);
+            if !report.event_log_read {
+                return Err(StageError(
+                    "the firmware didn't give us the event log".into(),
+                ));
+            }
+            report.log();
+            if report.truncated {
+                return Err(StageError(
+                    "the event log is truncated, which means it ran out space. So we can't verify any of the events in the event log!".into(),
+                ));
+            }
+            Ok(())
+        },
+    });
+    pipeline.register(Stage {
+        name: "interface",
+        requires: &[],
+        on_failure: OnFailure::Continue,
+        run: |app, _findings| {
+            app.require_exclusive()?;
+            interface::interface_report(&mut app.tcg).log();
+            Ok(())
+        },
+    });
+    #[cfg(feature = "bench")]
+    pipeline.register(Stage {
+        name: "bench",
+        requires: &[],
+        on_failure: OnFailure::Continue,
+        run: |app, _findings| {
+            app.require_exclusive()?;
+            let results = uefi_tpm2::bench::run(&mut app.tcg, BENCH_ITERATIONS)
+                .ok_or_else(|| StageError("there's no timer to time commands with".into()))?;
+            uefi_tpm2::bench::log_table(&results);
+            Ok(())
+        },
+    });
+    pipeline.register(Stage {
+        name: "pcr-self-check",
+        requires: &[],
+        on_failure: OnFailure::Continue,
+        run: |app, findings| {
+            app.require_exclusive()?;
+            analysis::scratch_pcr_self_check(&mut app.tcg, findings);
+            Ok(())
+        },
+    });
+    // Do TPM stuff for fun
+    pipeline.register(Stage {
+        name: "random",
+        requires: &[],
+        on_failure: OnFailure::Continue,
+        run: |app, _findings| {
+            app.require_exclusive()?;
+            let mut random_bytes = String::new();
+            match drain_entropy_to(&mut app.tcg, RANDOM_BYTES, &mut random_bytes) {
+                Err(DrainError::Tpm(e)) => Err(e).context("draw random bytes")?,
+                // A `String` takes everything written to it
+                Ok(()) | Err(DrainError::Write) => {}
+            }
+            log::debug!("Random bytes: {random_bytes}");
+            Ok(())
+        },
+    });
+    // CreatePrimary authorizes with the (hopefully empty) owner password
+    pipeline.register(Stage {
+        name: "create-primary",
+        requires: &["lockout", "identify"],
+        on_failure: OnFailure::Continue,
+        run: |app, _findings| {
+            app.require_exclusive()?;
+            let implemented = app
+                .commands
+                .as_ref()
+                .is_none_or(|commands| commands.supports(CommandCode::CREATE_PRIMARY));
+            if !implemented {
+                return Err(StageError("the TPM doesn't implement CreatePrimary".into()));
+            }
+            lockout::check()?;
+            let primary = create_primary(
+                &mut app.tcg,
+                TPM_RH_OWNER,
+                &AuthCommand::password(&[]),
+                &[],
+                &srk_template_rsa2048(),
+            )
+            .context("create a primary key")?;
+            info!("Created primary key {}", primary.name);
+            flush_context(&mut app.tcg, primary.handle).context("flush the primary key")?;
+            Ok(())
+        },
+    });
+    pipeline.register(Stage {
+        name: "provision",
+        requires: &["lockout"],
+        on_failure: OnFailure::Continue,
+        run: |app, _findings| {
+            app.require_exclusive()?;
+            let provisioned = provision(&mut app.tcg).context("provision the EK, SRK and AIK")?;
+            for line in provisioned.to_string().lines() {
+                info!("{line}");
+            }
+            Ok(())
+        },
+    });
+    pipeline
+}
+
+/// The volume `--volume` selects, or the one the app was loaded from
+fn open_file_system(volume: Option<&str>) -> Result<FileSystem, Status> {
+    let Some(selector) = volume else {
+        return Ok(FileSystem::new(
+            boot::get_image_file_system(boot::image_handle()).unwrap(),
+        ));
+    };
+    match volume::open_volume(selector) {
+        Ok(file_system) => Ok(file_system),
+        Err(VolumeError::NotFound) => {
+            log::error!("No volume matches {selector:?}. Volumes:");
+            for (i, volume) in volume::list_volumes()
+                .unwrap_or_default()
+                .iter()
+                .enumerate()
+            {
+                log::error!("  {i}: {volume}");
+            }
+            Err(Status::NOT_FOUND)
+        }
+        Err(VolumeError::NoMedia) => {
+            log::error!("Volume {selector:?} has no media in it");
+            Err(Status::NO_MEDIA)
+        }
+        Err(VolumeError::Uefi(status)) => {
+            log::error!("Failed to open volume {selector:?}: {status:?}");
+            Err(status)
+        }
+    }
+}
+
+/// Reads a whole file from the volume `--volume` selects
+fn read_file(volume: Option<&str>, path: &str) -> Result<(CString16, Vec<u8>), Status> {
+    let Ok(path) = CString16::try_from(path) else {
+        log::error!("Invalid path {path:?}");
+        return Err(Status::INVALID_PARAMETER);
+    };
+    let mut file_system = open_file_system(volume)?;
+    match file_system.read(PathBuf::from(path.clone())) {
+        Ok(bytes) => Ok((path, bytes)),
+        Err(e) => {
+            log::error!("Failed to read {path}: {e}");
+            Err(Status::NOT_FOUND)
+        }
+    }
+}
+
+/// Writes a whole file to the volume `--volume` selects
+fn write_file(volume: Option<&str>, path: &str, data: &[u8]) -> Result<(), Status> {
+    let Ok(path) = CString16::try_from(path) else {
+        log::error!("Invalid path {path:?}");
+        return Err(Status::INVALID_PARAMETER);
+    };
+    let mut file_system = open_file_system(volume)?;
+    file_system
+        .write(PathBuf::from(path.clone()), data)
+        .map_err(|e| {
+            log::error!("Failed to write {path}: {e}");
+            Status::DEVICE_ERROR
+        })
+}
+
+/// `--nv`: reads or writes an NV index
+fn nv_command(volume: Option<&str>, args: &[String], max_commands: u32) -> Status {
+    let command = match NvCommand::parse(args.iter().map(String::as_str)) {
+        Ok(command) => command,
+        Err(e) => {
+            log::error!("{e}");
+            return Status::INVALID_PARAMETER;
+        }
+    };
+    let mut tcg = match open_tcg_exclusive() {
+        Ok(tcg) => wrap_tcg(tcg, max_commands),
+        Err(e) => {
+            log::error!("{e}");
+            return Status::ACCESS_DENIED;
+        }
+    };
+    // So a wrong password can't push the TPM further into lockout
+    if let Err(e) = lockout::refresh(&mut tcg) {
+        log::warn!("Failed to read the lockout state: {e}");
+    }
+    let (NvCommand::Read { index, auth, .. } | NvCommand::Write { index, auth, .. }) = &command;
+    let nv_index = match TpmNvIndex::open(&mut tcg, *index, Box::new(Password(auth.clone()))) {
+        Ok(nv_index) => nv_index,
+        Err(e) => {
+            log::error!("Failed to open NV index {index:#x}: {e}");
+            return Status::NOT_FOUND;
+        }
+    };
+    let result = match &command {
+        NvCommand::Read {
+            offset,
+            len,
+            out,
+            resume,
+            ..
+        } => {
+            let mut data = if *resume {
+                read_file(volume, out).map_or(Vec::new(), |(_path, data)| data)
+            } else {
+                Vec::new()
+            };
+            let len = len.unwrap_or_else(|| nv_tool::remaining_len(nv_index.public(), *offset));
+            let result = nv_tool::resume_read(nv_index.public(), *offset, len, &mut data).and_then(
+                |(offset, len)| nv_tool::read_chunks(&mut tcg, &nv_index, offset, len, &mut data),
+            );
+            // Whatever was read is kept, so `resume` can carry on from it
+            if let Err(status) = write_file(volume, out, &data) {
+                return status;
+            }
+            result.map(|()| info!("Read {} bytes from {index:#x} into {out}", data.len()))
+        }
+        NvCommand::Write {
+            offset,
+            input,
+            resume,
+            ..
+        } => {
+            let data = match read_file(volume, input) {
+                Ok((_path, data)) => data,
+                Err(status) => return status,
+            };
+            nv_tool::write_verified(&mut tcg, &nv_index, *offset, &data, *resume).map(|written| {
+                info!(
+                    "Wrote {written} of {} bytes from {input} to {index:#x}, and read them back",
+                    data.len()
+                )
             })
-            .unwrap();
-        let mut hasher = Sha1::new();
-        let pcr_index = event.pcr_index().0 as usize;
-        hasher.update(&expected_sha1_pcr_values[pcr_index]);
-        hasher.update(digest);
-        expected_sha1_pcr_values[pcr_index] = hasher.finalize();
+        }
+    };
+    match result {
+        Ok(()) => Status::SUCCESS,
+        Err(e) => {
+            log::error!("{e}");
+            if let Some(hint) = nv_tool::resume_hint(&e) {
+                log::error!("{hint}");
+            }
+            match e {
+                NvToolError::Tpm { error, .. } => error.into(),
+                _ => Status::ABORTED,
+            }
+        }
     }
+}
 
-    // Do TPM stuff for fun
-    let mut command = GetRandom::new();
-    let random_bytes = submit_command(&mut tcg, &mut command).unwrap();
-    log::debug!("Random bytes: {:x?}", random_bytes);
-
-    for i in 0..24 {
-        let mut command = PcrRead::new(i);
-        let pcr_value = submit_command(&mut tcg, &mut command).unwrap();
-        if pcr_value.iter().all(|byte| *byte == u8::MAX) {
-            log::debug!("PCR {i}: unavailable");
-        } else if pcr_value == expected_sha1_pcr_values[i].as_slice() {
-            let pcr_value = pcr_value.plain_hex(false);
-            log::debug!("PCR {i}: {pcr_value:x} - matches event log");
-        } else {
-            let pcr_value = pcr_value.plain_hex(false);
-            log::debug!("PCR {i}: {pcr_value:x} - does not match event log!");
-        };
+/// `--repl`: the interactive prompt, until `quit`
+#[cfg(feature = "repl")]
+fn repl_command(max_commands: u32) -> Status {
+    let tcg = match open_tcg_exclusive() {
+        Ok(tcg) => tcg,
+        Err(e) => {
+            log::error!("{e}");
+            return Status::ACCESS_DENIED;
+        }
+    };
+    uefi_tpm2::repl::run(&mut wrap_tcg(tcg, max_commands));
+    Status::SUCCESS
+}
+
+#[cfg(not(feature = "repl"))]
+fn repl_command(_max_commands: u32) -> Status {
+    log::error!("--repl needs the repl feature");
+    Status::UNSUPPORTED
+}
+
+/// `--log-file`: analyzes a log file
+fn analyze_log_file(volume: Option<&str>, path: &str) -> Status {
+    let (path, bytes) = match read_file(volume, path) {
+        Ok(file) => file,
+        Err(status) => return status,
+    };
+    info!("Analyzing {path} ({} bytes)", bytes.len());
+    analysis::analyze_log_file(&bytes).log();
+    Status::SUCCESS
+}
+
+/// `--diff-logs`: lists the events only one of two event log files has
+fn diff_log_files(volume: Option<&str>, paths: &[String]) -> Status {
+    let [old_path, new_path] = paths else {
+        log::error!("--diff-logs takes the old log file and the new one");
+        return Status::INVALID_PARAMETER;
+    };
+    let (old_path, old) = match read_file(volume, old_path) {
+        Ok(file) => file,
+        Err(status) => return status,
+    };
+    let (new_path, new) = match read_file(volume, new_path) {
+        Ok(file) => file,
+        Err(status) => return status,
+    };
+    let (old, new) = match (parse_event_log(&old), parse_event_log(&new)) {
+        (Ok(old), Ok(new)) => (old, new),
+        (Err(e), _) => {
+            log::error!("Failed to parse {old_path}: {e}");
+            return Status::INVALID_PARAMETER;
+        }
+        (_, Err(e)) => {
+            log::error!("Failed to parse {new_path}: {e}");
+            return Status::INVALID_PARAMETER;
+        }
+    };
+    info!("Events only in {old_path} (-) or {new_path} (+):");
+    for line in log_diff::diff_logs(&old, &new).to_string().lines() {
+        info!("{line}");
     }
+    Status::SUCCESS
+}
 
-    let mut command = CreatePrimary::new();
-    submit_command(&mut tcg, &mut command).unwrap();
+/// `--measure-image`: has the firmware measure an EFI binary and cross-checks its Authenticode
+/// hash with ours
+fn measure_image_file(volume: Option<&str>, path: &str, max_commands: u32) -> Status {
+    // Loaded into pool memory, which is what the firmware expects for `PE_COFF_IMAGE`
+    let (path, image) = match read_file(volume, path) {
+        Ok(file) => file,
+        Err(status) => return status,
+    };
+    // Extending a PCR isn't read-only
+    let mut tcg = match open_tcg_exclusive() {
+        Ok(tcg) => wrap_tcg(tcg, max_commands),
+        Err(e) => {
+            log::error!("{e}");
+            return Status::ACCESS_DENIED;
+        }
+    };
+    info!("Measuring {path} ({} bytes)", image.len());
+    let mut findings = Findings::default();
+    analysis::measure_image(&mut tcg, &image, path.to_string().as_bytes(), &mut findings);
+    findings.log();
+    // Leave the PCR as we found it at boot, though the event stays in the log
+    if let Err(e) = pcr_reset(&mut tcg, analysis::IMAGE_MEASUREMENT_PCR) {
+        log::warn!(
+            "Failed to reset PCR {}: {e}",
+            analysis::IMAGE_MEASUREMENT_PCR
+        );
+    }
+    Status::SUCCESS
+}
 
-    loop {
-        boot::stall(3_000_000);
+/// `--export-certs`: writes the certificates measured into PCR 7 to a directory
+fn export_measured_certificates(
+    volume: Option<&str>,
+    directory: &str,
+    max_commands: u32,
+) -> Status {
+    let mut file_system = match open_file_system(volume) {
+        Ok(file_system) => file_system,
+        Err(status) => return status,
+    };
+    let mut tcg = match open_tcg() {
+        Ok((tcg, _access)) => wrap_tcg(tcg, max_commands),
+        Err(e) => {
+            log::error!("{e}");
+            return Status::ACCESS_DENIED;
+        }
+    };
+    let event_log = match tcg.protocol().get_event_log_v2() {
+        Ok(event_log) => event_log,
+        Err(e) => {
+            log::error!("Failed to get the event log: {e}");
+            return e.status();
+        }
+    };
+    let events = collect_events(&event_log);
+    let certs = cert_export::extract_certificates(&events);
+    if let Err(e) = cert_export::export_certificates(&mut file_system, directory, &certs) {
+        log::error!("{e}");
+        return Status::DEVICE_ERROR;
     }
-    // Status::SUCCESS
+    info!("Wrote {} certificates to {directory}", certs.len());
+    Status::SUCCESS
+}
+
+/// `--show-verdict`: shows what the driver found earlier in this boot
+fn show_driver_verdict() -> Status {
+    let data = match verdict::load_verdict() {
+        Ok(data) => data,
+        Err(e) if e.status() == Status::NOT_FOUND => {
+            info!("No verdict, so the driver didn't run this boot");
+            return Status::NOT_FOUND;
+        }
+        Err(e) => {
+            log::error!("Failed to read the verdict: {e}");
+            return e.status();
+        }
+    };
+    let verdict = match deserialize_verdict(&data) {
+        Ok(verdict) => verdict,
+        Err(e) => {
+            log::error!("{e}");
+            return Status::VOLUME_CORRUPTED;
+        }
+    };
+    match verdict.worst_severity() {
+        Some(severity) => info!("Driver verdict: worst finding is {severity}"),
+        None => info!("Driver verdict: no findings"),
+    }
+    for entry in &verdict.entries {
+        let summary = entry
+            .finding_code()
+            .map_or("unknown code", |code| code.summary());
+        match entry.pcr_index {
+            Some(pcr_index) => info!("[{}] PCR {pcr_index}: {summary}", entry.code),
+            None => info!("[{}]: {summary}", entry.code),
+        }
+    }
+    Status::SUCCESS
 }
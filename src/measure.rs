@@ -0,0 +1,83 @@
+//! Measuring our own data through the firmware, so it's both extended into a PCR and logged where
+//! the OS and verifiers will find it
+
+use uefi::proto::tcg::{
+    EventType, PcrIndex,
+    v2::{HashLogExtendEventFlags, PcrEventInputs, Tcg},
+};
+
+/// An event to measure: `pcr_index` is extended with the hash of `data`, and `data` itself is
+/// logged as the event data, so the event can be replayed from the log alone
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Measurement<'a> {
+    pub pcr_index: u32,
+    pub event_type: EventType,
+    pub data: &'a [u8],
+}
+
+impl Measurement<'_> {
+    pub fn measure(&self, tcg: &mut Tcg) -> uefi::Result {
+        let event =
+            PcrEventInputs::new_in_box(PcrIndex(self.pcr_index), self.event_type, self.data)?;
+        tcg.hash_log_extend_event(HashLogExtendEventFlags::empty(), self.data, &event)
+    }
+}
+
+/// Measures `data` as a [`Measurement`]
+pub fn measure_data(
+    tcg: &mut Tcg,
+    pcr_index: u32,
+    event_type: EventType,
+    data: &[u8],
+) -> uefi::Result {
+    Measurement {
+        pcr_index,
+        event_type,
+        data,
+    }
+    .measure(tcg)
+}
+
+/// The event for a string, like a kernel command line, the way `EV_IPL` events are: the event
+/// data is the UTF-8 bytes without a terminator
+pub fn string_measurement(pcr_index: u32, event_type: EventType, s: &str) -> Measurement<'_> {
+    Measurement {
+        pcr_index,
+        event_type,
+        data: s.as_bytes(),
+    }
+}
+
+/// Measures a string as [`string_measurement`] describes
+pub fn measure_string(
+    tcg: &mut Tcg,
+    pcr_index: u32,
+    event_type: EventType,
+    s: &str,
+) -> uefi::Result {
+    string_measurement(pcr_index, event_type, s).measure(tcg)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn string_event_data_is_the_string() {
+        let measurement = string_measurement(8, EventType::IPL, "root=/dev/sda1 quiet");
+        assert_eq!(measurement.pcr_index, 8);
+        assert_eq!(measurement.event_type, EventType::IPL);
+        assert_eq!(measurement.data, b"root=/dev/sda1 quiet");
+    }
+
+    #[test]
+    fn string_event_data_is_utf8_without_a_terminator() {
+        let measurement = string_measurement(16, EventType::EFI_ACTION, "größe");
+        assert_eq!(measurement.data, [0x67, 0x72, 0xc3, 0xb6, 0xc3, 0x9f, 0x65]);
+        assert!(
+            string_measurement(16, EventType::EFI_ACTION, "")
+                .data
+                .is_empty()
+        );
+    }
+}
@@ -0,0 +1,556 @@
+//! `--nv`: reading and writing NV indexes from the command line, for provisioning before an OS
+//! is installed.
+//!
+//! ```text
+//! --nv read 0x1c00002 offset=0 len=1024 out=\ek.der
+//! --nv write 0x1500001 in=\baseline.bin
+//! ```
+//!
+//! Data moves one [`MAX_NV_BUFFER_SIZE`] chunk at a time, and every chunk written is read back
+//! and compared, unless the index can't be read with a password. If a command fails partway, what
+//! was done so far is kept and the same command with `resume` added carries on from there: a read
+//! continues after the end of the output file, and a write skips the chunks the index already
+//! holds.
+
+use alloc::{format, string::String, vec::Vec};
+use core::fmt;
+
+use crate::tpm::{
+    TpmError, TpmHandle, TpmTransport,
+    nv::{
+        MAX_NV_BUFFER_SIZE, TPM_NT_ORDINARY, TPMA_NV_AUTHREAD, TPMA_NV_AUTHWRITE,
+        TPMA_NV_OWNERREAD, TPMA_NV_OWNERWRITE, TPMA_NV_POLICYREAD, TPMA_NV_POLICYWRITE,
+        TPMA_NV_PPREAD, TPMA_NV_PPWRITE, TPMA_NV_READLOCKED, TPMA_NV_WRITELOCKED, TPMA_NV_WRITTEN,
+        TpmNvIndex, TpmNvPublic, nv_type,
+    },
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NvCommand {
+    Read {
+        index: TpmHandle,
+        offset: u16,
+        /// To the end of the index if not given
+        len: Option<u16>,
+        out: String,
+        resume: bool,
+        auth: Vec<u8>,
+    },
+    Write {
+        index: TpmHandle,
+        offset: u16,
+        input: String,
+        resume: bool,
+        auth: Vec<u8>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NvArgError {
+    UnknownMode(String),
+    Missing(&'static str),
+    Invalid(String),
+}
+
+impl fmt::Display for NvArgError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownMode(mode) => {
+                write!(f, "unknown NV mode {mode:?}, expected read or write")
+            }
+            Self::Missing(name) => write!(f, "missing {name}"),
+            Self::Invalid(arg) => write!(f, "invalid argument {arg:?}"),
+        }
+    }
+}
+
+/// Decimal, or hexadecimal with `0x`
+fn parse_number(s: &str) -> Option<u32> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
+
+impl NvCommand {
+    /// Parses the arguments after `--nv`: the mode, the index, then `key=value` arguments and the
+    /// `resume` flag. `auth=` is the password for the index or the owner hierarchy, whichever the
+    /// index is authorized with, and is empty if not given.
+    pub fn parse<'s>(mut args: impl Iterator<Item = &'s str>) -> Result<Self, NvArgError> {
+        let mode = args.next().ok_or(NvArgError::Missing("read or write"))?;
+        let index = args.next().ok_or(NvArgError::Missing("NV index"))?;
+        let index = parse_number(index).ok_or_else(|| NvArgError::Invalid(index.into()))?;
+        let mut offset = 0;
+        let mut len = None;
+        let mut path = None;
+        let mut resume = false;
+        let mut auth = Vec::new();
+        for arg in args {
+            let invalid = || NvArgError::Invalid(arg.into());
+            let u16_value = |value| {
+                parse_number(value)
+                    .and_then(|value| u16::try_from(value).ok())
+                    .ok_or_else(invalid)
+            };
+            match arg.split_once('=') {
+                None if arg == "resume" => resume = true,
+                Some(("offset", value)) => offset = u16_value(value)?,
+                Some(("len", value)) if mode == "read" => len = Some(u16_value(value)?),
+                Some(("out", value)) if mode == "read" => path = Some(value.into()),
+                Some(("in", value)) if mode == "write" => path = Some(value.into()),
+                Some(("auth", value)) => auth = value.as_bytes().into(),
+                _ => return Err(invalid()),
+            }
+        }
+        match mode {
+            "read" => Ok(Self::Read {
+                index,
+                offset,
+                len,
+                out: path.ok_or(NvArgError::Missing("out=<path>"))?,
+                resume,
+                auth,
+            }),
+            "write" => Ok(Self::Write {
+                index,
+                offset,
+                input: path.ok_or(NvArgError::Missing("in=<path>"))?,
+                resume,
+                auth,
+            }),
+            mode => Err(NvArgError::UnknownMode(mode.into())),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NvToolError {
+    /// The range isn't inside the index
+    OutOfBounds { data_size: u16 },
+    /// The index's attributes don't allow it with a password
+    NotAllowed(&'static str),
+    /// A command failed after `done` bytes
+    Tpm { error: TpmError, done: usize },
+    /// The chunk at `offset` read back different from what was written
+    VerifyFailed { offset: u16 },
+}
+
+impl fmt::Display for NvToolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OutOfBounds { data_size } => {
+                write!(
+                    f,
+                    "the range doesn't fit in the index, which is {data_size} bytes"
+                )
+            }
+            Self::NotAllowed(reason) => f.write_str(reason),
+            Self::Tpm { error, done } => write!(f, "{error} after {done} bytes"),
+            Self::VerifyFailed { offset } => {
+                write!(
+                    f,
+                    "the data at offset {offset} read back different from what was written"
+                )
+            }
+        }
+    }
+}
+
+/// Refuses reads that a password can't authorize
+pub fn check_readable(public: &TpmNvPublic) -> Result<(), NvToolError> {
+    let attributes = public.attributes;
+    if attributes & TPMA_NV_READLOCKED != 0 {
+        Err(NvToolError::NotAllowed(
+            "the index is read-locked until the next reboot",
+        ))
+    } else if attributes & (TPMA_NV_AUTHREAD | TPMA_NV_OWNERREAD) == 0 {
+        Err(NvToolError::NotAllowed(
+            if attributes & TPMA_NV_POLICYREAD != 0 {
+                "the index can only be read with a policy session"
+            } else if attributes & TPMA_NV_PPREAD != 0 {
+                "the index can only be read with platform authorization, which the firmware has given up"
+            } else {
+                "the index can't be read"
+            },
+        ))
+    } else if attributes & TPMA_NV_POLICYREAD != 0 && attributes & TPMA_NV_AUTHREAD == 0 {
+        // `read_auth_handle` picks the index itself, which only a policy session would satisfy
+        Err(NvToolError::NotAllowed(
+            "the index is read with a policy session instead of the owner password",
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Refuses writes that a password can't authorize, and indexes that aren't plain data
+pub fn check_writable(public: &TpmNvPublic) -> Result<(), NvToolError> {
+    let attributes = public.attributes;
+    if nv_type(attributes) != TPM_NT_ORDINARY {
+        Err(NvToolError::NotAllowed(
+            "the index is a counter, bit field, extend or PIN index, not ordinary data",
+        ))
+    } else if attributes & TPMA_NV_WRITELOCKED != 0 {
+        Err(NvToolError::NotAllowed("the index is write-locked"))
+    } else if attributes & (TPMA_NV_AUTHWRITE | TPMA_NV_OWNERWRITE) == 0 {
+        Err(NvToolError::NotAllowed(
+            if attributes & TPMA_NV_POLICYWRITE != 0 {
+                "the index can only be written with a policy session"
+            } else if attributes & TPMA_NV_PPWRITE != 0 {
+                "the index can only be written with platform authorization, which the firmware has given up"
+            } else {
+                "the index can't be written"
+            },
+        ))
+    } else if attributes & TPMA_NV_POLICYWRITE != 0 && attributes & TPMA_NV_AUTHWRITE == 0 {
+        Err(NvToolError::NotAllowed(
+            "the index is written with a policy session instead of the owner password",
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+fn check_bounds(public: &TpmNvPublic, offset: u16, len: usize) -> Result<(), NvToolError> {
+    if offset as usize + len > public.data_size as usize {
+        return Err(NvToolError::OutOfBounds {
+            data_size: public.data_size,
+        });
+    }
+    Ok(())
+}
+
+/// How much of the index from `offset` to read when no length was given
+pub fn remaining_len(public: &TpmNvPublic, offset: u16) -> u16 {
+    public.data_size.saturating_sub(offset)
+}
+
+/// Where a read of `len` bytes from `offset` carries on with `resume`, given the start of it that
+/// `data` already holds: the offset and length of the rest. `data` is cut down to `len` bytes.
+pub fn resume_read(
+    public: &TpmNvPublic,
+    offset: u16,
+    len: u16,
+    data: &mut Vec<u8>,
+) -> Result<(u16, u16), NvToolError> {
+    let out_of_bounds = || NvToolError::OutOfBounds {
+        data_size: public.data_size,
+    };
+    data.truncate(len as usize);
+    let done = u16::try_from(data.len()).map_err(|_| out_of_bounds())?;
+    let resume_offset = offset.checked_add(done).ok_or_else(out_of_bounds)?;
+    Ok((resume_offset, len - done))
+}
+
+/// Reads `len` bytes from `offset`, appending them to `data` a chunk at a time. On failure `data`
+/// keeps the chunks that were read.
+pub fn read_chunks(
+    tcg: &mut dyn TpmTransport,
+    index: &TpmNvIndex,
+    offset: u16,
+    len: u16,
+    data: &mut Vec<u8>,
+) -> Result<(), NvToolError> {
+    check_readable(index.public())?;
+    check_bounds(index.public(), offset, len as usize)?;
+    let mut done = 0;
+    while done < len {
+        let chunk_len = (len - done).min(MAX_NV_BUFFER_SIZE as u16);
+        let chunk =
+            index
+                .read(tcg, offset + done, chunk_len)
+                .map_err(|error| NvToolError::Tpm {
+                    error,
+                    done: done as usize,
+                })?;
+        data.extend_from_slice(&chunk);
+        done += chunk_len;
+    }
+    Ok(())
+}
+
+/// Writes `data` at `offset` a chunk at a time, reading each one back to check it if the index
+/// can be read. With `resume`,
+/// chunks the index already holds are skipped. Returns how many bytes were actually written.
+pub fn write_verified(
+    tcg: &mut dyn TpmTransport,
+    index: &TpmNvIndex,
+    offset: u16,
+    data: &[u8],
+    resume: bool,
+) -> Result<usize, NvToolError> {
+    check_writable(index.public())?;
+    check_bounds(index.public(), offset, data.len())?;
+    // An index that was never written can't be read
+    let resume = resume && check_readable(index.public()).is_ok();
+    let resume = resume && index.public().attributes & TPMA_NV_WRITTEN != 0;
+    let mut written = 0;
+    for (i, chunk) in data.chunks(MAX_NV_BUFFER_SIZE).enumerate() {
+        let chunk_offset = offset + (i * MAX_NV_BUFFER_SIZE) as u16;
+        let done = i * MAX_NV_BUFFER_SIZE;
+        let tpm_error = |error| NvToolError::Tpm { error, done };
+        if resume
+            && index
+                .read(tcg, chunk_offset, chunk.len() as u16)
+                .map_err(tpm_error)?
+                == chunk
+        {
+            continue;
+        }
+        index.write(tcg, chunk, chunk_offset).map_err(tpm_error)?;
+        if check_readable(index.public()).is_ok() {
+            let read_back = index
+                .read(tcg, chunk_offset, chunk.len() as u16)
+                .map_err(tpm_error)?;
+            if read_back != chunk {
+                return Err(NvToolError::VerifyFailed {
+                    offset: chunk_offset,
+                });
+            }
+        }
+        written += chunk.len();
+    }
+    Ok(written)
+}
+
+/// How to carry on after `error`, for the user
+pub fn resume_hint(error: &NvToolError) -> Option<String> {
+    match error {
+        NvToolError::Tpm { done, .. } => Some(format!(
+            "{done} bytes were done. Run the same command with `resume` added to carry on."
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::boxed::Box;
+
+    use uefi::proto::tcg::AlgorithmId;
+
+    use super::*;
+    use crate::tpm::{
+        ResponseCode,
+        auth::Password,
+        mock::{
+            MockTransport, nv_read_command, nv_read_public_command, nv_read_public_response,
+            nv_read_response, nv_write_command, success_response,
+        },
+        tpm2b::Tpm2b,
+    };
+
+    const INDEX: TpmHandle = 0x0150_0016;
+
+    fn public(attributes: u32) -> TpmNvPublic {
+        TpmNvPublic {
+            nv_index: INDEX,
+            name_alg: AlgorithmId::SHA256,
+            attributes,
+            auth_policy: Tpm2b::default(),
+            data_size: 2000,
+        }
+    }
+
+    /// Opens the index through `tcg`, which has to expect `TPM2_NV_ReadPublic` first
+    fn open(tcg: &mut MockTransport) -> TpmNvIndex {
+        TpmNvIndex::open(tcg, INDEX, Box::new(Password::default())).unwrap()
+    }
+
+    fn data(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i % 251) as u8).collect()
+    }
+
+    #[test]
+    fn reads_are_split_into_chunks() {
+        let data = data(1500);
+        let mut tcg = MockTransport::default()
+            .expect(
+                nv_read_public_command(INDEX),
+                nv_read_public_response(&public(TPMA_NV_AUTHREAD)),
+            )
+            .expect(
+                nv_read_command(INDEX, 1024, 100),
+                nv_read_response(&data[..1024]),
+            )
+            .expect(
+                nv_read_command(INDEX, 476, 1124),
+                nv_read_response(&data[1024..]),
+            );
+        let index = open(&mut tcg);
+        let mut read = Vec::new();
+        read_chunks(&mut tcg, &index, 100, 1500, &mut read).unwrap();
+        tcg.assert_done();
+        assert_eq!(read, data);
+    }
+
+    #[test]
+    fn failed_reads_keep_the_chunks_before() {
+        let data = data(1500);
+        let mut tcg = MockTransport::default()
+            .expect(
+                nv_read_public_command(INDEX),
+                nv_read_public_response(&public(TPMA_NV_AUTHREAD)),
+            )
+            .expect(
+                nv_read_command(INDEX, 1024, 0),
+                nv_read_response(&data[..1024]),
+            )
+            .expect(
+                nv_read_command(INDEX, 476, 1024),
+                MockTransport::response_bytes(ResponseCode::FAILURE, &[]),
+            );
+        let index = open(&mut tcg);
+        let mut read = Vec::new();
+        let error = read_chunks(&mut tcg, &index, 0, 1500, &mut read).unwrap_err();
+        tcg.assert_done();
+        assert!(matches!(error, NvToolError::Tpm { done: 1024, .. }));
+        assert_eq!(read, data[..1024]);
+    }
+
+    #[test]
+    fn reads_past_the_end_send_nothing() {
+        let mut tcg = MockTransport::default().expect(
+            nv_read_public_command(INDEX),
+            nv_read_public_response(&public(TPMA_NV_AUTHREAD)),
+        );
+        let index = open(&mut tcg);
+        assert_eq!(
+            read_chunks(&mut tcg, &index, 1000, 1001, &mut Vec::new()),
+            Err(NvToolError::OutOfBounds { data_size: 2000 })
+        );
+        tcg.assert_done();
+    }
+
+    #[test]
+    fn resumed_reads_carry_on_after_the_data() {
+        let public = public(TPMA_NV_AUTHREAD);
+        let mut data = data(300);
+        assert_eq!(resume_read(&public, 100, 1000, &mut data), Ok((400, 700)));
+        assert_eq!(data.len(), 300);
+        // More than the read asks for, like a file from a longer read
+        assert_eq!(resume_read(&public, 100, 200, &mut data), Ok((300, 0)));
+        assert_eq!(data.len(), 200);
+        // Past the end of what an offset can be, instead of overflowing
+        assert_eq!(
+            resume_read(&public, u16::MAX - 10, 100, &mut data),
+            Err(NvToolError::OutOfBounds { data_size: 2000 })
+        );
+    }
+
+    #[test]
+    fn writes_are_read_back_a_chunk_at_a_time() {
+        let data = data(1500);
+        let mut tcg = MockTransport::default()
+            .expect(
+                nv_read_public_command(INDEX),
+                nv_read_public_response(&public(TPMA_NV_AUTHREAD | TPMA_NV_AUTHWRITE)),
+            )
+            .expect(
+                nv_write_command(INDEX, &data[..1024], 100),
+                success_response(),
+            )
+            .expect(
+                nv_read_command(INDEX, 1024, 100),
+                nv_read_response(&data[..1024]),
+            )
+            .expect(
+                nv_write_command(INDEX, &data[1024..], 1124),
+                success_response(),
+            )
+            .expect(
+                nv_read_command(INDEX, 476, 1124),
+                nv_read_response(&data[1024..]),
+            );
+        let index = open(&mut tcg);
+        assert_eq!(
+            write_verified(&mut tcg, &index, 100, &data, false),
+            Ok(1500)
+        );
+        tcg.assert_done();
+    }
+
+    #[test]
+    fn a_different_read_back_fails_the_write() {
+        let data = data(1500);
+        let mut tcg = MockTransport::default()
+            .expect(
+                nv_read_public_command(INDEX),
+                nv_read_public_response(&public(TPMA_NV_AUTHREAD | TPMA_NV_AUTHWRITE)),
+            )
+            .expect(
+                nv_write_command(INDEX, &data[..1024], 0),
+                success_response(),
+            )
+            .expect(
+                nv_read_command(INDEX, 1024, 0),
+                nv_read_response(&[0; 1024]),
+            );
+        let index = open(&mut tcg);
+        assert_eq!(
+            write_verified(&mut tcg, &index, 0, &data, false),
+            Err(NvToolError::VerifyFailed { offset: 0 })
+        );
+        tcg.assert_done();
+    }
+
+    #[test]
+    fn write_only_indexes_arent_read_back() {
+        let data = data(10);
+        let mut tcg = MockTransport::default()
+            .expect(
+                nv_read_public_command(INDEX),
+                nv_read_public_response(&public(TPMA_NV_AUTHWRITE)),
+            )
+            .expect(nv_write_command(INDEX, &data, 0), success_response());
+        let index = open(&mut tcg);
+        assert_eq!(write_verified(&mut tcg, &index, 0, &data, false), Ok(10));
+        tcg.assert_done();
+    }
+
+    #[test]
+    fn resumed_writes_skip_the_chunks_already_written() {
+        let data = data(1500);
+        let mut tcg = MockTransport::default()
+            .expect(
+                nv_read_public_command(INDEX),
+                nv_read_public_response(&public(
+                    TPMA_NV_AUTHREAD | TPMA_NV_AUTHWRITE | TPMA_NV_WRITTEN,
+                )),
+            )
+            .expect(
+                nv_read_command(INDEX, 1024, 0),
+                nv_read_response(&data[..1024]),
+            )
+            .expect(
+                nv_read_command(INDEX, 476, 1024),
+                nv_read_response(&[0; 476]),
+            )
+            .expect(
+                nv_write_command(INDEX, &data[1024..], 1024),
+                success_response(),
+            )
+            .expect(
+                nv_read_command(INDEX, 476, 1024),
+                nv_read_response(&data[1024..]),
+            );
+        let index = open(&mut tcg);
+        assert_eq!(write_verified(&mut tcg, &index, 0, &data, true), Ok(476));
+        tcg.assert_done();
+    }
+
+    #[test]
+    fn resuming_an_unwritten_index_writes_everything() {
+        let data = data(10);
+        let mut tcg = MockTransport::default()
+            .expect(
+                nv_read_public_command(INDEX),
+                nv_read_public_response(&public(TPMA_NV_AUTHREAD | TPMA_NV_AUTHWRITE)),
+            )
+            .expect(nv_write_command(INDEX, &data, 0), success_response())
+            .expect(nv_read_command(INDEX, 10, 0), nv_read_response(&data));
+        let index = open(&mut tcg);
+        assert_eq!(write_verified(&mut tcg, &index, 0, &data, true), Ok(10));
+        tcg.assert_done();
+    }
+}
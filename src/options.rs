@@ -0,0 +1,82 @@
+//! Parsing the app's load options. When started from the UEFI shell they hold the command line
+//! (including the app's own name) as a null-terminated UCS-2 string.
+
+use core::char::{REPLACEMENT_CHARACTER, decode_utf16};
+
+#[derive(Debug, Clone, Copy)]
+pub struct Options<'a>(&'a [u8]);
+
+impl<'a> Options<'a> {
+    pub fn new(load_options: &'a [u8]) -> Self {
+        Self(load_options)
+    }
+
+    /// Space-separated arguments
+    pub fn args(&self) -> impl Iterator<Item = Arg<'a>> + 'a {
+        const SPACE: [u8; 2] = [b' ', 0];
+        let len = self
+            .0
+            .as_chunks::<2>()
+            .0
+            .iter()
+            .position(|unit| *unit == [0, 0])
+            .unwrap_or(self.0.len() / 2);
+        let mut rest = &self.0[..len * 2];
+        core::iter::from_fn(move || {
+            while rest.starts_with(&SPACE) {
+                rest = &rest[2..];
+            }
+            if rest.is_empty() {
+                return None;
+            }
+            let end = rest
+                .as_chunks::<2>()
+                .0
+                .iter()
+                .position(|unit| *unit == SPACE)
+                .map_or(rest.len(), |i| i * 2);
+            let (arg, tail) = rest.split_at(end);
+            rest = tail;
+            Some(Arg(arg))
+        })
+    }
+
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.args().any(|arg| arg.eq_str(flag))
+    }
+
+    /// Every argument after `name`, for options that take the rest of the command line
+    pub fn rest(&self, name: &str) -> impl Iterator<Item = Arg<'a>> + 'a {
+        let mut args = self.args();
+        let found = args.any(|arg| arg.eq_str(name));
+        args.filter(move |_| found)
+    }
+
+    /// The argument after `name`, for options like `--log-file <path>`
+    pub fn value(&self, name: &str) -> Option<Arg<'a>> {
+        let mut args = self.args();
+        args.find(|arg| arg.eq_str(name))?;
+        args.next()
+    }
+}
+
+/// A single argument, still in UCS-2
+#[derive(Debug, Clone, Copy)]
+pub struct Arg<'a>(&'a [u8]);
+
+impl<'a> Arg<'a> {
+    pub fn chars(&self) -> impl Iterator<Item = char> + 'a {
+        decode_utf16(
+            self.0
+                .as_chunks::<2>()
+                .0
+                .iter()
+                .map(|unit| u16::from_le_bytes(*unit)),
+        )
+        .map(|c| c.unwrap_or(REPLACEMENT_CHARACTER))
+    }
+
+    pub fn eq_str(&self, s: &str) -> bool {
+        self.chars().eq(s.chars())
+    }
+}
@@ -0,0 +1,89 @@
+//! The panic handler for both binaries. It logs where the panic happened and, built with the
+//! `panic-marker` feature, measures a marker event into [`DEBUG_PCR`] first, so a verifier
+//! reading the log can tell the tool crashed instead of finishing.
+
+use alloc::{format, string::String};
+use core::panic::Location;
+#[cfg(not(test))]
+use core::{
+    panic::PanicInfo,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use uefi::proto::tcg::EventType;
+
+use crate::tpm::pcr::DEBUG_PCR;
+
+/// Where the marker goes. Nothing should be sealed to it, so the marker can't break anything.
+pub const PANIC_MARKER_PCR: u32 = DEBUG_PCR;
+
+/// The marker's event type. Its data is text, like other `EV_EFI_ACTION` events.
+pub const PANIC_MARKER_EVENT_TYPE: EventType = EventType::EFI_ACTION;
+
+/// Set by the first panic, so a panic while handling one doesn't try again
+#[cfg(not(test))]
+static PANICKING: AtomicBool = AtomicBool::new(false);
+
+/// The marker's event data: `uefi-tpm2 panicked`, and where if that's known
+pub fn panic_marker_event_data(location: Option<&Location<'_>>) -> String {
+    match location {
+        Some(location) => format!(
+            "uefi-tpm2 panicked at {}:{}:{}",
+            location.file(),
+            location.line(),
+            location.column()
+        ),
+        None => "uefi-tpm2 panicked".into(),
+    }
+}
+
+/// Opens the protocol shared if the app already has it, since the app can't close it anymore
+#[cfg(all(feature = "panic-marker", not(test)))]
+fn measure_panic_marker(info: &PanicInfo<'_>) {
+    let Ok((mut tcg, _access)) = crate::protocol::open_tcg() else {
+        return;
+    };
+    let data = panic_marker_event_data(info.location());
+    if let Err(e) =
+        crate::measure::measure_string(&mut tcg, PANIC_MARKER_PCR, PANIC_MARKER_EVENT_TYPE, &data)
+    {
+        log::error!("Failed to measure the panic marker: {e}");
+    }
+}
+
+#[cfg(not(test))]
+#[panic_handler]
+fn panic(info: &PanicInfo<'_>) -> ! {
+    log::error!("[PANIC]: {info}");
+    // A panic while measuring, like after `ExitBootServices`, lands here again
+    if !PANICKING.swap(true, Ordering::Relaxed) {
+        #[cfg(feature = "panic-marker")]
+        measure_panic_marker(info);
+    }
+    loop {
+        core::hint::spin_loop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marker_says_where() {
+        let (location, line) = (Location::caller(), line!());
+        assert_eq!(
+            panic_marker_event_data(Some(location)).as_bytes(),
+            // 33 is the column of `Location::caller()`
+            format!("uefi-tpm2 panicked at {}:{line}:33", file!()).as_bytes()
+        );
+    }
+
+    #[test]
+    fn marker_without_a_location() {
+        assert_eq!(
+            panic_marker_event_data(None).as_bytes(),
+            b"uefi-tpm2 panicked"
+        );
+    }
+}
@@ -0,0 +1,72 @@
+//! Converting TPM public keys to PEM `SubjectPublicKeyInfo`, so an EK or AIK can be handed to
+//! tooling that knows nothing about TPMs
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use der::pem::LineEnding;
+use p256::pkcs8::EncodePublicKey as _;
+use rsa::{BigUint, RsaPublicKey, pkcs8::EncodePublicKey as _};
+
+use crate::tpm::public::{PublicId, PublicParameters, TPM_ECC_NIST_P256, TpmtPublic};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConvertError {
+    /// Only RSA and ECC keys have a `SubjectPublicKeyInfo`
+    UnsupportedAlgorithm,
+    UnsupportedCurve(u16),
+    /// The public area doesn't describe a valid key
+    InvalidKey,
+    Encoding,
+}
+
+impl fmt::Display for ConvertError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnsupportedAlgorithm => f.write_str("not an RSA or ECC key"),
+            Self::UnsupportedCurve(curve_id) => write!(f, "unsupported curve {curve_id:#06x}"),
+            Self::InvalidKey => f.write_str("invalid public key"),
+            Self::Encoding => f.write_str("failed to encode key"),
+        }
+    }
+}
+
+/// RSA keys become a PKCS#8 `rsaEncryption` public key. ECC keys become an `id-ecPublicKey` with
+/// the curve OID and an uncompressed SEC1 point.
+pub fn tpmt_public_to_pem(public: &TpmtPublic) -> Result<Vec<u8>, ConvertError> {
+    let pem = match (&public.parameters, &public.unique) {
+        (PublicParameters::Rsa { exponent, .. }, PublicId::Rsa(n)) => {
+            let exponent = match exponent {
+                0 => 65537,
+                exponent => *exponent,
+            };
+            RsaPublicKey::new(
+                BigUint::from_bytes_be(n.as_slice()),
+                BigUint::from(exponent),
+            )
+            .map_err(|_| ConvertError::InvalidKey)?
+            .to_public_key_pem(LineEnding::LF)
+            .map_err(|_| ConvertError::Encoding)?
+        }
+        (PublicParameters::Ecc { curve_id, .. }, PublicId::Ecc { x, y }) => {
+            if *curve_id != TPM_ECC_NIST_P256 {
+                return Err(ConvertError::UnsupportedCurve(*curve_id));
+            }
+            let (x, y) = (x.as_slice(), y.as_slice());
+            if x.len() > 32 || y.len() > 32 {
+                return Err(ConvertError::InvalidKey);
+            }
+            // The TPM may strip leading zeros, so pad each coordinate back to 32 bytes
+            let mut point = [0; 65];
+            point[0] = 0x04;
+            point[33 - x.len()..33].copy_from_slice(x);
+            point[65 - y.len()..].copy_from_slice(y);
+            p256::PublicKey::from_sec1_bytes(&point)
+                .map_err(|_| ConvertError::InvalidKey)?
+                .to_public_key_pem(LineEnding::LF)
+                .map_err(|_| ConvertError::Encoding)?
+        }
+        _ => return Err(ConvertError::UnsupportedAlgorithm),
+    };
+    Ok(pem.into_bytes())
+}
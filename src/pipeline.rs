@@ -0,0 +1,354 @@
+//! Runs the app's modes as named stages, so several can run in one boot in a controlled order,
+//! like `--stages verify,create-primary`. Each stage declares the stages it needs, which are run
+//! first even if they weren't asked for.
+//!
+//! A stage that fails either stops the whole run ([`OnFailure::Abort`]) or lets the stages that
+//! don't depend on it carry on ([`OnFailure::Continue`]). Stages that need a failed or skipped
+//! stage are skipped.
+
+use alloc::{format, string::String, vec::Vec};
+use core::fmt;
+
+use uefi::runtime::{self, Time};
+
+use crate::{context::ContextError, findings::Findings, tpm::TpmError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnFailure {
+    Continue,
+    Abort,
+}
+
+/// Why a stage failed, for the user
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StageError(pub String);
+
+impl fmt::Display for StageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<TpmError> for StageError {
+    fn from(error: TpmError) -> Self {
+        Self(format!("{error}"))
+    }
+}
+
+impl From<ContextError> for StageError {
+    fn from(error: ContextError) -> Self {
+        Self(format!("{error}"))
+    }
+}
+
+impl From<uefi::Error> for StageError {
+    fn from(error: uefi::Error) -> Self {
+        Self(format!("{error}"))
+    }
+}
+
+/// A stage working on the app's state `C`. Findings go in the stage's own [`Findings`].
+pub struct Stage<C> {
+    pub name: &'static str,
+    pub requires: &'static [&'static str],
+    pub on_failure: OnFailure,
+    pub run: fn(&mut C, &mut Findings) -> Result<(), StageError>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScheduleError {
+    UnknownStage(String),
+    /// The stage ends up requiring itself
+    Cycle(&'static str),
+}
+
+impl fmt::Display for ScheduleError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownStage(name) => write!(f, "unknown stage {name:?}"),
+            Self::Cycle(name) => write!(f, "stage {name:?} requires itself"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum StageResult {
+    Succeeded,
+    Failed(StageError),
+    /// A stage it requires didn't succeed, or an earlier stage aborted the run
+    Skipped,
+}
+
+#[derive(Debug, Clone)]
+pub struct StageOutcome {
+    pub name: &'static str,
+    pub result: StageResult,
+    /// `None` if the stage didn't run or the firmware has no clock
+    pub elapsed_us: Option<u64>,
+    pub findings: Findings,
+}
+
+/// Which stages ran, for the end of the output
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StageSummary {
+    pub succeeded: Vec<&'static str>,
+    pub failed: Vec<&'static str>,
+    pub skipped: Vec<&'static str>,
+}
+
+impl StageSummary {
+    pub fn new(outcomes: &[StageOutcome]) -> Self {
+        let mut summary = Self::default();
+        for outcome in outcomes {
+            match outcome.result {
+                StageResult::Succeeded => summary.succeeded.push(outcome.name),
+                StageResult::Failed(_) => summary.failed.push(outcome.name),
+                StageResult::Skipped => summary.skipped.push(outcome.name),
+            }
+        }
+        summary
+    }
+}
+
+impl fmt::Display for StageSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |names: &[&str]| {
+            if names.is_empty() {
+                String::from("none")
+            } else {
+                names.join(", ")
+            }
+        };
+        write!(
+            f,
+            "succeeded: {}; failed: {}; skipped: {}",
+            list(&self.succeeded),
+            list(&self.failed),
+            list(&self.skipped)
+        )
+    }
+}
+
+/// Every stage the app knows about
+pub struct Pipeline<C> {
+    stages: Vec<Stage<C>>,
+}
+
+impl<C> Default for Pipeline<C> {
+    fn default() -> Self {
+        Self { stages: Vec::new() }
+    }
+}
+
+impl<C> Pipeline<C> {
+    pub fn register(&mut self, stage: Stage<C>) {
+        self.stages.push(stage);
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.stages.iter().map(|stage| stage.name)
+    }
+
+    fn find(&self, name: &str) -> Result<usize, ScheduleError> {
+        self.stages
+            .iter()
+            .position(|stage| stage.name == name)
+            .ok_or_else(|| ScheduleError::UnknownStage(name.into()))
+    }
+
+    /// The stages to run for `requested`, each after the stages it requires and otherwise in the
+    /// order asked for
+    pub fn schedule(&self, requested: &[&str]) -> Result<Vec<usize>, ScheduleError> {
+        let mut order = Vec::new();
+        let mut visiting = Vec::new();
+        for name in requested {
+            self.visit(self.find(name)?, &mut order, &mut visiting)?;
+        }
+        Ok(order)
+    }
+
+    fn visit(
+        &self,
+        index: usize,
+        order: &mut Vec<usize>,
+        visiting: &mut Vec<usize>,
+    ) -> Result<(), ScheduleError> {
+        if order.contains(&index) {
+            return Ok(());
+        }
+        if visiting.contains(&index) {
+            return Err(ScheduleError::Cycle(self.stages[index].name));
+        }
+        visiting.push(index);
+        for name in self.stages[index].requires {
+            self.visit(self.find(name)?, order, visiting)?;
+        }
+        visiting.pop();
+        order.push(index);
+        Ok(())
+    }
+
+    /// Schedules and runs `requested`, returning what happened to every scheduled stage
+    pub fn run(
+        &self,
+        context: &mut C,
+        requested: &[&str],
+    ) -> Result<Vec<StageOutcome>, ScheduleError> {
+        let order = self.schedule(requested)?;
+        let mut outcomes = Vec::<StageOutcome>::with_capacity(order.len());
+        let mut aborted = false;
+        for index in order {
+            let stage = &self.stages[index];
+            let requirements_met = stage.requires.iter().all(|name| {
+                outcomes.iter().any(|outcome| {
+                    outcome.name == *name && matches!(outcome.result, StageResult::Succeeded)
+                })
+            });
+            let mut outcome = StageOutcome {
+                name: stage.name,
+                result: StageResult::Skipped,
+                elapsed_us: None,
+                findings: Findings::default(),
+            };
+            if !aborted && requirements_met {
+                let start = runtime::get_time().ok();
+                let result = (stage.run)(context, &mut outcome.findings);
+                let end = runtime::get_time().ok();
+                outcome.elapsed_us = start.zip(end).map(|(start, end)| elapsed_us(&start, &end));
+                outcome.result = match result {
+                    Ok(()) => StageResult::Succeeded,
+                    Err(e) => {
+                        aborted = stage.on_failure == OnFailure::Abort;
+                        StageResult::Failed(e)
+                    }
+                };
+            }
+            outcomes.push(outcome);
+        }
+        Ok(outcomes)
+    }
+}
+
+/// Assumes less than a day passed, which is plenty for a stage
+fn elapsed_us(start: &Time, end: &Time) -> u64 {
+    const DAY_US: u64 = 24 * 60 * 60 * 1_000_000;
+    let time_of_day = |time: &Time| {
+        ((time.hour() as u64 * 60 + time.minute() as u64) * 60 + time.second() as u64) * 1_000_000
+            + time.nanosecond() as u64 / 1_000
+    };
+    (time_of_day(end) + DAY_US - time_of_day(start)) % DAY_US
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The stages that ran, in order
+    type Ran = Vec<&'static str>;
+
+    fn stage(
+        name: &'static str,
+        requires: &'static [&'static str],
+        on_failure: OnFailure,
+        run: fn(&mut Ran, &mut Findings) -> Result<(), StageError>,
+    ) -> Stage<Ran> {
+        Stage {
+            name,
+            requires,
+            on_failure,
+            run,
+        }
+    }
+
+    /// Like the app's: a verify stage that can't get the event log, and command stages that only
+    /// need the TPM
+    fn pipeline(verify_on_failure: OnFailure) -> Pipeline<Ran> {
+        let mut pipeline = Pipeline::default();
+        pipeline.register(stage("identify", &[], OnFailure::Abort, |ran, _| {
+            ran.push("identify");
+            Ok(())
+        }));
+        pipeline.register(stage(
+            "verify",
+            &["identify"],
+            verify_on_failure,
+            |ran, _| {
+                ran.push("verify");
+                Err(StageError(
+                    "the firmware didn't give us the event log".into(),
+                ))
+            },
+        ));
+        pipeline.register(stage(
+            "random",
+            &["identify"],
+            OnFailure::Abort,
+            |ran, _| {
+                ran.push("random");
+                Ok(())
+            },
+        ));
+        pipeline.register(stage("report", &["verify"], OnFailure::Abort, |ran, _| {
+            ran.push("report");
+            Ok(())
+        }));
+        pipeline
+    }
+
+    #[test]
+    fn commands_run_without_the_event_log() {
+        let mut ran = Ran::new();
+        let outcomes = pipeline(OnFailure::Continue)
+            .run(&mut ran, &["verify", "report", "random"])
+            .unwrap();
+        assert_eq!(ran, ["identify", "verify", "random"]);
+        let summary = StageSummary::new(&outcomes);
+        assert_eq!(
+            summary,
+            StageSummary {
+                succeeded: Vec::from(["identify", "random"]),
+                failed: Vec::from(["verify"]),
+                skipped: Vec::from(["report"]),
+            }
+        );
+        assert_eq!(
+            format!("{summary}"),
+            "succeeded: identify, random; failed: verify; skipped: report"
+        );
+        assert!(outcomes.iter().all(|outcome| outcome.elapsed_us.is_none()));
+    }
+
+    #[test]
+    fn abort_skips_everything_after() {
+        let mut ran = Ran::new();
+        let outcomes = pipeline(OnFailure::Abort)
+            .run(&mut ran, &["verify", "random"])
+            .unwrap();
+        assert_eq!(ran, ["identify", "verify"]);
+        assert_eq!(
+            format!("{}", StageSummary::new(&outcomes)),
+            "succeeded: identify; failed: verify; skipped: random"
+        );
+    }
+
+    #[test]
+    fn requirements_are_scheduled_first() {
+        let pipeline = pipeline(OnFailure::Continue);
+        assert_eq!(
+            pipeline.schedule(&["report", "identify"]),
+            Ok(Vec::from([0, 1, 3]))
+        );
+        assert_eq!(
+            pipeline.schedule(&["bench"]),
+            Err(ScheduleError::UnknownStage("bench".into()))
+        );
+    }
+
+    #[test]
+    fn cycles_are_rejected() {
+        let mut pipeline = Pipeline::<Ran>::default();
+        pipeline.register(stage("a", &["b"], OnFailure::Abort, |_, _| Ok(())));
+        pipeline.register(stage("b", &["a"], OnFailure::Abort, |_, _| Ok(())));
+        assert_eq!(pipeline.schedule(&["a"]), Err(ScheduleError::Cycle("a")));
+    }
+}
@@ -0,0 +1,123 @@
+//! Opening the TCG2 protocol. We want it exclusively, so nothing else submits commands between
+//! ours, but the firmware's own drivers or another copy of the app may already hold it. Reading
+//! the event log works without exclusive access, so in that case it's opened shared and only the
+//! read-only analysis runs.
+//!
+//! Some firmware only has the TCG 1.2 protocol, even with a TPM 2.0. Commands can still be sent
+//! through it with [`open_tcg_v1_exclusive`], but there's no TPM 2.0 event log to analyze.
+
+use core::fmt;
+
+use uefi::{
+    Guid, Handle, Identify, Status,
+    boot::{self, OpenProtocolAttributes, OpenProtocolParams, ScopedProtocol, SearchType},
+    proto::tcg::{v1, v2::Tcg},
+};
+
+/// How the protocol was opened
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TcgAccess {
+    /// Commands can be submitted
+    Exclusive,
+    /// Only for reading the event log. Someone else may be submitting commands.
+    Shared,
+}
+
+impl TcgAccess {
+    pub fn can_submit_commands(self) -> bool {
+        self == Self::Exclusive
+    }
+}
+
+impl fmt::Display for TcgAccess {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Exclusive => "exclusive",
+            Self::Shared => "shared (read-only)",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TcgOpenError {
+    /// There's no TPM, or the firmware doesn't support TPM 2.0
+    NotFound,
+    /// There's only the TCG 1.2 protocol
+    OnlyV1,
+    /// Opened exclusively by someone else, and couldn't be opened shared either
+    InUse,
+    Uefi(Status),
+}
+
+impl fmt::Display for TcgOpenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound => f.write_str("there's no TCG2 protocol, so no TPM 2.0"),
+            Self::OnlyV1 => f.write_str(
+                "the firmware only has the TCG 1.2 protocol, so there's no TPM 2.0 event log. Commands can still be sent through it if the TPM is a TPM 2.0.",
+            ),
+            Self::InUse => f.write_str(
+                "the TCG2 protocol is open exclusively by another driver or app (or another copy of this one)",
+            ),
+            Self::Uefi(status) => write!(f, "failed to open the TCG2 protocol: {status:?}"),
+        }
+    }
+}
+
+fn find_protocol(guid: &Guid) -> Option<Handle> {
+    boot::locate_handle_buffer(SearchType::ByProtocol(guid))
+        .ok()
+        .and_then(|handles| handles.first().copied())
+}
+
+fn find_tcg() -> Result<Handle, TcgOpenError> {
+    find_protocol(&Tcg::GUID).ok_or_else(|| match find_protocol(&v1::Tcg::GUID) {
+        Some(_) => TcgOpenError::OnlyV1,
+        None => TcgOpenError::NotFound,
+    })
+}
+
+fn open_shared(handle: Handle) -> uefi::Result<ScopedProtocol<Tcg>> {
+    // SAFETY: nothing we do uninstalls the protocol. Callers don't submit commands with it.
+    unsafe {
+        boot::open_protocol::<Tcg>(
+            OpenProtocolParams {
+                handle,
+                agent: boot::image_handle(),
+                controller: None,
+            },
+            OpenProtocolAttributes::GetProtocol,
+        )
+    }
+}
+
+/// Opens the protocol exclusively, or shared if something else has it
+pub fn open_tcg() -> Result<(ScopedProtocol<Tcg>, TcgAccess), TcgOpenError> {
+    let handle = find_tcg()?;
+    match boot::open_protocol_exclusive::<Tcg>(handle) {
+        Ok(tcg) => Ok((tcg, TcgAccess::Exclusive)),
+        Err(e) if matches!(e.status(), Status::ACCESS_DENIED | Status::ALREADY_STARTED) => {
+            let tcg = open_shared(handle).map_err(|_| TcgOpenError::InUse)?;
+            Ok((tcg, TcgAccess::Shared))
+        }
+        Err(e) => Err(TcgOpenError::Uefi(e.status())),
+    }
+}
+
+/// For features that submit commands: fails with [`TcgOpenError::InUse`] instead of opening
+/// shared
+pub fn open_tcg_exclusive() -> Result<ScopedProtocol<Tcg>, TcgOpenError> {
+    boot::open_protocol_exclusive::<Tcg>(find_tcg()?).map_err(|e| match e.status() {
+        Status::ACCESS_DENIED | Status::ALREADY_STARTED => TcgOpenError::InUse,
+        status => TcgOpenError::Uefi(status),
+    })
+}
+
+/// The TCG 1.2 protocol, for sending commands on firmware without the 2.0 one
+pub fn open_tcg_v1_exclusive() -> Result<ScopedProtocol<v1::Tcg>, TcgOpenError> {
+    let handle = find_protocol(&v1::Tcg::GUID).ok_or(TcgOpenError::NotFound)?;
+    boot::open_protocol_exclusive::<v1::Tcg>(handle).map_err(|e| match e.status() {
+        Status::ACCESS_DENIED | Status::ALREADY_STARTED => TcgOpenError::InUse,
+        status => TcgOpenError::Uefi(status),
+    })
+}
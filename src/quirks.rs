@@ -0,0 +1,225 @@
+//! Firmware known to log events whose digest doesn't match the event data. When the digest check
+//! fails on one of these, the finding points at the known issue instead of a bare mismatch.
+//!
+//! Each entry names the firmware it applies to and the event type it mis-measures. Firmware
+//! fields that are `None` match any value. The firmware we're running on has to be known for an
+//! entry to match, so nothing matches when analyzing a log file from another machine.
+
+use alloc::string::String;
+
+use uefi::proto::tcg::EventType;
+
+use crate::tpm::{
+    TpmError, TpmTransport,
+    capability::{
+        TPM_PT_FIRMWARE_VERSION_1, TPM_PT_FIRMWARE_VERSION_2, TPM_PT_MANUFACTURER, TaggedProperty,
+        get_tpm_properties,
+    },
+};
+
+/// What we know about the firmware that made the event log
+#[derive(Debug, Clone, Default)]
+pub struct FirmwareInfo {
+    /// The system table's `FirmwareVendor`
+    pub vendor: Option<String>,
+    /// The system table's `FirmwareRevision`
+    pub revision: Option<u32>,
+    /// `TPM_PT_MANUFACTURER`, 4 ASCII characters like `IFX` followed by a space
+    pub tpm_manufacturer: Option<u32>,
+    /// `TPM_PT_FIRMWARE_VERSION_1` and `_2`
+    pub tpm_firmware_version: Option<u64>,
+}
+
+impl FirmwareInfo {
+    /// Fills in the TPM fields
+    pub fn read_tpm(&mut self, tcg: &mut dyn TpmTransport) -> Result<(), TpmError> {
+        let mut properties = [TaggedProperty::default();
+            (TPM_PT_FIRMWARE_VERSION_2 - TPM_PT_MANUFACTURER + 1) as usize];
+        let count = get_tpm_properties(tcg, TPM_PT_MANUFACTURER, &mut properties)?;
+        let get = |property| {
+            properties[..count]
+                .iter()
+                .find(|tagged| tagged.property == property)
+                .map(|tagged| tagged.value)
+        };
+        self.tpm_manufacturer = get(TPM_PT_MANUFACTURER);
+        self.tpm_firmware_version = get(TPM_PT_FIRMWARE_VERSION_1)
+            .zip(get(TPM_PT_FIRMWARE_VERSION_2))
+            .map(|(high, low)| (high as u64) << 32 | low as u64);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Quirk {
+    /// Stable, like finding codes
+    pub id: &'static str,
+    pub firmware_vendor: &'static str,
+    /// Inclusive
+    pub firmware_revisions: Option<(u32, u32)>,
+    pub tpm_manufacturer: Option<u32>,
+    /// Inclusive
+    pub tpm_firmware_versions: Option<(u64, u64)>,
+    pub event_type: EventType,
+    pub description: &'static str,
+    /// Where to read more
+    pub reference: &'static str,
+}
+
+impl Quirk {
+    pub fn matches(&self, firmware: &FirmwareInfo, event_type: EventType) -> bool {
+        fn in_range<T: PartialOrd>(range: Option<(T, T)>, value: Option<T>) -> bool {
+            match (range, value) {
+                (None, _) => true,
+                (Some((first, last)), Some(value)) => first <= value && value <= last,
+                (Some(_), None) => false,
+            }
+        }
+        event_type == self.event_type
+            && firmware.vendor.as_deref() == Some(self.firmware_vendor)
+            && in_range(self.firmware_revisions, firmware.revision)
+            && self
+                .tpm_manufacturer
+                .is_none_or(|manufacturer| firmware.tpm_manufacturer == Some(manufacturer))
+            && in_range(self.tpm_firmware_versions, firmware.tpm_firmware_version)
+    }
+}
+
+pub static QUIRKS: &[Quirk] = &[Quirk {
+    id: "EDK2-BOOT2-DATA-ONLY",
+    firmware_vendor: "EDK II",
+    firmware_revisions: None,
+    tpm_manufacturer: None,
+    tpm_firmware_versions: None,
+    event_type: EventType::EFI_VARIABLE_BOOT2,
+    description: "EV_EFI_VARIABLE_BOOT2 is hashed over VariableData only, like EV_EFI_VARIABLE_BOOT, instead of the whole UEFI_VARIABLE_DATA",
+    reference: "MeasureVariable in EDK II's SecurityPkg/Tcg/Tcg2Dxe/Tcg2Dxe.c",
+}];
+
+/// The first known issue that explains a digest mismatch on `event_type`
+pub fn find_quirk(firmware: &FirmwareInfo, event_type: EventType) -> Option<&'static Quirk> {
+    QUIRKS
+        .iter()
+        .find(|quirk| quirk.matches(firmware, event_type))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const QUIRK: Quirk = Quirk {
+        id: "TEST",
+        firmware_vendor: "Vendor",
+        firmware_revisions: Some((0x10, 0x20)),
+        tpm_manufacturer: Some(u32::from_be_bytes(*b"IFX ")),
+        tpm_firmware_versions: Some((0x0007_0000_0000_0000, 0x0007_0055_ffff_ffff)),
+        event_type: EventType::EFI_VARIABLE_BOOT2,
+        description: "",
+        reference: "",
+    };
+
+    fn firmware() -> FirmwareInfo {
+        FirmwareInfo {
+            vendor: Some("Vendor".into()),
+            revision: Some(0x10),
+            tpm_manufacturer: Some(u32::from_be_bytes(*b"IFX ")),
+            tpm_firmware_version: Some(0x0007_0055_0000_0000),
+        }
+    }
+
+    #[test]
+    fn quirk_matches_its_firmware() {
+        assert!(QUIRK.matches(&firmware(), EventType::EFI_VARIABLE_BOOT2));
+        // Ranges are inclusive at both ends
+        let last_revision = FirmwareInfo {
+            revision: Some(0x20),
+            ..firmware()
+        };
+        assert!(QUIRK.matches(&last_revision, EventType::EFI_VARIABLE_BOOT2));
+    }
+
+    #[test]
+    fn quirk_doesnt_match_other_firmware() {
+        assert!(!QUIRK.matches(&firmware(), EventType::EFI_VARIABLE_BOOT));
+        let others = [
+            FirmwareInfo {
+                vendor: Some("Other".into()),
+                ..firmware()
+            },
+            FirmwareInfo {
+                revision: Some(0x21),
+                ..firmware()
+            },
+            FirmwareInfo {
+                tpm_manufacturer: Some(u32::from_be_bytes(*b"NTC\0")),
+                ..firmware()
+            },
+            FirmwareInfo {
+                tpm_firmware_version: Some(0x0008_0000_0000_0000),
+                ..firmware()
+            },
+            // Unknown firmware only matches fields the quirk doesn't constrain
+            FirmwareInfo {
+                revision: None,
+                ..firmware()
+            },
+            FirmwareInfo::default(),
+        ];
+        for firmware in others {
+            assert!(
+                !QUIRK.matches(&firmware, EventType::EFI_VARIABLE_BOOT2),
+                "{firmware:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn unconstrained_fields_match_anything() {
+        let quirk = Quirk {
+            firmware_revisions: None,
+            tpm_manufacturer: None,
+            tpm_firmware_versions: None,
+            ..QUIRK
+        };
+        let vendor_only = FirmwareInfo {
+            vendor: Some("Vendor".into()),
+            ..Default::default()
+        };
+        assert!(quirk.matches(&vendor_only, EventType::EFI_VARIABLE_BOOT2));
+    }
+
+    #[test]
+    fn catalogue_is_well_formed() {
+        for (index, quirk) in QUIRKS.iter().enumerate() {
+            assert!(!quirk.id.is_empty());
+            assert!(
+                QUIRKS[..index].iter().all(|other| other.id != quirk.id),
+                "duplicate {}",
+                quirk.id
+            );
+            assert!(!quirk.firmware_vendor.is_empty());
+            assert!(!quirk.description.is_empty());
+            assert!(!quirk.reference.is_empty());
+            if let Some((first, last)) = quirk.firmware_revisions {
+                assert!(first <= last, "{}", quirk.id);
+            }
+            if let Some((first, last)) = quirk.tpm_firmware_versions {
+                assert!(first <= last, "{}", quirk.id);
+            }
+        }
+    }
+
+    #[test]
+    fn find_quirk_finds_the_edk2_boot2_quirk() {
+        let edk2 = FirmwareInfo {
+            vendor: Some("EDK II".into()),
+            ..Default::default()
+        };
+        assert_eq!(
+            find_quirk(&edk2, EventType::EFI_VARIABLE_BOOT2).map(|quirk| quirk.id),
+            Some("EDK2-BOOT2-DATA-ONLY")
+        );
+        assert!(find_quirk(&edk2, EventType::EFI_VARIABLE_BOOT).is_none());
+        assert!(find_quirk(&FirmwareInfo::default(), EventType::EFI_VARIABLE_BOOT2).is_none());
+    }
+}
@@ -0,0 +1,304 @@
+//! `--repl`: a prompt on the firmware console for exploring the TPM by hand. One command per
+//! line:
+//!
+//! ```text
+//! tpm> random 32
+//! tpm> pcrread sha256 7
+//! tpm> caps
+//! tpm> log
+//! tpm> quit
+//! ```
+
+use alloc::string::String;
+use core::fmt::{self, Write};
+
+use hex_slice::AsHex;
+use uefi::{
+    boot,
+    proto::{
+        console::text::{Input, Key},
+        tcg::AlgorithmId,
+    },
+    system,
+};
+
+use crate::{
+    analysis::PCR_COUNT,
+    event_log::parser::collect_events,
+    tpm::{
+        TcgTransport, alg,
+        capability::{TpmSpecVersion, active_pcr_banks, read_tpm_unique_id},
+        pcr::pcr_read_single,
+        random::drain_entropy_to,
+    },
+};
+
+/// The most bytes `random` draws at once
+pub const MAX_RANDOM_BYTES: usize = 1024;
+
+/// The longest line the prompt takes. Keys past it are ignored.
+const MAX_LINE: usize = 256;
+
+const PROMPT: &str = "tpm> ";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReplCommand {
+    /// `random <n>`
+    Random(usize),
+    /// `pcrread <alg> <index>`, with an index below [`PCR_COUNT`]
+    PcrRead {
+        algorithm: AlgorithmId,
+        pcr_index: u32,
+    },
+    /// `caps`
+    Caps,
+    /// `log`
+    Log,
+    /// `quit`
+    Quit,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplParseError {
+    UnknownCommand(String),
+    Missing(&'static str),
+    Invalid(String),
+    /// More arguments than the command takes
+    Unexpected(String),
+}
+
+impl fmt::Display for ReplParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownCommand(command) => write!(
+                f,
+                "unknown command {command:?}, expected random, pcrread, caps, log or quit"
+            ),
+            Self::Missing(name) => write!(f, "missing {name}"),
+            Self::Invalid(arg) => write!(f, "invalid argument {arg:?}"),
+            Self::Unexpected(arg) => write!(f, "unexpected argument {arg:?}"),
+        }
+    }
+}
+
+impl ReplCommand {
+    /// Parses one line, split on whitespace. `None` for a blank line.
+    pub fn parse(line: &str) -> Result<Option<Self>, ReplParseError> {
+        let mut words = line.split_whitespace();
+        let Some(verb) = words.next() else {
+            return Ok(None);
+        };
+        let command = match verb {
+            "random" => {
+                let n = words.next().ok_or(ReplParseError::Missing("<n>"))?;
+                Self::Random(
+                    n.parse()
+                        .ok()
+                        .filter(|n| (1..=MAX_RANDOM_BYTES).contains(n))
+                        .ok_or_else(|| ReplParseError::Invalid(n.into()))?,
+                )
+            }
+            "pcrread" => {
+                let algorithm = words.next().ok_or(ReplParseError::Missing("<alg>"))?;
+                let pcr_index = words.next().ok_or(ReplParseError::Missing("<index>"))?;
+                Self::PcrRead {
+                    algorithm: alg::from_name(algorithm)
+                        .ok_or_else(|| ReplParseError::Invalid(algorithm.into()))?,
+                    pcr_index: pcr_index
+                        .parse()
+                        .ok()
+                        .filter(|pcr_index| (*pcr_index as usize) < PCR_COUNT)
+                        .ok_or_else(|| ReplParseError::Invalid(pcr_index.into()))?,
+                }
+            }
+            "caps" => Self::Caps,
+            "log" => Self::Log,
+            "quit" => Self::Quit,
+            verb => return Err(ReplParseError::UnknownCommand(verb.into())),
+        };
+        match words.next() {
+            Some(arg) => Err(ReplParseError::Unexpected(arg.into())),
+            None => Ok(Some(command)),
+        }
+    }
+}
+
+/// Reads a line from `input`, echoing it to `out`. `None` if the console can't be read.
+fn read_line(input: &mut Input, out: &mut impl Write) -> Option<String> {
+    let mut line = String::new();
+    loop {
+        let mut events = [input.wait_for_key_event()?];
+        boot::wait_for_event(&mut events).ok()?;
+        let Some(Key::Printable(c)) = input.read_key().ok()? else {
+            continue;
+        };
+        match u16::from(c) {
+            // Carriage return
+            0x0D => {
+                let _ = out.write_str("\n");
+                return Some(line);
+            }
+            // Backspace
+            0x08 => {
+                if line.pop().is_some() {
+                    let _ = out.write_str("\u{8} \u{8}");
+                }
+            }
+            _ if line.len() >= MAX_LINE => {}
+            _ => {
+                let c = char::from(c);
+                line.push(c);
+                let _ = out.write_char(c);
+            }
+        }
+    }
+}
+
+/// Runs `command`, writing what it found to `out`. TPM errors are written too, so the prompt
+/// carries on after them.
+pub fn execute(
+    tcg: &mut dyn TcgTransport,
+    command: ReplCommand,
+    out: &mut impl Write,
+) -> fmt::Result {
+    match command {
+        ReplCommand::Random(n) => match drain_entropy_to(tcg, n, out) {
+            Ok(()) => writeln!(out),
+            Err(e) => writeln!(out, "\n{e}"),
+        },
+        ReplCommand::PcrRead {
+            algorithm,
+            pcr_index,
+        } => match pcr_read_single(tcg, algorithm, pcr_index) {
+            Ok(Some(pcr)) => writeln!(out, "{:x}", pcr.digest.as_slice().plain_hex(false)),
+            Ok(None) => writeln!(out, "the TPM doesn't have PCR {pcr_index} in that bank"),
+            Err(e) => writeln!(out, "{e}"),
+        },
+        ReplCommand::Caps => {
+            match TpmSpecVersion::read(tcg) {
+                Ok(version) => writeln!(out, "{version}")?,
+                Err(e) => writeln!(out, "specification version: {e}")?,
+            }
+            match read_tpm_unique_id(tcg) {
+                Ok(id) => writeln!(out, "model ID: {id}")?,
+                Err(e) => writeln!(out, "model ID: {e}")?,
+            }
+            match active_pcr_banks(tcg) {
+                Ok(banks) => {
+                    for (algorithm, bitmap) in banks {
+                        let name = alg::name(algorithm).unwrap_or("unknown");
+                        writeln!(out, "{name} bank: {} PCRs", bitmap.count_ones())?;
+                    }
+                    Ok(())
+                }
+                Err(e) => writeln!(out, "PCR banks: {e}"),
+            }
+        }
+        ReplCommand::Log => {
+            let event_log = match tcg.protocol().get_event_log_v2() {
+                Ok(event_log) => event_log,
+                Err(e) => return writeln!(out, "failed to get the event log: {e}"),
+            };
+            for (event_index, event) in collect_events(&event_log).iter().enumerate() {
+                write!(
+                    out,
+                    "#{event_index} PCR {} {:?}",
+                    event.pcr_index, event.event_type
+                )?;
+                if let Some(digest) = event.digest(AlgorithmId::SHA256) {
+                    write!(out, " SHA256 {:x}", digest.plain_hex(false))?;
+                }
+                writeln!(out)?;
+            }
+            Ok(())
+        }
+        // `run` stops instead of executing it
+        ReplCommand::Quit => Ok(()),
+    }
+}
+
+/// Prompts for commands until `quit`, or until the console can't be read
+pub fn run(tcg: &mut dyn TcgTransport) {
+    loop {
+        system::with_stdout(|out| out.write_str(PROMPT)).ok();
+        let Some(line) =
+            system::with_stdin(|input| system::with_stdout(|out| read_line(input, out)))
+        else {
+            return;
+        };
+        let command = match ReplCommand::parse(&line) {
+            Ok(Some(ReplCommand::Quit)) => return,
+            Ok(Some(command)) => command,
+            Ok(None) => continue,
+            Err(e) => {
+                system::with_stdout(|out| writeln!(out, "{e}")).ok();
+                continue;
+            }
+        };
+        system::with_stdout(|out| execute(tcg, command, out)).ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_each_command() {
+        assert_eq!(
+            ReplCommand::parse("random 32"),
+            Ok(Some(ReplCommand::Random(32)))
+        );
+        assert_eq!(
+            ReplCommand::parse("  pcrread sha256 23 "),
+            Ok(Some(ReplCommand::PcrRead {
+                algorithm: AlgorithmId::SHA256,
+                pcr_index: 23,
+            }))
+        );
+        assert_eq!(ReplCommand::parse("caps"), Ok(Some(ReplCommand::Caps)));
+        assert_eq!(ReplCommand::parse("log"), Ok(Some(ReplCommand::Log)));
+        assert_eq!(ReplCommand::parse("quit"), Ok(Some(ReplCommand::Quit)));
+        assert_eq!(ReplCommand::parse(" "), Ok(None));
+    }
+
+    #[test]
+    fn rejects_out_of_range_arguments() {
+        assert_eq!(
+            ReplCommand::parse("pcrread sha256 24"),
+            Err(ReplParseError::Invalid("24".into()))
+        );
+        assert_eq!(
+            ReplCommand::parse("pcrread sha256 4294967295"),
+            Err(ReplParseError::Invalid("4294967295".into()))
+        );
+        assert_eq!(
+            ReplCommand::parse("random 0"),
+            Err(ReplParseError::Invalid("0".into()))
+        );
+        assert_eq!(
+            ReplCommand::parse("random 1025"),
+            Err(ReplParseError::Invalid("1025".into()))
+        );
+    }
+
+    #[test]
+    fn rejects_missing_and_extra_arguments() {
+        assert_eq!(
+            ReplCommand::parse("pcrread sha256"),
+            Err(ReplParseError::Missing("<index>"))
+        );
+        assert_eq!(
+            ReplCommand::parse("pcrread md5 0"),
+            Err(ReplParseError::Invalid("md5".into()))
+        );
+        assert_eq!(
+            ReplCommand::parse("caps now"),
+            Err(ReplParseError::Unexpected("now".into()))
+        );
+        assert_eq!(
+            ReplCommand::parse("help"),
+            Err(ReplParseError::UnknownCommand("help".into()))
+        );
+    }
+}
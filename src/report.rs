@@ -0,0 +1,806 @@
+//! Everything the app's analysis of the running system found, as data instead of log lines, so
+//! it can be logged, saved or exported by whoever called [`analyze`]
+//!
+//! [`analyze`] reads the event log itself: the log borrows the protocol, and so do the checks
+//! against the live TPM that come after it.
+
+use alloc::{format, string::String, vec::Vec};
+use core::fmt;
+
+use hex_slice::AsHex;
+use log::info;
+use sha1::{Digest, Sha1};
+use uefi::{
+    CStr16, Guid, cstr16,
+    proto::tcg::{AlgorithmId, EventType, v2::EventLogFormat},
+    runtime::{self, VariableVendor},
+};
+
+use crate::{
+    analysis,
+    event_log::{
+        parser::{LogEvent, collect_events},
+        signature_list::{SignatureLists, is_signature_database, signature_type_name},
+        variable::VariableData,
+    },
+    findings::{Findings, codes},
+    log_formats::{Sha1LogEntry, compare_log_formats},
+    protocol::TcgAccess,
+    quirks::FirmwareInfo,
+    tpm::{
+        TcgTransport,
+        capability::get_pcr_allocation,
+        marshal::Reader,
+        pcr::{DRTM_PCRS, TpmDigest, pcr_purpose},
+        pcr_selection::PcrSelectionList,
+    },
+};
+
+/// An `EV_EFI_BOOT_SERVICES_APPLICATION`, `_DRIVER` or `EV_EFI_RUNTIME_SERVICES_DRIVER` event
+#[derive(Debug, Clone)]
+pub struct ImageLoad {
+    pub event_index: usize,
+    pub pcr_index: u32,
+    pub event_type: EventType,
+    /// `ImageLengthInMemory` from the `UEFI_IMAGE_LOAD_EVENT`, if it could be parsed
+    pub image_length: Option<u64>,
+    /// SHA-256 if the log has it, otherwise SHA-1
+    pub digest: Option<TpmDigest>,
+    /// From the `UEFI_IMAGE_LOAD_EVENT`. Only decoded with the `decoders` feature.
+    pub device_path: Option<String>,
+}
+
+/// An `EV_EFI_VARIABLE_*` event
+#[derive(Debug, Clone)]
+pub struct VarSummary {
+    pub event_index: usize,
+    pub pcr_index: u32,
+    pub event_type: EventType,
+    pub vendor: Guid,
+    pub name: String,
+    pub data_len: usize,
+}
+
+/// A certificate or hash in `PK`, `KEK`, `db` or `dbx`, as measured into PCR 7
+#[derive(Debug, Clone)]
+pub struct SignatureDbEntry {
+    pub event_index: usize,
+    pub variable: String,
+    /// Which list in the variable, and which signature in that list
+    pub list_index: usize,
+    pub entry_index: usize,
+    pub signature_type: Guid,
+    pub owner: Guid,
+    /// The signature is the hash of an image, allowed in `db` and forbidden in `dbx`
+    pub is_hash: bool,
+    /// For hashes, the hash itself. For certificates and anything else, the SHA-1 of the
+    /// signature, which is what certificates are usually looked up by.
+    pub fingerprint: Vec<u8>,
+}
+
+/// An `EV_EFI_ACTION` event. The ones in [`analysis::SECURITY_ACTIONS`] are also findings.
+#[derive(Debug, Clone)]
+pub struct ActionEvent {
+    pub event_index: usize,
+    pub pcr_index: u32,
+    /// Bytes that aren't ASCII are replaced
+    pub text: String,
+}
+
+/// How much of an [`OemEvent`]'s data is kept
+pub const OEM_EVENT_DATA_PREFIX: usize = 64;
+
+/// An event whose type is in neither of the ranges the TCG defines types in, so it's
+/// vendor-specific rather than a standard type we don't parse
+#[derive(Debug, Clone)]
+pub struct OemEvent {
+    pub event_index: usize,
+    pub pcr_index: u32,
+    pub event_type: EventType,
+    pub data_len: usize,
+    /// At most [`OEM_EVENT_DATA_PREFIX`] bytes
+    pub data_prefix: Vec<u8>,
+}
+
+/// Outside the TCG's `0x0000_0000..=0x0000_FFFF` and the `EV_EFI_EVENT_BASE` range
+pub fn is_oem_event_type(event_type: EventType) -> bool {
+    !matches!(event_type.0, 0x0000_0000..=0x0000_FFFF | 0x8000_0000..=0x8000_00FF)
+}
+
+/// The PCRs allocated in one bank
+#[derive(Debug, Clone)]
+pub struct PcrBank {
+    pub algorithm: AlgorithmId,
+    pub pcrs: Vec<u32>,
+}
+
+/// The Secure Boot mode, from the `SetupMode`, `AuditMode` and `DeployedMode` variables
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BootMode {
+    Setup,
+    Audit,
+    User,
+    Deployed,
+}
+
+impl fmt::Display for BootMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Setup => "setup",
+            Self::Audit => "audit",
+            Self::User => "user",
+            Self::Deployed => "deployed",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MeasurementReport {
+    /// The `SecureBoot` variable as measured into PCR 7, not as it is now
+    pub secure_boot: Option<bool>,
+    pub boot_mode: Option<BootMode>,
+    pub loaded_images: Vec<ImageLoad>,
+    pub variables: Vec<VarSummary>,
+    /// Everything in `PK`, `KEK`, `db` and `dbx`, in the order it was measured
+    pub signature_db: Vec<SignatureDbEntry>,
+    pub actions: Vec<ActionEvent>,
+    pub oem_events: Vec<OemEvent>,
+    pub pcr_banks: Vec<PcrBank>,
+    /// The DRTM PCRs the log has events for, in the order they first show up
+    pub drtm_pcrs: Vec<u32>,
+    /// The SP800-155 platform ID event, if the firmware logged one
+    pub platform_id: Option<String>,
+    /// The PCRs were read from the TPM, which needs exclusive access to the protocol
+    pub pcrs_compared: bool,
+    /// Every PCR the TPM has in the SHA-1 bank matches the replay
+    pub replay_ok: bool,
+    /// The firmware gave us the event log. Without it, nothing else in the report is checked.
+    pub event_log_read: bool,
+    /// The firmware ran out of space for events, so nothing else in the report is checked
+    pub truncated: bool,
+    pub findings: Findings,
+}
+
+/// Runs every check on the running system. Without exclusive access to the protocol, the checks
+/// that send the TPM commands are skipped like for a log file.
+pub fn analyze(
+    tcg: &mut dyn TcgTransport,
+    firmware: &FirmwareInfo,
+    access: TcgAccess,
+) -> MeasurementReport {
+    let mut report = MeasurementReport {
+        boot_mode: read_boot_mode(),
+        ..Default::default()
+    };
+    let allocation = if access.can_submit_commands() {
+        get_pcr_allocation(tcg)
+    } else {
+        Ok(PcrSelectionList::new())
+    };
+    match allocation {
+        Ok(allocation) => {
+            report.pcr_banks = allocation
+                .as_slice()
+                .iter()
+                .map(|selection| PcrBank {
+                    algorithm: selection.hash,
+                    pcrs: selection.pcrs().collect(),
+                })
+                .collect()
+        }
+        Err(e) => log::warn!("Failed to read the PCR allocation: {e}"),
+    }
+    // Copied out first, since each log borrows the protocol
+    let sha1_log = read_sha1_log(tcg);
+    let event_log = match tcg.protocol().get_event_log_v2() {
+        Ok(event_log) => event_log,
+        Err(e) => {
+            report.findings.add(
+                &codes::LOG_UNPARSABLE,
+                None,
+                None,
+                format!("failed to get the event log: {e}"),
+            );
+            return report;
+        }
+    };
+    report.event_log_read = true;
+    if event_log.is_truncated() {
+        report.truncated = true;
+        report.findings.add(
+            &codes::LOG_TRUNCATED,
+            None,
+            None,
+            "the firmware ran out of space for events".into(),
+        );
+        return report;
+    }
+    let events = collect_events(&event_log);
+    summarize_events(&events, &mut report);
+    let replay = analysis::analyze_events(&events, firmware, &mut report.findings);
+    if let Some(sha1_log) = sha1_log {
+        compare_log_formats(&sha1_log, &events, &mut report.findings);
+    }
+    report.pcrs_compared = access.can_submit_commands();
+    if report.pcrs_compared {
+        analysis::compare_sha1_pcrs(tcg, &replay, &mut report.findings);
+    } else {
+        analysis::report_replay_skipped(&replay, &mut report.findings);
+    }
+    report.replay_ok = !report
+        .findings
+        .iter()
+        .any(|finding| finding.code == &codes::REPLAY_MISMATCH);
+    report
+}
+
+/// The device path at the end of a `UEFI_IMAGE_LOAD_EVENT`, as text
+#[cfg(feature = "decoders")]
+fn image_device_path(event_data: &[u8]) -> Option<String> {
+    // After `ImageLocationInMemory`, `ImageLengthInMemory` and `ImageLinkTimeAddress`
+    let mut reader = Reader::new(event_data.get(24..)?);
+    let length = reader.u64_le().ok()?;
+    let path = reader.bytes(usize::try_from(length).ok()?).ok()?;
+    let mut text = String::new();
+    crate::event_log::device_path::render_device_path(path, &mut text);
+    Some(text)
+}
+
+#[cfg(not(feature = "decoders"))]
+fn image_device_path(_event_data: &[u8]) -> Option<String> {
+    None
+}
+
+fn summarize_events(events: &[LogEvent<'_>], report: &mut MeasurementReport) {
+    for (event_index, event) in events.iter().enumerate() {
+        if DRTM_PCRS.contains(&event.pcr_index)
+            && event.event_type != EventType::NO_ACTION
+            && !report.drtm_pcrs.contains(&event.pcr_index)
+        {
+            report.drtm_pcrs.push(event.pcr_index);
+        }
+        match event.event_type {
+            EventType::EFI_BOOT_SERVICES_APPLICATION
+            | EventType::EFI_BOOT_SERVICES_DRIVER
+            | EventType::EFI_RUNTIME_SERVICES_DRIVER => report.loaded_images.push(ImageLoad {
+                event_index,
+                pcr_index: event.pcr_index,
+                event_type: event.event_type,
+                // `ImageLocationInMemory` comes first
+                image_length: Reader::new(event.event_data.get(8..).unwrap_or_default())
+                    .u64_le()
+                    .ok(),
+                digest: [AlgorithmId::SHA256, AlgorithmId::SHA1]
+                    .into_iter()
+                    .find_map(|algorithm| {
+                        // A wrong size is reported as a malformed event by the analysis
+                        TpmDigest::try_from((algorithm, event.digest(algorithm)?)).ok()
+                    }),
+                device_path: image_device_path(event.event_data),
+            }),
+            EventType::EFI_VARIABLE_DRIVER_CONFIG
+            | EventType::EFI_VARIABLE_BOOT
+            | EventType::EFI_VARIABLE_BOOT2
+            | EventType::EFI_VARIABLE_AUTHORITY => {
+                let Some(variable) = VariableData::parse(event.event_data) else {
+                    continue;
+                };
+                if event.event_type == EventType::EFI_VARIABLE_DRIVER_CONFIG
+                    && variable.vendor == VariableVendor::GLOBAL_VARIABLE.0
+                    && variable.name_eq("SecureBoot")
+                {
+                    report.secure_boot = variable.data.first().map(|enabled| *enabled == 1);
+                }
+                if event.event_type == EventType::EFI_VARIABLE_DRIVER_CONFIG
+                    && is_signature_database(&variable)
+                {
+                    summarize_signature_db(event_index, event.pcr_index, &variable, report);
+                }
+                report.variables.push(VarSummary {
+                    event_index,
+                    pcr_index: event.pcr_index,
+                    event_type: event.event_type,
+                    vendor: variable.vendor,
+                    name: variable.name(),
+                    data_len: variable.data.len(),
+                });
+            }
+            EventType::EFI_ACTION => report.actions.push(ActionEvent {
+                event_index,
+                pcr_index: event.pcr_index,
+                text: String::from_utf8_lossy(analysis::action_text(event.event_data)).into(),
+            }),
+            #[cfg(feature = "decoders")]
+            EventType::NO_ACTION => {
+                if let Some(platform_id) =
+                    crate::event_log::sp800_155::Sp800_155Event::parse(event.event_data)
+                {
+                    report.platform_id = Some(format!("{platform_id}"));
+                }
+            }
+            event_type if is_oem_event_type(event_type) => report.oem_events.push(OemEvent {
+                event_index,
+                pcr_index: event.pcr_index,
+                event_type,
+                data_len: event.event_data.len(),
+                data_prefix: event.event_data[..event.event_data.len().min(OEM_EVENT_DATA_PREFIX)]
+                    .into(),
+            }),
+            _ => {}
+        }
+    }
+}
+
+fn read_flag_variable(name: &CStr16) -> Option<bool> {
+    let mut buffer = [0; 1];
+    let (data, _attributes) =
+        runtime::get_variable(name, &VariableVendor::GLOBAL_VARIABLE, &mut buffer).ok()?;
+    Some(data.first() == Some(&1))
+}
+
+fn read_boot_mode() -> Option<BootMode> {
+    if read_flag_variable(cstr16!("SetupMode"))? {
+        return Some(BootMode::Setup);
+    }
+    // Only UEFI 2.5 and later have the other two
+    Some(
+        match (
+            read_flag_variable(cstr16!("AuditMode")),
+            read_flag_variable(cstr16!("DeployedMode")),
+        ) {
+            (Some(true), _) => BootMode::Audit,
+            (_, Some(true)) => BootMode::Deployed,
+            _ => BootMode::User,
+        },
+    )
+}
+
+/// Adds the signatures in a `PK`, `KEK`, `db` or `dbx` event to the report. A list that's cut
+/// short or doesn't add up is reported, since whatever comes after it can't be read.
+fn summarize_signature_db(
+    event_index: usize,
+    pcr_index: u32,
+    variable: &VariableData<'_>,
+    report: &mut MeasurementReport,
+) {
+    let name = variable.name();
+    let mut lists = SignatureLists::new(variable.data);
+    for entry in lists.by_ref() {
+        let is_hash = entry.is_hash();
+        report.signature_db.push(SignatureDbEntry {
+            event_index,
+            variable: name.clone(),
+            list_index: entry.list_index,
+            entry_index: entry.entry_index,
+            signature_type: entry.signature_type,
+            owner: entry.owner,
+            is_hash,
+            fingerprint: if is_hash {
+                entry.data.into()
+            } else {
+                Sha1::digest(entry.data).to_vec()
+            },
+        });
+    }
+    if lists.is_malformed() {
+        report.findings.add(
+            &codes::LOG_MALFORMED_EVENT,
+            Some(pcr_index),
+            Some(event_index),
+            format!(
+                "{name} has a truncated or oversized signature list, so the rest of it wasn't read"
+            ),
+        );
+    }
+}
+
+/// The TCG 1.2 format log, if the firmware keeps one alongside the crypto agile log
+fn read_sha1_log(tcg: &mut dyn TcgTransport) -> Option<Vec<Sha1LogEntry>> {
+    let capability = tcg.protocol().get_capability().ok()?;
+    let both = EventLogFormat::TCG_1_2 | EventLogFormat::TCG_2;
+    if !capability.supported_event_logs.contains(both) {
+        return None;
+    }
+    match tcg.protocol().get_event_log_v1() {
+        Ok(event_log) => Some(event_log.iter().map(Sha1LogEntry::from).collect()),
+        Err(e) => {
+            log::warn!("The firmware supports the 1.2 log format but failed to get it: {e}");
+            None
+        }
+    }
+}
+
+impl MeasurementReport {
+    /// The log output backend. Details are at debug level, the summary and findings above that.
+    pub fn log(&self) {
+        for bank in &self.pcr_banks {
+            info!("{:?} bank: {} PCRs", bank.algorithm, bank.pcrs.len());
+        }
+        match self.secure_boot {
+            Some(enabled) => info!("Secure Boot (as measured): {enabled}"),
+            None => info!("Secure Boot (as measured): not in the log"),
+        }
+        if let Some(boot_mode) = self.boot_mode {
+            info!("Boot mode: {boot_mode}");
+        }
+        for pcr_index in &self.drtm_pcrs {
+            info!(
+                "PCR {pcr_index} ({}) is in the log",
+                pcr_purpose(*pcr_index)
+            );
+        }
+        if let Some(platform_id) = &self.platform_id {
+            info!("SP800-155 platform ID: {platform_id}");
+        }
+        for image in &self.loaded_images {
+            match &image.digest {
+                Some(digest) => log::debug!(
+                    "#{} {:?} PCR {}: {} {:x}",
+                    image.event_index,
+                    image.event_type,
+                    image.pcr_index,
+                    if digest.algorithm == AlgorithmId::SHA256 {
+                        "sha256"
+                    } else {
+                        "sha1"
+                    },
+                    digest.digest.as_slice().plain_hex(false)
+                ),
+                None => log::debug!(
+                    "#{} {:?} PCR {}",
+                    image.event_index,
+                    image.event_type,
+                    image.pcr_index
+                ),
+            }
+            if let Some(device_path) = &image.device_path {
+                log::debug!("  from {device_path}");
+            }
+        }
+        for variable in &self.variables {
+            log::debug!(
+                "#{} {:?} PCR {}: {} ({} bytes)",
+                variable.event_index,
+                variable.event_type,
+                variable.pcr_index,
+                variable.name,
+                variable.data_len
+            );
+        }
+        for entry in &self.signature_db {
+            let signature_type = signature_type_name(entry.signature_type)
+                .map_or_else(|| format!("{}", entry.signature_type), String::from);
+            log::debug!(
+                "#{} {}[{}][{}]: {signature_type} {} {:x}, owner {}",
+                entry.event_index,
+                entry.variable,
+                entry.list_index,
+                entry.entry_index,
+                if entry.is_hash { "hash" } else { "SHA-1" },
+                entry.fingerprint.plain_hex(false),
+                entry.owner
+            );
+        }
+        for action in &self.actions {
+            log::debug!(
+                "#{} EV_EFI_ACTION PCR {}: {}",
+                action.event_index,
+                action.pcr_index,
+                action.text
+            );
+        }
+        for event in &self.oem_events {
+            info!(
+                "#{} OEM-defined event type {:#x} PCR {}: {} bytes, starting {:x}",
+                event.event_index,
+                event.event_type.0,
+                event.pcr_index,
+                event.data_len,
+                event.data_prefix.plain_hex(false)
+            );
+        }
+        info!(
+            "{} images loaded, {} variables measured",
+            self.loaded_images.len(),
+            self.variables.len()
+        );
+        for variable in ["PK", "KEK", "db", "dbx"] {
+            let entries = self
+                .signature_db
+                .iter()
+                .filter(|entry| entry.variable == variable);
+            let hashes = entries.clone().filter(|entry| entry.is_hash).count();
+            let total = entries.count();
+            if total > 0 {
+                info!(
+                    "{variable}: {} certificates or keys, {hashes} hashes",
+                    total - hashes
+                );
+            }
+        }
+        self.findings.log();
+        if self.truncated {
+            log::error!("The event log is truncated, so none of it can be verified");
+        } else if !self.pcrs_compared {
+            log::warn!("The PCRs weren't compared with the event log, since that needs the TPM");
+        } else if self.replay_ok {
+            info!("Every PCR matches the event log");
+        } else {
+            log::error!("Some PCRs don't match the event log");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event_log::signature_list::{EFI_CERT_SHA256_GUID, EFI_CERT_X509_GUID};
+
+    fn event(pcr_index: u32, event_type: EventType, event_data: &[u8]) -> LogEvent<'_> {
+        LogEvent {
+            pcr_index,
+            event_type,
+            digests: Vec::new(),
+            event_data,
+        }
+    }
+
+    #[test]
+    fn every_action_is_kept() {
+        let events = [
+            event(
+                4,
+                EventType::EFI_ACTION,
+                b"Calling EFI Application from Boot Option",
+            ),
+            event(7, EventType::EFI_ACTION, b"UEFI Debug Mode\0"),
+            event(5, EventType::EFI_ACTION, b"Bad \xff byte"),
+        ];
+        let mut report = MeasurementReport::default();
+        summarize_events(&events, &mut report);
+        let actions = report
+            .actions
+            .iter()
+            .map(|action| (action.event_index, action.pcr_index, action.text.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            actions,
+            [
+                (0, 4, "Calling EFI Application from Boot Option"),
+                (1, 7, "UEFI Debug Mode"),
+                (2, 5, "Bad \u{fffd} byte"),
+            ]
+        );
+    }
+
+    #[test]
+    fn image_digest_prefers_sha256() {
+        let mut event_data = Vec::from(0x1000u64.to_le_bytes());
+        event_data.extend_from_slice(&0x2345u64.to_le_bytes());
+        let mut both = event(4, EventType::EFI_BOOT_SERVICES_APPLICATION, &event_data);
+        both.digests = Vec::from([
+            (AlgorithmId::SHA1, &[0x11; 20][..]),
+            (AlgorithmId::SHA256, &[0x22; 32][..]),
+        ]);
+        let mut sha1_only = event(2, EventType::EFI_BOOT_SERVICES_DRIVER, &[]);
+        sha1_only.digests = Vec::from([(AlgorithmId::SHA1, &[0x33; 20][..])]);
+        let neither = event(2, EventType::EFI_RUNTIME_SERVICES_DRIVER, &[]);
+        let mut report = MeasurementReport::default();
+        summarize_events(&[both, sha1_only, neither], &mut report);
+        let images = &report.loaded_images;
+        assert_eq!(
+            images[0].digest,
+            TpmDigest::new(AlgorithmId::SHA256, &[0x22; 32])
+        );
+        assert_eq!(images[0].image_length, Some(0x2345));
+        assert_eq!(images[1].digest, Some(TpmDigest::sha1([0x33; 20])));
+        assert_eq!(images[1].image_length, None);
+        assert_eq!(images[2].digest, None);
+    }
+
+    #[test]
+    fn image_digest_of_the_wrong_size_is_skipped() {
+        let mut image = event(4, EventType::EFI_BOOT_SERVICES_APPLICATION, &[]);
+        image.digests = Vec::from([
+            (AlgorithmId::SHA1, &[0x11; 20][..]),
+            (AlgorithmId::SHA256, &[0x22; 20][..]),
+        ]);
+        let mut report = MeasurementReport::default();
+        summarize_events(&[image], &mut report);
+        assert_eq!(
+            report.loaded_images[0].digest,
+            Some(TpmDigest::sha1([0x11; 20]))
+        );
+    }
+
+    #[test]
+    fn drtm_pcrs_in_the_log_are_listed_once() {
+        let events = [
+            event(17, EventType::NO_ACTION, &[]),
+            event(18, EventType::EFI_ACTION, b"launch"),
+            event(0, EventType::CRTM_VERSION, &[]),
+            event(17, EventType::EFI_ACTION, b"launch"),
+            event(18, EventType::EFI_ACTION, b"again"),
+        ];
+        let mut report = MeasurementReport::default();
+        summarize_events(&events, &mut report);
+        assert_eq!(report.drtm_pcrs, [18, 17]);
+    }
+
+    /// A `UEFI_VARIABLE_DATA`
+    fn variable_event_data(vendor: VariableVendor, name: &str, value: &[u8]) -> Vec<u8> {
+        let name = name.encode_utf16().collect::<Vec<_>>();
+        let mut data = Vec::from(vendor.0.to_bytes());
+        data.extend_from_slice(&(name.len() as u64).to_le_bytes());
+        data.extend_from_slice(&(value.len() as u64).to_le_bytes());
+        for unit in name {
+            data.extend_from_slice(&unit.to_le_bytes());
+        }
+        data.extend_from_slice(value);
+        data
+    }
+
+    /// The `UEFI_VARIABLE_DATA` of `db` holding `signature_lists`
+    fn db_event_data(signature_lists: &[u8]) -> Vec<u8> {
+        variable_event_data(
+            VariableVendor::IMAGE_SECURITY_DATABASE,
+            "db",
+            signature_lists,
+        )
+    }
+
+    #[test]
+    fn synthetic_log_builds_a_report() {
+        let secure_boot = variable_event_data(VariableVendor::GLOBAL_VARIABLE, "SecureBoot", &[1]);
+        let boot_order =
+            variable_event_data(VariableVendor::GLOBAL_VARIABLE, "BootOrder", &[1, 0, 2, 0]);
+        let mut image = event(4, EventType::EFI_BOOT_SERVICES_APPLICATION, &[]);
+        image.digests = Vec::from([(AlgorithmId::SHA1, &[0x11; 20][..])]);
+        let events = [
+            event(7, EventType::EFI_VARIABLE_DRIVER_CONFIG, &secure_boot),
+            event(1, EventType::EFI_VARIABLE_BOOT, &boot_order),
+            event(7, EventType::SEPARATOR, &[0; 4]),
+            image,
+            // Not a `UEFI_VARIABLE_DATA`, so left out
+            event(1, EventType::EFI_VARIABLE_BOOT, &[0; 8]),
+        ];
+        let mut report = MeasurementReport::default();
+        summarize_events(&events, &mut report);
+        assert_eq!(report.secure_boot, Some(true));
+        assert_eq!(
+            report
+                .variables
+                .iter()
+                .map(|variable| (
+                    variable.event_index,
+                    variable.pcr_index,
+                    variable.name.as_str(),
+                    variable.data_len
+                ))
+                .collect::<Vec<_>>(),
+            [(0, 7, "SecureBoot", 1), (1, 1, "BootOrder", 4)]
+        );
+        assert_eq!(report.loaded_images.len(), 1);
+        assert_eq!(report.loaded_images[0].event_index, 3);
+        assert!(report.signature_db.is_empty());
+    }
+
+    #[test]
+    fn secure_boot_only_counts_in_driver_config_events() {
+        let secure_boot = variable_event_data(VariableVendor::GLOBAL_VARIABLE, "SecureBoot", &[1]);
+        let mut report = MeasurementReport::default();
+        summarize_events(
+            &[event(1, EventType::EFI_VARIABLE_BOOT, &secure_boot)],
+            &mut report,
+        );
+        assert_eq!(report.secure_boot, None);
+        assert_eq!(report.variables.len(), 1);
+    }
+
+    #[test]
+    fn oem_events_keep_a_prefix_of_their_data() {
+        let data = (0..100).collect::<Vec<u8>>();
+        let events = [
+            event(0, EventType::POST_CODE, b"BIOS"),
+            event(1, EventType(0x9000_0001), &data),
+            // `EV_EFI_EVENT_BASE` types we don't parse aren't OEM events
+            event(1, EventType(0x8000_00ff), &data),
+            event(7, EventType(0x0000_ffff), &data),
+            event(7, EventType(0x8000_0100), b"short"),
+        ];
+        let mut report = MeasurementReport::default();
+        summarize_events(&events, &mut report);
+        assert_eq!(
+            report
+                .oem_events
+                .iter()
+                .map(|event| (
+                    event.event_index,
+                    event.pcr_index,
+                    event.event_type,
+                    event.data_len,
+                    event.data_prefix.as_slice()
+                ))
+                .collect::<Vec<_>>(),
+            [
+                (
+                    1,
+                    1,
+                    EventType(0x9000_0001),
+                    100,
+                    &data[..OEM_EVENT_DATA_PREFIX]
+                ),
+                (4, 7, EventType(0x8000_0100), 5, b"short".as_slice()),
+            ]
+        );
+    }
+
+    /// An `EFI_SIGNATURE_LIST` with one signature owned by the nil GUID
+    fn signature_list(signature_type: Guid, signature: &[u8]) -> Vec<u8> {
+        let mut list = Vec::from(signature_type.to_bytes());
+        list.extend_from_slice(&(28 + 16 + signature.len() as u32).to_le_bytes());
+        list.extend_from_slice(&0u32.to_le_bytes());
+        list.extend_from_slice(&(16 + signature.len() as u32).to_le_bytes());
+        list.extend_from_slice(&[0; 16]);
+        list.extend_from_slice(signature);
+        list
+    }
+
+    #[test]
+    fn db_entries_are_summarized() {
+        let mut lists = signature_list(EFI_CERT_SHA256_GUID, &[0x11; 32]);
+        lists.extend(signature_list(EFI_CERT_X509_GUID, b"abc"));
+        let data = db_event_data(&lists);
+        let events = [event(7, EventType::EFI_VARIABLE_DRIVER_CONFIG, &data)];
+        let mut report = MeasurementReport::default();
+        summarize_events(&events, &mut report);
+        let entries = report
+            .signature_db
+            .iter()
+            .map(|entry| {
+                (
+                    entry.variable.as_str(),
+                    entry.list_index,
+                    entry.is_hash,
+                    entry.fingerprint.as_slice(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            entries,
+            [
+                ("db", 0, true, &[0x11; 32][..]),
+                // The SHA-1 of "abc"
+                (
+                    "db",
+                    1,
+                    false,
+                    &[
+                        0xa9, 0x99, 0x3e, 0x36, 0x47, 0x06, 0x81, 0x6a, 0xba, 0x3e, 0x25, 0x71,
+                        0x78, 0x50, 0xc2, 0x6c, 0x9c, 0xd0, 0xd8, 0x9d,
+                    ][..]
+                ),
+            ]
+        );
+        assert_eq!(report.findings.iter().count(), 0);
+    }
+
+    #[test]
+    fn truncated_db_is_a_finding() {
+        let mut lists = signature_list(EFI_CERT_SHA256_GUID, &[0x11; 32]);
+        lists.truncate(lists.len() - 1);
+        let data = db_event_data(&lists);
+        let events = [event(7, EventType::EFI_VARIABLE_DRIVER_CONFIG, &data)];
+        let mut report = MeasurementReport::default();
+        summarize_events(&events, &mut report);
+        assert!(report.signature_db.is_empty());
+        assert_eq!(
+            report
+                .findings
+                .iter()
+                .map(|finding| (finding.code.code(), finding.event_index))
+                .collect::<Vec<_>>(),
+            [("LOG-002", Some(0))]
+        );
+    }
+}
@@ -0,0 +1,208 @@
+//! A stable file format for a sealed object, so it can be saved and loaded again on a later boot.
+//!
+//! All integers are big-endian, like TPM structures:
+//!
+//! | Size | Field |
+//! |------|-------|
+//! | 4 | Magic, `SEAL` in ASCII |
+//! | 1 | Format version, currently 1 |
+//! | 2 + n | `TPM2B_PUBLIC`: `u16` size followed by the marshaled `TPMT_PUBLIC` |
+//! | 2 + n | `TPM2B_PRIVATE`: `u16` size followed by the TPM's encrypted private area |
+//! | 4 | CRC-32 (IEEE 802.3, as used by zlib) of all the preceding bytes |
+//!
+//! The CRC only catches storage corruption. It is not a security measure: the TPM itself checks
+//! the integrity of the private area when the object is loaded.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+pub const MAGIC: [u8; 4] = *b"SEAL";
+pub const VERSION: u8 = 1;
+
+/// The public and private parts returned by `TPM2_Create`, which are all that's needed to load a
+/// sealed object again
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SealedBlob {
+    /// The contents of the `TPM2B_PUBLIC`, without the size
+    pub public: Vec<u8>,
+    /// The contents of the `TPM2B_PRIVATE`, without the size
+    pub private: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    Truncated,
+    ChecksumMismatch { expected: u32, actual: u32 },
+    TrailingData,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadMagic => f.write_str("not a sealed blob"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported sealed blob version {version}")
+            }
+            Self::Truncated => f.write_str("sealed blob is truncated"),
+            Self::ChecksumMismatch { expected, actual } => write!(
+                f,
+                "sealed blob is corrupted (CRC {actual:#010x}, expected {expected:#010x})"
+            ),
+            Self::TrailingData => f.write_str("unexpected data after sealed blob"),
+        }
+    }
+}
+
+/// A part of a [`SealedBlob`] too large for its `TPM2B` size field, which the TPM never returns
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PartTooLarge {
+    pub size: usize,
+}
+
+impl fmt::Display for PartTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "sealed blob part of {} bytes doesn't fit in a TPM2B",
+            self.size
+        )
+    }
+}
+
+pub fn serialize_sealed_blob(blob: &SealedBlob) -> Result<Vec<u8>, PartTooLarge> {
+    let mut data = Vec::with_capacity(4 + 1 + 2 + blob.public.len() + 2 + blob.private.len() + 4);
+    data.extend_from_slice(&MAGIC);
+    data.push(VERSION);
+    for part in [&blob.public, &blob.private] {
+        let size = u16::try_from(part.len()).map_err(|_| PartTooLarge { size: part.len() })?;
+        data.extend_from_slice(&size.to_be_bytes());
+        data.extend_from_slice(part);
+    }
+    let crc = crc32(&data);
+    data.extend_from_slice(&crc.to_be_bytes());
+    Ok(data)
+}
+
+pub fn deserialize_sealed_blob(data: &[u8]) -> Result<SealedBlob, ParseError> {
+    let (body, crc) = data.split_last_chunk::<4>().ok_or(ParseError::Truncated)?;
+    let (magic, rest) = body.split_first_chunk::<4>().ok_or(ParseError::Truncated)?;
+    if *magic != MAGIC {
+        return Err(ParseError::BadMagic);
+    }
+    let (version, mut rest) = rest.split_first().ok_or(ParseError::Truncated)?;
+    if *version != VERSION {
+        return Err(ParseError::UnsupportedVersion(*version));
+    }
+    // Check the CRC after the magic and version so the errors are more useful
+    let expected = u32::from_be_bytes(*crc);
+    let actual = crc32(body);
+    if actual != expected {
+        return Err(ParseError::ChecksumMismatch { expected, actual });
+    }
+    let mut read_tpm2b = || {
+        let (size, tail) = rest.split_first_chunk::<2>().ok_or(ParseError::Truncated)?;
+        let (contents, tail) = tail
+            .split_at_checked(u16::from_be_bytes(*size) as usize)
+            .ok_or(ParseError::Truncated)?;
+        rest = tail;
+        Ok::<_, ParseError>(contents.to_vec())
+    };
+    let public = read_tpm2b()?;
+    let private = read_tpm2b()?;
+    if !rest.is_empty() {
+        return Err(ParseError::TrailingData);
+    }
+    Ok(SealedBlob { public, private })
+}
+
+/// CRC-32 with the reflected 0xEDB88320 polynomial
+fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(u32::MAX, |crc, byte| {
+        (0..8).fold(crc ^ *byte as u32, |crc, _| {
+            if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB88320
+            } else {
+                crc >> 1
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn blob() -> SealedBlob {
+        SealedBlob {
+            public: b"public area".to_vec(),
+            private: b"private area".to_vec(),
+        }
+    }
+
+    #[test]
+    fn crc32_check_value() {
+        assert_eq!(crc32(b"123456789"), 0xCBF43926);
+    }
+
+    #[test]
+    fn round_trip() {
+        let data = serialize_sealed_blob(&blob()).unwrap();
+        assert_eq!(data[..5], *b"SEAL\x01");
+        assert_eq!(data[5..7], 11u16.to_be_bytes());
+        assert_eq!(data.len(), 4 + 1 + 2 + 11 + 2 + 12 + 4);
+        assert_eq!(deserialize_sealed_blob(&data), Ok(blob()));
+    }
+
+    #[test]
+    fn corruption_fails_the_crc() {
+        let mut data = serialize_sealed_blob(&blob()).unwrap();
+        data[10] ^= 0x80;
+        assert!(matches!(
+            deserialize_sealed_blob(&data),
+            Err(ParseError::ChecksumMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_malformed_blobs() {
+        let data = serialize_sealed_blob(&blob()).unwrap();
+        assert_eq!(
+            deserialize_sealed_blob(&data[..3]),
+            Err(ParseError::Truncated)
+        );
+        let mut bad_magic = data.clone();
+        bad_magic[0] = b'X';
+        assert_eq!(
+            deserialize_sealed_blob(&bad_magic),
+            Err(ParseError::BadMagic)
+        );
+        let mut bad_version = data.clone();
+        bad_version[4] = 2;
+        assert_eq!(
+            deserialize_sealed_blob(&bad_version),
+            Err(ParseError::UnsupportedVersion(2))
+        );
+        // A valid CRC over a body with a byte after the private area
+        let mut trailing = data[..data.len() - 4].to_vec();
+        trailing.push(0);
+        trailing.extend_from_slice(&crc32(&trailing).to_be_bytes());
+        assert_eq!(
+            deserialize_sealed_blob(&trailing),
+            Err(ParseError::TrailingData)
+        );
+    }
+
+    #[test]
+    fn rejects_a_part_too_large_for_a_tpm2b() {
+        let blob = SealedBlob {
+            public: Vec::new(),
+            private: alloc::vec![0; 0x10000],
+        };
+        assert_eq!(
+            serialize_sealed_blob(&blob),
+            Err(PartTooLarge { size: 0x10000 })
+        );
+    }
+}
@@ -0,0 +1,51 @@
+//! Microsecond timing for things that take less than the firmware clock's resolution, like a
+//! single TPM command. `GetTime` often only counts whole seconds, so this counts CPU timestamp
+//! ticks instead and finds out how many there are per microsecond by stalling for a known time.
+//!
+//! Only x86_64 has a timestamp counter we can use. Elsewhere [`TickTimer::calibrate`] gives up.
+
+use uefi::boot;
+
+/// How long to stall for when calibrating. Boot services stalls are accurate to a few
+/// microseconds, so this keeps the error well under 1%.
+const CALIBRATION_US: u64 = 10_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TickTimer {
+    ticks_per_us: u64,
+}
+
+#[cfg(target_arch = "x86_64")]
+fn ticks() -> Option<u64> {
+    // SAFETY: every x86_64 CPU has `rdtsc`
+    Some(unsafe { core::arch::x86_64::_rdtsc() })
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn ticks() -> Option<u64> {
+    None
+}
+
+impl TickTimer {
+    /// `None` if there's no counter, or it didn't move while stalling
+    pub fn calibrate() -> Option<Self> {
+        let start = ticks()?;
+        boot::stall(CALIBRATION_US as usize);
+        let ticks_per_us = ticks()?.wrapping_sub(start) / CALIBRATION_US;
+        (ticks_per_us != 0).then_some(Self { ticks_per_us })
+    }
+
+    /// A timer with a known rate, for tests on the host, which have no boot services to stall with
+    #[cfg(test)]
+    pub fn with_ticks_per_us(ticks_per_us: u64) -> Self {
+        Self { ticks_per_us }
+    }
+
+    /// Runs `f`, returning its result and how many microseconds it took
+    pub fn time<R>(&self, f: impl FnOnce() -> R) -> (R, u64) {
+        let start = ticks().unwrap_or_default();
+        let result = f();
+        let end = ticks().unwrap_or_default();
+        (result, end.wrapping_sub(start) / self.ticks_per_us)
+    }
+}
@@ -0,0 +1,85 @@
+use uefi::proto::tcg::AlgorithmId;
+
+/// The name the TPM spec uses for a hash algorithm, without the `TPM_ALG_` prefix
+pub fn name(algorithm: AlgorithmId) -> Option<&'static str> {
+    Some(match algorithm {
+        AlgorithmId::SHA1 => "SHA1",
+        AlgorithmId::SHA256 => "SHA256",
+        AlgorithmId::SHA384 => "SHA384",
+        AlgorithmId::SHA512 => "SHA512",
+        AlgorithmId::SM3_256 => "SM3_256",
+        _ => return None,
+    })
+}
+
+/// The hash algorithms [`name`] and [`digest_size`] know
+pub const HASH_ALGORITHMS: [AlgorithmId; 5] = [
+    AlgorithmId::SHA1,
+    AlgorithmId::SHA256,
+    AlgorithmId::SHA384,
+    AlgorithmId::SHA512,
+    AlgorithmId::SM3_256,
+];
+
+/// The algorithm [`name`] gives this name, ignoring case
+pub fn from_name(name: &str) -> Option<AlgorithmId> {
+    HASH_ALGORITHMS.into_iter().find(|algorithm| {
+        self::name(*algorithm).is_some_and(|known| known.eq_ignore_ascii_case(name))
+    })
+}
+
+/// The size of a hash algorithm's digests, for the algorithms [`name`] knows
+pub fn digest_size(algorithm: AlgorithmId) -> Option<usize> {
+    Some(match algorithm {
+        AlgorithmId::SHA1 => 20,
+        AlgorithmId::SHA256 | AlgorithmId::SM3_256 => 32,
+        AlgorithmId::SHA384 => 48,
+        AlgorithmId::SHA512 => 64,
+        _ => return None,
+    })
+}
+
+pub const TPM_ALG_RSA: AlgorithmId = AlgorithmId(0x0001);
+pub const TPM_ALG_HMAC: AlgorithmId = AlgorithmId(0x0005);
+pub const TPM_ALG_AES: AlgorithmId = AlgorithmId(0x0006);
+pub const TPM_ALG_KEYEDHASH: AlgorithmId = AlgorithmId(0x0008);
+pub const TPM_ALG_XOR: AlgorithmId = AlgorithmId(0x000A);
+pub const TPM_ALG_NULL: AlgorithmId = AlgorithmId(0x0010);
+pub const TPM_ALG_RSASSA: AlgorithmId = AlgorithmId(0x0014);
+pub const TPM_ALG_RSAES: AlgorithmId = AlgorithmId(0x0015);
+pub const TPM_ALG_RSAPSS: AlgorithmId = AlgorithmId(0x0016);
+pub const TPM_ALG_OAEP: AlgorithmId = AlgorithmId(0x0017);
+pub const TPM_ALG_ECDSA: AlgorithmId = AlgorithmId(0x0018);
+pub const TPM_ALG_ECDH: AlgorithmId = AlgorithmId(0x0019);
+pub const TPM_ALG_ECDAA: AlgorithmId = AlgorithmId(0x001A);
+pub const TPM_ALG_ECC: AlgorithmId = AlgorithmId(0x0023);
+pub const TPM_ALG_SYMCIPHER: AlgorithmId = AlgorithmId(0x0025);
+pub const TPM_ALG_CFB: AlgorithmId = AlgorithmId(0x0043);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_hash_algorithm_has_a_name_and_size() {
+        for algorithm in HASH_ALGORITHMS {
+            let name = name(algorithm).unwrap();
+            assert!(digest_size(algorithm).is_some());
+            assert_eq!(from_name(name), Some(algorithm));
+        }
+    }
+
+    #[test]
+    fn from_name_ignores_case() {
+        assert_eq!(from_name("sha256"), Some(AlgorithmId::SHA256));
+        assert_eq!(from_name("Sm3_256"), Some(AlgorithmId::SM3_256));
+        assert_eq!(from_name("sha3_256"), None);
+        assert_eq!(from_name(""), None);
+    }
+
+    #[test]
+    fn non_hash_algorithms_have_no_digest_size() {
+        assert_eq!(digest_size(TPM_ALG_RSA), None);
+        assert_eq!(digest_size(TPM_ALG_NULL), None);
+    }
+}
@@ -0,0 +1,175 @@
+//! `TPMS_ATTEST`, the structure the TPM signs for `TPM2_Quote` and its other attestation commands.
+//! Only quotes are parsed; checking the signature over them is up to the caller.
+
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout, Unaligned};
+
+use super::{
+    TpmError,
+    marshal::{Reader, U32, U64},
+    pcr_selection::PcrSelectionList,
+    tpm2b::{Tpm2b, Tpm2bDigest, Tpm2bName},
+};
+
+/// `TPM_GENERATED_VALUE`, which starts everything the TPM signs, so it can't be made to sign
+/// something that looks like attestation
+pub const TPM_GENERATED_VALUE: u32 = 0xFF544347;
+
+/// `TPM_ST_ATTEST_QUOTE`
+pub const TPM_ST_ATTEST_QUOTE: u16 = 0x8018;
+
+/// `TPMS_CLOCK_INFO`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockInfo {
+    pub clock: u64,
+    pub reset_count: u32,
+    pub restart_count: u32,
+    pub safe: bool,
+}
+
+/// `TPMS_CLOCK_INFO` as it's laid out in responses, for [`Reader::fixed`]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, FromBytes, IntoBytes, Immutable, KnownLayout, Unaligned,
+)]
+#[repr(C)]
+pub struct RawClockInfo {
+    pub clock: U64,
+    pub reset_count: U32,
+    pub restart_count: U32,
+    pub safe: u8,
+}
+
+impl From<&RawClockInfo> for ClockInfo {
+    fn from(raw: &RawClockInfo) -> Self {
+        Self {
+            clock: raw.clock.get(),
+            reset_count: raw.reset_count.get(),
+            restart_count: raw.restart_count.get(),
+            safe: raw.safe != 0,
+        }
+    }
+}
+
+impl From<&ClockInfo> for RawClockInfo {
+    fn from(clock_info: &ClockInfo) -> Self {
+        Self {
+            clock: clock_info.clock.into(),
+            reset_count: clock_info.reset_count.into(),
+            restart_count: clock_info.restart_count.into(),
+            safe: clock_info.safe as u8,
+        }
+    }
+}
+
+/// `TPMS_QUOTE_INFO`: the PCRs that were quoted and the digest of their values
+#[derive(Debug, Clone, Copy)]
+pub struct QuoteInfo {
+    pub pcr_select: PcrSelectionList,
+    pub pcr_digest: Tpm2bDigest,
+}
+
+/// A `TPMS_ATTEST` of type `TPM_ST_ATTEST_QUOTE`
+#[derive(Debug, Clone, Copy)]
+pub struct Quote {
+    pub qualified_signer: Tpm2bName,
+    /// The caller's nonce
+    pub extra_data: Tpm2b<64>,
+    pub clock_info: ClockInfo,
+    pub firmware_version: u64,
+    pub quote_info: QuoteInfo,
+}
+
+impl Quote {
+    /// Parses the `quoted` bytes from `TPM2_Quote`, without the `TPM2B_ATTEST` size
+    pub fn parse(attest: &[u8]) -> Result<Self, TpmError> {
+        let mut reader = Reader::new(attest);
+        if reader.u32()? != TPM_GENERATED_VALUE || reader.u16()? != TPM_ST_ATTEST_QUOTE {
+            return Err(TpmError::Malformed);
+        }
+        Ok(Self {
+            qualified_signer: Tpm2b::read(&mut reader)?,
+            extra_data: Tpm2b::read(&mut reader)?,
+            clock_info: reader.fixed::<RawClockInfo>()?.into(),
+            firmware_version: reader.u64()?,
+            quote_info: QuoteInfo {
+                pcr_select: PcrSelectionList::read(&mut reader)?,
+                pcr_digest: Tpm2b::read(&mut reader)?,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tpm::marshal::Writer;
+
+    #[test]
+    fn clock_info_round_trips() {
+        let clock_info = ClockInfo {
+            clock: 0x1122334455667788,
+            reset_count: 0x01020304,
+            restart_count: 5,
+            safe: false,
+        };
+        let mut buffer = [0; 32];
+        let mut writer = Writer::new(&mut buffer);
+        writer.fixed(&RawClockInfo::from(&clock_info)).unwrap();
+        let written = writer.into_slice();
+        assert_eq!(
+            written,
+            [
+                0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x01, 0x02, 0x03, 0x04, 0x00, 0x00,
+                0x00, 0x05, 0x00,
+            ]
+        );
+        let parsed = ClockInfo::from(Reader::new(written).fixed::<RawClockInfo>().unwrap());
+        assert_eq!(parsed, clock_info);
+    }
+
+    #[test]
+    fn any_nonzero_safe_is_safe() {
+        let mut bytes = [0; 17];
+        bytes[16] = 0x02;
+        let parsed = ClockInfo::from(Reader::new(&bytes).fixed::<RawClockInfo>().unwrap());
+        assert!(parsed.safe);
+    }
+
+    /// A quote by a signer named `ab`, with nonce `01 02`, over PCRs 0 and 1 in the SHA-1 bank
+    const QUOTE: [u8; 53] = [
+        0xff, 0x54, 0x43, 0x47, 0x80, 0x18, 0x00, 0x02, 0x61, 0x62, 0x00, 0x02, 0x01, 0x02, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x03,
+        0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x07, 0x00, 0x00, 0x00, 0x01, 0x00, 0x04,
+        0x03, 0x03, 0x00, 0x00, 0x00, 0x02, 0xaa, 0xbb,
+    ];
+
+    #[test]
+    fn quote_parses() {
+        let quote = Quote::parse(&QUOTE).unwrap();
+        assert_eq!(quote.qualified_signer.as_slice(), b"ab");
+        assert_eq!(quote.extra_data.as_slice(), [1, 2]);
+        assert_eq!(
+            quote.clock_info,
+            ClockInfo {
+                clock: 0x10,
+                reset_count: 2,
+                restart_count: 3,
+                safe: true,
+            }
+        );
+        assert_eq!(quote.firmware_version, 7);
+        let selections = quote.quote_info.pcr_select.as_slice();
+        assert_eq!(selections.len(), 1);
+        assert_eq!(selections[0].pcrs().collect::<alloc::vec::Vec<_>>(), [0, 1]);
+        assert_eq!(quote.quote_info.pcr_digest.as_slice(), [0xaa, 0xbb]);
+    }
+
+    #[test]
+    fn other_attestations_are_malformed() {
+        let mut certify = QUOTE;
+        certify[5] = 0x17;
+        assert_eq!(Quote::parse(&certify).unwrap_err(), TpmError::Malformed);
+        let mut unsigned = QUOTE;
+        unsigned[0] = 0;
+        assert_eq!(Quote::parse(&unsigned).unwrap_err(), TpmError::Malformed);
+    }
+}
@@ -0,0 +1,165 @@
+use alloc::{vec, vec::Vec};
+
+use super::{TpmError, lockout, marshal::Writer};
+
+/// `TPM_RS_PW`, the handle for a password authorization
+pub const TPM_RS_PW: u32 = 0x40000009;
+
+/// `TPMA_SESSION.continueSession`
+pub const CONTINUE_SESSION: u8 = 1 << 0;
+
+/// `TPMS_AUTH_COMMAND`
+#[derive(Debug, Clone, Copy)]
+pub struct AuthCommand<'a> {
+    pub session_handle: u32,
+    pub nonce: &'a [u8],
+    pub session_attributes: u8,
+    pub hmac: &'a [u8],
+}
+
+impl<'a> AuthCommand<'a> {
+    /// A plaintext password authorization. Most hierarchies have an empty password.
+    pub const fn password(password: &'a [u8]) -> Self {
+        Self {
+            session_handle: TPM_RS_PW,
+            nonce: &[],
+            session_attributes: 0,
+            hmac: password,
+        }
+    }
+}
+
+/// Something that can authorize commands, so it can be stored next to the object it authorizes
+/// instead of being passed to every command
+pub trait TpmAuth {
+    fn auth_command(&self) -> AuthCommand<'_>;
+}
+
+/// A plaintext password, sent with [`TPM_RS_PW`]
+#[derive(Debug, Clone, Default)]
+pub struct Password(pub Vec<u8>);
+
+impl TpmAuth for Password {
+    fn auth_command(&self) -> AuthCommand<'_> {
+        AuthCommand::password(&self.0)
+    }
+}
+
+fn auth_command_size(auth: &AuthCommand<'_>) -> usize {
+    4 + 2 + auth.nonce.len() + 1 + 2 + auth.hmac.len()
+}
+
+fn write_auth_commands(writer: &mut Writer<'_>, auths: &[AuthCommand<'_>]) -> Result<(), TpmError> {
+    for auth in auths {
+        writer.u32(auth.session_handle)?;
+        writer.tpm2b(auth.nonce)?;
+        writer.u8(auth.session_attributes)?;
+        writer.tpm2b(auth.hmac)?;
+    }
+    Ok(())
+}
+
+/// Writes `authorizationSize` followed by the authorization area.
+///
+/// Every command with authorizations goes through here, so this is where we refuse to risk
+/// making a dictionary-attack lockout worse.
+pub fn write_auth_area(writer: &mut Writer<'_>, auths: &[AuthCommand<'_>]) -> Result<(), TpmError> {
+    lockout::check()?;
+    let size = auths.iter().map(auth_command_size).sum::<usize>();
+    writer.u32(size as u32)?;
+    write_auth_commands(writer, auths)
+}
+
+/// The authorizations of a command with more than one authorized handle, like
+/// `TPM2_ActivateCredential` or `TPM2_NV_Certify`, in the order of the handles
+#[derive(Debug, Clone, Default)]
+pub struct AuthArea<'a> {
+    auths: Vec<AuthCommand<'a>>,
+}
+
+impl<'a> AuthArea<'a> {
+    pub fn push(mut self, auth: AuthCommand<'a>) -> Self {
+        self.auths.push(auth);
+        self
+    }
+
+    pub fn auths(&self) -> &[AuthCommand<'a>] {
+        &self.auths
+    }
+
+    /// `authorizationSize` and the area it covers. Checks the lockout like [`write_auth_area`].
+    pub fn marshal(&self) -> Result<(u32, Vec<u8>), TpmError> {
+        lockout::check()?;
+        let size = self.auths.iter().map(auth_command_size).sum::<usize>();
+        let mut area = vec![0; size];
+        write_auth_commands(&mut Writer::new(&mut area), &self.auths)
+            .expect("the buffer is exactly the size of the area");
+        Ok((size as u32, area))
+    }
+
+    /// Like [`write_auth_area`]
+    pub fn write(&self, writer: &mut Writer<'_>) -> Result<(), TpmError> {
+        write_auth_area(writer, &self.auths)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A password for the first handle, then an HMAC session that stays open
+    fn two_auths() -> AuthArea<'static> {
+        AuthArea::default()
+            .push(AuthCommand::password(&[]))
+            .push(AuthCommand {
+                session_handle: 0x02000000,
+                nonce: &[0xaa, 0xbb],
+                session_attributes: CONTINUE_SESSION,
+                hmac: &[0xcc],
+            })
+    }
+
+    #[test]
+    fn empty_password_encoding() {
+        let mut buffer = [0; 16];
+        let mut writer = Writer::new(&mut buffer);
+        write_auth_area(&mut writer, &[AuthCommand::password(&[])]).unwrap();
+        assert_eq!(
+            writer.into_slice(),
+            [
+                0x00, 0x00, 0x00, 0x09, 0x40, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00, 0x00, 0x00,
+            ]
+        );
+    }
+
+    #[test]
+    fn auth_area_marshals_in_handle_order() {
+        let (size, area) = two_auths().marshal().unwrap();
+        assert_eq!(size, 21);
+        assert_eq!(
+            area,
+            [
+                0x40, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00,
+                0x02, 0xaa, 0xbb, 0x01, 0x00, 0x01, 0xcc,
+            ]
+        );
+    }
+
+    #[test]
+    fn auth_area_writes_like_marshal() {
+        let auths = two_auths();
+        let (size, area) = auths.marshal().unwrap();
+        let mut buffer = [0; 32];
+        let mut writer = Writer::new(&mut buffer);
+        auths.write(&mut writer).unwrap();
+        let written = writer.into_slice();
+        assert_eq!(written[..4], size.to_be_bytes());
+        assert_eq!(written[4..], area);
+        assert_eq!(auths.auths().len(), 2);
+    }
+
+    #[test]
+    fn empty_auth_area_is_just_its_size() {
+        assert_eq!(AuthArea::default().marshal(), Ok((0, Vec::new())));
+    }
+}
@@ -0,0 +1,88 @@
+//! Waiting between polls of a TPM that's still busy. The wait starts short, so a TPM that's
+//! almost done isn't kept waiting, and doubles up to a cap, so a slow one isn't flooded with
+//! commands.
+
+use uefi::boot;
+
+use super::TpmError;
+
+/// The waits between polls, in microseconds. Each is twice the last, up to `max_us`, and they add
+/// up to at most `total_us`, after which there are none.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    next_us: u64,
+    max_us: u64,
+    remaining_us: u64,
+    total_us: u64,
+}
+
+impl Backoff {
+    pub const fn new(initial_us: u64, max_us: u64, total_us: u64) -> Self {
+        Self {
+            next_us: initial_us,
+            max_us,
+            remaining_us: total_us,
+            total_us,
+        }
+    }
+
+    /// Stalls for the next wait, or fails with [`TpmError::Timeout`] once the total is used up
+    pub fn wait(&mut self) -> Result<(), TpmError> {
+        let delay_us = self.next().ok_or(TpmError::Timeout {
+            waited_us: self.total_us,
+        })?;
+        boot::stall(delay_us as usize);
+        Ok(())
+    }
+}
+
+impl Iterator for Backoff {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        if self.remaining_us == 0 {
+            return None;
+        }
+        // At least 1µs, so a zero initial wait can't stop the total from being used up
+        let delay_us = self
+            .next_us
+            .clamp(1, self.max_us.max(1))
+            .min(self.remaining_us);
+        self.remaining_us -= delay_us;
+        self.next_us = self.next_us.saturating_mul(2);
+        Some(delay_us)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+
+    #[test]
+    fn waits_double_up_to_the_cap_and_the_total() {
+        let waits = Backoff::new(10, 40, 150).collect::<Vec<_>>();
+        assert_eq!(waits, [10, 20, 40, 40, 40]);
+    }
+
+    #[test]
+    fn last_wait_is_cut_to_the_total() {
+        let waits = Backoff::new(10, 40, 100).collect::<Vec<_>>();
+        assert_eq!(waits, [10, 20, 40, 30]);
+    }
+
+    #[test]
+    fn zero_initial_wait_still_ends() {
+        let waits = Backoff::new(0, 0, 3).collect::<Vec<_>>();
+        assert_eq!(waits, [1, 1, 1]);
+    }
+
+    #[test]
+    fn wait_times_out_once_the_total_is_used_up() {
+        let mut backoff = Backoff::new(10, 10, 20);
+        assert_eq!(backoff.wait(), Ok(()));
+        assert_eq!(backoff.wait(), Ok(()));
+        assert_eq!(backoff.wait(), Err(TpmError::Timeout { waited_us: 20 }));
+    }
+}
@@ -0,0 +1,878 @@
+use alloc::vec::Vec;
+use core::{fmt, iter::FusedIterator};
+
+use uefi::proto::tcg::AlgorithmId;
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout, Unaligned};
+
+use super::{
+    BUFFER_SIZE, CommandCode, TPM_ST_NO_SESSIONS, TpmError, TpmHandle, TpmTransport, begin_command,
+    finish_command,
+    marshal::{Reader, U32, Writer},
+    pcr_selection::PcrSelectionList,
+    submit,
+};
+
+/// `TPM_CAP`
+pub const TPM_CAP_HANDLES: u32 = 0x00000001;
+pub const TPM_CAP_COMMANDS: u32 = 0x00000002;
+pub const TPM_CAP_PCRS: u32 = 0x00000005;
+pub const TPM_CAP_TPM_PROPERTIES: u32 = 0x00000006;
+pub const TPM_CAP_PCR_PROPERTIES: u32 = 0x00000007;
+
+/// `TPM_PT` values in the `PT_FIXED` group, which don't change
+pub const TPM_PT_FAMILY_INDICATOR: u32 = 0x100;
+pub const TPM_PT_LEVEL: u32 = 0x101;
+pub const TPM_PT_REVISION: u32 = 0x102;
+pub const TPM_PT_DAY_OF_YEAR: u32 = 0x103;
+pub const TPM_PT_YEAR: u32 = 0x104;
+pub const TPM_PT_MANUFACTURER: u32 = 0x105;
+pub const TPM_PT_VENDOR_STRING_1: u32 = 0x106;
+pub const TPM_PT_VENDOR_STRING_2: u32 = 0x107;
+pub const TPM_PT_VENDOR_STRING_3: u32 = 0x108;
+pub const TPM_PT_VENDOR_STRING_4: u32 = 0x109;
+pub const TPM_PT_VENDOR_TPM_TYPE: u32 = 0x10A;
+pub const TPM_PT_FIRMWARE_VERSION_1: u32 = 0x10B;
+pub const TPM_PT_FIRMWARE_VERSION_2: u32 = 0x10C;
+pub const TPM_PT_HR_PERSISTENT_MIN: u32 = 0x10F;
+
+/// `TPM_PT` values in the `PT_VAR` group, which can change while the TPM is running
+pub const TPM_PT_PERMANENT: u32 = 0x200;
+pub const TPM_PT_STARTUP_CLEAR: u32 = 0x201;
+pub const TPM_PT_HR_PERSISTENT: u32 = 0x208;
+pub const TPM_PT_HR_PERSISTENT_AVAIL: u32 = 0x209;
+pub const TPM_PT_LOCKOUT_COUNTER: u32 = 0x20E;
+pub const TPM_PT_MAX_AUTH_FAIL: u32 = 0x20F;
+pub const TPM_PT_LOCKOUT_INTERVAL: u32 = 0x210;
+pub const TPM_PT_LOCKOUT_RECOVERY: u32 = 0x211;
+
+/// `TPM_PT_PCR` values
+pub const TPM_PT_PCR_RESET_L0: u32 = 0x02;
+
+/// `TPMA_PERMANENT` bits
+pub const TPMA_PERMANENT_OWNER_AUTH_SET: u32 = 1 << 0;
+pub const TPMA_PERMANENT_ENDORSEMENT_AUTH_SET: u32 = 1 << 1;
+pub const TPMA_PERMANENT_LOCKOUT_AUTH_SET: u32 = 1 << 2;
+pub const TPMA_PERMANENT_DISABLE_CLEAR: u32 = 1 << 8;
+pub const TPMA_PERMANENT_IN_LOCKOUT: u32 = 1 << 9;
+pub const TPMA_PERMANENT_TPM_GENERATED_EPS: u32 = 1 << 10;
+
+/// `TPMA_STARTUP_CLEAR` bits
+pub const TPMA_STARTUP_CLEAR_PH_ENABLE: u32 = 1 << 0;
+pub const TPMA_STARTUP_CLEAR_SH_ENABLE: u32 = 1 << 1;
+pub const TPMA_STARTUP_CLEAR_EH_ENABLE: u32 = 1 << 2;
+pub const TPMA_STARTUP_CLEAR_PH_ENABLE_NV: u32 = 1 << 3;
+pub const TPMA_STARTUP_CLEAR_ORDERLY: u32 = 1 << 31;
+
+/// `TPMA_CC` fields
+pub const TPMA_CC_COMMAND_INDEX: u32 = 0xFFFF;
+pub const TPMA_CC_V: u32 = 1 << 29;
+
+/// `TPMS_TAGGED_PROPERTY`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TaggedProperty {
+    pub property: u32,
+    pub value: u32,
+}
+
+/// The start of every `TPM2_GetCapability` response: `moreData`, then the `TPM_CAP` the data is
+/// for
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, FromBytes, IntoBytes, Immutable, KnownLayout, Unaligned,
+)]
+#[repr(C)]
+pub struct CapabilityHeader {
+    pub more_data: u8,
+    pub capability: U32,
+}
+
+/// `TPM2_GetCapability`. Returns `moreData` and the capability-specific data.
+pub fn get_capability<'r>(
+    tcg: &mut dyn TpmTransport,
+    capability: u32,
+    property: u32,
+    property_count: u32,
+    response_buffer: &'r mut [u8],
+) -> Result<(bool, Reader<'r>), TpmError> {
+    let mut command_buffer = [0; 22];
+    let mut writer = Writer::new(&mut command_buffer);
+    begin_command(&mut writer, TPM_ST_NO_SESSIONS, CommandCode::GET_CAPABILITY)?;
+    writer.u32(capability)?;
+    writer.u32(property)?;
+    writer.u32(property_count)?;
+    let response = submit(tcg, finish_command(writer), response_buffer)?;
+    let mut parameters = response.parameters()?;
+    let header = parameters.fixed::<CapabilityHeader>()?;
+    if header.capability.get() != capability {
+        return Err(TpmError::Malformed);
+    }
+    Ok((header.more_data != 0, parameters))
+}
+
+/// An entry in a capability's list, which [`CapabilityIter`] pages through
+pub trait CapabilityItem: Copy {
+    /// The `TPM_CAP` the list is under
+    const CAPABILITY: u32;
+
+    fn read(reader: &mut Reader<'_>) -> Result<Self, TpmError>;
+
+    /// The property to ask for to carry on after this entry
+    fn next_property(&self) -> u32;
+}
+
+impl CapabilityItem for TaggedProperty {
+    const CAPABILITY: u32 = TPM_CAP_TPM_PROPERTIES;
+
+    fn read(reader: &mut Reader<'_>) -> Result<Self, TpmError> {
+        Ok(Self {
+            property: reader.u32()?,
+            value: reader.u32()?,
+        })
+    }
+
+    fn next_property(&self) -> u32 {
+        self.property + 1
+    }
+}
+
+impl CapabilityItem for CommandCode {
+    const CAPABILITY: u32 = TPM_CAP_COMMANDS;
+
+    /// A `TPMA_CC`, keeping only the command code
+    fn read(reader: &mut Reader<'_>) -> Result<Self, TpmError> {
+        Ok(command_code(reader.u32()?))
+    }
+
+    fn next_property(&self) -> u32 {
+        self.0 + 1
+    }
+}
+
+/// `TPM_HT_*`, the top byte of a handle, which says what it's a handle of
+pub const TPM_HT_LOADED_SESSION: u8 = 0x02;
+pub const TPM_HT_SAVED_SESSION: u8 = 0x03;
+pub const TPM_HT_TRANSIENT: u8 = 0x80;
+pub const TPM_HT_PERSISTENT: u8 = 0x81;
+/// `PERSISTENT_FIRST`
+pub const PERSISTENT_FIRST: TpmHandle = 0x81000000;
+
+/// The handle of an object `TPM2_EvictControl` made persistent
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TpmPersistentHandle(pub TpmHandle);
+
+impl CapabilityItem for TpmPersistentHandle {
+    const CAPABILITY: u32 = TPM_CAP_HANDLES;
+
+    fn read(reader: &mut Reader<'_>) -> Result<Self, TpmError> {
+        Ok(Self(reader.u32()?))
+    }
+
+    fn next_property(&self) -> u32 {
+        self.0 + 1
+    }
+}
+
+impl fmt::Display for TpmPersistentHandle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#010x}", self.0)
+    }
+}
+
+impl CapabilityItem for TpmHandle {
+    const CAPABILITY: u32 = TPM_CAP_HANDLES;
+
+    fn read(reader: &mut Reader<'_>) -> Result<Self, TpmError> {
+        reader.u32()
+    }
+
+    fn next_property(&self) -> u32 {
+        self + 1
+    }
+}
+
+/// Every handle of `handle_type`, in order
+pub fn list_handles(
+    tcg: &mut dyn TpmTransport,
+    handle_type: u8,
+) -> Result<Vec<TpmHandle>, TpmError> {
+    // The TPM carries on into the handle types after this one
+    CapabilityIter::new(tcg, (handle_type as TpmHandle) << 24)
+        .take_while(|handle| {
+            handle.as_ref().map_or(true, |handle: &TpmHandle| {
+                (handle >> 24) as u8 == handle_type
+            })
+        })
+        .collect()
+}
+
+/// Every persistent object's handle, in order
+pub fn list_persistent_handles(
+    tcg: &mut dyn TpmTransport,
+) -> Result<Vec<TpmPersistentHandle>, TpmError> {
+    Ok(list_handles(tcg, TPM_HT_PERSISTENT)?
+        .into_iter()
+        .map(TpmPersistentHandle)
+        .collect())
+}
+
+/// Every entry of a capability from `first` on, asking the TPM for the next page whenever it
+/// said there's `moreData`. Stops after the first error.
+pub struct CapabilityIter<'tcg, T> {
+    tcg: &'tcg mut dyn TpmTransport,
+    next_property: u32,
+    more_data: bool,
+    buffer: Vec<T>,
+    pos: usize,
+}
+
+impl<'tcg, T: CapabilityItem> CapabilityIter<'tcg, T> {
+    pub fn new(tcg: &'tcg mut dyn TpmTransport, first: u32) -> Self {
+        Self {
+            tcg,
+            next_property: first,
+            more_data: true,
+            buffer: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    fn fetch(&mut self) -> Result<(), TpmError> {
+        self.buffer.clear();
+        self.pos = 0;
+        // Stays false if this fails, so the iterator ends after the error
+        self.more_data = false;
+        let mut response_buffer = [0; BUFFER_SIZE];
+        let (more_data, mut data) = get_capability(
+            self.tcg,
+            T::CAPABILITY,
+            self.next_property,
+            u32::MAX,
+            &mut response_buffer,
+        )?;
+        for _ in 0..data.u32()? {
+            self.buffer.push(T::read(&mut data)?);
+        }
+        if let Some(last) = self.buffer.last() {
+            self.next_property = last.next_property();
+            self.more_data = more_data;
+        }
+        Ok(())
+    }
+}
+
+impl<T: CapabilityItem> Iterator for CapabilityIter<'_, T> {
+    type Item = Result<T, TpmError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(item) = self.buffer.get(self.pos) {
+                self.pos += 1;
+                return Some(Ok(*item));
+            }
+            if !self.more_data {
+                return None;
+            }
+            if let Err(e) = self.fetch() {
+                return Some(Err(e));
+            }
+        }
+    }
+}
+
+impl<T: CapabilityItem> FusedIterator for CapabilityIter<'_, T> {}
+
+/// Reads consecutive TPM properties starting at `first` into `properties`, returning how many
+/// were read. The TPM leaves out properties it doesn't implement, so check the `property` field.
+pub fn get_tpm_properties(
+    tcg: &mut dyn TpmTransport,
+    first: u32,
+    properties: &mut [TaggedProperty],
+) -> Result<usize, TpmError> {
+    let mut response_buffer = [0; BUFFER_SIZE];
+    let (_more_data, mut data) = get_capability(
+        tcg,
+        TPM_CAP_TPM_PROPERTIES,
+        first,
+        properties.len() as u32,
+        &mut response_buffer,
+    )?;
+    let count = data.u32()? as usize;
+    if count > properties.len() {
+        return Err(TpmError::Malformed);
+    }
+    for property in &mut properties[..count] {
+        property.property = data.u32()?;
+        property.value = data.u32()?;
+    }
+    Ok(count)
+}
+
+/// Reads a single TPM property, or `None` if the TPM doesn't implement it
+pub fn get_tpm_property(
+    tcg: &mut dyn TpmTransport,
+    property: u32,
+) -> Result<Option<u32>, TpmError> {
+    let mut properties = [TaggedProperty::default()];
+    let count = get_tpm_properties(tcg, property, &mut properties)?;
+    Ok(properties[..count]
+        .iter()
+        .find(|tagged| tagged.property == property)
+        .map(|tagged| tagged.value))
+}
+
+/// The version of the TPM 2.0 library specification the TPM implements
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TpmSpecVersion {
+    /// 4 ASCII characters, `2.0` followed by a null
+    pub family: u32,
+    pub level: u32,
+    /// Times 100, so 1.59 is 159
+    pub revision: u32,
+    /// The specification's date
+    pub day_of_year: u32,
+    pub year: u32,
+}
+
+impl TpmSpecVersion {
+    pub fn read(tcg: &mut dyn TpmTransport) -> Result<Self, TpmError> {
+        let mut properties =
+            [TaggedProperty::default(); (TPM_PT_YEAR - TPM_PT_FAMILY_INDICATOR + 1) as usize];
+        let count = get_tpm_properties(tcg, TPM_PT_FAMILY_INDICATOR, &mut properties)?;
+        let mut version = Self::default();
+        for property in &properties[..count] {
+            let field = match property.property {
+                TPM_PT_FAMILY_INDICATOR => &mut version.family,
+                TPM_PT_LEVEL => &mut version.level,
+                TPM_PT_REVISION => &mut version.revision,
+                TPM_PT_DAY_OF_YEAR => &mut version.day_of_year,
+                TPM_PT_YEAR => &mut version.year,
+                _ => continue,
+            };
+            *field = property.value;
+        }
+        Ok(version)
+    }
+}
+
+impl fmt::Display for TpmSpecVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("TPM ")?;
+        for byte in self.family.to_be_bytes() {
+            if byte != 0 {
+                write!(f, "{}", char::from(byte))?;
+            }
+        }
+        write!(
+            f,
+            " level {} revision {}.{:02} ({}, day {})",
+            self.level,
+            self.revision / 100,
+            self.revision % 100,
+            self.year,
+            self.day_of_year
+        )
+    }
+}
+
+/// Identifies a model of TPM and its firmware: `TPM_PT_MANUFACTURER`, the four
+/// `TPM_PT_VENDOR_STRING`s, `TPM_PT_VENDOR_TPM_TYPE` and `TPM_PT_FIRMWARE_VERSION_1` and `_2`,
+/// each big-endian. Properties the TPM doesn't report are zeros.
+///
+/// `TPM_PT_VENDOR_TPM_TYPE` is the vendor's model number. Vendors reuse the same vendor strings
+/// across models, so without it two different chips on the same firmware version would have the
+/// same ID.
+///
+/// TPMs don't have a `TPM_PT` for a serial number, so TPMs of the same model with the same
+/// firmware have the same ID. Only the endorsement key tells them apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct TpmUniqueId(pub [u8; 32]);
+
+impl fmt::Display for TpmUniqueId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0 {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the [`TpmUniqueId`] with one `TPM2_GetCapability`
+pub fn read_tpm_unique_id(tcg: &mut dyn TpmTransport) -> Result<TpmUniqueId, TpmError> {
+    let mut properties =
+        [TaggedProperty::default(); (TPM_PT_FIRMWARE_VERSION_2 - TPM_PT_MANUFACTURER + 1) as usize];
+    let count = get_tpm_properties(tcg, TPM_PT_MANUFACTURER, &mut properties)?;
+    let mut id = [0; 32];
+    for property in &properties[..count] {
+        if let Some(offset) = property.property.checked_sub(TPM_PT_MANUFACTURER) {
+            let offset = offset as usize * 4;
+            if let Some(bytes) = id.get_mut(offset..offset + 4) {
+                bytes.copy_from_slice(&property.value.to_be_bytes());
+            }
+        }
+    }
+    Ok(TpmUniqueId(id))
+}
+
+/// Whether the storage (owner) hierarchy is enabled. When it isn't, anything under
+/// `TPM_RH_OWNER` fails with `TPM_RC_HIERARCHY`.
+///
+/// The enables are in `TPMA_STARTUP_CLEAR`, not `TPMA_PERMANENT`: the platform can disable a
+/// hierarchy until the next `TPM2_Startup(CLEAR)`, which is usually a reboot.
+pub fn is_storage_hierarchy_enabled(tcg: &mut dyn TpmTransport) -> Result<bool, TpmError> {
+    let startup_clear = get_tpm_property(tcg, TPM_PT_STARTUP_CLEAR)?.ok_or(TpmError::Malformed)?;
+    Ok(startup_clear & TPMA_STARTUP_CLEAR_SH_ENABLE != 0)
+}
+
+/// How many persistent objects there are, and how many more `TPM2_EvictControl` can make
+/// persistent before it fails with `TPM_RC_NV_SPACE`.
+///
+/// The TPM reports the second number itself as `TPM_PT_HR_PERSISTENT_AVAIL`. If it doesn't, this
+/// falls back to what's left of the `TPM_PT_HR_PERSISTENT_MIN` every TPM guarantees, which can
+/// only underestimate, since persistent objects share NV space with everything else.
+pub fn get_persistent_slot_count(tcg: &mut dyn TpmTransport) -> Result<(u32, u32), TpmError> {
+    let used = get_tpm_property(tcg, TPM_PT_HR_PERSISTENT)?.ok_or(TpmError::Malformed)?;
+    let available = match get_tpm_property(tcg, TPM_PT_HR_PERSISTENT_AVAIL)? {
+        Some(available) => available,
+        None => get_tpm_property(tcg, TPM_PT_HR_PERSISTENT_MIN)?
+            .ok_or(TpmError::Malformed)?
+            .saturating_sub(used),
+    };
+    Ok((used, available))
+}
+
+/// The PCRs allocated in each bank. Banks that aren't active are empty or left out.
+///
+/// Unlike the other capabilities, `TPM_CAP_PCRS` isn't a list of tagged values but a
+/// `TPML_PCR_SELECTION`: a count of banks, each a hash algorithm followed by its own
+/// `sizeofSelect` and bitmap.
+pub fn get_pcr_allocation(tcg: &mut dyn TpmTransport) -> Result<PcrSelectionList, TpmError> {
+    let mut response_buffer = [0; BUFFER_SIZE];
+    let (_more_data, mut data) = get_capability(tcg, TPM_CAP_PCRS, 0, 1, &mut response_buffer)?;
+    PcrSelectionList::read(&mut data)
+}
+
+/// The banks with at least one PCR allocated, each with a bitmap of its PCRs (PCR `n` in bit `n`)
+pub fn active_pcr_banks(tcg: &mut dyn TpmTransport) -> Result<Vec<(AlgorithmId, u32)>, TpmError> {
+    Ok(get_pcr_allocation(tcg)?
+        .as_slice()
+        .iter()
+        .filter(|selection| !selection.is_empty())
+        .map(|selection| (selection.hash, selection.bitmap()))
+        .collect())
+}
+
+/// The PCRs with a `TPM_PT_PCR` property, as a bitmap (PCR `n` in bit `n`)
+pub fn get_pcr_property(tcg: &mut dyn TpmTransport, property: u32) -> Result<u32, TpmError> {
+    let mut response_buffer = [0; BUFFER_SIZE];
+    let (_more_data, mut data) = get_capability(
+        tcg,
+        TPM_CAP_PCR_PROPERTIES,
+        property,
+        1,
+        &mut response_buffer,
+    )?;
+    if data.u32()? == 0 {
+        return Ok(0);
+    }
+    // `TPMS_TAG_PCR_SELECT`
+    if data.u32()? != property {
+        // The TPM doesn't have this property and returned the next one
+        return Ok(0);
+    }
+    let size_of_select = data.u8()? as usize;
+    let mut bitmap = 0;
+    for i in 0..size_of_select {
+        let byte = data.u8()?;
+        if i < 4 {
+            bitmap |= (byte as u32) << (i * 8);
+        }
+    }
+    Ok(bitmap)
+}
+
+/// The command code a `TPMA_CC` describes
+fn command_code(attributes: u32) -> CommandCode {
+    // Vendor-specific commands have the V bit in the same place in both
+    CommandCode(attributes & (TPMA_CC_COMMAND_INDEX | TPMA_CC_V))
+}
+
+/// Whether the TPM implements `code`. Asks the TPM every time, so use [`CommandSet`] to check
+/// more than one command.
+pub fn supports(tcg: &mut dyn TpmTransport, code: CommandCode) -> Result<bool, TpmError> {
+    let mut response_buffer = [0; BUFFER_SIZE];
+    let (_more_data, mut data) =
+        get_capability(tcg, TPM_CAP_COMMANDS, code.0, 1, &mut response_buffer)?;
+    // The list starts at the first implemented command at or after `code`
+    Ok(data.u32()? > 0 && command_code(data.u32()?) == code)
+}
+
+/// Every command the TPM implements, read once so checking a command before sending it doesn't
+/// cost a round trip
+#[derive(Debug, Clone, Default)]
+pub struct CommandSet {
+    /// Sorted, since the TPM lists them in order
+    codes: Vec<CommandCode>,
+}
+
+impl CommandSet {
+    pub fn read(tcg: &mut dyn TpmTransport) -> Result<Self, TpmError> {
+        let mut codes =
+            CapabilityIter::<CommandCode>::new(tcg, 0).collect::<Result<Vec<_>, _>>()?;
+        codes.sort_unstable();
+        Ok(Self { codes })
+    }
+
+    pub fn supports(&self, code: CommandCode) -> bool {
+        self.codes.binary_search(&code).is_ok()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = CommandCode> + '_ {
+        self.codes.iter().copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tpm::{
+        ResponseCode,
+        mock::{
+            MockTransport, get_capability_command, handles_response, list_handles_command,
+            properties_response,
+        },
+        pcr_selection::PcrSelection,
+    };
+
+    #[test]
+    fn capability_header_round_trips() {
+        let header = CapabilityHeader {
+            more_data: 1,
+            capability: TPM_CAP_PCRS.into(),
+        };
+        let mut buffer = [0; 8];
+        let mut writer = Writer::new(&mut buffer);
+        writer.fixed(&header).unwrap();
+        let written = writer.into_slice();
+        assert_eq!(written, [0x01, 0x00, 0x00, 0x00, 0x05]);
+        assert_eq!(
+            Reader::new(written).fixed::<CapabilityHeader>().unwrap(),
+            &header
+        );
+    }
+
+    #[test]
+    fn get_capability_returns_more_data_and_the_rest() {
+        let mut tpm = MockTransport::success(&[0x01, 0x00, 0x00, 0x00, 0x01, 0xAA]);
+        let mut response_buffer = [0; 64];
+        let (more_data, data) =
+            get_capability(&mut tpm, TPM_CAP_HANDLES, 0, 1, &mut response_buffer).unwrap();
+        assert!(more_data);
+        assert_eq!(data.remaining(), [0xAA]);
+    }
+
+    #[test]
+    fn get_capability_rejects_data_for_another_capability() {
+        let mut tpm = MockTransport::success(&[0x00, 0x00, 0x00, 0x00, 0x02]);
+        let mut response_buffer = [0; 64];
+        assert!(matches!(
+            get_capability(&mut tpm, TPM_CAP_HANDLES, 0, 1, &mut response_buffer),
+            Err(TpmError::Malformed)
+        ));
+    }
+
+    #[test]
+    fn get_capability_rejects_a_truncated_header() {
+        let mut tpm = MockTransport::success(&[0x00, 0x00, 0x00]);
+        let mut response_buffer = [0; 64];
+        assert!(matches!(
+            get_capability(&mut tpm, TPM_CAP_HANDLES, 0, 1, &mut response_buffer),
+            Err(TpmError::UnexpectedEnd)
+        ));
+    }
+
+    #[test]
+    fn list_handles_stops_at_the_next_handle_type() {
+        let mut tpm = MockTransport::default().expect(
+            list_handles_command(0x80000000),
+            handles_response(true, &[0x80000000, 0x80000002, 0x81000001]),
+        );
+        assert_eq!(
+            list_handles(&mut tpm, TPM_HT_TRANSIENT).unwrap(),
+            [0x80000000, 0x80000002]
+        );
+        tpm.assert_done();
+    }
+
+    #[test]
+    fn list_handles_pages_through_more_data() {
+        let mut tpm = MockTransport::default()
+            .expect(
+                list_handles_command(0x81000000),
+                handles_response(true, &[0x81000001]),
+            )
+            .expect(
+                list_handles_command(0x81000002),
+                handles_response(false, &[0x81010001]),
+            );
+        assert_eq!(
+            list_persistent_handles(&mut tpm).unwrap(),
+            [
+                TpmPersistentHandle(0x81000001),
+                TpmPersistentHandle(0x81010001)
+            ]
+        );
+        tpm.assert_done();
+    }
+
+    /// `TPM2_GetCapability(TPM_CAP_PCR_PROPERTIES)` answering with one `TPMS_TAG_PCR_SELECT`
+    fn pcr_property_response(tag: u32, select: &[u8]) -> Vec<u8> {
+        let mut parameters = Vec::from([0]);
+        parameters.extend_from_slice(&TPM_CAP_PCR_PROPERTIES.to_be_bytes());
+        parameters.extend_from_slice(&1u32.to_be_bytes());
+        parameters.extend_from_slice(&tag.to_be_bytes());
+        parameters.push(select.len() as u8);
+        parameters.extend_from_slice(select);
+        MockTransport::response_bytes(ResponseCode::SUCCESS, &parameters)
+    }
+
+    #[test]
+    fn pcr_property_is_a_bitmap() {
+        let mut tcg = MockTransport::default().expect(
+            [
+                get_capability_command(TPM_CAP_PCR_PROPERTIES, TPM_PT_PCR_RESET_L0),
+                1u32.to_be_bytes().to_vec(),
+            ]
+            .concat(),
+            pcr_property_response(TPM_PT_PCR_RESET_L0, &[0x00, 0x00, 0x81]),
+        );
+        assert_eq!(
+            get_pcr_property(&mut tcg, TPM_PT_PCR_RESET_L0),
+            Ok((1 << 16) | (1 << 23))
+        );
+        tcg.assert_done();
+    }
+
+    #[test]
+    fn missing_pcr_property_is_empty() {
+        // The TPM skipped to the next property it has
+        let mut tcg = MockTransport::new(pcr_property_response(0x03, &[0xff, 0xff, 0xff]));
+        assert_eq!(get_pcr_property(&mut tcg, TPM_PT_PCR_RESET_L0), Ok(0));
+    }
+
+    /// A page of `TPM_CAP_COMMANDS` with the `TPMA_CC`s
+    fn commands_response(more_data: bool, attributes: &[u32]) -> Vec<u8> {
+        let mut parameters = Vec::from([more_data as u8]);
+        parameters.extend_from_slice(&TPM_CAP_COMMANDS.to_be_bytes());
+        parameters.extend_from_slice(&(attributes.len() as u32).to_be_bytes());
+        for attributes in attributes {
+            parameters.extend_from_slice(&attributes.to_be_bytes());
+        }
+        MockTransport::response_bytes(ResponseCode::SUCCESS, &parameters)
+    }
+
+    /// `TPM2_GetCapability(TPM_CAP_COMMANDS, code, 1)`
+    fn supports_command(code: CommandCode) -> Vec<u8> {
+        [
+            get_capability_command(TPM_CAP_COMMANDS, code.0),
+            1u32.to_be_bytes().to_vec(),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn supported_command() {
+        // `PCR_Extend` with one handle, `cHandles` in bits 25 to 27
+        let mut tcg = MockTransport::default().expect(
+            supports_command(CommandCode::PCR_EXTEND),
+            commands_response(true, &[0x0200_0182]),
+        );
+        assert_eq!(supports(&mut tcg, CommandCode::PCR_EXTEND), Ok(true));
+        tcg.assert_done();
+    }
+
+    #[test]
+    fn missing_command() {
+        // The list starts at the next command the TPM has
+        let mut tcg = MockTransport::default().expect(
+            supports_command(CommandCode::PCR_READ),
+            commands_response(true, &[0x0000_017f]),
+        );
+        assert_eq!(supports(&mut tcg, CommandCode::PCR_READ), Ok(false));
+        tcg.assert_done();
+
+        // Or is empty if there's none after it
+        let mut tcg = MockTransport::new(commands_response(false, &[]));
+        assert_eq!(supports(&mut tcg, CommandCode::PCR_READ), Ok(false));
+    }
+
+    #[test]
+    fn command_set_reads_every_page() {
+        let mut tcg = MockTransport::default()
+            .expect(
+                get_capability_command(TPM_CAP_COMMANDS, 0),
+                commands_response(true, &[0x0000_017e, 0x0200_0182]),
+            )
+            .expect(
+                get_capability_command(TPM_CAP_COMMANDS, 0x183),
+                // A vendor command, with the V bit
+                commands_response(false, &[0x2000_0001]),
+            );
+        let commands = CommandSet::read(&mut tcg).unwrap();
+        tcg.assert_done();
+        assert!(commands.supports(CommandCode::PCR_READ));
+        assert!(commands.supports(CommandCode::PCR_EXTEND));
+        assert!(commands.supports(CommandCode(0x2000_0001)));
+        assert!(!commands.supports(CommandCode::PCR_RESET));
+        assert_eq!(
+            commands.iter().collect::<Vec<_>>(),
+            [
+                CommandCode::PCR_READ,
+                CommandCode::PCR_EXTEND,
+                CommandCode(0x2000_0001)
+            ]
+        );
+    }
+
+    /// `TPM_CAP_PCRS` from a PC with the SHA-1 and SHA-256 banks, only SHA-256 allocated
+    fn two_bank_allocation_response() -> Vec<u8> {
+        MockTransport::response_bytes(
+            ResponseCode::SUCCESS,
+            &[
+                0x00, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x02, 0x00, 0x04, 0x03, 0x00, 0x00,
+                0x00, 0x00, 0x0b, 0x03, 0xff, 0xff, 0xff,
+            ],
+        )
+    }
+
+    #[test]
+    fn two_bank_pcr_allocation() {
+        let mut tcg = MockTransport::default().expect(
+            [
+                get_capability_command(TPM_CAP_PCRS, 0),
+                1u32.to_be_bytes().to_vec(),
+            ]
+            .concat(),
+            two_bank_allocation_response(),
+        );
+        let allocation = get_pcr_allocation(&mut tcg).unwrap();
+        tcg.assert_done();
+        assert_eq!(
+            allocation.as_slice(),
+            [
+                PcrSelection::new(AlgorithmId::SHA1),
+                PcrSelection {
+                    hash: AlgorithmId::SHA256,
+                    size_of_select: 3,
+                    pcr_select: [0xff, 0xff, 0xff, 0x00],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_banks_arent_active() {
+        let mut tcg = MockTransport::new(two_bank_allocation_response());
+        assert_eq!(
+            active_pcr_banks(&mut tcg),
+            Ok(Vec::from([(AlgorithmId::SHA256, 0x00ff_ffff)]))
+        );
+    }
+
+    #[test]
+    fn spec_version_is_read_from_the_fixed_properties() {
+        let mut tcg = MockTransport::default().expect(
+            [
+                get_capability_command(TPM_CAP_TPM_PROPERTIES, TPM_PT_FAMILY_INDICATOR),
+                5u32.to_be_bytes().to_vec(),
+            ]
+            .concat(),
+            properties_response(&[
+                (TPM_PT_FAMILY_INDICATOR, u32::from_be_bytes(*b"2.0\0")),
+                (TPM_PT_LEVEL, 0),
+                (TPM_PT_REVISION, 159),
+                (TPM_PT_DAY_OF_YEAR, 312),
+                (TPM_PT_YEAR, 2019),
+            ]),
+        );
+        let version = TpmSpecVersion::read(&mut tcg).unwrap();
+        tcg.assert_done();
+        assert_eq!(
+            version,
+            TpmSpecVersion {
+                family: 0x322e_3000,
+                level: 0,
+                revision: 159,
+                day_of_year: 312,
+                year: 2019,
+            }
+        );
+        assert_eq!(
+            version.to_string(),
+            "TPM 2.0 level 0 revision 1.59 (2019, day 312)"
+        );
+    }
+
+    #[test]
+    fn spec_version_skips_other_properties() {
+        // The TPM has no TPM_PT_DAY_OF_YEAR, and carried on past TPM_PT_YEAR
+        let mut tcg = MockTransport::new(properties_response(&[
+            (TPM_PT_FAMILY_INDICATOR, u32::from_be_bytes(*b"2.0\0")),
+            (TPM_PT_REVISION, 138),
+            (TPM_PT_YEAR, 2016),
+            (TPM_PT_MANUFACTURER, u32::from_be_bytes(*b"IBM ")),
+        ]));
+        let version = TpmSpecVersion::read(&mut tcg).unwrap();
+        assert_eq!(
+            (version.revision, version.day_of_year, version.year),
+            (138, 0, 2016)
+        );
+    }
+
+    #[test]
+    fn unique_id_is_the_identifying_properties() {
+        let mut tcg = MockTransport::default().expect(
+            [
+                get_capability_command(TPM_CAP_TPM_PROPERTIES, TPM_PT_MANUFACTURER),
+                8u32.to_be_bytes().to_vec(),
+            ]
+            .concat(),
+            properties_response(&[
+                (TPM_PT_MANUFACTURER, u32::from_be_bytes(*b"IFX\0")),
+                (TPM_PT_VENDOR_STRING_1, u32::from_be_bytes(*b"SLB9")),
+                (TPM_PT_VENDOR_STRING_2, u32::from_be_bytes(*b"672\0")),
+                (TPM_PT_VENDOR_STRING_3, 0),
+                (TPM_PT_VENDOR_STRING_4, 0),
+                (TPM_PT_VENDOR_TPM_TYPE, 0),
+                (TPM_PT_FIRMWARE_VERSION_1, 0x0007_0055),
+                (TPM_PT_FIRMWARE_VERSION_2, 0x0011_cf00),
+            ]),
+        );
+        let id = read_tpm_unique_id(&mut tcg).unwrap();
+        tcg.assert_done();
+        assert_eq!(
+            id.0,
+            [
+                b'I', b'F', b'X', 0x00, b'S', b'L', b'B', b'9', b'6', b'7', b'2', 0x00, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x07, 0x00, 0x55,
+                0x00, 0x11, 0xcf, 0x00,
+            ]
+        );
+        assert_eq!(
+            id.to_string(),
+            "49465800534c423936373200000000000000000000000000000700550011cf00"
+        );
+    }
+
+    #[test]
+    fn unique_id_has_zeros_for_missing_properties() {
+        let mut tcg = MockTransport::new(properties_response(&[
+            (TPM_PT_MANUFACTURER, u32::from_be_bytes(*b"MSFT")),
+            (TPM_PT_VENDOR_TPM_TYPE, 1),
+            (TPM_PT_FIRMWARE_VERSION_2, 2),
+        ]));
+        let mut expected = [0; 32];
+        expected[..4].copy_from_slice(b"MSFT");
+        expected[20..24].copy_from_slice(&1u32.to_be_bytes());
+        expected[28..].copy_from_slice(&2u32.to_be_bytes());
+        assert_eq!(read_tpm_unique_id(&mut tcg), Ok(TpmUniqueId(expected)));
+    }
+}
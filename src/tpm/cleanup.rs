@@ -0,0 +1,271 @@
+//! Leaving the TPM tidy for the OS: whatever objects and sessions we loaded take up slots the OS
+//! can't free without knowing they're there. Meant for an `ExitBootServices` handler, where a
+//! failure can't stop the OS from loading anyway.
+
+use super::{
+    BUFFER_SIZE, CommandCode, TPM_ST_NO_SESSIONS, TpmError, TpmTransport, begin_command,
+    capability::{TPM_HT_LOADED_SESSION, TPM_HT_SAVED_SESSION, TPM_HT_TRANSIENT, list_handles},
+    finish_command,
+    marshal::Writer,
+    object::flush_context,
+    response_code::ResponseCode,
+    submit,
+};
+
+/// `TPM_SU_CLEAR`: the next `TPM2_Startup` starts from scratch
+pub const TPM_SU_CLEAR: u16 = 0x0000;
+/// `TPM_SU_STATE`: the next `TPM2_Startup` can resume from the saved state
+pub const TPM_SU_STATE: u16 = 0x0001;
+
+/// `TPM2_Shutdown`
+pub fn shutdown(tcg: &mut dyn TpmTransport, shutdown_type: u16) -> Result<(), TpmError> {
+    let mut command_buffer = [0; 12];
+    let mut writer = Writer::new(&mut command_buffer);
+    begin_command(&mut writer, TPM_ST_NO_SESSIONS, CommandCode::SHUTDOWN)?;
+    writer.u16(shutdown_type)?;
+    let mut response_buffer = [0; BUFFER_SIZE];
+    submit(tcg, finish_command(writer), &mut response_buffer)?;
+    Ok(())
+}
+
+/// Whether `error` means there's nothing left to clean up: the TPM is already shut down, or in
+/// lockout, or the handle was flushed by someone else
+fn already_clean(error: &TpmError) -> bool {
+    matches!(error, TpmError::InLockout { .. })
+        || error.response_code().is_some_and(|code| {
+            [
+                ResponseCode::INITIALIZE,
+                ResponseCode::LOCKOUT,
+                ResponseCode::HANDLE,
+            ]
+            .contains(&code.base())
+        })
+}
+
+fn flush_all(tcg: &mut dyn TpmTransport) -> Result<(), TpmError> {
+    for handle_type in [
+        TPM_HT_TRANSIENT,
+        TPM_HT_LOADED_SESSION,
+        TPM_HT_SAVED_SESSION,
+    ] {
+        for handle in list_handles(tcg, handle_type)? {
+            match flush_context(tcg, handle) {
+                Err(e) if !already_clean(&e) => return Err(e),
+                _ => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Flushes every transient object and session, then sends `TPM2_Shutdown(TPM_SU_CLEAR)` if
+/// `clean_shutdown` is set. Running it again, or on a TPM that's already shut down or in lockout,
+/// succeeds without doing anything.
+pub fn cleanup_tpm_state(tcg: &mut dyn TpmTransport, clean_shutdown: bool) -> Result<(), TpmError> {
+    let result = flush_all(tcg).and_then(|()| {
+        if clean_shutdown {
+            shutdown(tcg, TPM_SU_CLEAR)
+        } else {
+            Ok(())
+        }
+    });
+    match result {
+        Err(e) if !already_clean(&e) => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Puts a TPM simulator back in the state each test starts from: no transient objects or
+/// sessions, and [`DEBUG_PCR`](super::pcr::DEBUG_PCR) zeros. Only built with the `swtpm-tests`
+/// feature, since on a real TPM it throws away whatever the firmware loaded.
+#[cfg(feature = "swtpm-tests")]
+pub fn reset_for_test(tcg: &mut dyn TpmTransport) -> Result<(), TpmError> {
+    flush_all(tcg)?;
+    super::pcr::pcr_reset(tcg, super::pcr::DEBUG_PCR)
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::tpm::mock::{
+        MockTransport, handles_response, list_handles_command, success_response,
+    };
+
+    fn flush_context_command(handle: u32) -> Vec<u8> {
+        let mut command = Vec::from([0x80, 0x01, 0x00, 0x00, 0x00, 0x0e, 0x00, 0x00, 0x01, 0x65]);
+        command.extend_from_slice(&handle.to_be_bytes());
+        command
+    }
+
+    #[test]
+    fn flushes_every_transient_object_and_session() {
+        let mut tpm = MockTransport::default()
+            .expect(
+                list_handles_command(0x80000000),
+                handles_response(false, &[0x80000000]),
+            )
+            .expect(flush_context_command(0x80000000), success_response())
+            .expect(
+                list_handles_command(0x02000000),
+                handles_response(false, &[0x02000001]),
+            )
+            .expect(flush_context_command(0x02000001), success_response())
+            .expect(
+                list_handles_command(0x03000000),
+                handles_response(false, &[0x03000002]),
+            )
+            .expect(flush_context_command(0x03000002), success_response())
+            .expect(
+                [
+                    0x80, 0x01, 0x00, 0x00, 0x00, 0x0c, 0x00, 0x00, 0x01, 0x45, 0x00, 0x00,
+                ],
+                success_response(),
+            );
+        cleanup_tpm_state(&mut tpm, true).unwrap();
+        tpm.assert_done();
+    }
+
+    #[test]
+    fn handles_someone_else_flushed_are_skipped() {
+        let mut tpm = MockTransport::default()
+            .expect(
+                list_handles_command(0x80000000),
+                handles_response(false, &[0x80000000, 0x80000001]),
+            )
+            // TPM_RC_HANDLE for handle 1: someone else flushed it
+            .expect(
+                flush_context_command(0x80000000),
+                MockTransport::response_bytes(ResponseCode(0x18B), &[]),
+            )
+            .expect(flush_context_command(0x80000001), success_response())
+            .expect(
+                list_handles_command(0x02000000),
+                handles_response(false, &[]),
+            )
+            .expect(
+                list_handles_command(0x03000000),
+                handles_response(false, &[]),
+            );
+        cleanup_tpm_state(&mut tpm, false).unwrap();
+        tpm.assert_done();
+    }
+
+    #[test]
+    fn a_shut_down_tpm_is_already_clean() {
+        let mut tpm =
+            MockTransport::new(MockTransport::response_bytes(ResponseCode::INITIALIZE, &[]));
+        cleanup_tpm_state(&mut tpm, true).unwrap();
+        assert_eq!(tpm.commands.len(), 1);
+    }
+
+    #[test]
+    fn other_errors_are_returned() {
+        let mut tpm = MockTransport::default()
+            .expect(
+                list_handles_command(0x80000000),
+                handles_response(false, &[0x80000000]),
+            )
+            .expect(
+                flush_context_command(0x80000000),
+                MockTransport::response_bytes(ResponseCode::HIERARCHY, &[]),
+            );
+        assert!(cleanup_tpm_state(&mut tpm, true).is_err());
+        tpm.assert_done();
+    }
+
+    #[cfg(feature = "swtpm-tests")]
+    mod reset_for_test {
+        use super::*;
+        use crate::tpm::{
+            capability::{TPM_CAP_PCR_PROPERTIES, TPM_PT_PCR_RESET_L0},
+            mock::get_capability_command,
+            pcr::DEBUG_PCR,
+        };
+
+        /// `TPM_PT_PCR_RESET_L0` with the PCRs in `select` (3 bytes, PCR 0 in the low bit)
+        fn reset_l0_response(select: [u8; 3]) -> Vec<u8> {
+            let mut parameters = Vec::from([0x00]);
+            parameters.extend_from_slice(&TPM_CAP_PCR_PROPERTIES.to_be_bytes());
+            parameters.extend_from_slice(&1u32.to_be_bytes());
+            parameters.extend_from_slice(&TPM_PT_PCR_RESET_L0.to_be_bytes());
+            parameters.push(3);
+            parameters.extend_from_slice(&select);
+            MockTransport::response_bytes(ResponseCode::SUCCESS, &parameters)
+        }
+
+        fn flushes(tpm: MockTransport) -> MockTransport {
+            tpm.expect(
+                list_handles_command(0x80000000),
+                handles_response(false, &[0x80000000, 0x80000001]),
+            )
+            .expect(flush_context_command(0x80000000), success_response())
+            .expect(flush_context_command(0x80000001), success_response())
+            .expect(
+                list_handles_command(0x02000000),
+                handles_response(false, &[0x02000000]),
+            )
+            .expect(flush_context_command(0x02000000), success_response())
+            .expect(
+                list_handles_command(0x03000000),
+                handles_response(false, &[]),
+            )
+            .expect(
+                get_capability_command(TPM_CAP_PCR_PROPERTIES, TPM_PT_PCR_RESET_L0),
+                reset_l0_response([0x00, 0x00, 0x01]),
+            )
+        }
+
+        #[test]
+        fn flushes_everything_and_resets_the_debug_pcr() {
+            let mut tpm = flushes(MockTransport::default()).expect(
+                [
+                    0x80,
+                    0x02,
+                    0x00,
+                    0x00,
+                    0x00,
+                    0x1b,
+                    0x00,
+                    0x00,
+                    0x01,
+                    0x3d,
+                    0x00,
+                    0x00,
+                    0x00,
+                    DEBUG_PCR as u8,
+                ],
+                success_response(),
+            );
+            reset_for_test(&mut tpm).unwrap();
+            tpm.assert_done();
+        }
+
+        #[test]
+        fn fails_if_the_debug_pcr_cant_be_reset() {
+            let mut tpm = MockTransport::default()
+                .expect(
+                    list_handles_command(0x80000000),
+                    handles_response(false, &[]),
+                )
+                .expect(
+                    list_handles_command(0x02000000),
+                    handles_response(false, &[]),
+                )
+                .expect(
+                    list_handles_command(0x03000000),
+                    handles_response(false, &[]),
+                )
+                .expect(
+                    get_capability_command(TPM_CAP_PCR_PROPERTIES, TPM_PT_PCR_RESET_L0),
+                    reset_l0_response([0x00, 0x00, 0x00]),
+                );
+            assert!(matches!(
+                reset_for_test(&mut tpm),
+                Err(TpmError::PcrNotResettable(DEBUG_PCR))
+            ));
+            tpm.assert_done();
+        }
+    }
+}
@@ -0,0 +1,131 @@
+//! `TPM2_ReadClock`: how long the TPM has been running, and the clock it puts in attestations
+
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout, Unaligned};
+
+use super::{
+    CommandCode, TPM_ST_NO_SESSIONS, TpmError, TpmTransport,
+    attest::{ClockInfo, RawClockInfo},
+    begin_command, finish_command,
+    marshal::{U64, Writer},
+    submit,
+};
+
+/// `TPMS_TIME_INFO`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeInfo {
+    /// Milliseconds since the TPM was last powered on or reset
+    pub time: u64,
+    pub clock_info: ClockInfo,
+}
+
+/// `TPMS_TIME_INFO` as it's laid out in the `TPM2_ReadClock` response
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, FromBytes, IntoBytes, Immutable, KnownLayout, Unaligned,
+)]
+#[repr(C)]
+pub struct RawTimeInfo {
+    pub time: U64,
+    pub clock_info: RawClockInfo,
+}
+
+impl From<&RawTimeInfo> for TimeInfo {
+    fn from(raw: &RawTimeInfo) -> Self {
+        Self {
+            time: raw.time.get(),
+            clock_info: (&raw.clock_info).into(),
+        }
+    }
+}
+
+impl From<&TimeInfo> for RawTimeInfo {
+    fn from(time_info: &TimeInfo) -> Self {
+        Self {
+            time: time_info.time.into(),
+            clock_info: (&time_info.clock_info).into(),
+        }
+    }
+}
+
+/// `TPM2_ReadClock`
+pub fn read_clock(tcg: &mut dyn TpmTransport) -> Result<TimeInfo, TpmError> {
+    let mut command_buffer = [0; 10];
+    let mut writer = Writer::new(&mut command_buffer);
+    begin_command(&mut writer, TPM_ST_NO_SESSIONS, CommandCode::READ_CLOCK)?;
+    let mut response_buffer = [0; 64];
+    let response = submit(tcg, finish_command(writer), &mut response_buffer)?;
+    Ok(response.parameters()?.fixed::<RawTimeInfo>()?.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tpm::{marshal::Reader, mock::MockTransport};
+
+    /// `time` 0x0102030405060708, `clock` 0x1112131415161718, `resetCount` 3, `restartCount` 4,
+    /// `safe`
+    const TIME_INFO: [u8; 25] = [
+        0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17,
+        0x18, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x04, 0x01,
+    ];
+
+    fn time_info() -> TimeInfo {
+        TimeInfo {
+            time: 0x0102030405060708,
+            clock_info: ClockInfo {
+                clock: 0x1112131415161718,
+                reset_count: 3,
+                restart_count: 4,
+                safe: true,
+            },
+        }
+    }
+
+    #[test]
+    fn raw_time_info_is_the_size_of_the_structure() {
+        assert_eq!(size_of::<RawTimeInfo>(), 25);
+    }
+
+    #[test]
+    fn time_info_parses() {
+        let mut reader = Reader::new(&TIME_INFO);
+        let parsed = TimeInfo::from(reader.fixed::<RawTimeInfo>().unwrap());
+        assert_eq!(parsed, time_info());
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn time_info_round_trips() {
+        let mut buffer = [0; 32];
+        let mut writer = Writer::new(&mut buffer);
+        writer.fixed(&RawTimeInfo::from(&time_info())).unwrap();
+        let written = writer.into_slice();
+        assert_eq!(written, TIME_INFO);
+        let parsed = TimeInfo::from(Reader::new(written).fixed::<RawTimeInfo>().unwrap());
+        assert_eq!(parsed, time_info());
+    }
+
+    #[test]
+    fn truncated_time_info_is_rejected() {
+        let mut reader = Reader::new(&TIME_INFO[..24]);
+        assert!(matches!(
+            reader.fixed::<RawTimeInfo>(),
+            Err(TpmError::UnexpectedEnd)
+        ));
+    }
+
+    #[test]
+    fn read_clock_sends_the_command_and_parses_the_time() {
+        let mut tpm = MockTransport::success(&TIME_INFO);
+        assert_eq!(read_clock(&mut tpm).unwrap(), time_info());
+        assert_eq!(
+            tpm.commands,
+            [[0x80, 0x01, 0x00, 0x00, 0x00, 0x0a, 0x00, 0x00, 0x01, 0x81]]
+        );
+    }
+
+    #[test]
+    fn short_read_clock_response_is_rejected() {
+        let mut tpm = MockTransport::success(&TIME_INFO[..17]);
+        assert!(matches!(read_clock(&mut tpm), Err(TpmError::UnexpectedEnd)));
+    }
+}
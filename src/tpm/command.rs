@@ -0,0 +1,186 @@
+//! Commands as types, so the framing is written once instead of in every wrapper: the header,
+//! the handle and authorization areas, submitting, and splitting the response.
+
+use super::{
+    BUFFER_SIZE, CommandCode, TPM_ST_NO_SESSIONS, TPM_ST_SESSIONS, TpmError, TpmTransport,
+    auth::{AuthCommand, write_auth_area},
+    begin_command, finish_command,
+    marshal::{Reader, Writer},
+    submit,
+};
+
+/// A command's request, which knows how to read its own response
+pub trait TpmCommand {
+    const CODE: CommandCode;
+    /// How many handles the response starts with
+    const HANDLE_COUNT: usize = 0;
+
+    type Response;
+
+    /// The handle area
+    fn write_handles(&self, _writer: &mut Writer<'_>) -> Result<(), TpmError> {
+        Ok(())
+    }
+
+    /// One for each handle that needs authorization. The command is sent with `TPM_ST_SESSIONS`
+    /// if there are any.
+    fn auths(&self) -> &[AuthCommand<'_>] {
+        &[]
+    }
+
+    fn write_parameters(&self, writer: &mut Writer<'_>) -> Result<(), TpmError>;
+
+    fn read_response(
+        handles: &mut Reader<'_>,
+        parameters: &mut Reader<'_>,
+    ) -> Result<Self::Response, TpmError>;
+}
+
+/// Sends `command` and reads its response
+pub fn execute<C: TpmCommand>(
+    tcg: &mut dyn TpmTransport,
+    command: &C,
+) -> Result<C::Response, TpmError> {
+    execute_with_capacity::<C, BUFFER_SIZE>(tcg, command)
+}
+
+/// [`execute`] with `CAPACITY` byte buffers instead of [`BUFFER_SIZE`] ones. Both buffers are on
+/// the stack, so this costs `2 * CAPACITY` bytes of it instead of 8 KiB, but a command that
+/// doesn't fit fails with [`TpmError::CommandTooLarge`] and a response that doesn't with
+/// [`TpmError::ResponseTooLarge`]. Most responses are well under 1 KiB; reading a certificate or
+/// a big NV index needs more.
+pub fn execute_with_capacity<C: TpmCommand, const CAPACITY: usize>(
+    tcg: &mut dyn TpmTransport,
+    command: &C,
+) -> Result<C::Response, TpmError> {
+    let mut command_buffer = [0; CAPACITY];
+    let mut writer = Writer::new(&mut command_buffer);
+    let auths = command.auths();
+    let tag = if auths.is_empty() {
+        TPM_ST_NO_SESSIONS
+    } else {
+        TPM_ST_SESSIONS
+    };
+    begin_command(&mut writer, tag, C::CODE)?;
+    command.write_handles(&mut writer)?;
+    if !auths.is_empty() {
+        write_auth_area(&mut writer, auths)?;
+    }
+    command.write_parameters(&mut writer)?;
+    let mut response_buffer = [0; CAPACITY];
+    let response = submit(tcg, finish_command(writer), &mut response_buffer)?;
+    let (mut handles, mut parameters) = response.split(C::HANDLE_COUNT)?;
+    C::read_response(&mut handles, &mut parameters)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tpm::{
+        TPM_RH_OWNER, TpmHandle,
+        mock::{MockTransport, get_random_response},
+        random::GetRandomCommand,
+    };
+
+    /// A command with an authorized handle and a response handle, shaped like `CreatePrimary`
+    struct HandleCommand {
+        auth: [AuthCommand<'static>; 1],
+        parameter: u16,
+    }
+
+    impl TpmCommand for HandleCommand {
+        const CODE: CommandCode = CommandCode::CREATE_PRIMARY;
+        const HANDLE_COUNT: usize = 1;
+
+        type Response = (TpmHandle, u16);
+
+        fn write_handles(&self, writer: &mut Writer<'_>) -> Result<(), TpmError> {
+            writer.u32(TPM_RH_OWNER)
+        }
+
+        fn auths(&self) -> &[AuthCommand<'_>] {
+            &self.auth
+        }
+
+        fn write_parameters(&self, writer: &mut Writer<'_>) -> Result<(), TpmError> {
+            writer.u16(self.parameter)
+        }
+
+        fn read_response(
+            handles: &mut Reader<'_>,
+            parameters: &mut Reader<'_>,
+        ) -> Result<Self::Response, TpmError> {
+            Ok((handles.u32()?, parameters.u16()?))
+        }
+    }
+
+    #[test]
+    fn small_buffers_are_enough_for_small_responses() {
+        let mut tcg = MockTransport::new(get_random_response(&[0x5a; 32]));
+        let response = execute_with_capacity::<_, 64>(
+            &mut tcg,
+            &GetRandomCommand {
+                bytes_requested: 32,
+            },
+        )
+        .unwrap();
+        assert_eq!(response.random_bytes.as_slice(), [0x5a; 32]);
+        assert_eq!(
+            tcg.commands,
+            [[
+                0x80, 0x01, 0x00, 0x00, 0x00, 0x0c, 0x00, 0x00, 0x01, 0x7b, 0x00, 0x20
+            ]]
+        );
+    }
+
+    #[test]
+    fn responses_larger_than_the_buffer_are_rejected() {
+        let mut tcg = MockTransport::new(get_random_response(&[0x5a; 64]));
+        assert_eq!(
+            execute_with_capacity::<_, 64>(
+                &mut tcg,
+                &GetRandomCommand {
+                    bytes_requested: 64,
+                },
+            )
+            .unwrap_err(),
+            TpmError::ResponseTooLarge {
+                size: 76,
+                capacity: 64,
+            }
+        );
+    }
+
+    #[test]
+    fn commands_larger_than_the_buffer_are_rejected() {
+        let mut tcg = MockTransport::new(get_random_response(&[0x5a; 8]));
+        assert_eq!(
+            execute_with_capacity::<_, 11>(&mut tcg, &GetRandomCommand { bytes_requested: 8 })
+                .unwrap_err(),
+            TpmError::CommandTooLarge
+        );
+        assert!(tcg.commands.is_empty());
+    }
+
+    #[test]
+    fn execute_frames_handles_and_sessions() {
+        let response = [
+            0x80, 0x02, 0x00, 0x00, 0x00, 0x19, 0x00, 0x00, 0x00, 0x00, 0x80, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x02, 0x12, 0x34, 0x00, 0x00, 0x01, 0x00, 0x00,
+        ];
+        let mut tcg = MockTransport::new(response);
+        let command = HandleCommand {
+            auth: [AuthCommand::password(&[])],
+            parameter: 0xbeef,
+        };
+        assert_eq!(execute(&mut tcg, &command), Ok((0x8000_0000, 0x1234)));
+        assert_eq!(
+            tcg.commands,
+            [[
+                0x80, 0x02, 0x00, 0x00, 0x00, 0x1d, 0x00, 0x00, 0x01, 0x31, 0x40, 0x00, 0x00, 0x01,
+                0x00, 0x00, 0x00, 0x09, 0x40, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00, 0x00, 0x00, 0xbe,
+                0xef,
+            ]]
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use core::fmt;
+
+/// `TPM_CC`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CommandCode(pub u32);
+
+impl CommandCode {
+    pub const EVICT_CONTROL: Self = Self(0x00000120);
+    pub const CHANGE_EPS: Self = Self(0x00000124);
+    pub const CHANGE_PPS: Self = Self(0x00000125);
+    pub const CREATE_PRIMARY: Self = Self(0x00000131);
+    pub const NV_WRITE: Self = Self(0x00000137);
+    pub const DICTIONARY_ATTACK_LOCK_RESET: Self = Self(0x00000139);
+    pub const PCR_RESET: Self = Self(0x0000013D);
+    pub const SELF_TEST: Self = Self(0x00000143);
+    pub const SHUTDOWN: Self = Self(0x00000145);
+    pub const NV_READ: Self = Self(0x0000014E);
+    pub const POLICY_SECRET: Self = Self(0x00000151);
+    pub const RSA_DECRYPT: Self = Self(0x00000159);
+    pub const POLICY_SIGNED: Self = Self(0x00000160);
+    pub const FLUSH_CONTEXT: Self = Self(0x00000165);
+    pub const NV_READ_PUBLIC: Self = Self(0x00000169);
+    pub const READ_PUBLIC: Self = Self(0x00000173);
+    pub const RSA_ENCRYPT: Self = Self(0x00000174);
+    pub const ECC_PARAMETERS: Self = Self(0x00000178);
+    pub const GET_CAPABILITY: Self = Self(0x0000017A);
+    pub const GET_RANDOM: Self = Self(0x0000017B);
+    pub const GET_TEST_RESULT: Self = Self(0x0000017C);
+    pub const PCR_READ: Self = Self(0x0000017E);
+    pub const POLICY_PCR: Self = Self(0x0000017F);
+    pub const READ_CLOCK: Self = Self(0x00000181);
+    pub const PCR_EXTEND: Self = Self(0x00000182);
+    pub const POLICY_GET_DIGEST: Self = Self(0x00000189);
+    pub const POLICY_NV_WRITTEN: Self = Self(0x0000018F);
+}
+
+impl fmt::Display for CommandCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match *self {
+            Self::EVICT_CONTROL => "EvictControl",
+            Self::CHANGE_EPS => "ChangeEPS",
+            Self::CHANGE_PPS => "ChangePPS",
+            Self::CREATE_PRIMARY => "CreatePrimary",
+            Self::NV_WRITE => "NV_Write",
+            Self::DICTIONARY_ATTACK_LOCK_RESET => "DictionaryAttackLockReset",
+            Self::PCR_RESET => "PCR_Reset",
+            Self::SELF_TEST => "SelfTest",
+            Self::SHUTDOWN => "Shutdown",
+            Self::NV_READ => "NV_Read",
+            Self::POLICY_SECRET => "PolicySecret",
+            Self::RSA_DECRYPT => "RSA_Decrypt",
+            Self::POLICY_SIGNED => "PolicySigned",
+            Self::FLUSH_CONTEXT => "FlushContext",
+            Self::NV_READ_PUBLIC => "NV_ReadPublic",
+            Self::READ_PUBLIC => "ReadPublic",
+            Self::RSA_ENCRYPT => "RSA_Encrypt",
+            Self::ECC_PARAMETERS => "ECC_Parameters",
+            Self::GET_CAPABILITY => "GetCapability",
+            Self::GET_RANDOM => "GetRandom",
+            Self::GET_TEST_RESULT => "GetTestResult",
+            Self::PCR_READ => "PCR_Read",
+            Self::POLICY_PCR => "PolicyPCR",
+            Self::READ_CLOCK => "ReadClock",
+            Self::PCR_EXTEND => "PCR_Extend",
+            Self::POLICY_GET_DIGEST => "PolicyGetDigest",
+            Self::POLICY_NV_WRITTEN => "PolicyNvWritten",
+            Self(other) => return write!(f, "{other:#x}"),
+        };
+        // Padded, so it lines up in tables
+        f.pad(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::format;
+
+    use super::*;
+
+    #[test]
+    fn command_codes_are_named() {
+        assert_eq!(format!("{}", CommandCode::GET_RANDOM), "GetRandom");
+        assert_eq!(format!("{}", CommandCode::PCR_READ), "PCR_Read");
+        assert_eq!(format!("{}", CommandCode(0x20000001)), "0x20000001");
+    }
+
+    #[test]
+    fn names_are_padded_for_tables() {
+        assert_eq!(format!("{:<12}|", CommandCode::GET_RANDOM), "GetRandom   |");
+    }
+}
@@ -0,0 +1,103 @@
+//! A cap on how many commands one run sends, so a loop that never ends (like paging through a
+//! capability whose `moreData` is never clear) fails instead of hanging the boot. The app sends
+//! everything through one [`CommandLimit`], and once the cap is reached every command fails with
+//! [`TpmError::CommandLimit`].
+
+use uefi::proto::tcg::v2::Tcg;
+
+use super::{TcgTransport, TpmError, TpmTransport};
+
+/// Far more than a full run sends
+pub const DEFAULT_MAX_COMMANDS: u32 = 10_000;
+
+/// Counts the commands sent through it, and refuses to send any more after `max`
+#[derive(Debug)]
+pub struct CommandLimit<T> {
+    pub inner: T,
+    pub max: u32,
+    sent: u32,
+}
+
+impl<T> CommandLimit<T> {
+    pub fn new(inner: T, max: u32) -> Self {
+        Self {
+            inner,
+            max,
+            sent: 0,
+        }
+    }
+
+    /// How many commands have been sent so far
+    pub fn commands_sent(&self) -> u32 {
+        self.sent
+    }
+}
+
+impl<T: TpmTransport> TpmTransport for CommandLimit<T> {
+    /// Fails without sending or counting the command if the cap has been reached
+    fn transmit(&mut self, command: &[u8], response: &mut [u8]) -> Result<(), TpmError> {
+        if self.sent >= self.max {
+            return Err(TpmError::CommandLimit { limit: self.max });
+        }
+        self.sent += 1;
+        self.inner.transmit(command, response)
+    }
+}
+
+impl<T: TcgTransport> TcgTransport for CommandLimit<T> {
+    fn protocol(&mut self) -> &mut Tcg {
+        self.inner.protocol()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tpm::{
+        ResponseCode,
+        capability::list_persistent_handles,
+        mock::{MockTransport, handles_response},
+        random::get_random,
+    };
+
+    #[test]
+    fn exceeding_the_cap_fails() {
+        let mut tpm = CommandLimit::new(MockTransport::success(&[0, 1, 0xAA]), 3);
+        for _ in 0..3 {
+            assert_eq!(get_random(&mut tpm, &mut [0; 1]), Ok(1));
+        }
+        assert_eq!(
+            get_random(&mut tpm, &mut [0; 1]),
+            Err(TpmError::CommandLimit { limit: 3 })
+        );
+        // The refused command never reached the TPM
+        assert_eq!(tpm.inner.commands.len(), 3);
+        assert_eq!(tpm.commands_sent(), 3);
+    }
+
+    #[test]
+    fn failed_commands_count() {
+        let error = MockTransport::response_bytes(ResponseCode::HIERARCHY, &[]);
+        let mut tpm = CommandLimit::new(MockTransport::new(error), 1);
+        assert!(matches!(
+            get_random(&mut tpm, &mut [0; 1]),
+            Err(TpmError::Response { .. })
+        ));
+        assert_eq!(
+            get_random(&mut tpm, &mut [0; 1]),
+            Err(TpmError::CommandLimit { limit: 1 })
+        );
+    }
+
+    #[test]
+    fn stops_pagination_that_never_ends() {
+        // Always `moreData`, with the same persistent handle on every page
+        let page = handles_response(true, &[0x8100_0001]);
+        let mut tpm = CommandLimit::new(MockTransport::new(page), 100);
+        assert_eq!(
+            list_persistent_handles(&mut tpm),
+            Err(TpmError::CommandLimit { limit: 100 })
+        );
+        assert_eq!(tpm.inner.commands.len(), 100);
+    }
+}
@@ -0,0 +1,206 @@
+//! The ECC curves the TPM implements, so a curve can be checked for before creating keys on it
+
+use alloc::vec::Vec;
+
+use super::{
+    BUFFER_SIZE, CommandCode, TPM_ST_NO_SESSIONS, TpmError, TpmTransport, begin_command,
+    capability::{CapabilityItem, CapabilityIter},
+    finish_command,
+    marshal::{Reader, Writer},
+    public::{MAX_ECC_KEY_BYTES, Scheme},
+    submit,
+    tpm2b::Tpm2b,
+};
+
+/// `TPM_CAP_ECC_CURVES`
+pub const TPM_CAP_ECC_CURVES: u32 = 0x00000008;
+
+/// `TPM2B_ECC_PARAMETER`
+pub type EccParameter = Tpm2b<MAX_ECC_KEY_BYTES>;
+
+/// A `TPM_ECC_CURVE`, like [`TPM_ECC_NIST_P256`](super::public::TPM_ECC_NIST_P256)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct EccCurve(pub u16);
+
+impl CapabilityItem for EccCurve {
+    const CAPABILITY: u32 = TPM_CAP_ECC_CURVES;
+
+    fn read(reader: &mut Reader<'_>) -> Result<Self, TpmError> {
+        Ok(Self(reader.u16()?))
+    }
+
+    fn next_property(&self) -> u32 {
+        self.0 as u32 + 1
+    }
+}
+
+/// `TPMS_ALGORITHM_DETAIL_ECC`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EccCurveDetails {
+    pub curve_id: u16,
+    pub key_size: u16,
+    pub kdf: Scheme,
+    pub sign: Scheme,
+    /// The field's prime
+    pub p: EccParameter,
+    pub a: EccParameter,
+    pub b: EccParameter,
+    /// The base point
+    pub g_x: EccParameter,
+    pub g_y: EccParameter,
+    /// The base point's order
+    pub n: EccParameter,
+    /// The cofactor
+    pub h: EccParameter,
+}
+
+impl EccCurveDetails {
+    pub fn read(reader: &mut Reader<'_>) -> Result<Self, TpmError> {
+        Ok(Self {
+            curve_id: reader.u16()?,
+            key_size: reader.u16()?,
+            kdf: Scheme::read(reader)?,
+            sign: Scheme::read(reader)?,
+            p: Tpm2b::read(reader)?,
+            a: Tpm2b::read(reader)?,
+            b: Tpm2b::read(reader)?,
+            g_x: Tpm2b::read(reader)?,
+            g_y: Tpm2b::read(reader)?,
+            n: Tpm2b::read(reader)?,
+            h: Tpm2b::read(reader)?,
+        })
+    }
+}
+
+/// `TPM2_ECC_Parameters`. Fails with `TPM_RC_CURVE` for a curve the TPM doesn't implement.
+pub fn ecc_parameters(
+    tcg: &mut dyn TpmTransport,
+    curve_id: u16,
+) -> Result<EccCurveDetails, TpmError> {
+    let mut command_buffer = [0; 12];
+    let mut writer = Writer::new(&mut command_buffer);
+    begin_command(&mut writer, TPM_ST_NO_SESSIONS, CommandCode::ECC_PARAMETERS)?;
+    writer.u16(curve_id)?;
+    let mut response_buffer = [0; BUFFER_SIZE];
+    let response = submit(tcg, finish_command(writer), &mut response_buffer)?;
+    EccCurveDetails::read(&mut response.parameters()?)
+}
+
+/// Every curve the TPM implements, in order
+pub fn supported_curves(tcg: &mut dyn TpmTransport) -> Result<Vec<EccCurve>, TpmError> {
+    CapabilityIter::new(tcg, 0).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tpm::{
+        ResponseCode, alg::TPM_ALG_NULL, mock::MockTransport, public::TPM_ECC_NIST_P256,
+    };
+
+    const P256_P: [u8; 32] = [
+        0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0xff, 0xff,
+    ];
+    const P256_A: [u8; 32] = [
+        0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0xff, 0xfc,
+    ];
+    const P256_B: [u8; 32] = [
+        0x5a, 0xc6, 0x35, 0xd8, 0xaa, 0x3a, 0x93, 0xe7, 0xb3, 0xeb, 0xbd, 0x55, 0x76, 0x98, 0x86,
+        0xbc, 0x65, 0x1d, 0x06, 0xb0, 0xcc, 0x53, 0xb0, 0xf6, 0x3b, 0xce, 0x3c, 0x3e, 0x27, 0xd2,
+        0x60, 0x4b,
+    ];
+    const P256_GX: [u8; 32] = [
+        0x6b, 0x17, 0xd1, 0xf2, 0xe1, 0x2c, 0x42, 0x47, 0xf8, 0xbc, 0xe6, 0xe5, 0x63, 0xa4, 0x40,
+        0xf2, 0x77, 0x03, 0x7d, 0x81, 0x2d, 0xeb, 0x33, 0xa0, 0xf4, 0xa1, 0x39, 0x45, 0xd8, 0x98,
+        0xc2, 0x96,
+    ];
+    const P256_GY: [u8; 32] = [
+        0x4f, 0xe3, 0x42, 0xe2, 0xfe, 0x1a, 0x7f, 0x9b, 0x8e, 0xe7, 0xeb, 0x4a, 0x7c, 0x0f, 0x9e,
+        0x16, 0x2b, 0xce, 0x33, 0x57, 0x6b, 0x31, 0x5e, 0xce, 0xcb, 0xb6, 0x40, 0x68, 0x37, 0xbf,
+        0x51, 0xf5,
+    ];
+    const P256_N: [u8; 32] = [
+        0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+        0xff, 0xbc, 0xe6, 0xfa, 0xad, 0xa7, 0x17, 0x9e, 0x84, 0xf3, 0xb9, 0xca, 0xc2, 0xfc, 0x63,
+        0x25, 0x51,
+    ];
+
+    /// P-256's `TPMS_ALGORITHM_DETAIL_ECC` as a TPM returns it, with no KDF or signing scheme
+    fn p256_details() -> Vec<u8> {
+        let mut parameters = Vec::from([0x00, 0x03, 0x01, 0x00, 0x00, 0x10, 0x00, 0x10]);
+        for parameter in [P256_P, P256_A, P256_B, P256_GX, P256_GY, P256_N] {
+            parameters.extend_from_slice(&[0x00, 0x20]);
+            parameters.extend_from_slice(&parameter);
+        }
+        parameters.extend_from_slice(&[0x00, 0x01, 0x01]);
+        parameters
+    }
+
+    /// `TPM2_GetCapability(TPM_CAP_ECC_CURVES)` answering with `curves`
+    fn curves_response(more_data: bool, curves: &[u16]) -> Vec<u8> {
+        let mut parameters = Vec::from([more_data as u8]);
+        parameters.extend_from_slice(&TPM_CAP_ECC_CURVES.to_be_bytes());
+        parameters.extend_from_slice(&(curves.len() as u32).to_be_bytes());
+        for curve in curves {
+            parameters.extend_from_slice(&curve.to_be_bytes());
+        }
+        MockTransport::response_bytes(ResponseCode::SUCCESS, &parameters)
+    }
+
+    #[test]
+    fn p256_parameters_parse() {
+        let mut tcg = MockTransport::default().expect(
+            [0x80, 0x01, 0, 0, 0, 0x0c, 0, 0, 0x01, 0x78, 0x00, 0x03],
+            MockTransport::response_bytes(ResponseCode::SUCCESS, &p256_details()),
+        );
+        let details = ecc_parameters(&mut tcg, TPM_ECC_NIST_P256).unwrap();
+        tcg.assert_done();
+        assert_eq!(details.curve_id, TPM_ECC_NIST_P256);
+        assert_eq!(details.key_size, 256);
+        assert_eq!(details.kdf, Scheme::NULL);
+        assert_eq!(details.sign.scheme, TPM_ALG_NULL);
+        assert_eq!(details.p.as_slice(), P256_P);
+        assert_eq!(details.a.as_slice(), P256_A);
+        assert_eq!(details.b.as_slice(), P256_B);
+        assert_eq!(details.g_x.as_slice(), P256_GX);
+        assert_eq!(details.g_y.as_slice(), P256_GY);
+        assert_eq!(details.n.as_slice(), P256_N);
+        assert_eq!(details.h.as_slice(), [1]);
+    }
+
+    #[test]
+    fn truncated_parameters_fail() {
+        let details = p256_details();
+        let mut tcg = MockTransport::success(&details[..details.len() - 1]);
+        assert_eq!(
+            ecc_parameters(&mut tcg, TPM_ECC_NIST_P256).unwrap_err(),
+            TpmError::UnexpectedEnd
+        );
+    }
+
+    #[test]
+    fn curves_are_listed_across_pages() {
+        let mut tcg = MockTransport::default()
+            .expect(
+                [
+                    0x80, 0x01, 0, 0, 0, 0x16, 0, 0, 0x01, 0x7a, 0, 0, 0, 0x08, 0, 0, 0, 0,
+                ],
+                curves_response(true, &[0x0003]),
+            )
+            .expect(
+                [
+                    0x80, 0x01, 0, 0, 0, 0x16, 0, 0, 0x01, 0x7a, 0, 0, 0, 0x08, 0, 0, 0, 0x04,
+                ],
+                curves_response(false, &[0x0004, 0x0010]),
+            );
+        assert_eq!(
+            supported_curves(&mut tcg).unwrap(),
+            [EccCurve(0x0003), EccCurve(0x0004), EccCurve(0x0010)]
+        );
+        tcg.assert_done();
+    }
+}
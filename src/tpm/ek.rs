@@ -0,0 +1,147 @@
+//! The endorsement key (EK), created from the templates in the TCG EK Credential Profile so it
+//! matches the EK certificate the manufacturer provisioned
+
+use uefi::proto::tcg::AlgorithmId;
+
+use super::{
+    TPM_RH_ENDORSEMENT, TpmError, TpmTransport,
+    alg::{TPM_ALG_AES, TPM_ALG_CFB},
+    auth::AuthCommand,
+    object::{CreatedPrimary, create_primary},
+    public::{
+        PublicId, PublicParameters, Scheme, SymDefObject, TPM_ECC_NIST_P256,
+        TPMA_OBJECT_ADMIN_WITH_POLICY, TPMA_OBJECT_DECRYPT, TPMA_OBJECT_FIXED_PARENT,
+        TPMA_OBJECT_FIXED_TPM, TPMA_OBJECT_RESTRICTED, TPMA_OBJECT_SENSITIVE_DATA_ORIGIN,
+        TpmtPublic,
+    },
+    tpm2b::Tpm2b,
+};
+
+/// `PolicySecret(TPM_RH_ENDORSEMENT)` with SHA-256, the policy of the default EK templates. Using
+/// the EK requires proving knowledge of the endorsement hierarchy's authorization.
+pub const EK_POLICY_SHA256: [u8; 32] = [
+    0x83, 0x71, 0x97, 0x67, 0x44, 0x84, 0xB3, 0xF8, 0x1A, 0x90, 0xCC, 0x8D, 0x46, 0xA5, 0xD7, 0x24,
+    0xFD, 0x52, 0xD7, 0x6E, 0x06, 0x52, 0x0B, 0x64, 0xF2, 0xA1, 0xDA, 0x1B, 0x33, 0x14, 0x69, 0xAA,
+];
+
+/// `fixedTPM | fixedParent | sensitiveDataOrigin | adminWithPolicy | restricted | decrypt`
+pub const EK_ATTRIBUTES: u32 = TPMA_OBJECT_FIXED_TPM
+    | TPMA_OBJECT_FIXED_PARENT
+    | TPMA_OBJECT_SENSITIVE_DATA_ORIGIN
+    | TPMA_OBJECT_ADMIN_WITH_POLICY
+    | TPMA_OBJECT_RESTRICTED
+    | TPMA_OBJECT_DECRYPT;
+
+const EK_SYMMETRIC: SymDefObject = SymDefObject {
+    algorithm: TPM_ALG_AES,
+    key_bits: 128,
+    mode: TPM_ALG_CFB,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EkAlgorithm {
+    /// Template L-1
+    Rsa2048,
+    /// Template L-2
+    EccNistP256,
+}
+
+/// The profile's low range template for `algorithm`
+pub fn ek_template(algorithm: EkAlgorithm) -> TpmtPublic {
+    let (parameters, unique) = match algorithm {
+        EkAlgorithm::Rsa2048 => (
+            PublicParameters::Rsa {
+                symmetric: EK_SYMMETRIC,
+                scheme: Scheme::NULL,
+                key_bits: 2048,
+                exponent: 0,
+            },
+            PublicId::Rsa(Tpm2b::new(&[0; 256]).unwrap()),
+        ),
+        EkAlgorithm::EccNistP256 => (
+            PublicParameters::Ecc {
+                symmetric: EK_SYMMETRIC,
+                scheme: Scheme::NULL,
+                curve_id: TPM_ECC_NIST_P256,
+                kdf: Scheme::NULL,
+            },
+            PublicId::Ecc {
+                x: Tpm2b::new(&[0; 32]).unwrap(),
+                y: Tpm2b::new(&[0; 32]).unwrap(),
+            },
+        ),
+    };
+    TpmtPublic {
+        name_alg: AlgorithmId::SHA256,
+        object_attributes: EK_ATTRIBUTES,
+        auth_policy: Tpm2b::new(&EK_POLICY_SHA256).unwrap(),
+        parameters,
+        unique,
+    }
+}
+
+/// Creates the EK under the endorsement hierarchy, assuming the hierarchy has an empty password
+/// like it does unless someone has taken ownership
+pub fn create_ek(
+    tcg: &mut dyn TpmTransport,
+    algorithm: EkAlgorithm,
+) -> Result<CreatedPrimary, TpmError> {
+    create_primary(
+        tcg,
+        TPM_RH_ENDORSEMENT,
+        &AuthCommand::password(&[]),
+        &[],
+        &ek_template(algorithm),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::tpm::marshal::Writer;
+
+    /// `TPMT_PUBLIC` from the start of the profile's templates up to `unique`: the type, then
+    /// `TPM_ALG_SHA256`, `0x000300b2` and the `PolicySecret(TPM_RH_ENDORSEMENT)` policy
+    fn template_start(algorithm: u8) -> Vec<u8> {
+        let mut start = Vec::from([
+            0x00, algorithm, 0x00, 0x0b, 0x00, 0x03, 0x00, 0xb2, 0x00, 0x20,
+        ]);
+        start.extend_from_slice(&[
+            0x83, 0x71, 0x97, 0x67, 0x44, 0x84, 0xb3, 0xf8, 0x1a, 0x90, 0xcc, 0x8d, 0x46, 0xa5,
+            0xd7, 0x24, 0xfd, 0x52, 0xd7, 0x6e, 0x06, 0x52, 0x0b, 0x64, 0xf2, 0xa1, 0xda, 0x1b,
+            0x33, 0x14, 0x69, 0xaa,
+        ]);
+        // AES-128 CFB
+        start.extend_from_slice(&[0x00, 0x06, 0x00, 0x80, 0x00, 0x43]);
+        start
+    }
+
+    fn marshal(template: &TpmtPublic) -> Vec<u8> {
+        let mut buffer = [0; 512];
+        let mut writer = Writer::new(&mut buffer);
+        template.write(&mut writer).unwrap();
+        writer.into_slice().to_vec()
+    }
+
+    #[test]
+    fn rsa_template_is_l1() {
+        let mut l1 = template_start(0x01);
+        // TPM_ALG_NULL scheme, 2048 bits, the default exponent, and 256 zeros
+        l1.extend_from_slice(&[0x00, 0x10, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00]);
+        l1.extend_from_slice(&[0; 256]);
+        assert_eq!(marshal(&ek_template(EkAlgorithm::Rsa2048)), l1);
+    }
+
+    #[test]
+    fn ecc_template_is_l2() {
+        let mut l2 = template_start(0x23);
+        // TPM_ALG_NULL scheme, TPM_ECC_NIST_P256, TPM_ALG_NULL KDF, and 32 zeros for x and y
+        l2.extend_from_slice(&[0x00, 0x10, 0x00, 0x03, 0x00, 0x10, 0x00, 0x20]);
+        l2.extend_from_slice(&[0; 32]);
+        l2.extend_from_slice(&[0x00, 0x20]);
+        l2.extend_from_slice(&[0; 32]);
+        assert_eq!(marshal(&ek_template(EkAlgorithm::EccNistP256)), l2);
+    }
+}
@@ -0,0 +1,286 @@
+use core::fmt;
+
+use uefi::{Status, proto::tcg::AlgorithmId};
+
+use super::{CommandCode, ResponseCode, TpmHandle, alg};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TpmError {
+    /// The UEFI protocol call itself failed
+    Uefi(Status),
+    /// The TPM processed the command but returned an error. `command` and `handle` are filled in
+    /// by [`submit`](super::submit) and the command's wrapper, if they know them.
+    Response {
+        code: ResponseCode,
+        command: Option<CommandCode>,
+        handle: Option<TpmHandle>,
+    },
+    /// The TPM answered `TPM_RC_FAILURE`: it's in failure mode, and refuses every command but
+    /// `TPM2_GetTestResult` and `TPM2_GetCapability` until it's reset. `command` is filled in by
+    /// [`submit`](super::submit).
+    FailureMode { command: Option<CommandCode> },
+    /// The response has a TPM 1.2 tag, so the chip behind a TCG 1.2 protocol is a TPM 1.2, which
+    /// doesn't understand TPM 2.0 commands
+    Tpm12,
+    /// The response ended before everything we expected was read
+    UnexpectedEnd,
+    /// The response has a field with a value that makes no sense
+    Malformed,
+    /// The command didn't fit in the command buffer
+    CommandTooLarge,
+    /// The TPM said its response is `size` bytes, more than the `capacity` of the response buffer
+    ResponseTooLarge { size: u32, capacity: usize },
+    /// A digest we were asked to send isn't the size of its algorithm's digests
+    DigestSize { expected: usize, actual: usize },
+    /// Two digests that had to be from the same bank weren't
+    AlgorithmMismatch {
+        expected: AlgorithmId,
+        actual: AlgorithmId,
+    },
+    /// We can't hash with this algorithm, or don't know it
+    UnsupportedAlgorithm(AlgorithmId),
+    /// We didn't send `PCR_Reset` because the PCR can't be reset from locality 0
+    PcrNotResettable(u32),
+    /// A PCR index past the 32 a `TPMS_PCR_SELECTION` can hold
+    PcrOutOfRange(u32),
+    /// The TPM was still busy after we'd waited `waited_us` for it
+    Timeout { waited_us: u64 },
+    /// We didn't send the command because this run has already sent `limit`, see
+    /// [`CommandLimit`](super::command_limit::CommandLimit)
+    CommandLimit { limit: u32 },
+    /// We didn't send an authorized command because the TPM is in dictionary-attack lockout
+    InLockout {
+        failures: u32,
+        recovery_seconds: u32,
+    },
+}
+
+impl fmt::Display for TpmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Uefi(status) => write!(f, "UEFI error: {status:?}"),
+            Self::Response {
+                code,
+                command: Some(command),
+                handle,
+            } => {
+                write!(f, "TPM2_{command}(")?;
+                if let Some(handle) = handle {
+                    write!(f, "handle {handle:#x}")?;
+                }
+                write!(f, ") → {code}")
+            }
+            Self::Response { code, .. } => write!(f, "{code}"),
+            Self::FailureMode { command } => {
+                if let Some(command) = command {
+                    write!(f, "TPM2_{command}: ")?;
+                }
+                f.write_str(
+                    "the TPM is in failure mode and only answers TPM2_GetTestResult and TPM2_GetCapability. Run the self-test stage, which reads the test result, to see why",
+                )
+            }
+            Self::Tpm12 => {
+                f.write_str("the TPM is a TPM 1.2, which doesn't support TPM 2.0 commands")
+            }
+            Self::UnexpectedEnd => f.write_str("response ended unexpectedly"),
+            Self::Malformed => f.write_str("malformed response"),
+            Self::CommandTooLarge => f.write_str("command too large for buffer"),
+            Self::ResponseTooLarge { size, capacity } => {
+                write!(
+                    f,
+                    "{size} byte response too large for {capacity} byte buffer"
+                )
+            }
+            Self::DigestSize { expected, actual } => {
+                write!(f, "digest is {actual} bytes instead of {expected}")
+            }
+            Self::AlgorithmMismatch { expected, actual } => write!(
+                f,
+                "{} digest where a {} one was expected",
+                AlgorithmName(*actual),
+                AlgorithmName(*expected)
+            ),
+            Self::UnsupportedAlgorithm(algorithm) => {
+                write!(
+                    f,
+                    "unsupported hash algorithm {}",
+                    AlgorithmName(*algorithm)
+                )
+            }
+            Self::PcrNotResettable(pcr_index) => write!(
+                f,
+                "PCR {pcr_index} can't be reset from locality 0, only PCR 16 and 23 usually can"
+            ),
+            Self::PcrOutOfRange(pcr_index) => write!(
+                f,
+                "PCR {pcr_index} is out of range, PCR selections only go up to PCR 31"
+            ),
+            Self::Timeout { waited_us } => {
+                write!(f, "the TPM was still busy after {} ms", waited_us / 1000)
+            }
+            Self::CommandLimit { limit } => write!(
+                f,
+                "gave up after sending {limit} TPM commands, which probably means a loop that never ends. Raise it with --max-commands"
+            ),
+            Self::InLockout {
+                failures,
+                recovery_seconds,
+            } => write!(
+                f,
+                "TPM is in dictionary-attack lockout ({failures} failures, recovery in {recovery_seconds} seconds)"
+            ),
+        }
+    }
+}
+
+/// The spec's name for an algorithm, or its number
+struct AlgorithmName(AlgorithmId);
+
+impl fmt::Display for AlgorithmName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match alg::name(self.0) {
+            Some(name) => f.write_str(name),
+            None => write!(f, "{:#06x}", self.0.0),
+        }
+    }
+}
+
+impl TpmError {
+    /// Records which command an error response was for
+    pub fn in_command(self, command: CommandCode) -> Self {
+        match self {
+            Self::Response { code, handle, .. } => Self::Response {
+                code,
+                command: Some(command),
+                handle,
+            },
+            Self::FailureMode { .. } => Self::FailureMode {
+                command: Some(command),
+            },
+            other => other,
+        }
+    }
+
+    /// Records the handle an error response was about, for commands that take one
+    pub fn with_handle(self, handle: TpmHandle) -> Self {
+        match self {
+            Self::Response { code, command, .. } => Self::Response {
+                code,
+                command,
+                handle: Some(handle),
+            },
+            other => other,
+        }
+    }
+
+    /// The response code, if the TPM returned an error
+    pub fn response_code(&self) -> Option<ResponseCode> {
+        match self {
+            Self::Response { code, .. } => Some(*code),
+            Self::FailureMode { .. } => Some(ResponseCode::FAILURE),
+            _ => None,
+        }
+    }
+}
+
+impl From<uefi::Error> for TpmError {
+    fn from(error: uefi::Error) -> Self {
+        Self::Uefi(error.status())
+    }
+}
+
+/// For an app's exit status, so whatever started it can tell what kind of failure it was
+impl From<TpmError> for Status {
+    fn from(error: TpmError) -> Self {
+        match error {
+            TpmError::Uefi(status) => status,
+            TpmError::Response { code, .. }
+                if [
+                    ResponseCode::AUTH_FAIL,
+                    ResponseCode::BAD_AUTH,
+                    ResponseCode::POLICY_FAIL,
+                    ResponseCode::LOCKOUT,
+                ]
+                .contains(&code.base()) =>
+            {
+                Status::SECURITY_VIOLATION
+            }
+            TpmError::InLockout { .. } | TpmError::PcrNotResettable(_) => {
+                Status::SECURITY_VIOLATION
+            }
+            TpmError::UnexpectedEnd | TpmError::Malformed => Status::PROTOCOL_ERROR,
+            TpmError::Timeout { .. } => Status::TIMEOUT,
+            TpmError::CommandLimit { .. } => Status::ABORTED,
+            TpmError::ResponseTooLarge { .. } => Status::BUFFER_TOO_SMALL,
+            TpmError::Tpm12 | TpmError::UnsupportedAlgorithm(_) => Status::UNSUPPORTED,
+            TpmError::DigestSize { .. }
+            | TpmError::AlgorithmMismatch { .. }
+            | TpmError::PcrOutOfRange(_) => Status::INVALID_PARAMETER,
+            TpmError::Response { .. }
+            | TpmError::FailureMode { .. }
+            | TpmError::CommandTooLarge => Status::DEVICE_ERROR,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(code: u32) -> TpmError {
+        TpmError::Response {
+            code: ResponseCode(code),
+            command: None,
+            handle: None,
+        }
+    }
+
+    #[test]
+    fn authorization_failures_are_security_violations() {
+        // `TPM_RC_POLICY_FAIL` for session 1, `TPM_RC_AUTH_FAIL` for session 1, `TPM_RC_BAD_AUTH`
+        // for parameter 1 and `TPM_RC_LOCKOUT`
+        for code in [0x99D, 0x98E, 0x1E2, 0x921] {
+            assert_eq!(
+                Status::from(response(code)),
+                Status::SECURITY_VIOLATION,
+                "{code:#x}"
+            );
+        }
+    }
+
+    #[test]
+    fn other_response_codes_are_device_errors() {
+        // `TPM_RC_HANDLE` for handle 1 and `TPM_RC_INITIALIZE`
+        for code in [0x18B, 0x100] {
+            assert_eq!(
+                Status::from(response(code)),
+                Status::DEVICE_ERROR,
+                "{code:#x}"
+            );
+        }
+    }
+
+    #[test]
+    fn failure_mode_is_a_device_error() {
+        assert_eq!(
+            Status::from(TpmError::FailureMode { command: None }),
+            Status::DEVICE_ERROR
+        );
+    }
+
+    #[test]
+    fn algorithms_are_named() {
+        let mismatch = TpmError::AlgorithmMismatch {
+            expected: AlgorithmId::SHA256,
+            actual: AlgorithmId::SHA1,
+        };
+        assert_eq!(
+            format!("{mismatch}"),
+            "SHA1 digest where a SHA256 one was expected"
+        );
+        assert_eq!(
+            format!("{}", TpmError::UnsupportedAlgorithm(AlgorithmId(0x1234))),
+            "unsupported hash algorithm 0x1234"
+        );
+    }
+}
@@ -0,0 +1,111 @@
+//! Commands that change a hierarchy itself rather than the objects in it
+
+use super::{
+    BUFFER_SIZE, CommandCode, TPM_RH_PLATFORM, TPM_ST_SESSIONS, TpmError, TpmTransport,
+    auth::{AuthCommand, write_auth_area},
+    begin_command, finish_command,
+    marshal::Writer,
+    submit,
+};
+
+/// Passed to [`change_eps`] and [`change_pps`] to say the caller means it: every key derived from
+/// the old seed is gone for good, including the EK its certificate was issued for
+#[derive(Debug, Clone, Copy)]
+pub struct ConfirmSeedChange(());
+
+impl ConfirmSeedChange {
+    pub const KEYS_WILL_BE_LOST: Self = Self(());
+}
+
+fn change_seed(
+    tcg: &mut dyn TpmTransport,
+    code: CommandCode,
+    platform_auth: &AuthCommand<'_>,
+) -> Result<(), TpmError> {
+    let mut command_buffer = [0; BUFFER_SIZE];
+    let mut writer = Writer::new(&mut command_buffer);
+    begin_command(&mut writer, TPM_ST_SESSIONS, code)?;
+    writer.u32(TPM_RH_PLATFORM)?;
+    write_auth_area(&mut writer, core::slice::from_ref(platform_auth))?;
+    let mut response_buffer = [0; BUFFER_SIZE];
+    submit(tcg, finish_command(writer), &mut response_buffer)
+        .map_err(|e| e.with_handle(TPM_RH_PLATFORM))?;
+    Ok(())
+}
+
+/// `TPM2_ChangeEPS`: replaces the endorsement primary seed, so the EK and every other key in the
+/// endorsement hierarchy can never be created again. Fails with `TPM_RC_HIERARCHY` if the
+/// firmware has disabled the platform hierarchy, which it usually has by the time apps run.
+pub fn change_eps(
+    tcg: &mut dyn TpmTransport,
+    platform_auth: &AuthCommand<'_>,
+    _confirm: ConfirmSeedChange,
+) -> Result<(), TpmError> {
+    change_seed(tcg, CommandCode::CHANGE_EPS, platform_auth)
+}
+
+/// `TPM2_ChangePPS`: replaces the platform primary seed, like [`change_eps`] does for the
+/// endorsement hierarchy
+pub fn change_pps(
+    tcg: &mut dyn TpmTransport,
+    platform_auth: &AuthCommand<'_>,
+    _confirm: ConfirmSeedChange,
+) -> Result<(), TpmError> {
+    change_seed(tcg, CommandCode::CHANGE_PPS, platform_auth)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tpm::{ResponseCode, mock::MockTransport};
+
+    /// The command for `code` with an empty platform password
+    fn change_seed_command(code: u8) -> [u8; 27] {
+        [
+            0x80, 0x02, 0x00, 0x00, 0x00, 0x1b, 0x00, 0x00, 0x01, code, 0x40, 0x00, 0x00, 0x0c,
+            0x00, 0x00, 0x00, 0x09, 0x40, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00, 0x00, 0x00,
+        ]
+    }
+
+    #[test]
+    fn change_eps_encoding() {
+        let mut tcg = MockTransport::success(&[]);
+        change_eps(
+            &mut tcg,
+            &AuthCommand::password(&[]),
+            ConfirmSeedChange::KEYS_WILL_BE_LOST,
+        )
+        .unwrap();
+        assert_eq!(tcg.commands, [change_seed_command(0x24)]);
+    }
+
+    #[test]
+    fn change_pps_encoding() {
+        let mut tcg = MockTransport::success(&[]);
+        change_pps(
+            &mut tcg,
+            &AuthCommand::password(&[]),
+            ConfirmSeedChange::KEYS_WILL_BE_LOST,
+        )
+        .unwrap();
+        assert_eq!(tcg.commands, [change_seed_command(0x25)]);
+    }
+
+    #[test]
+    fn disabled_platform_hierarchy_names_the_handle() {
+        let mut tcg =
+            MockTransport::new(MockTransport::response_bytes(ResponseCode::HIERARCHY, &[]));
+        assert_eq!(
+            change_eps(
+                &mut tcg,
+                &AuthCommand::password(&[]),
+                ConfirmSeedChange::KEYS_WILL_BE_LOST,
+            ),
+            Err(TpmError::Response {
+                code: ResponseCode::HIERARCHY,
+                command: Some(CommandCode::CHANGE_EPS),
+                handle: Some(TPM_RH_PLATFORM),
+            })
+        );
+    }
+}
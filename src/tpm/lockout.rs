@@ -0,0 +1,305 @@
+//! The dictionary-attack (DA) lockout state, cached so the auth path can check it without talking
+//! to the TPM. Every failed authorization while locked out extends the lockout, so we don't send
+//! any auth-bearing command while the TPM says it is locked out.
+
+use core::{
+    fmt,
+    sync::atomic::{AtomicBool, AtomicU32, Ordering},
+};
+
+use super::{
+    BUFFER_SIZE, CommandCode, TPM_RH_LOCKOUT, TPM_ST_SESSIONS, TpmError, TpmTransport,
+    auth::{AuthCommand, write_auth_area},
+    begin_command,
+    capability::{
+        TPM_PT_LOCKOUT_COUNTER, TPM_PT_LOCKOUT_INTERVAL, TPM_PT_LOCKOUT_RECOVERY,
+        TPM_PT_MAX_AUTH_FAIL, TPM_PT_PERMANENT, TPMA_PERMANENT_IN_LOCKOUT, TaggedProperty,
+        get_tpm_properties,
+    },
+    finish_command,
+    marshal::Writer,
+    submit,
+};
+
+/// What [`refresh`] last saw, and `--force-auth`
+struct Cache {
+    in_lockout: AtomicBool,
+    failures: AtomicU32,
+    interval: AtomicU32,
+    force_auth: AtomicBool,
+}
+
+impl Cache {
+    const fn new() -> Self {
+        Self {
+            in_lockout: AtomicBool::new(false),
+            failures: AtomicU32::new(0),
+            interval: AtomicU32::new(0),
+            force_auth: AtomicBool::new(false),
+        }
+    }
+}
+
+#[cfg(not(test))]
+fn cache() -> &'static Cache {
+    static CACHE: Cache = Cache::new();
+    &CACHE
+}
+
+/// One per test thread, so a test that puts the TPM in lockout doesn't fail the tests running
+/// next to it
+#[cfg(test)]
+fn cache() -> &'static Cache {
+    std::thread_local! {
+        static CACHE: &'static Cache = std::boxed::Box::leak(std::boxed::Box::new(Cache::new()));
+    }
+    CACHE.with(|cache| *cache)
+}
+
+/// The dictionary-attack state, from `TPM_PT_PERMANENT` and `TPM_PT_LOCKOUT_*`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LockoutStatus {
+    /// Failed authorizations so far
+    pub counter: u32,
+    /// Failures before the TPM locks out
+    pub max_tries: u32,
+    /// Seconds until the counter goes down by one
+    pub recovery_time: u32,
+    /// Seconds after a failed lockout authorization before `lockoutAuth` can be tried again
+    pub lockout_recovery: u32,
+    /// `TPMA_PERMANENT.inLockout`
+    pub in_lockout: bool,
+}
+
+impl fmt::Display for LockoutStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} of {} failed authorizations, one forgiven every {} s, lockout auth retry after {} s",
+            self.counter, self.max_tries, self.recovery_time, self.lockout_recovery
+        )?;
+        if self.in_lockout {
+            f.write_str(", in lockout")?;
+        }
+        Ok(())
+    }
+}
+
+/// Reads the dictionary-attack state. Doesn't send anything that needs authorization, so it can't
+/// make the lockout worse.
+pub fn get_lockout_status(tcg: &mut dyn TpmTransport) -> Result<LockoutStatus, TpmError> {
+    // TPM_PT_PERMANENT through TPM_PT_LOCKOUT_RECOVERY
+    let mut properties =
+        [TaggedProperty::default(); (TPM_PT_LOCKOUT_RECOVERY - TPM_PT_PERMANENT + 1) as usize];
+    let count = get_tpm_properties(tcg, TPM_PT_PERMANENT, &mut properties)?;
+    let mut status = LockoutStatus::default();
+    for property in &properties[..count] {
+        match property.property {
+            TPM_PT_PERMANENT => status.in_lockout = property.value & TPMA_PERMANENT_IN_LOCKOUT != 0,
+            TPM_PT_LOCKOUT_COUNTER => status.counter = property.value,
+            TPM_PT_MAX_AUTH_FAIL => status.max_tries = property.value,
+            TPM_PT_LOCKOUT_INTERVAL => status.recovery_time = property.value,
+            TPM_PT_LOCKOUT_RECOVERY => status.lockout_recovery = property.value,
+            _ => {}
+        }
+    }
+    Ok(status)
+}
+
+/// Re-reads the lockout state with [`get_lockout_status`] and caches it for [`check`]
+pub fn refresh(tcg: &mut dyn TpmTransport) -> Result<LockoutStatus, TpmError> {
+    let status = get_lockout_status(tcg)?;
+    let cache = cache();
+    cache.in_lockout.store(status.in_lockout, Ordering::Relaxed);
+    cache.failures.store(status.counter, Ordering::Relaxed);
+    cache
+        .interval
+        .store(status.recovery_time, Ordering::Relaxed);
+    Ok(status)
+}
+
+/// Clears the failure counter with `TPM2_DictionaryAttackLockReset`, if there's something to clear
+/// and it's safe to try. Returns whether it was sent.
+///
+/// Nothing is sent if the counter is already zero, or if the TPM is in lockout: the reset would
+/// fail, and a failed lockout authorization means waiting `lockout_recovery` seconds before
+/// trying again.
+pub fn reset_da_lockout_if_safe(
+    tcg: &mut dyn TpmTransport,
+    lockout_auth: &[u8],
+) -> Result<bool, TpmError> {
+    let status = refresh(tcg)?;
+    if status.counter == 0 {
+        return Ok(false);
+    }
+    if status.in_lockout {
+        return Err(TpmError::InLockout {
+            failures: status.counter,
+            recovery_seconds: status.recovery_time,
+        });
+    }
+    let mut command_buffer = [0; BUFFER_SIZE];
+    let mut writer = Writer::new(&mut command_buffer);
+    begin_command(
+        &mut writer,
+        TPM_ST_SESSIONS,
+        CommandCode::DICTIONARY_ATTACK_LOCK_RESET,
+    )?;
+    writer.u32(TPM_RH_LOCKOUT)?;
+    write_auth_area(&mut writer, &[AuthCommand::password(lockout_auth)])?;
+    let mut response_buffer = [0; BUFFER_SIZE];
+    submit(tcg, finish_command(writer), &mut response_buffer)
+        .map_err(|e| e.with_handle(TPM_RH_LOCKOUT))?;
+    refresh(tcg)?;
+    Ok(true)
+}
+
+/// Send auth-bearing commands even when the TPM is in lockout (`--force-auth`)
+pub fn set_force_auth(force: bool) {
+    cache().force_auth.store(force, Ordering::Relaxed);
+}
+
+/// Fails with [`TpmError::InLockout`] if the last [`refresh`] saw the TPM in lockout
+pub fn check() -> Result<(), TpmError> {
+    let cache = cache();
+    if cache.in_lockout.load(Ordering::Relaxed) && !cache.force_auth.load(Ordering::Relaxed) {
+        Err(TpmError::InLockout {
+            failures: cache.failures.load(Ordering::Relaxed),
+            recovery_seconds: cache.interval.load(Ordering::Relaxed),
+        })
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uefi::proto::tcg::AlgorithmId;
+
+    use super::*;
+    use crate::tpm::{
+        auth::AuthArea,
+        capability::TPM_CAP_TPM_PROPERTIES,
+        mock::{MockTransport, get_capability_command, properties_response, success_response},
+        pcr::{DEBUG_PCR, pcr_extend},
+    };
+
+    /// `TPM2_GetCapability(TPM_CAP_TPM_PROPERTIES, TPM_PT_PERMANENT, 18)`
+    fn lockout_status_command() -> Vec<u8> {
+        [
+            get_capability_command(TPM_CAP_TPM_PROPERTIES, TPM_PT_PERMANENT),
+            18u32.to_be_bytes().to_vec(),
+        ]
+        .concat()
+    }
+
+    /// 3 failures of 32, in lockout
+    fn in_lockout_response() -> Vec<u8> {
+        properties_response(&[
+            (TPM_PT_PERMANENT, TPMA_PERMANENT_IN_LOCKOUT),
+            (TPM_PT_LOCKOUT_COUNTER, 3),
+            (TPM_PT_MAX_AUTH_FAIL, 32),
+            (TPM_PT_LOCKOUT_INTERVAL, 600),
+            (TPM_PT_LOCKOUT_RECOVERY, 86400),
+        ])
+    }
+
+    /// The start of `TPM2_PCR_Extend(DEBUG_PCR)`
+    const PCR_EXTEND: [u8; 14] = [
+        0x80,
+        0x02,
+        0,
+        0,
+        0,
+        0x35,
+        0,
+        0,
+        0x01,
+        0x82,
+        0,
+        0,
+        0,
+        DEBUG_PCR as u8,
+    ];
+
+    fn extend(tcg: &mut MockTransport) -> Result<(), TpmError> {
+        pcr_extend(tcg, DEBUG_PCR, &[(AlgorithmId::SHA1, &[0; 20])])
+    }
+
+    #[test]
+    fn status_is_read_from_the_properties() {
+        let mut tcg =
+            MockTransport::default().expect(lockout_status_command(), in_lockout_response());
+        let status = refresh(&mut tcg).unwrap();
+        tcg.assert_done();
+        assert_eq!(
+            status,
+            LockoutStatus {
+                counter: 3,
+                max_tries: 32,
+                recovery_time: 600,
+                lockout_recovery: 86400,
+                in_lockout: true,
+            }
+        );
+        assert_eq!(
+            status.to_string(),
+            "3 of 32 failed authorizations, one forgiven every 600 s, lockout auth retry after \
+             86400 s, in lockout"
+        );
+    }
+
+    #[test]
+    fn lockout_stops_auth_before_anything_is_sent() {
+        let mut tcg =
+            MockTransport::default().expect(lockout_status_command(), in_lockout_response());
+        refresh(&mut tcg).unwrap();
+        let in_lockout = Err(TpmError::InLockout {
+            failures: 3,
+            recovery_seconds: 600,
+        });
+        let mut buffer = [0; 16];
+        assert_eq!(
+            write_auth_area(&mut Writer::new(&mut buffer), &[AuthCommand::password(&[])]),
+            in_lockout
+        );
+        assert_eq!(
+            AuthArea::default()
+                .push(AuthCommand::password(&[]))
+                .marshal()
+                .err(),
+            in_lockout.err()
+        );
+        assert_eq!(extend(&mut tcg), in_lockout);
+        assert_eq!(tcg.commands.len(), 1);
+    }
+
+    #[test]
+    fn force_auth_lets_commands_through() {
+        let mut tcg = MockTransport::default()
+            .expect(lockout_status_command(), in_lockout_response())
+            .expect(PCR_EXTEND, success_response());
+        refresh(&mut tcg).unwrap();
+        set_force_auth(true);
+        let extended = extend(&mut tcg);
+        set_force_auth(false);
+        assert_eq!(extended, Ok(()));
+        tcg.assert_done();
+    }
+
+    #[test]
+    fn leaving_lockout_lets_commands_through() {
+        let mut tcg = MockTransport::default()
+            .expect(lockout_status_command(), in_lockout_response())
+            .expect(
+                lockout_status_command(),
+                properties_response(&[(TPM_PT_PERMANENT, 0), (TPM_PT_LOCKOUT_COUNTER, 3)]),
+            )
+            .expect(PCR_EXTEND, success_response());
+        refresh(&mut tcg).unwrap();
+        assert!(check().is_err());
+        refresh(&mut tcg).unwrap();
+        assert_eq!(extend(&mut tcg), Ok(()));
+        tcg.assert_done();
+    }
+}
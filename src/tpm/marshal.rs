@@ -0,0 +1,407 @@
+use zerocopy::{FromBytes, Immutable, IntoBytes, KnownLayout, Unaligned};
+
+use super::TpmError;
+
+/// Big-endian integers for the fields of fixed-layout structures read with [`Reader::fixed`]
+pub use zerocopy::byteorder::big_endian::{U16, U32, U64};
+
+/// Reads big-endian TPM structures out of a byte slice
+#[derive(Debug, Clone)]
+pub struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], TpmError> {
+        let bytes = self
+            .data
+            .get(self.position..)
+            .and_then(|rest| rest.get(..len))
+            .ok_or(TpmError::UnexpectedEnd)?;
+        self.position += len;
+        Ok(bytes)
+    }
+
+    pub fn array<const N: usize>(&mut self) -> Result<[u8; N], TpmError> {
+        Ok(self.bytes(N)?.try_into().unwrap())
+    }
+
+    pub fn u8(&mut self) -> Result<u8, TpmError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    pub fn u16(&mut self) -> Result<u16, TpmError> {
+        Ok(u16::from_be_bytes(self.array()?))
+    }
+
+    pub fn u32(&mut self) -> Result<u32, TpmError> {
+        Ok(u32::from_be_bytes(self.array()?))
+    }
+
+    pub fn u64(&mut self) -> Result<u64, TpmError> {
+        Ok(u64::from_be_bytes(self.array()?))
+    }
+
+    /// A structure whose layout is fixed, like `TPMS_CLOCK_INFO`, borrowed from the data instead
+    /// of read field by field. `T` has no padding or alignment, so it's exactly the next
+    /// `size_of::<T>()` bytes.
+    pub fn fixed<T: FromBytes + KnownLayout + Immutable + Unaligned>(
+        &mut self,
+    ) -> Result<&'a T, TpmError> {
+        T::ref_from_bytes(self.bytes(size_of::<T>())?).map_err(|_| TpmError::Malformed)
+    }
+
+    /// UEFI and event log structures are little-endian, unlike TPM structures
+    pub fn u16_le(&mut self) -> Result<u16, TpmError> {
+        Ok(u16::from_le_bytes(self.array()?))
+    }
+
+    pub fn u32_le(&mut self) -> Result<u32, TpmError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    pub fn u64_le(&mut self) -> Result<u64, TpmError> {
+        Ok(u64::from_le_bytes(self.array()?))
+    }
+
+    /// A `TPM2B_*`: a `u16` size followed by that many bytes
+    pub fn tpm2b(&mut self) -> Result<&'a [u8], TpmError> {
+        let size = self.u16()?;
+        self.bytes(size as usize)
+    }
+
+    /// Everything that hasn't been read yet
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.data[self.position..]
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.position == self.data.len()
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+}
+
+/// Writes big-endian TPM structures into a fixed buffer
+#[derive(Debug)]
+pub struct Writer<'a> {
+    buffer: &'a mut [u8],
+    len: usize,
+}
+
+impl<'a> Writer<'a> {
+    pub fn new(buffer: &'a mut [u8]) -> Self {
+        Self { buffer, len: 0 }
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) -> Result<(), TpmError> {
+        self.buffer
+            .get_mut(self.len..self.len + bytes.len())
+            .ok_or(TpmError::CommandTooLarge)?
+            .copy_from_slice(bytes);
+        self.len += bytes.len();
+        Ok(())
+    }
+
+    pub fn u8(&mut self, value: u8) -> Result<(), TpmError> {
+        self.bytes(&[value])
+    }
+
+    pub fn u16(&mut self, value: u16) -> Result<(), TpmError> {
+        self.bytes(&value.to_be_bytes())
+    }
+
+    pub fn u32(&mut self, value: u32) -> Result<(), TpmError> {
+        self.bytes(&value.to_be_bytes())
+    }
+
+    pub fn u64(&mut self, value: u64) -> Result<(), TpmError> {
+        self.bytes(&value.to_be_bytes())
+    }
+
+    /// The bytes of a structure [`Reader::fixed`] reads
+    pub fn fixed<T: IntoBytes + Immutable>(&mut self, value: &T) -> Result<(), TpmError> {
+        self.bytes(value.as_bytes())
+    }
+
+    pub fn tpm2b(&mut self, bytes: &[u8]) -> Result<(), TpmError> {
+        let size = u16::try_from(bytes.len()).map_err(|_| TpmError::CommandTooLarge)?;
+        self.u16(size)?;
+        self.bytes(bytes)
+    }
+
+    /// Overwrites a `u16` that was already written, for size fields that are only known later
+    pub fn patch_u16(&mut self, offset: usize, value: u16) {
+        self.buffer[offset..offset + 2].copy_from_slice(&value.to_be_bytes());
+    }
+
+    /// Overwrites a `u32` that was already written, for size fields that are only known later
+    pub fn patch_u32(&mut self, offset: usize, value: u32) {
+        self.buffer[offset..offset + 4].copy_from_slice(&value.to_be_bytes());
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn into_slice(self) -> &'a [u8] {
+        &self.buffer[..self.len]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use uefi::proto::tcg::AlgorithmId;
+
+    use super::*;
+    use crate::tpm::{
+        alg::{TPM_ALG_AES, TPM_ALG_CFB, TPM_ALG_ECDAA, TPM_ALG_OAEP, TPM_ALG_XOR},
+        pcr_selection::{PcrSelection, PcrSelectionList},
+        public::{PublicId, PublicParameters, Scheme, SymDefObject, TpmtPublic},
+        tpm2b::{Tpm2b, Tpm2bDigest},
+    };
+
+    /// xorshift32, so the "random" inputs are the same on every run
+    fn pseudo_random_bytes(seed: u32, len: usize) -> Vec<u8> {
+        let mut state = seed.max(1);
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect()
+    }
+
+    /// A public area of the kind picked by `seed`, with its numbers and buffers filled in from
+    /// [`pseudo_random_bytes`]. Null schemes and symmetric definitions are left in the only form
+    /// they're marshalled in.
+    fn pseudo_random_public(seed: u32) -> TpmtPublic {
+        let numbers = pseudo_random_bytes(seed, 32);
+        let mut numbers = Reader::new(&numbers);
+        let mut number = || numbers.u16().unwrap();
+        let buffer = |salt: u32, max_len: usize| {
+            pseudo_random_bytes(seed * salt, (seed * salt) as usize % (max_len + 1))
+        };
+        let symmetric = match seed % 2 {
+            0 => SymDefObject::NULL,
+            _ => SymDefObject {
+                algorithm: TPM_ALG_AES,
+                key_bits: number(),
+                mode: TPM_ALG_CFB,
+            },
+        };
+        let scheme = match seed % 3 {
+            0 => Scheme::NULL,
+            1 => Scheme::new(TPM_ALG_OAEP, AlgorithmId(number())),
+            _ => Scheme {
+                extra: number(),
+                ..Scheme::new(TPM_ALG_ECDAA, AlgorithmId(number()))
+            },
+        };
+        let (parameters, unique) = match seed % 4 {
+            0 => (
+                PublicParameters::Rsa {
+                    symmetric,
+                    scheme,
+                    key_bits: number(),
+                    exponent: u32::from(number()) << 16 | u32::from(number()),
+                },
+                PublicId::Rsa(Tpm2b::new(&buffer(3, 512)).unwrap()),
+            ),
+            1 => (
+                PublicParameters::Ecc {
+                    symmetric,
+                    scheme,
+                    curve_id: number(),
+                    kdf: Scheme::NULL,
+                },
+                PublicId::Ecc {
+                    x: Tpm2b::new(&buffer(5, 66)).unwrap(),
+                    y: Tpm2b::new(&buffer(7, 66)).unwrap(),
+                },
+            ),
+            2 => (
+                PublicParameters::KeyedHash {
+                    scheme: Scheme {
+                        extra: number(),
+                        ..Scheme::new(TPM_ALG_XOR, AlgorithmId(number()))
+                    },
+                },
+                PublicId::Digest(Tpm2b::new(&buffer(11, 64)).unwrap()),
+            ),
+            _ => (
+                PublicParameters::SymCipher { symmetric },
+                PublicId::Digest(Tpm2b::new(&buffer(13, 64)).unwrap()),
+            ),
+        };
+        TpmtPublic {
+            name_alg: AlgorithmId(number()),
+            object_attributes: u32::from(number()) << 16 | u32::from(number()),
+            auth_policy: Tpm2b::new(&buffer(17, 64)).unwrap(),
+            parameters,
+            unique,
+        }
+    }
+
+    #[test]
+    fn integers_round_trip() {
+        let mut buffer = [0; 32];
+        let mut writer = Writer::new(&mut buffer);
+        writer.u8(0x12).unwrap();
+        writer.u16(0x3456).unwrap();
+        writer.u32(0x789A_BCDE).unwrap();
+        writer.u64(0x0102_0304_0506_0708).unwrap();
+        writer.tpm2b(b"abc").unwrap();
+        let written = writer.into_slice();
+        assert_eq!(written[..7], [0x12, 0x34, 0x56, 0x78, 0x9A, 0xBC, 0xDE]);
+        assert_eq!(written[15..], [0, 3, b'a', b'b', b'c']);
+
+        let mut reader = Reader::new(written);
+        assert_eq!(reader.u8(), Ok(0x12));
+        assert_eq!(reader.u16(), Ok(0x3456));
+        assert_eq!(reader.u32(), Ok(0x789A_BCDE));
+        assert_eq!(reader.u64(), Ok(0x0102_0304_0506_0708));
+        assert_eq!(reader.tpm2b(), Ok(&b"abc"[..]));
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn little_endian_reads() {
+        let mut reader = Reader::new(&[1, 0, 2, 0, 0, 0, 3, 0, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(reader.u16_le(), Ok(1));
+        assert_eq!(reader.u32_le(), Ok(2));
+        assert_eq!(reader.u64_le(), Ok(3));
+    }
+
+    #[test]
+    fn every_truncation_is_an_error() {
+        let mut buffer = [0; 32];
+        let mut writer = Writer::new(&mut buffer);
+        writer.u32(7).unwrap();
+        writer.tpm2b(&[0xAA; 8]).unwrap();
+        let written = writer.into_slice();
+        for len in 0..written.len() {
+            let mut reader = Reader::new(&written[..len]);
+            let result = reader.u32().and_then(|_| reader.tpm2b());
+            assert_eq!(result, Err(TpmError::UnexpectedEnd), "{len} bytes");
+        }
+    }
+
+    #[test]
+    fn failed_reads_consume_nothing() {
+        let mut reader = Reader::new(&[0, 5, 1, 2]);
+        assert_eq!(reader.bytes(5), Err(TpmError::UnexpectedEnd));
+        assert_eq!(reader.position(), 0);
+        assert_eq!(reader.u16(), Ok(5));
+        assert_eq!(reader.remaining(), [1, 2]);
+    }
+
+    #[test]
+    fn writer_overflow_is_an_error() {
+        let mut buffer = [0; 5];
+        let mut writer = Writer::new(&mut buffer);
+        writer.u32(1).unwrap();
+        assert_eq!(writer.u16(2), Err(TpmError::CommandTooLarge));
+        assert_eq!(writer.len(), 4);
+        assert_eq!(
+            Writer::new(&mut [0; 4]).tpm2b(&[0; 0x10000]),
+            Err(TpmError::CommandTooLarge)
+        );
+    }
+
+    #[test]
+    fn patches_overwrite_in_place() {
+        let mut buffer = [0; 6];
+        let mut writer = Writer::new(&mut buffer);
+        writer.u16(0).unwrap();
+        writer.u32(0).unwrap();
+        writer.patch_u16(0, 0xABCD);
+        writer.patch_u32(2, 6);
+        assert_eq!(writer.into_slice(), [0xAB, 0xCD, 0, 0, 0, 6]);
+    }
+
+    #[test]
+    fn structures_round_trip() {
+        let mut list = PcrSelectionList::new();
+        list.push(
+            PcrSelection::new(AlgorithmId::SHA1)
+                .with_pcr(0)
+                .unwrap()
+                .with_pcr(23)
+                .unwrap(),
+        )
+        .unwrap();
+        list.push(PcrSelection::new(AlgorithmId::SHA256).with_pcr(7).unwrap())
+            .unwrap();
+        let digest = Tpm2bDigest::new(&[0x5A; 32]).unwrap();
+
+        let mut buffer = [0; 64];
+        let mut writer = Writer::new(&mut buffer);
+        list.write(&mut writer).unwrap();
+        digest.write(&mut writer).unwrap();
+        let mut reader = Reader::new(writer.into_slice());
+        assert_eq!(PcrSelectionList::read(&mut reader), Ok(list));
+        assert_eq!(Tpm2bDigest::read(&mut reader), Ok(digest));
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn public_areas_round_trip() {
+        for seed in 1..200 {
+            let public = pseudo_random_public(seed);
+            let mut buffer = [0; 1024];
+            let mut writer = Writer::new(&mut buffer);
+            public.write_tpm2b(&mut writer).unwrap();
+            let mut reader = Reader::new(writer.into_slice());
+            assert_eq!(
+                TpmtPublic::read_tpm2b(&mut reader),
+                Ok(public),
+                "seed {seed}"
+            );
+            assert!(reader.is_empty());
+        }
+    }
+
+    #[test]
+    fn arbitrary_bytes_never_panic() {
+        for seed in 1..500 {
+            let bytes = pseudo_random_bytes(seed, seed as usize % 80);
+            let _ = PcrSelectionList::read(&mut Reader::new(&bytes));
+            let _ = TpmtPublic::read_tpm2b(&mut Reader::new(&bytes));
+            let _ = Tpm2b::<16>::read(&mut Reader::new(&bytes));
+            let _ = Reader::new(&bytes).tpm2b();
+        }
+    }
+
+    #[test]
+    fn oversized_structures_are_malformed() {
+        // A `TPM2B` longer than the type holds
+        let mut bytes = [0; 20];
+        bytes[1] = 17;
+        assert_eq!(
+            Tpm2b::<16>::read(&mut Reader::new(&bytes)),
+            Err(TpmError::Malformed)
+        );
+        // A selection with more than `PCR_SELECT_MAX` bytes
+        let bytes = [0, 0, 0, 1, 0x00, 0x0B, 5, 0, 0, 0, 0, 0];
+        assert_eq!(
+            PcrSelectionList::read(&mut Reader::new(&bytes)),
+            Err(TpmError::Malformed)
+        );
+    }
+}
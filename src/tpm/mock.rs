@@ -0,0 +1,295 @@
+//! A fake TPM for exercising the command layer without firmware or a simulator
+
+use alloc::{collections::VecDeque, vec::Vec};
+
+use hex_slice::AsHex;
+
+use super::{
+    RESPONSE_HEADER_SIZE, ResponseCode, TPM_ST_NO_SESSIONS, TpmError, TpmHandle, TpmTransport,
+    capability::{TPM_CAP_HANDLES, TPM_CAP_TPM_PROPERTIES},
+    marshal::Writer,
+    nv::TpmNvPublic,
+    public::TpmtPublic,
+};
+
+/// A command a scripted [`MockTransport`] expects next, and what it answers
+#[derive(Debug, Clone)]
+pub struct ScriptStep {
+    /// The start of the command, at least its header so the command code is checked
+    pub command_prefix: Vec<u8>,
+    pub response: Vec<u8>,
+}
+
+/// Records every command and answers each one with the same canned response, or, once given a
+/// script with [`MockTransport::expect`], answers the commands in the script's order and panics on
+/// any other command
+#[derive(Debug, Clone, Default)]
+pub struct MockTransport {
+    pub response: Vec<u8>,
+    pub commands: Vec<Vec<u8>>,
+    pub script: VecDeque<ScriptStep>,
+    scripted: bool,
+}
+
+impl MockTransport {
+    pub fn new(response: impl Into<Vec<u8>>) -> Self {
+        Self {
+            response: response.into(),
+            ..Self::default()
+        }
+    }
+
+    /// A `TPM_ST_NO_SESSIONS` response with the given code and parameters
+    pub fn response_bytes(code: ResponseCode, parameters: &[u8]) -> Vec<u8> {
+        let size = (RESPONSE_HEADER_SIZE + parameters.len()) as u32;
+        let mut response = Vec::with_capacity(size as usize);
+        response.extend_from_slice(&TPM_ST_NO_SESSIONS.to_be_bytes());
+        response.extend_from_slice(&size.to_be_bytes());
+        response.extend_from_slice(&code.0.to_be_bytes());
+        response.extend_from_slice(parameters);
+        response
+    }
+
+    /// Answers with `TPM_RC_SUCCESS` and `parameters`
+    pub fn success(parameters: &[u8]) -> Self {
+        Self::new(Self::response_bytes(ResponseCode::SUCCESS, parameters))
+    }
+
+    /// Adds a step to the script: the next command must start with `command_prefix`, and gets
+    /// `response`. Once there's a script, the canned response is never used.
+    pub fn expect(
+        mut self,
+        command_prefix: impl Into<Vec<u8>>,
+        response: impl Into<Vec<u8>>,
+    ) -> Self {
+        self.script.push_back(ScriptStep {
+            command_prefix: command_prefix.into(),
+            response: response.into(),
+        });
+        self.scripted = true;
+        self
+    }
+
+    /// Panics if the script has steps no command got to
+    pub fn assert_done(&self) {
+        if let Some(step) = self.script.front() {
+            panic!(
+                "{} scripted commands weren't sent, starting with {:02x}",
+                self.script.len(),
+                step.command_prefix.plain_hex(false)
+            );
+        }
+    }
+}
+
+impl TpmTransport for MockTransport {
+    fn transmit(&mut self, command: &[u8], response: &mut [u8]) -> Result<(), TpmError> {
+        self.commands.push(command.to_vec());
+        let canned = if self.scripted {
+            let Some(step) = self.script.pop_front() else {
+                panic!(
+                    "unexpected command after the script ended: {:02x}",
+                    command.plain_hex(false)
+                );
+            };
+            assert!(
+                command.starts_with(&step.command_prefix),
+                "command {} is {:02x}, but the script expected it to start with {:02x}",
+                self.commands.len(),
+                command.plain_hex(false),
+                step.command_prefix.plain_hex(false)
+            );
+            step.response
+        } else {
+            self.response.clone()
+        };
+        let len = canned.len().min(response.len());
+        response[..len].copy_from_slice(&canned[..len]);
+        Ok(())
+    }
+}
+
+/// A `TPM_RC_SUCCESS` response without parameters
+pub fn success_response() -> Vec<u8> {
+    MockTransport::response_bytes(ResponseCode::SUCCESS, &[])
+}
+
+/// A `TPM2_GetRandom` command asking for `bytes_requested`
+pub fn get_random_command(bytes_requested: u16) -> Vec<u8> {
+    let mut command = Vec::new();
+    command.extend_from_slice(&TPM_ST_NO_SESSIONS.to_be_bytes());
+    command.extend_from_slice(&12u32.to_be_bytes());
+    command.extend_from_slice(&0x17Bu32.to_be_bytes());
+    command.extend_from_slice(&bytes_requested.to_be_bytes());
+    command
+}
+
+/// A `TPM2_GetRandom` response with `random_bytes`
+pub fn get_random_response(random_bytes: &[u8]) -> Vec<u8> {
+    let mut parameters = (random_bytes.len() as u16).to_be_bytes().to_vec();
+    parameters.extend_from_slice(random_bytes);
+    MockTransport::response_bytes(ResponseCode::SUCCESS, &parameters)
+}
+
+/// The start of `TPM2_GetCapability(capability, property)`, without the property count
+pub fn get_capability_command(capability: u32, property: u32) -> Vec<u8> {
+    let mut command = Vec::from([0x80, 0x01, 0x00, 0x00, 0x00, 0x16, 0x00, 0x00, 0x01, 0x7a]);
+    command.extend_from_slice(&capability.to_be_bytes());
+    command.extend_from_slice(&property.to_be_bytes());
+    command
+}
+
+/// The start of `TPM2_GetCapability(TPM_CAP_HANDLES, first)`
+pub fn list_handles_command(first: TpmHandle) -> Vec<u8> {
+    get_capability_command(TPM_CAP_HANDLES, first)
+}
+
+/// A page of `TPM_CAP_HANDLES`
+pub fn handles_response(more_data: bool, handles: &[TpmHandle]) -> Vec<u8> {
+    let mut parameters = Vec::from([more_data as u8]);
+    parameters.extend_from_slice(&TPM_CAP_HANDLES.to_be_bytes());
+    parameters.extend_from_slice(&(handles.len() as u32).to_be_bytes());
+    for handle in handles {
+        parameters.extend_from_slice(&handle.to_be_bytes());
+    }
+    MockTransport::response_bytes(ResponseCode::SUCCESS, &parameters)
+}
+
+/// A page of `TPM_CAP_TPM_PROPERTIES` with the `(property, value)` pairs
+pub fn properties_response(properties: &[(u32, u32)]) -> Vec<u8> {
+    let mut parameters = Vec::from([0x00]);
+    parameters.extend_from_slice(&TPM_CAP_TPM_PROPERTIES.to_be_bytes());
+    parameters.extend_from_slice(&(properties.len() as u32).to_be_bytes());
+    for (property, value) in properties {
+        parameters.extend_from_slice(&property.to_be_bytes());
+        parameters.extend_from_slice(&value.to_be_bytes());
+    }
+    MockTransport::response_bytes(ResponseCode::SUCCESS, &parameters)
+}
+
+/// `TPM2_ReadPublic(handle)`
+pub fn read_public_command(handle: TpmHandle) -> Vec<u8> {
+    let mut command = Vec::from([0x80, 0x01, 0x00, 0x00, 0x00, 0x0e, 0x00, 0x00, 0x01, 0x73]);
+    command.extend_from_slice(&handle.to_be_bytes());
+    command
+}
+
+/// The SHA-256 name [`read_public_response`] answers with
+pub fn read_public_name() -> Vec<u8> {
+    [[0x00, 0x0b].as_slice(), &[0x5a; 32]].concat()
+}
+
+/// `TPM2_ReadPublic` answering with `public` and [`read_public_name`]
+pub fn read_public_response(public: &TpmtPublic) -> Vec<u8> {
+    let mut parameters = [0; 1024];
+    let mut writer = Writer::new(&mut parameters);
+    public.write_tpm2b(&mut writer).unwrap();
+    // The name and the qualified name
+    writer.tpm2b(&read_public_name()).unwrap();
+    writer.tpm2b(&read_public_name()).unwrap();
+    let len = writer.len();
+    MockTransport::response_bytes(ResponseCode::SUCCESS, &parameters[..len])
+}
+
+/// `TPM2_NV_ReadPublic(index)`
+pub fn nv_read_public_command(index: TpmHandle) -> Vec<u8> {
+    let mut command = Vec::from([0x80, 0x01, 0x00, 0x00, 0x00, 0x0e, 0x00, 0x00, 0x01, 0x69]);
+    command.extend_from_slice(&index.to_be_bytes());
+    command
+}
+
+/// `TPM2_NV_ReadPublic` answering with `public` and [`read_public_name`]
+pub fn nv_read_public_response(public: &TpmNvPublic) -> Vec<u8> {
+    let mut parameters = Vec::new();
+    let auth_policy = public.auth_policy.as_slice();
+    parameters.extend_from_slice(&(14 + auth_policy.len() as u16).to_be_bytes());
+    parameters.extend_from_slice(&public.nv_index.to_be_bytes());
+    parameters.extend_from_slice(&public.name_alg.0.to_be_bytes());
+    parameters.extend_from_slice(&public.attributes.to_be_bytes());
+    parameters.extend_from_slice(&(auth_policy.len() as u16).to_be_bytes());
+    parameters.extend_from_slice(auth_policy);
+    parameters.extend_from_slice(&public.data_size.to_be_bytes());
+    parameters.extend_from_slice(&(read_public_name().len() as u16).to_be_bytes());
+    parameters.extend_from_slice(&read_public_name());
+    MockTransport::response_bytes(ResponseCode::SUCCESS, &parameters)
+}
+
+/// `TPM2_NV_Read` of `size` bytes at `offset`, authorized by the index with an empty password
+pub fn nv_read_command(index: TpmHandle, size: u16, offset: u16) -> Vec<u8> {
+    let mut command = Vec::from([0x80, 0x02, 0x00, 0x00, 0x00, 0x23, 0x00, 0x00, 0x01, 0x4e]);
+    command.extend_from_slice(&index.to_be_bytes());
+    command.extend_from_slice(&index.to_be_bytes());
+    command.extend_from_slice(&[0, 0, 0, 9, 0x40, 0, 0, 9, 0, 0, 0, 0, 0]);
+    command.extend_from_slice(&size.to_be_bytes());
+    command.extend_from_slice(&offset.to_be_bytes());
+    command
+}
+
+/// A `TPM2_NV_Read` response with `data`
+pub fn nv_read_response(data: &[u8]) -> Vec<u8> {
+    let mut parameters = (data.len() as u16).to_be_bytes().to_vec();
+    parameters.extend_from_slice(data);
+    MockTransport::response_bytes(ResponseCode::SUCCESS, &parameters)
+}
+
+/// `TPM2_NV_Write` of `data` at `offset`, authorized by the index with an empty password
+pub fn nv_write_command(index: TpmHandle, data: &[u8], offset: u16) -> Vec<u8> {
+    let mut command = Vec::from([0x80, 0x02]);
+    command.extend_from_slice(&(35 + data.len() as u32).to_be_bytes());
+    command.extend_from_slice(&0x137u32.to_be_bytes());
+    command.extend_from_slice(&index.to_be_bytes());
+    command.extend_from_slice(&index.to_be_bytes());
+    command.extend_from_slice(&[0, 0, 0, 9, 0x40, 0, 0, 9, 0, 0, 0, 0, 0]);
+    command.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    command.extend_from_slice(data);
+    command.extend_from_slice(&offset.to_be_bytes());
+    command
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tpm::random::fill_random;
+
+    #[test]
+    fn script_answers_in_order() {
+        // The TPM gives at most 32 bytes, so the rest takes a second command
+        let mut tpm = MockTransport::default()
+            .expect(get_random_command(40), get_random_response(&[1; 32]))
+            .expect(get_random_command(8), get_random_response(&[2; 8]));
+        let mut bytes = [0; 40];
+        fill_random(&mut tpm, &mut bytes).unwrap();
+        tpm.assert_done();
+        assert_eq!(bytes[..32], [1; 32]);
+        assert_eq!(bytes[32..], [2; 8]);
+        assert_eq!(
+            tpm.commands,
+            [get_random_command(40), get_random_command(8)]
+        );
+    }
+
+    #[test]
+    fn canned_response_answers_every_command() {
+        let mut tpm = MockTransport::new(get_random_response(&[3; 4]));
+        let mut bytes = [0; 8];
+        fill_random(&mut tpm, &mut bytes).unwrap();
+        assert_eq!(bytes, [3; 8]);
+        assert_eq!(tpm.commands.len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "the script expected")]
+    fn script_panics_on_the_wrong_command() {
+        let mut tpm =
+            MockTransport::default().expect(get_random_command(8), get_random_response(&[1; 8]));
+        fill_random(&mut tpm, &mut [0; 16]).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "scripted commands weren't sent")]
+    fn assert_done_panics_on_unsent_steps() {
+        MockTransport::default()
+            .expect(get_random_command(8), get_random_response(&[1; 8]))
+            .assert_done();
+    }
+}
@@ -0,0 +1,271 @@
+pub mod alg;
+pub mod attest;
+pub mod auth;
+pub mod backoff;
+pub mod capability;
+pub mod cleanup;
+pub mod clock;
+pub mod command;
+mod command_code;
+pub mod command_limit;
+pub mod ecc;
+pub mod ek;
+mod error;
+pub mod hierarchy;
+pub mod lockout;
+pub mod marshal;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
+pub mod name;
+pub mod nv;
+pub mod object;
+pub mod pcr;
+pub mod pcr_selection;
+pub mod policy;
+pub mod provision;
+pub mod public;
+pub mod random;
+mod response_code;
+pub mod rsa;
+pub mod self_test;
+pub mod srk;
+#[cfg(all(test, feature = "swtpm-tests"))]
+pub mod swtpm;
+pub mod timeout;
+pub mod tpm2b;
+mod transport;
+
+pub use command_code::CommandCode;
+pub use error::TpmError;
+pub use response_code::{ResponseCode, ResponsePosition};
+pub use transport::{
+    LoggingTransport, RecordedCommand, RecordingTransport, TcgTransport, TpmCommandRecord,
+    TpmTransport,
+};
+
+use marshal::{Reader, Writer};
+
+/// `TPM_HANDLE`
+pub type TpmHandle = u32;
+
+/// `TPM_RH` permanent handles
+pub const TPM_RH_OWNER: TpmHandle = 0x40000001;
+pub const TPM_RH_LOCKOUT: TpmHandle = 0x4000000A;
+pub const TPM_RH_ENDORSEMENT: TpmHandle = 0x4000000B;
+pub const TPM_RH_PLATFORM: TpmHandle = 0x4000000C;
+
+pub const TPM_ST_NO_SESSIONS: u16 = 0x8001;
+pub const TPM_ST_SESSIONS: u16 = 0x8002;
+/// `TPM_TAG_RSP_COMMAND`, the tag of a TPM 1.2 response
+pub const TPM_TAG_RSP_COMMAND: u16 = 0x00C4;
+
+pub const COMMAND_HEADER_SIZE: usize = 10;
+pub const RESPONSE_HEADER_SIZE: usize = 10;
+
+/// Plenty for every command and response we send. The TCG protocol tells us the real limits in
+/// `max_command_size` and `max_response_size`.
+///
+/// Every command puts a command and a response buffer of this size on the stack, 8 KiB in all.
+/// [`command::execute_with_capacity`] takes smaller ones where the stack is tight.
+pub const BUFFER_SIZE: usize = 4096;
+
+/// Writes the command header with a placeholder size. Call [`finish_command`] once the rest of the
+/// command has been written.
+pub fn begin_command(writer: &mut Writer<'_>, tag: u16, code: CommandCode) -> Result<(), TpmError> {
+    writer.u16(tag)?;
+    writer.u32(0)?;
+    writer.u32(code.0)
+}
+
+/// Fills in `commandSize` and returns the finished command
+pub fn finish_command(mut writer: Writer<'_>) -> &[u8] {
+    let size = writer.len() as u32;
+    writer.patch_u32(2, size);
+    writer.into_slice()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResponseHeader {
+    pub tag: u16,
+    pub response_size: u32,
+    pub response_code: ResponseCode,
+}
+
+/// A successful response, limited to the `responseSize` bytes the TPM actually sent
+#[derive(Debug, Clone, Copy)]
+pub struct Response<'a> {
+    pub header: ResponseHeader,
+    /// Everything after the header
+    pub body: &'a [u8],
+}
+
+impl<'a> Response<'a> {
+    /// Splits the body into the response handles and the parameter area. With `TPM_ST_SESSIONS`
+    /// the parameter area is prefixed with `parameterSize` and followed by the response sessions,
+    /// which are left out.
+    pub fn split(&self, handle_count: usize) -> Result<(Reader<'a>, Reader<'a>), TpmError> {
+        let mut reader = Reader::new(self.body);
+        let handles = Reader::new(reader.bytes(handle_count * 4)?);
+        let parameters = if self.header.tag == TPM_ST_SESSIONS {
+            let parameter_size = reader.u32()?;
+            reader.bytes(parameter_size as usize)?
+        } else {
+            reader.remaining()
+        };
+        Ok((handles, Reader::new(parameters)))
+    }
+
+    /// The parameter area of a response with no handles
+    pub fn parameters(&self) -> Result<Reader<'a>, TpmError> {
+        Ok(self.split(0)?.1)
+    }
+}
+
+/// Sends a command and parses the response out of `response_buffer`
+pub fn submit<'r>(
+    tcg: &mut dyn TpmTransport,
+    command: &[u8],
+    response_buffer: &'r mut [u8],
+) -> Result<Response<'r>, TpmError> {
+    tcg.transmit(command, response_buffer)?;
+    parse_response(response_buffer).map_err(|error| match command.get(6..10) {
+        Some(code) => error.in_command(CommandCode(u32::from_be_bytes(code.try_into().unwrap()))),
+        None => error,
+    })
+}
+
+/// The shared parse entry point for everything `submit_command` gives back.
+///
+/// `submit_command` doesn't tell us how many bytes it wrote, so the header's `responseSize` is the
+/// authoritative length. It's [`TpmError::ResponseTooLarge`] if that's more than the buffer holds.
+/// Bytes in the buffer beyond it are indeterminate (left over from earlier use or never written)
+/// and are never read.
+pub fn parse_response(buffer: &[u8]) -> Result<Response<'_>, TpmError> {
+    let mut reader = Reader::new(buffer);
+    let header = ResponseHeader {
+        tag: reader.u16()?,
+        response_size: reader.u32()?,
+        response_code: ResponseCode(reader.u32()?),
+    };
+    if (header.response_size as usize) < RESPONSE_HEADER_SIZE {
+        return Err(TpmError::Malformed);
+    }
+    let len = header.response_size as usize;
+    if len > buffer.len() {
+        return Err(TpmError::ResponseTooLarge {
+            size: header.response_size,
+            capacity: buffer.len(),
+        });
+    }
+    // A TPM 2.0 only uses it for `TPM_RC_BAD_TAG`, and our tags are never bad
+    if header.tag == TPM_TAG_RSP_COMMAND {
+        return Err(TpmError::Tpm12);
+    }
+    if header.response_code == ResponseCode::FAILURE {
+        return Err(TpmError::FailureMode { command: None });
+    }
+    if header.response_code != ResponseCode::SUCCESS {
+        return Err(TpmError::Response {
+            code: header.response_code,
+            command: None,
+            handle: None,
+        });
+    }
+    if header.tag != TPM_ST_NO_SESSIONS && header.tag != TPM_ST_SESSIONS {
+        return Err(TpmError::Malformed);
+    }
+    Ok(Response {
+        header,
+        body: &buffer[RESPONSE_HEADER_SIZE..len],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mock::MockTransport;
+
+    #[test]
+    fn failure_response_is_failure_mode() {
+        let response = MockTransport::response_bytes(ResponseCode::FAILURE, &[]);
+        assert_eq!(
+            parse_response(&response).unwrap_err(),
+            TpmError::FailureMode { command: None }
+        );
+    }
+
+    #[test]
+    fn submit_names_the_command_that_hit_failure_mode() {
+        let mut tcg = MockTransport::new(MockTransport::response_bytes(ResponseCode::FAILURE, &[]));
+        let error = random::get_random(&mut tcg, &mut [0; 8]).unwrap_err();
+        assert_eq!(
+            error,
+            TpmError::FailureMode {
+                command: Some(CommandCode::GET_RANDOM)
+            }
+        );
+        assert_eq!(error.response_code(), Some(ResponseCode::FAILURE));
+        assert!(error.to_string().contains("failure mode"), "{error}");
+    }
+
+    #[test]
+    fn other_response_codes_are_response_errors() {
+        let response = MockTransport::response_bytes(ResponseCode::TESTING, &[]);
+        assert_eq!(
+            parse_response(&response).unwrap_err(),
+            TpmError::Response {
+                code: ResponseCode::TESTING,
+                command: None,
+                handle: None,
+            }
+        );
+    }
+
+    #[test]
+    fn tpm12_response_is_rejected() {
+        // `TPM_TAG_RSP_COMMAND` with `TPM_BADTAG`, what a TPM 1.2 says to a TPM 2.0 command
+        let response = [0x00, 0xc4, 0x00, 0x00, 0x00, 0x0a, 0x00, 0x00, 0x00, 0x1e];
+        let mut tcg = MockTransport::new(response);
+        let error = random::get_random(&mut tcg, &mut [0; 8]).unwrap_err();
+        assert_eq!(error, TpmError::Tpm12);
+        assert_eq!(uefi::Status::from(error), uefi::Status::UNSUPPORTED);
+    }
+
+    #[test]
+    fn only_response_size_bytes_are_parsed() {
+        // A `TPM2_GetRandom` response with 2 bytes, in a buffer with junk after it
+        let mut buffer = [0xee; 64];
+        let response =
+            MockTransport::response_bytes(ResponseCode::SUCCESS, &[0x00, 0x02, 0xab, 0xcd]);
+        buffer[..response.len()].copy_from_slice(&response);
+        let parsed = parse_response(&buffer).unwrap();
+        assert_eq!(parsed.header.response_size, 14);
+        assert_eq!(parsed.body, [0x00, 0x02, 0xab, 0xcd]);
+        assert_eq!(
+            parsed.parameters().unwrap().remaining(),
+            [0x00, 0x02, 0xab, 0xcd]
+        );
+    }
+
+    #[test]
+    fn response_size_past_the_buffer_is_too_large() {
+        let mut response =
+            MockTransport::response_bytes(ResponseCode::SUCCESS, &[0x00, 0x02, 0xab, 0xcd]);
+        // Says there's one more byte than there is
+        response[2..6].copy_from_slice(&15u32.to_be_bytes());
+        assert_eq!(
+            parse_response(&response).unwrap_err(),
+            TpmError::ResponseTooLarge {
+                size: 15,
+                capacity: 14
+            }
+        );
+    }
+
+    #[test]
+    fn response_size_shorter_than_the_header_is_malformed() {
+        let mut response = MockTransport::response_bytes(ResponseCode::SUCCESS, &[]);
+        response[2..6].copy_from_slice(&9u32.to_be_bytes());
+        assert_eq!(parse_response(&response).unwrap_err(), TpmError::Malformed);
+    }
+}
@@ -0,0 +1,208 @@
+//! Names, which identify an object or NV index by the digest of its public area. A name the TPM
+//! returns can be checked against [`compute_name`] of the public area we expect, to tell that a
+//! loaded key is the one we meant.
+
+use core::fmt;
+
+use hex_slice::AsHex;
+use uefi::proto::tcg::AlgorithmId;
+
+use super::{
+    BUFFER_SIZE, TpmError, alg,
+    marshal::{Reader, Writer},
+    pcr::TpmDigest,
+    public::TpmtPublic,
+    tpm2b::Tpm2bName,
+};
+use crate::ct::ct_eq;
+
+/// A `TPM2B_NAME`: `nameAlg` followed by the digest of the public area, or only a handle for
+/// entities without one, like PCRs and hierarchies. Compared in constant time, since names are
+/// checked against ones an attacker may be trying to match.
+#[derive(Clone, Copy, Default)]
+pub struct TpmName(pub Tpm2bName);
+
+impl TpmName {
+    pub fn as_slice(&self) -> &[u8] {
+        self.0.as_slice()
+    }
+
+    /// `nameAlg`, or `None` for a name that's only a handle
+    pub fn algorithm(&self) -> Option<AlgorithmId> {
+        // A handle is 4 bytes, and every digest is longer than the 2 that would leave
+        match self.as_slice() {
+            [high, low, ..] if self.as_slice().len() > 4 => {
+                Some(AlgorithmId(u16::from_be_bytes([*high, *low])))
+            }
+            _ => None,
+        }
+    }
+
+    /// The digest after `nameAlg`, or `None` for a name that's only a handle
+    pub fn digest(&self) -> Option<&[u8]> {
+        self.algorithm().map(|_| &self.as_slice()[2..])
+    }
+
+    pub fn read(reader: &mut Reader<'_>) -> Result<Self, TpmError> {
+        Ok(Self(Tpm2bName::read(reader)?))
+    }
+
+    pub fn write(&self, writer: &mut Writer<'_>) -> Result<(), TpmError> {
+        self.0.write(writer)
+    }
+}
+
+impl PartialEq for TpmName {
+    fn eq(&self, other: &Self) -> bool {
+        ct_eq(self.as_slice(), other.as_slice())
+    }
+}
+
+impl Eq for TpmName {}
+
+impl fmt::Display for TpmName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.algorithm(), self.digest()) {
+            (Some(algorithm), Some(digest)) => match alg::name(algorithm) {
+                Some(name) => write!(f, "{name}:{:02x}", digest.plain_hex(false)),
+                None => write!(f, "{:#06x}:{:02x}", algorithm.0, digest.plain_hex(false)),
+            },
+            _ => write!(f, "{:02x}", self.as_slice().plain_hex(false)),
+        }
+    }
+}
+
+impl fmt::Debug for TpmName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TpmName({self})")
+    }
+}
+
+/// The name of an object with this public area: `name_alg` followed by the `name_alg` digest of
+/// the marshalled `TPMT_PUBLIC`. `name_alg` is normally `public.name_alg`, which is what the TPM
+/// uses. SHA-1 is always supported, and SHA-256, SHA-384 and SHA-512 with the `crypto` feature.
+pub fn compute_name(public: &TpmtPublic, name_alg: AlgorithmId) -> Result<TpmName, TpmError> {
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut writer = Writer::new(&mut buffer);
+    public.write(&mut writer)?;
+    let digest = TpmDigest::hash(name_alg, &[writer.into_slice()])?;
+    let mut name = [0; 2 + 64];
+    name[..2].copy_from_slice(&name_alg.0.to_be_bytes());
+    let len = 2 + digest.digest.as_slice().len();
+    name[2..len].copy_from_slice(digest.digest.as_slice());
+    // `TpmDigest::hash` digests are never more than 64 bytes
+    Ok(TpmName(Tpm2bName::new(&name[..len]).unwrap()))
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{format, vec::Vec};
+
+    use super::*;
+    use crate::tpm::{
+        alg::TPM_ALG_NULL,
+        public::{PublicId, PublicParameters, Scheme},
+        tpm2b::Tpm2bDigest,
+    };
+
+    /// A sealed data object's `TPMT_PUBLIC`: keyed hash, SHA-256 name, `fixedTPM|fixedParent`, no
+    /// policy, the null scheme and a `unique` of 32 0x11 bytes
+    const SEALED_PUBLIC: [u8; 46] = [
+        0x00, 0x08, 0x00, 0x0b, 0x00, 0x00, 0x00, 0x12, 0x00, 0x00, 0x00, 0x10, 0x00, 0x20, 0x11,
+        0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11,
+        0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x11,
+        0x11,
+    ];
+
+    fn sealed_public() -> TpmtPublic {
+        TpmtPublic {
+            name_alg: AlgorithmId::SHA256,
+            object_attributes: 0x12,
+            auth_policy: Tpm2bDigest::default(),
+            parameters: PublicParameters::KeyedHash {
+                scheme: Scheme::new(TPM_ALG_NULL, TPM_ALG_NULL),
+            },
+            unique: PublicId::Digest(Tpm2bDigest::new(&[0x11; 32]).unwrap()),
+        }
+    }
+
+    fn name(bytes: &[u8]) -> TpmName {
+        TpmName(Tpm2bName::new(bytes).unwrap())
+    }
+
+    #[test]
+    fn sealed_public_marshals_to_the_known_bytes() {
+        let mut buffer = [0; 64];
+        let mut writer = Writer::new(&mut buffer);
+        sealed_public().write(&mut writer).unwrap();
+        assert_eq!(writer.into_slice(), SEALED_PUBLIC);
+    }
+
+    #[cfg(feature = "crypto")]
+    #[test]
+    fn compute_name_sha256() {
+        let name = compute_name(&sealed_public(), AlgorithmId::SHA256).unwrap();
+        let mut expected = Vec::from([0x00, 0x0b]);
+        expected.extend_from_slice(&[
+            0xc8, 0x7c, 0x2a, 0x14, 0x62, 0x32, 0xd6, 0xdf, 0xac, 0x44, 0x6d, 0x0c, 0x63, 0x1e,
+            0xc3, 0x80, 0xca, 0xd7, 0x76, 0x4a, 0xa2, 0x2e, 0xdc, 0x59, 0xd1, 0x87, 0x0c, 0x78,
+            0x92, 0xcd, 0x9b, 0xab,
+        ]);
+        assert_eq!(name.as_slice(), expected);
+        assert_eq!(name.algorithm(), Some(AlgorithmId::SHA256));
+        assert_eq!(name.digest(), Some(&expected[2..]));
+    }
+
+    #[test]
+    fn compute_name_sha1() {
+        let name = compute_name(&sealed_public(), AlgorithmId::SHA1).unwrap();
+        let mut expected = Vec::from([0x00, 0x04]);
+        expected.extend_from_slice(&[
+            0x40, 0x3f, 0x62, 0x04, 0xc0, 0xc7, 0xc1, 0xe3, 0x44, 0xe3, 0x1f, 0xd2, 0x16, 0xae,
+            0xce, 0x14, 0xc2, 0x59, 0xd0, 0xe2,
+        ]);
+        assert_eq!(name.as_slice(), expected);
+    }
+
+    #[test]
+    fn compute_name_matches_the_name_of_the_read_back_area() {
+        let public = TpmtPublic::read(&mut Reader::new(&SEALED_PUBLIC)).unwrap();
+        assert_eq!(public, sealed_public());
+        assert_eq!(
+            compute_name(&public, AlgorithmId::SHA1).unwrap(),
+            compute_name(&sealed_public(), AlgorithmId::SHA1).unwrap()
+        );
+    }
+
+    #[test]
+    fn handle_names_have_no_algorithm() {
+        let name = name(&[0x40, 0x00, 0x00, 0x01]);
+        assert_eq!(name.algorithm(), None);
+        assert_eq!(name.digest(), None);
+        assert_eq!(format!("{name}"), "40000001");
+    }
+
+    #[test]
+    fn names_display_the_algorithm_and_digest() {
+        assert_eq!(
+            format!("{}", name(&[0x00, 0x04, 0xab, 0x0c, 0xef])),
+            "SHA1:ab0cef"
+        );
+        assert_eq!(
+            format!("{}", name(&[0x12, 0x34, 0xab, 0xcd, 0xef])),
+            "0x1234:abcdef"
+        );
+    }
+
+    #[test]
+    fn names_differing_anywhere_are_unequal() {
+        let reference = [0x00, 0x04, 1, 2, 3, 4, 5];
+        assert_eq!(name(&reference), name(&reference));
+        for i in 0..reference.len() {
+            let mut other = reference;
+            other[i] ^= 0x80;
+            assert_ne!(name(&reference), name(&other));
+        }
+        assert_ne!(name(&reference), name(&reference[..6]));
+    }
+}
@@ -0,0 +1,297 @@
+use alloc::{boxed::Box, vec::Vec};
+
+use uefi::proto::tcg::AlgorithmId;
+
+use super::{
+    BUFFER_SIZE, CommandCode, TPM_RH_OWNER, TPM_RH_PLATFORM, TPM_ST_NO_SESSIONS, TPM_ST_SESSIONS,
+    TpmError, TpmHandle, TpmTransport,
+    auth::{TpmAuth, write_auth_area},
+    begin_command, finish_command,
+    marshal::{Reader, Writer},
+    name::TpmName,
+    submit,
+    tpm2b::{Tpm2b, Tpm2bDigest},
+};
+
+/// `TPMA_NV` bits
+pub const TPMA_NV_PPWRITE: u32 = 1 << 0;
+pub const TPMA_NV_OWNERWRITE: u32 = 1 << 1;
+pub const TPMA_NV_AUTHWRITE: u32 = 1 << 2;
+pub const TPMA_NV_POLICYWRITE: u32 = 1 << 3;
+pub const TPMA_NV_TPM_NT: u32 = 0xF << 4;
+pub const TPMA_NV_WRITELOCKED: u32 = 1 << 11;
+pub const TPMA_NV_PPREAD: u32 = 1 << 16;
+pub const TPMA_NV_OWNERREAD: u32 = 1 << 17;
+pub const TPMA_NV_AUTHREAD: u32 = 1 << 18;
+pub const TPMA_NV_POLICYREAD: u32 = 1 << 19;
+pub const TPMA_NV_NO_DA: u32 = 1 << 25;
+pub const TPMA_NV_READLOCKED: u32 = 1 << 28;
+pub const TPMA_NV_WRITTEN: u32 = 1 << 29;
+
+/// `TPM_NT`, the kind of index
+pub const TPM_NT_ORDINARY: u32 = 0x0;
+pub const TPM_NT_COUNTER: u32 = 0x1;
+pub const TPM_NT_BITS: u32 = 0x2;
+pub const TPM_NT_EXTEND: u32 = 0x4;
+pub const TPM_NT_PIN_FAIL: u32 = 0x8;
+pub const TPM_NT_PIN_PASS: u32 = 0x9;
+
+/// The `TPM_NT` in `TPMA_NV`
+pub fn nv_type(attributes: u32) -> u32 {
+    (attributes & TPMA_NV_TPM_NT) >> 4
+}
+
+/// `MAX_NV_BUFFER_SIZE` in the PC Client profile. Larger reads and writes are split up.
+pub const MAX_NV_BUFFER_SIZE: usize = 1024;
+
+/// `TPMS_NV_PUBLIC`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TpmNvPublic {
+    pub nv_index: TpmHandle,
+    pub name_alg: AlgorithmId,
+    pub attributes: u32,
+    pub auth_policy: Tpm2bDigest,
+    pub data_size: u16,
+}
+
+impl TpmNvPublic {
+    /// `TPM2B_NV_PUBLIC`
+    pub fn read_tpm2b(reader: &mut Reader<'_>) -> Result<Self, TpmError> {
+        let mut inner = Reader::new(reader.tpm2b()?);
+        let public = Self {
+            nv_index: inner.u32()?,
+            name_alg: AlgorithmId(inner.u16()?),
+            attributes: inner.u32()?,
+            auth_policy: Tpm2b::read(&mut inner)?,
+            data_size: inner.u16()?,
+        };
+        if !inner.is_empty() {
+            return Err(TpmError::Malformed);
+        }
+        Ok(public)
+    }
+
+    /// The hierarchy or index to authorize a read with, going by which kinds of authorization the
+    /// attributes allow
+    fn read_auth_handle(&self) -> TpmHandle {
+        if self.attributes & (TPMA_NV_AUTHREAD | TPMA_NV_POLICYREAD) != 0 {
+            self.nv_index
+        } else if self.attributes & TPMA_NV_OWNERREAD != 0 {
+            TPM_RH_OWNER
+        } else if self.attributes & TPMA_NV_PPREAD != 0 {
+            TPM_RH_PLATFORM
+        } else {
+            self.nv_index
+        }
+    }
+
+    /// Like [`Self::read_auth_handle`], for writes
+    fn write_auth_handle(&self) -> TpmHandle {
+        if self.attributes & (TPMA_NV_AUTHWRITE | TPMA_NV_POLICYWRITE) != 0 {
+            self.nv_index
+        } else if self.attributes & TPMA_NV_OWNERWRITE != 0 {
+            TPM_RH_OWNER
+        } else if self.attributes & TPMA_NV_PPWRITE != 0 {
+            TPM_RH_PLATFORM
+        } else {
+            self.nv_index
+        }
+    }
+}
+
+/// `TPM2_NV_ReadPublic`. Returns the public area and the index's name.
+pub fn nv_read_public(
+    tcg: &mut dyn TpmTransport,
+    nv_index: TpmHandle,
+) -> Result<(TpmNvPublic, TpmName), TpmError> {
+    let mut command_buffer = [0; 14];
+    let mut writer = Writer::new(&mut command_buffer);
+    begin_command(&mut writer, TPM_ST_NO_SESSIONS, CommandCode::NV_READ_PUBLIC)?;
+    writer.u32(nv_index)?;
+    let mut response_buffer = [0; BUFFER_SIZE];
+    let response = submit(tcg, finish_command(writer), &mut response_buffer)
+        .map_err(|e| e.with_handle(nv_index))?;
+    let mut parameters = response.parameters()?;
+    let public = TpmNvPublic::read_tpm2b(&mut parameters)?;
+    let name = TpmName::read(&mut parameters)?;
+    Ok((public, name))
+}
+
+/// An NV index together with its public area and the authorization for reading and writing it
+pub struct TpmNvIndex {
+    handle: TpmHandle,
+    public: TpmNvPublic,
+    auth: Box<dyn TpmAuth>,
+}
+
+impl TpmNvIndex {
+    /// Reads the public area, which also checks that the index exists
+    pub fn open(
+        tcg: &mut dyn TpmTransport,
+        handle: TpmHandle,
+        auth: Box<dyn TpmAuth>,
+    ) -> Result<Self, TpmError> {
+        let (public, _name) = nv_read_public(tcg, handle)?;
+        if public.nv_index != handle {
+            return Err(TpmError::Malformed);
+        }
+        Ok(Self {
+            handle,
+            public,
+            auth,
+        })
+    }
+
+    pub fn handle(&self) -> TpmHandle {
+        self.handle
+    }
+
+    /// The public area as it was when the index was opened
+    pub fn public(&self) -> &TpmNvPublic {
+        &self.public
+    }
+
+    /// `TPM2_NV_Read`, split into chunks of [`MAX_NV_BUFFER_SIZE`]
+    pub fn read(
+        &self,
+        tcg: &mut dyn TpmTransport,
+        offset: u16,
+        size: u16,
+    ) -> Result<Vec<u8>, TpmError> {
+        let mut data = Vec::with_capacity(size as usize);
+        self.read_chunks(
+            tcg,
+            offset,
+            size,
+            MAX_NV_BUFFER_SIZE as u16,
+            |_offset, chunk| data.extend_from_slice(chunk),
+        )?;
+        Ok(data)
+    }
+
+    /// `TPM2_NV_Read` of `size` bytes at `offset`, a chunk of at most `chunk_size` (and
+    /// [`MAX_NV_BUFFER_SIZE`]) bytes at a time. Each chunk is passed to `on_chunk` with its offset
+    /// as soon as it's read, so nothing has to hold the whole range.
+    pub fn read_chunks(
+        &self,
+        tcg: &mut dyn TpmTransport,
+        offset: u16,
+        size: u16,
+        chunk_size: u16,
+        mut on_chunk: impl FnMut(u16, &[u8]),
+    ) -> Result<(), TpmError> {
+        let chunk_size = chunk_size.clamp(1, MAX_NV_BUFFER_SIZE as u16);
+        let mut done = 0;
+        while done < size {
+            let chunk_len = (size - done).min(chunk_size);
+            let mut command_buffer = [0; BUFFER_SIZE];
+            let mut writer = Writer::new(&mut command_buffer);
+            begin_command(&mut writer, TPM_ST_SESSIONS, CommandCode::NV_READ)?;
+            writer.u32(self.public.read_auth_handle())?;
+            writer.u32(self.handle)?;
+            write_auth_area(&mut writer, &[self.auth.auth_command()])?;
+            writer.u16(chunk_len)?;
+            writer.u16(offset + done)?;
+            let mut response_buffer = [0; BUFFER_SIZE];
+            let response = submit(tcg, finish_command(writer), &mut response_buffer)
+                .map_err(|e| e.with_handle(self.handle))?;
+            let chunk = response.parameters()?.tpm2b()?;
+            if chunk.len() != chunk_len as usize {
+                return Err(TpmError::Malformed);
+            }
+            on_chunk(offset + done, chunk);
+            done += chunk_len;
+        }
+        Ok(())
+    }
+
+    /// `TPM2_NV_Write`, split into chunks of [`MAX_NV_BUFFER_SIZE`]
+    pub fn write(
+        &self,
+        tcg: &mut dyn TpmTransport,
+        data: &[u8],
+        offset: u16,
+    ) -> Result<(), TpmError> {
+        for (i, chunk) in data.chunks(MAX_NV_BUFFER_SIZE).enumerate() {
+            let mut command_buffer = [0; BUFFER_SIZE];
+            let mut writer = Writer::new(&mut command_buffer);
+            begin_command(&mut writer, TPM_ST_SESSIONS, CommandCode::NV_WRITE)?;
+            writer.u32(self.public.write_auth_handle())?;
+            writer.u32(self.handle)?;
+            write_auth_area(&mut writer, &[self.auth.auth_command()])?;
+            writer.tpm2b(chunk)?;
+            writer.u16(offset + (i * MAX_NV_BUFFER_SIZE) as u16)?;
+            let mut response_buffer = [0; BUFFER_SIZE];
+            submit(tcg, finish_command(writer), &mut response_buffer)
+                .map_err(|e| e.with_handle(self.handle))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tpm::{
+        auth::Password,
+        mock::{MockTransport, nv_read_command, nv_read_response},
+    };
+
+    const INDEX: TpmHandle = 0x0150_0016;
+
+    fn index() -> TpmNvIndex {
+        TpmNvIndex {
+            handle: INDEX,
+            public: TpmNvPublic {
+                nv_index: INDEX,
+                name_alg: AlgorithmId::SHA256,
+                attributes: TPMA_NV_AUTHREAD | TPMA_NV_AUTHWRITE,
+                auth_policy: Tpm2b::default(),
+                data_size: 300,
+            },
+            auth: Box::new(Password::default()),
+        }
+    }
+
+    #[test]
+    fn read_chunks_passes_each_chunk_with_its_offset() {
+        let data = (0..300).map(|i| i as u8).collect::<Vec<_>>();
+        let mut tcg = MockTransport::default()
+            .expect(
+                nv_read_command(INDEX, 128, 0),
+                nv_read_response(&data[..128]),
+            )
+            .expect(
+                nv_read_command(INDEX, 128, 128),
+                nv_read_response(&data[128..256]),
+            )
+            .expect(
+                nv_read_command(INDEX, 44, 256),
+                nv_read_response(&data[256..]),
+            );
+        let mut chunks = Vec::new();
+        index()
+            .read_chunks(&mut tcg, 0, 300, 128, |offset, chunk| {
+                chunks.push((offset, chunk.to_vec()))
+            })
+            .unwrap();
+        tcg.assert_done();
+        assert_eq!(
+            chunks,
+            [
+                (0, data[..128].to_vec()),
+                (128, data[128..256].to_vec()),
+                (256, data[256..].to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn read_chunks_rejects_a_short_chunk() {
+        let mut tcg = MockTransport::new(nv_read_response(&[0; 10]));
+        assert_eq!(
+            index().read_chunks(&mut tcg, 0, 300, 128, |_, _| {}),
+            Err(TpmError::Malformed)
+        );
+    }
+}
@@ -0,0 +1,198 @@
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::{
+    BUFFER_SIZE, CommandCode, TPM_ST_NO_SESSIONS, TPM_ST_SESSIONS, TpmError, TpmHandle,
+    TpmTransport,
+    auth::{AuthCommand, write_auth_area},
+    begin_command,
+    capability::{TpmPersistentHandle, list_persistent_handles},
+    finish_command,
+    marshal::Writer,
+    name::TpmName,
+    pcr_selection::PcrSelectionList,
+    public::{ObjectAttributes, TpmtPublic},
+    submit,
+    timeout::{CREATE_PRIMARY_TIMEOUT, TimeoutTransport},
+};
+
+/// What `CreatePrimary` returns that we use. The creation data and ticket are left out.
+#[derive(Debug, Clone, Copy)]
+pub struct CreatedPrimary {
+    pub handle: TpmHandle,
+    pub public: TpmtPublic,
+    pub name: TpmName,
+}
+
+/// `TPM2_CreatePrimary` under `primary_handle`, authorized by `auth`. The new object gets
+/// `user_auth` as its authorization value. No outside info or creation PCRs are included.
+///
+/// Generating the key can take the TPM a long time, so it's sent with [`CREATE_PRIMARY_TIMEOUT`].
+pub fn create_primary(
+    tcg: &mut dyn TpmTransport,
+    primary_handle: TpmHandle,
+    auth: &AuthCommand<'_>,
+    user_auth: &[u8],
+    template: &TpmtPublic,
+) -> Result<CreatedPrimary, TpmError> {
+    let mut command_buffer = [0; BUFFER_SIZE];
+    let mut writer = Writer::new(&mut command_buffer);
+    begin_command(&mut writer, TPM_ST_SESSIONS, CommandCode::CREATE_PRIMARY)?;
+    writer.u32(primary_handle)?;
+    write_auth_area(&mut writer, core::slice::from_ref(auth))?;
+    // TPM2B_SENSITIVE_CREATE
+    writer.u16(2 + user_auth.len() as u16 + 2)?;
+    writer.tpm2b(user_auth)?;
+    writer.tpm2b(&[])?;
+    template.write_tpm2b(&mut writer)?;
+    // outsideInfo
+    writer.tpm2b(&[])?;
+    PcrSelectionList::new().write(&mut writer)?;
+    let mut tcg = TimeoutTransport {
+        inner: tcg,
+        timeout: CREATE_PRIMARY_TIMEOUT,
+    };
+    let mut response_buffer = [0; BUFFER_SIZE];
+    let response = submit(&mut tcg, finish_command(writer), &mut response_buffer)
+        .map_err(|e| e.with_handle(primary_handle))?;
+    let (mut handles, mut parameters) = response.split(1)?;
+    let handle = handles.u32()?;
+    let public = TpmtPublic::read_tpm2b(&mut parameters)?;
+    // creationData, creationHash, creationTicket
+    parameters.tpm2b()?;
+    parameters.tpm2b()?;
+    parameters.u16()?;
+    parameters.u32()?;
+    parameters.tpm2b()?;
+    let name = TpmName::read(&mut parameters)?;
+    Ok(CreatedPrimary {
+        handle,
+        public,
+        name,
+    })
+}
+
+/// `TPM2_EvictControl`: makes the transient object at `object_handle` persistent at
+/// `persistent_handle`, authorized by `auth` for `hierarchy` (the owner or platform). Given a
+/// persistent object instead, removes it.
+pub fn evict_control(
+    tcg: &mut dyn TpmTransport,
+    hierarchy: TpmHandle,
+    auth: &AuthCommand<'_>,
+    object_handle: TpmHandle,
+    persistent_handle: TpmHandle,
+) -> Result<(), TpmError> {
+    let mut command_buffer = [0; BUFFER_SIZE];
+    let mut writer = Writer::new(&mut command_buffer);
+    begin_command(&mut writer, TPM_ST_SESSIONS, CommandCode::EVICT_CONTROL)?;
+    writer.u32(hierarchy)?;
+    writer.u32(object_handle)?;
+    write_auth_area(&mut writer, core::slice::from_ref(auth))?;
+    writer.u32(persistent_handle)?;
+    let mut response_buffer = [0; BUFFER_SIZE];
+    submit(tcg, finish_command(writer), &mut response_buffer)
+        .map_err(|e| e.with_handle(object_handle))?;
+    Ok(())
+}
+
+/// `TPM2_FlushContext`, unloading a transient object or session
+pub fn flush_context(tcg: &mut dyn TpmTransport, handle: TpmHandle) -> Result<(), TpmError> {
+    let mut command_buffer = [0; 14];
+    let mut writer = Writer::new(&mut command_buffer);
+    begin_command(&mut writer, TPM_ST_NO_SESSIONS, CommandCode::FLUSH_CONTEXT)?;
+    writer.u32(handle)?;
+    let mut response_buffer = [0; BUFFER_SIZE];
+    submit(tcg, finish_command(writer), &mut response_buffer).map_err(|e| e.with_handle(handle))?;
+    Ok(())
+}
+
+/// `TPM2_ReadPublic`. Returns the object's public area and name.
+pub fn read_public(
+    tcg: &mut dyn TpmTransport,
+    object_handle: TpmHandle,
+) -> Result<(TpmtPublic, TpmName), TpmError> {
+    let mut command_buffer = [0; 14];
+    let mut writer = Writer::new(&mut command_buffer);
+    begin_command(&mut writer, TPM_ST_NO_SESSIONS, CommandCode::READ_PUBLIC)?;
+    writer.u32(object_handle)?;
+    let mut response_buffer = [0; BUFFER_SIZE];
+    let response = submit(tcg, finish_command(writer), &mut response_buffer)
+        .map_err(|e| e.with_handle(object_handle))?;
+    let mut parameters = response.parameters()?;
+    let public = TpmtPublic::read_tpm2b(&mut parameters)?;
+    let name = TpmName::read(&mut parameters)?;
+    Ok((public, name))
+}
+
+/// A persistent object and what kind of key it is
+#[derive(Debug, Clone, Copy)]
+pub struct PersistentObject {
+    pub handle: TpmPersistentHandle,
+    pub public: TpmtPublic,
+}
+
+impl fmt::Display for PersistentObject {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} ({})",
+            self.handle,
+            self.public.parameters,
+            ObjectAttributes(self.public.object_attributes)
+        )
+    }
+}
+
+/// Every persistent object with its public area, in handle order, to see what's already
+/// provisioned before creating or evicting anything
+pub fn list_persistent_objects(
+    tcg: &mut dyn TpmTransport,
+) -> Result<Vec<PersistentObject>, TpmError> {
+    list_persistent_handles(tcg)?
+        .into_iter()
+        .map(|handle| {
+            let (public, _name) = read_public(tcg, handle.0)?;
+            Ok(PersistentObject { handle, public })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tpm::{
+        ek::{EkAlgorithm, ek_template},
+        mock::{
+            MockTransport, handles_response, list_handles_command, read_public_command,
+            read_public_response,
+        },
+        srk::srk_template_rsa2048,
+    };
+
+    #[test]
+    fn persistent_objects_are_listed_with_their_public_areas() {
+        let srk = srk_template_rsa2048();
+        let ek = ek_template(EkAlgorithm::EccNistP256);
+        let mut tcg = MockTransport::default()
+            .expect(
+                list_handles_command(0x81000000),
+                handles_response(false, &[0x81000001, 0x81010002]),
+            )
+            .expect(read_public_command(0x81000001), read_public_response(&srk))
+            .expect(read_public_command(0x81010002), read_public_response(&ek));
+        let objects = list_persistent_objects(&mut tcg).unwrap();
+        tcg.assert_done();
+        assert_eq!(
+            objects
+                .iter()
+                .map(|object| (object.handle.0, object.public))
+                .collect::<Vec<_>>(),
+            [(0x81000001, srk), (0x81010002, ek)]
+        );
+        assert_eq!(
+            objects[0].to_string(),
+            "0x81000001: RSA 2048 \
+             (fixedTPM|fixedParent|sensitiveDataOrigin|userWithAuth|noDA|restricted|decrypt)"
+        );
+    }
+}
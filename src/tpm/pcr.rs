@@ -0,0 +1,587 @@
+use alloc::vec::Vec;
+use core::ops::RangeInclusive;
+
+use uefi::proto::tcg::AlgorithmId;
+
+use super::{
+    BUFFER_SIZE, CommandCode, TPM_ST_NO_SESSIONS, TPM_ST_SESSIONS, TpmError, TpmTransport, alg,
+    auth::{AuthCommand, write_auth_area},
+    begin_command,
+    capability::{TPM_PT_PCR_RESET_L0, active_pcr_banks, get_pcr_property},
+    finish_command,
+    marshal::Writer,
+    pcr_selection::{PcrSelection, PcrSelectionList},
+    submit,
+    tpm2b::Tpm2bDigest,
+};
+
+/// A digest with the bank it's from, like `TPMT_HA`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TpmDigest {
+    pub algorithm: AlgorithmId,
+    pub digest: Tpm2bDigest,
+}
+
+impl TpmDigest {
+    /// `None` if `digest` is too long for a `TPM2B_DIGEST`
+    pub fn new(algorithm: AlgorithmId, digest: &[u8]) -> Option<Self> {
+        Some(Self {
+            algorithm,
+            digest: Tpm2bDigest::new(digest)?,
+        })
+    }
+
+    pub fn sha1(digest: [u8; 20]) -> Self {
+        // 20 bytes always fit
+        Self::new(AlgorithmId::SHA1, &digest).unwrap()
+    }
+
+    /// What a PCR holds after a reset, or `None` for algorithms [`alg::digest_size`] doesn't know
+    pub fn zero(algorithm: AlgorithmId) -> Option<Self> {
+        Self::new(algorithm, &[0; 64][..alg::digest_size(algorithm)?])
+    }
+
+    /// The PCR extend operation, `self = H(self || other)`. `other` has to be from the same bank
+    /// and the size of its digests. SHA-1 is always supported, and SHA-256, SHA-384 and SHA-512
+    /// with the `crypto` feature.
+    pub fn extend(&mut self, other: &TpmDigest) -> Result<(), TpmError> {
+        if other.algorithm != self.algorithm {
+            return Err(TpmError::AlgorithmMismatch {
+                expected: self.algorithm,
+                actual: other.algorithm,
+            });
+        }
+        let expected = alg::digest_size(self.algorithm)
+            .ok_or(TpmError::UnsupportedAlgorithm(self.algorithm))?;
+        let actual = other.digest.as_slice().len();
+        if actual != expected {
+            return Err(TpmError::DigestSize { expected, actual });
+        }
+        self.digest = hash_parts(
+            self.algorithm,
+            &[self.digest.as_slice(), other.digest.as_slice()],
+        )?;
+        Ok(())
+    }
+
+    /// The hash of `parts` concatenated, with the algorithms [`extend`](Self::extend) supports
+    pub fn hash(algorithm: AlgorithmId, parts: &[&[u8]]) -> Result<Self, TpmError> {
+        Ok(Self {
+            algorithm,
+            digest: hash_parts(algorithm, parts)?,
+        })
+    }
+}
+
+/// A digest from the event log, like the ones [`LogEvent::digests`] has. Fails with
+/// [`TpmError::UnsupportedAlgorithm`] for algorithms [`alg::digest_size`] doesn't know, and with
+/// [`TpmError::DigestSize`] if `digest` isn't the size of the algorithm's digests.
+///
+/// [`LogEvent::digests`]: crate::event_log::parser::LogEvent::digests
+impl TryFrom<(AlgorithmId, &[u8])> for TpmDigest {
+    type Error = TpmError;
+
+    fn try_from((algorithm, digest): (AlgorithmId, &[u8])) -> Result<Self, Self::Error> {
+        let expected =
+            alg::digest_size(algorithm).ok_or(TpmError::UnsupportedAlgorithm(algorithm))?;
+        if digest.len() != expected {
+            return Err(TpmError::DigestSize {
+                expected,
+                actual: digest.len(),
+            });
+        }
+        // No digest size is more than a `TPM2B_DIGEST` holds
+        Ok(Self::new(algorithm, digest).unwrap())
+    }
+}
+
+/// What a PCR holding `initial` will hold after `measurements` are extended into it in order, for
+/// sealing to a boot state that hasn't happened yet, like one with a kernel that's about to be
+/// installed. Start from [`TpmDigest::zero`] for a PCR that's only extended after the reset.
+pub fn predict_pcr(initial: TpmDigest, measurements: &[TpmDigest]) -> Result<TpmDigest, TpmError> {
+    let mut pcr = initial;
+    for measurement in measurements {
+        pcr.extend(measurement)?;
+    }
+    Ok(pcr)
+}
+
+fn hash_concat<D: sha1::Digest>(parts: &[&[u8]]) -> Tpm2bDigest {
+    let mut hasher = D::new();
+    for part in parts {
+        hasher.update(part);
+    }
+    // Never more than SHA-512's 64 bytes
+    Tpm2bDigest::new(&hasher.finalize()).unwrap()
+}
+
+fn hash_parts(algorithm: AlgorithmId, parts: &[&[u8]]) -> Result<Tpm2bDigest, TpmError> {
+    Ok(match algorithm {
+        AlgorithmId::SHA1 => hash_concat::<sha1::Sha1>(parts),
+        #[cfg(feature = "crypto")]
+        AlgorithmId::SHA256 => hash_concat::<sha2::Sha256>(parts),
+        #[cfg(feature = "crypto")]
+        AlgorithmId::SHA384 => hash_concat::<sha2::Sha384>(parts),
+        #[cfg(feature = "crypto")]
+        AlgorithmId::SHA512 => hash_concat::<sha2::Sha512>(parts),
+        algorithm => return Err(TpmError::UnsupportedAlgorithm(algorithm)),
+    })
+}
+
+/// The response to `TPM2_PCR_Read`
+#[derive(Debug, Clone)]
+pub struct PcrReadResult {
+    pub update_counter: u32,
+    /// What was actually read. The TPM leaves out banks that aren't allocated, and stops after 8
+    /// digests.
+    pub selection: PcrSelectionList,
+    /// In the order of `selection`: bank by bank, then by increasing PCR index
+    pub digests: Vec<Tpm2bDigest>,
+}
+
+/// `TPM2_PCR_Read`
+pub fn pcr_read(
+    tcg: &mut dyn TpmTransport,
+    selection: &PcrSelectionList,
+) -> Result<PcrReadResult, TpmError> {
+    let mut command_buffer = [0; BUFFER_SIZE];
+    let mut writer = Writer::new(&mut command_buffer);
+    begin_command(&mut writer, TPM_ST_NO_SESSIONS, CommandCode::PCR_READ)?;
+    selection.write(&mut writer)?;
+    let mut response_buffer = [0; BUFFER_SIZE];
+    let response = submit(tcg, finish_command(writer), &mut response_buffer)?;
+    let mut parameters = response.parameters()?;
+    let update_counter = parameters.u32()?;
+    let selection = PcrSelectionList::read(&mut parameters)?;
+    let digests = (0..parameters.u32()?)
+        .map(|_| Tpm2bDigest::read(&mut parameters))
+        .collect::<Result<_, _>>()?;
+    Ok(PcrReadResult {
+        update_counter,
+        selection,
+        digests,
+    })
+}
+
+/// Reads one PCR from one bank. `None` if the TPM doesn't have that PCR in that bank.
+pub fn pcr_read_single(
+    tcg: &mut dyn TpmTransport,
+    algorithm: AlgorithmId,
+    pcr_index: u32,
+) -> Result<Option<TpmDigest>, TpmError> {
+    let mut selection = PcrSelectionList::new();
+    // A new list always has room
+    let _ = selection.push(PcrSelection::new(algorithm).with_pcr(pcr_index)?);
+    let result = pcr_read(tcg, &selection)?;
+    let read = result
+        .selection
+        .as_slice()
+        .iter()
+        .any(|selection| selection.hash == algorithm && selection.is_selected(pcr_index));
+    Ok(result
+        .digests
+        .first()
+        .filter(|_| read)
+        .map(|digest| TpmDigest {
+            algorithm,
+            digest: *digest,
+        }))
+}
+
+/// `TPM2_PCR_Extend` with one digest per bank, as a `TPML_DIGEST_VALUES`. PCRs have an empty
+/// password unless the platform set one, so that's what authorizes it.
+///
+/// The digests are sent as they are, not hashed again, so they can come from an event log or a
+/// measurement made elsewhere. Each has to be the size of its algorithm's digests, or nothing is
+/// sent: the TPM would read the rest of the command as part of a short digest.
+pub fn pcr_extend(
+    tcg: &mut dyn TpmTransport,
+    pcr_index: u32,
+    digests: &[(AlgorithmId, &[u8])],
+) -> Result<(), TpmError> {
+    for (algorithm, digest) in digests {
+        match alg::digest_size(*algorithm) {
+            Some(expected) if expected != digest.len() => {
+                return Err(TpmError::DigestSize {
+                    expected,
+                    actual: digest.len(),
+                });
+            }
+            _ => {}
+        }
+    }
+    let mut command_buffer = [0; BUFFER_SIZE];
+    let mut writer = Writer::new(&mut command_buffer);
+    begin_command(&mut writer, TPM_ST_SESSIONS, CommandCode::PCR_EXTEND)?;
+    writer.u32(pcr_index)?;
+    write_auth_area(&mut writer, &[AuthCommand::password(&[])])?;
+    writer.u32(digests.len() as u32)?;
+    for (algorithm, digest) in digests {
+        writer.u16(algorithm.0)?;
+        writer.bytes(digest)?;
+    }
+    let mut response_buffer = [0; BUFFER_SIZE];
+    submit(tcg, finish_command(writer), &mut response_buffer)
+        .map_err(|e| e.with_handle(pcr_index))?;
+    Ok(())
+}
+
+/// Reads one PCR from every active bank that has it, in a single command
+pub fn pcr_read_all_banks(
+    tcg: &mut dyn TpmTransport,
+    pcr_index: u32,
+) -> Result<Vec<TpmDigest>, TpmError> {
+    let mut selection = PcrSelectionList::new();
+    for (algorithm, bitmap) in active_pcr_banks(tcg)? {
+        if bitmap
+            .checked_shr(pcr_index)
+            .is_some_and(|bits| bits & 1 != 0)
+        {
+            // There are at most as many active banks as the list has room for
+            let _ = selection.push(PcrSelection::new(algorithm).with_pcr(pcr_index)?);
+        }
+    }
+    if selection.is_empty() {
+        return Ok(Vec::new());
+    }
+    let result = pcr_read(tcg, &selection)?;
+    if result.digests.len() != result.selection.len() {
+        return Err(TpmError::Malformed);
+    }
+    Ok(result
+        .selection
+        .as_slice()
+        .iter()
+        .zip(&result.digests)
+        .map(|(selection, digest)| TpmDigest {
+            algorithm: selection.hash,
+            digest: *digest,
+        })
+        .collect())
+}
+
+/// The PCRs for a dynamic launch (DRTM), like Intel TXT's `GETSEC[SENTER]` or AMD's `SKINIT`.
+/// They start out as all ones at power-on, and only the launch, from locality 4, resets them to
+/// zeros, so all ones means there was no launch since the TPM was reset.
+pub const DRTM_PCRS: RangeInclusive<u32> = 17..=22;
+
+/// What a PCR is for on a PC Client platform
+pub fn pcr_purpose(pcr_index: u32) -> &'static str {
+    match pcr_index {
+        0 => "firmware code",
+        1 => "firmware configuration",
+        2 => "option ROM code",
+        3 => "option ROM configuration",
+        4 => "boot loader code",
+        5 => "boot loader configuration and GPT",
+        6 => "platform manufacturer",
+        7 => "Secure Boot policy",
+        8..=15 => "OS",
+        16 => "debug",
+        17 => "DRTM",
+        18 => "DRTM, trusted OS start-up code",
+        19 => "DRTM, trusted OS configuration",
+        20 => "DRTM, trusted OS kernel",
+        21 | 22 => "DRTM, defined by the trusted OS",
+        23 => "application",
+        _ => "unknown",
+    }
+}
+
+/// PCR 16, for debugging, and PCR 23, for applications, can be extended and reset by anything, so
+/// nothing should be sealed to them. Those are the ones we extend.
+pub const DEBUG_PCR: u32 = 16;
+pub const APPLICATION_PCR: u32 = 23;
+
+/// `TPM2_PCR_Reset`, setting every bank of the PCR back to zeros. Only PCRs the TPM says can be
+/// reset from locality 0 (normally [`DEBUG_PCR`] and [`APPLICATION_PCR`]) are tried, so resetting
+/// anything else fails with [`TpmError::PcrNotResettable`] instead of a bare `TPM_RC_LOCALITY`.
+pub fn pcr_reset(tcg: &mut dyn TpmTransport, pcr_index: u32) -> Result<(), TpmError> {
+    let resettable = get_pcr_property(tcg, TPM_PT_PCR_RESET_L0)?;
+    if pcr_index >= u32::BITS || resettable & (1 << pcr_index) == 0 {
+        return Err(TpmError::PcrNotResettable(pcr_index));
+    }
+    let mut command_buffer = [0; BUFFER_SIZE];
+    let mut writer = Writer::new(&mut command_buffer);
+    begin_command(&mut writer, TPM_ST_SESSIONS, CommandCode::PCR_RESET)?;
+    writer.u32(pcr_index)?;
+    write_auth_area(&mut writer, &[AuthCommand::password(&[])])?;
+    let mut response_buffer = [0; BUFFER_SIZE];
+    submit(tcg, finish_command(writer), &mut response_buffer)
+        .map_err(|e| e.with_handle(pcr_index))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tpm::{
+        ResponseCode,
+        capability::TPM_CAP_PCR_PROPERTIES,
+        mock::{MockTransport, get_capability_command, success_response},
+    };
+
+    // The "abc" test vectors from FIPS 180-2, hashed as two parts so the concatenation is covered
+
+    #[test]
+    fn sha1_abc() {
+        let digest = TpmDigest::hash(AlgorithmId::SHA1, &[b"a", b"bc"]).unwrap();
+        assert_eq!(
+            digest.digest.as_slice(),
+            [
+                0xa9, 0x99, 0x3e, 0x36, 0x47, 0x06, 0x81, 0x6a, 0xba, 0x3e, 0x25, 0x71, 0x78, 0x50,
+                0xc2, 0x6c, 0x9c, 0xd0, 0xd8, 0x9d,
+            ]
+        );
+    }
+
+    #[cfg(feature = "crypto")]
+    #[test]
+    fn sha256_abc() {
+        let digest = TpmDigest::hash(AlgorithmId::SHA256, &[b"a", b"bc"]).unwrap();
+        assert_eq!(
+            digest.digest.as_slice(),
+            [
+                0xba, 0x78, 0x16, 0xbf, 0x8f, 0x01, 0xcf, 0xea, 0x41, 0x41, 0x40, 0xde, 0x5d, 0xae,
+                0x22, 0x23, 0xb0, 0x03, 0x61, 0xa3, 0x96, 0x17, 0x7a, 0x9c, 0xb4, 0x10, 0xff, 0x61,
+                0xf2, 0x00, 0x15, 0xad,
+            ]
+        );
+    }
+
+    #[cfg(feature = "crypto")]
+    #[test]
+    fn sha384_abc() {
+        let digest = TpmDigest::hash(AlgorithmId::SHA384, &[b"a", b"bc"]).unwrap();
+        assert_eq!(
+            digest.digest.as_slice(),
+            [
+                0xcb, 0x00, 0x75, 0x3f, 0x45, 0xa3, 0x5e, 0x8b, 0xb5, 0xa0, 0x3d, 0x69, 0x9a, 0xc6,
+                0x50, 0x07, 0x27, 0x2c, 0x32, 0xab, 0x0e, 0xde, 0xd1, 0x63, 0x1a, 0x8b, 0x60, 0x5a,
+                0x43, 0xff, 0x5b, 0xed, 0x80, 0x86, 0x07, 0x2b, 0xa1, 0xe7, 0xcc, 0x23, 0x58, 0xba,
+                0xec, 0xa1, 0x34, 0xc8, 0x25, 0xa7,
+            ]
+        );
+    }
+
+    #[cfg(feature = "crypto")]
+    #[test]
+    fn sha512_abc() {
+        let digest = TpmDigest::hash(AlgorithmId::SHA512, &[b"a", b"bc"]).unwrap();
+        assert_eq!(
+            digest.digest.as_slice(),
+            [
+                0xdd, 0xaf, 0x35, 0xa1, 0x93, 0x61, 0x7a, 0xba, 0xcc, 0x41, 0x73, 0x49, 0xae, 0x20,
+                0x41, 0x31, 0x12, 0xe6, 0xfa, 0x4e, 0x89, 0xa9, 0x7e, 0xa2, 0x0a, 0x9e, 0xee, 0xe6,
+                0x4b, 0x55, 0xd3, 0x9a, 0x21, 0x92, 0x99, 0x2a, 0x27, 0x4f, 0xc1, 0xa8, 0x36, 0xba,
+                0x3c, 0x23, 0xa3, 0xfe, 0xeb, 0xbd, 0x45, 0x4d, 0x44, 0x23, 0x64, 0x3c, 0xe8, 0x0e,
+                0x2a, 0x9a, 0xc9, 0x4f, 0xa5, 0x4c, 0xa4, 0x9f,
+            ]
+        );
+    }
+
+    #[cfg(feature = "crypto")]
+    #[test]
+    fn sha384_bank_extends() {
+        let measurement = TpmDigest::hash(AlgorithmId::SHA384, &[b"abc"]).unwrap();
+        let pcr = predict_pcr(
+            TpmDigest::zero(AlgorithmId::SHA384).unwrap(),
+            &[measurement],
+        )
+        .unwrap();
+        let expected = TpmDigest::hash(
+            AlgorithmId::SHA384,
+            &[&[0; 48], measurement.digest.as_slice()],
+        )
+        .unwrap();
+        assert_eq!(pcr, expected);
+    }
+
+    #[test]
+    fn unknown_algorithm_is_unsupported() {
+        assert!(matches!(
+            TpmDigest::hash(AlgorithmId::SM3_256, &[b"abc"]),
+            Err(TpmError::UnsupportedAlgorithm(AlgorithmId::SM3_256))
+        ));
+    }
+
+    #[test]
+    fn log_digest_converts() {
+        let digest = TpmDigest::try_from((AlgorithmId::SHA1, &[0x11; 20][..])).unwrap();
+        assert_eq!(digest, TpmDigest::sha1([0x11; 20]));
+    }
+
+    #[test]
+    fn log_digest_of_the_wrong_size_is_rejected() {
+        assert_eq!(
+            TpmDigest::try_from((AlgorithmId::SHA256, &[0x11; 20][..])),
+            Err(TpmError::DigestSize {
+                expected: 32,
+                actual: 20,
+            })
+        );
+    }
+
+    #[test]
+    fn log_digest_of_an_unknown_algorithm_is_unsupported() {
+        assert_eq!(
+            TpmDigest::try_from((alg::TPM_ALG_NULL, &[0x11; 32][..])),
+            Err(TpmError::UnsupportedAlgorithm(alg::TPM_ALG_NULL))
+        );
+    }
+
+    #[test]
+    fn drtm_pcrs_are_labelled() {
+        assert_eq!(pcr_purpose(17), "DRTM");
+        assert_eq!(pcr_purpose(18), "DRTM, trusted OS start-up code");
+        assert!(
+            DRTM_PCRS
+                .clone()
+                .all(|pcr_index| pcr_purpose(pcr_index).starts_with("DRTM"))
+        );
+        assert!(!pcr_purpose(16).starts_with("DRTM"));
+    }
+
+    #[test]
+    fn pcr_read_all_banks_past_the_bitmap_reads_nothing() {
+        // `TPM_CAP_PCRS` with a SHA-1 bank of 24 PCRs
+        let mut tcg = MockTransport::success(&[
+            0x00, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x01, 0x00, 0x04, 0x03, 0xff, 0xff,
+            0xff,
+        ]);
+        assert_eq!(pcr_read_all_banks(&mut tcg, 40), Ok(Vec::new()));
+        assert_eq!(tcg.commands.len(), 1);
+    }
+
+    #[test]
+    fn predict_pcr_matches_a_hand_computed_reference() {
+        // SHA-1 of "vmlinuz" and of "initrd.img"
+        let kernel = TpmDigest::sha1([
+            0x08, 0x1b, 0x67, 0x54, 0x53, 0x2b, 0x40, 0x22, 0xdb, 0x4a, 0xbe, 0xce, 0x05, 0x14,
+            0x07, 0x25, 0xe1, 0x13, 0xc5, 0x77,
+        ]);
+        let initrd = TpmDigest::sha1([
+            0x6e, 0xfe, 0x78, 0x09, 0xe3, 0x64, 0x92, 0xee, 0xbe, 0x2d, 0x60, 0x15, 0x41, 0xde,
+            0x71, 0xd2, 0x15, 0x5b, 0x13, 0x31,
+        ]);
+        let initial = TpmDigest::zero(AlgorithmId::SHA1).unwrap();
+        assert_eq!(
+            predict_pcr(initial, &[kernel, initrd]),
+            Ok(TpmDigest::sha1([
+                0x48, 0xc9, 0x7c, 0x3d, 0x75, 0x88, 0x54, 0x5b, 0x3e, 0x62, 0x44, 0xd2, 0xbe, 0xab,
+                0x8a, 0xb2, 0x72, 0x89, 0x70, 0xc5,
+            ]))
+        );
+    }
+
+    #[test]
+    fn predict_pcr_without_measurements_is_the_initial_value() {
+        let initial = TpmDigest::sha1([0x11; 20]);
+        assert_eq!(predict_pcr(initial, &[]), Ok(initial));
+    }
+
+    #[test]
+    fn predict_pcr_needs_measurements_in_the_same_bank() {
+        let initial = TpmDigest::zero(AlgorithmId::SHA256).unwrap();
+        assert_eq!(
+            predict_pcr(initial, &[TpmDigest::sha1([0x11; 20])]),
+            Err(TpmError::AlgorithmMismatch {
+                expected: AlgorithmId::SHA256,
+                actual: AlgorithmId::SHA1,
+            })
+        );
+    }
+
+    #[test]
+    fn sha1_extend_known_answer() {
+        let mut pcr = TpmDigest::zero(AlgorithmId::SHA1).unwrap();
+        pcr.extend(&TpmDigest::hash(AlgorithmId::SHA1, &[b"abc"]).unwrap())
+            .unwrap();
+        assert_eq!(
+            pcr,
+            TpmDigest::sha1([
+                0xcc, 0xd5, 0xbd, 0x41, 0x45, 0x8d, 0xe6, 0x44, 0xac, 0x34, 0xa2, 0x47, 0x8b, 0x58,
+                0xff, 0x81, 0x9b, 0xef, 0x5a, 0xcf,
+            ])
+        );
+    }
+
+    #[cfg(feature = "crypto")]
+    #[test]
+    fn sha256_extend_known_answer() {
+        let mut pcr = TpmDigest::zero(AlgorithmId::SHA256).unwrap();
+        pcr.extend(&TpmDigest::hash(AlgorithmId::SHA256, &[b"abc"]).unwrap())
+            .unwrap();
+        assert_eq!(
+            pcr.digest.as_slice(),
+            [
+                0x58, 0x9f, 0x9f, 0xfe, 0xd4, 0xc4, 0x77, 0x96, 0x6b, 0xfb, 0x8d, 0x41, 0xf3, 0x78,
+                0x95, 0xb0, 0x8c, 0x69, 0x04, 0x7d, 0xf8, 0xf9, 0x11, 0xd6, 0xf3, 0xb5, 0x7f, 0xbe,
+                0x08, 0xfa, 0xee, 0x8d,
+            ]
+        );
+    }
+
+    #[cfg(not(feature = "crypto"))]
+    #[test]
+    fn sha256_extend_needs_the_crypto_feature() {
+        let mut pcr = TpmDigest::zero(AlgorithmId::SHA256).unwrap();
+        let measurement = TpmDigest::new(AlgorithmId::SHA256, &[0x11; 32]).unwrap();
+        assert_eq!(
+            pcr.extend(&measurement),
+            Err(TpmError::UnsupportedAlgorithm(AlgorithmId::SHA256))
+        );
+    }
+
+    #[test]
+    fn extend_rejects_a_digest_of_the_wrong_size() {
+        let mut pcr = TpmDigest::zero(AlgorithmId::SHA1).unwrap();
+        let short = TpmDigest::new(AlgorithmId::SHA1, &[0x11; 19]).unwrap();
+        assert_eq!(
+            pcr.extend(&short),
+            Err(TpmError::DigestSize {
+                expected: 20,
+                actual: 19,
+            })
+        );
+        assert_eq!(pcr, TpmDigest::zero(AlgorithmId::SHA1).unwrap());
+    }
+
+    /// `TPM_PT_PCR_RESET_L0` saying only PCRs 16 and 23 can be reset
+    fn resettable_response() -> Vec<u8> {
+        let mut parameters = Vec::from([0, 0, 0, 0, 0x07, 0, 0, 0, 0x01, 0, 0, 0, 0x02, 0x03]);
+        parameters.extend_from_slice(&[0x00, 0x00, 0x81]);
+        MockTransport::response_bytes(ResponseCode::SUCCESS, &parameters)
+    }
+
+    #[test]
+    fn pcr_reset_resets_the_debug_pcr() {
+        let mut tcg = MockTransport::default()
+            .expect(
+                get_capability_command(TPM_CAP_PCR_PROPERTIES, TPM_PT_PCR_RESET_L0),
+                resettable_response(),
+            )
+            .expect(
+                [
+                    0x80, 0x02, 0, 0, 0, 0x1b, 0, 0, 0x01, 0x3d, 0, 0, 0, 0x10, 0, 0, 0, 0x09,
+                    0x40, 0, 0, 0x09, 0, 0, 0, 0, 0,
+                ],
+                success_response(),
+            );
+        assert_eq!(pcr_reset(&mut tcg, DEBUG_PCR), Ok(()));
+        tcg.assert_done();
+    }
+
+    #[test]
+    fn pcr_reset_refuses_pcrs_locality_0_cant_reset() {
+        for pcr_index in [0, 7, 40] {
+            let mut tcg = MockTransport::new(resettable_response());
+            assert_eq!(
+                pcr_reset(&mut tcg, pcr_index),
+                Err(TpmError::PcrNotResettable(pcr_index))
+            );
+            // Only the capability was read
+            assert_eq!(tcg.commands.len(), 1);
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use core::fmt;
+
+use uefi::proto::tcg::AlgorithmId;
+
+use super::{
+    TpmError, alg,
+    marshal::{Reader, Writer},
+};
+
+/// Enough bytes for 32 PCRs. TPMs with 24 PCRs use 3 of them.
+pub const PCR_SELECT_MAX: usize = 4;
+/// The most banks a `TPML_PCR_SELECTION` can hold (one per hash algorithm)
+pub const MAX_PCR_BANKS: usize = 8;
+
+/// `TPMS_PCR_SELECTION`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PcrSelection {
+    pub hash: AlgorithmId,
+    pub size_of_select: u8,
+    pub pcr_select: [u8; PCR_SELECT_MAX],
+}
+
+impl PcrSelection {
+    /// An empty selection big enough for 24 PCRs
+    pub const fn new(hash: AlgorithmId) -> Self {
+        Self {
+            hash,
+            size_of_select: 3,
+            pcr_select: [0; PCR_SELECT_MAX],
+        }
+    }
+
+    pub fn with_pcr(mut self, pcr_index: u32) -> Result<Self, TpmError> {
+        self.select(pcr_index)?;
+        Ok(self)
+    }
+
+    /// Fails with [`TpmError::PcrOutOfRange`] for PCR 32 and up
+    pub fn select(&mut self, pcr_index: u32) -> Result<(), TpmError> {
+        let byte = pcr_index as usize / 8;
+        if byte >= PCR_SELECT_MAX {
+            return Err(TpmError::PcrOutOfRange(pcr_index));
+        }
+        self.pcr_select[byte] |= 1 << (pcr_index % 8);
+        if byte >= self.size_of_select as usize {
+            self.size_of_select = byte as u8 + 1;
+        }
+        Ok(())
+    }
+
+    pub fn is_selected(&self, pcr_index: u32) -> bool {
+        let byte = pcr_index as usize / 8;
+        byte < self.size_of_select as usize && self.pcr_select[byte] & (1 << (pcr_index % 8)) != 0
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pcr_select.iter().all(|byte| *byte == 0)
+    }
+
+    /// `pcrSelect` as one number, with PCR `n` in bit `n`
+    pub fn bitmap(&self) -> u32 {
+        // Bytes past `size_of_select` are always zero
+        u32::from_le_bytes(self.pcr_select)
+    }
+
+    /// The selected PCR indexes, in increasing order
+    pub fn pcrs(&self) -> impl Iterator<Item = u32> + '_ {
+        (0..self.size_of_select as u32 * 8).filter(|i| self.is_selected(*i))
+    }
+
+    pub fn read(reader: &mut Reader<'_>) -> Result<Self, TpmError> {
+        let hash = AlgorithmId(reader.u16()?);
+        let size_of_select = reader.u8()?;
+        let mut selection = Self::new(hash);
+        selection.size_of_select = size_of_select;
+        selection
+            .pcr_select
+            .get_mut(..size_of_select as usize)
+            .ok_or(TpmError::Malformed)?
+            .copy_from_slice(reader.bytes(size_of_select as usize)?);
+        Ok(selection)
+    }
+
+    pub fn write(&self, writer: &mut Writer<'_>) -> Result<(), TpmError> {
+        writer.u16(self.hash.0)?;
+        writer.u8(self.size_of_select)?;
+        writer.bytes(&self.pcr_select[..self.size_of_select as usize])
+    }
+}
+
+/// `TPML_PCR_SELECTION`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PcrSelectionList {
+    selections: [PcrSelection; MAX_PCR_BANKS],
+    count: usize,
+}
+
+impl Default for PcrSelectionList {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PcrSelectionList {
+    pub const fn new() -> Self {
+        Self {
+            selections: [PcrSelection::new(AlgorithmId(0)); MAX_PCR_BANKS],
+            count: 0,
+        }
+    }
+
+    /// Returns the selection back if the list is full
+    pub fn push(&mut self, selection: PcrSelection) -> Result<(), PcrSelection> {
+        if self.count == MAX_PCR_BANKS {
+            return Err(selection);
+        }
+        self.selections[self.count] = selection;
+        self.count += 1;
+        Ok(())
+    }
+
+    pub fn as_slice(&self) -> &[PcrSelection] {
+        &self.selections[..self.count]
+    }
+
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    pub fn read(reader: &mut Reader<'_>) -> Result<Self, TpmError> {
+        let mut list = Self::new();
+        for _ in 0..reader.u32()? {
+            list.push(PcrSelection::read(reader)?)
+                .map_err(|_| TpmError::Malformed)?;
+        }
+        Ok(list)
+    }
+
+    /// Splits the selection into the PCRs the TPM has allocated according to `allocation` (from
+    /// [`get_pcr_allocation`]) and the ones it would silently leave out, since they are in a bank
+    /// that isn't active. Returns `(effective, dropped)`, leaving out banks with nothing selected.
+    ///
+    /// [`get_pcr_allocation`]: super::capability::get_pcr_allocation
+    pub fn intersect(&self, allocation: &PcrSelectionList) -> (Self, Self) {
+        let mut effective = Self::new();
+        let mut dropped = Self::new();
+        for requested in self.as_slice() {
+            let allocated = allocation
+                .as_slice()
+                .iter()
+                .find(|bank| bank.hash == requested.hash);
+            let mut kept = PcrSelection::new(requested.hash);
+            let mut missing = PcrSelection::new(requested.hash);
+            for pcr_index in requested.pcrs() {
+                // Can't fail, since the index is from a selection
+                let _ = if allocated.is_some_and(|bank| bank.is_selected(pcr_index)) {
+                    kept.select(pcr_index)
+                } else {
+                    missing.select(pcr_index)
+                };
+            }
+            // Neither can overflow, since they have at most as many banks as `self`
+            if !kept.is_empty() {
+                let _ = effective.push(kept);
+            }
+            if !missing.is_empty() {
+                let _ = dropped.push(missing);
+            }
+        }
+        (effective, dropped)
+    }
+
+    pub fn write(&self, writer: &mut Writer<'_>) -> Result<(), TpmError> {
+        writer.u32(self.count as u32)?;
+        for selection in self.as_slice() {
+            selection.write(writer)?;
+        }
+        Ok(())
+    }
+
+    /// Something you can put in a log message, like `SHA256: PCR[0,1,7]`
+    pub fn display(&self) -> DisplayPcrSelectionList<'_> {
+        DisplayPcrSelectionList(self)
+    }
+}
+
+/// Formats the selection as e.g. `SHA256: PCR[0,1,2,3,4,5,6,7]; SHA384: PCR[0,1,7]`
+pub fn format_pcr_selection_list<W: fmt::Write>(
+    selection: &PcrSelectionList,
+    writer: &mut W,
+) -> fmt::Result {
+    if selection.is_empty() {
+        return writer.write_str("(empty)");
+    }
+    for (i, bank) in selection.as_slice().iter().enumerate() {
+        if i > 0 {
+            writer.write_str("; ")?;
+        }
+        match alg::name(bank.hash) {
+            Some(name) => writer.write_str(name)?,
+            None => write!(writer, "{:#06x}", bank.hash.0)?,
+        }
+        writer.write_str(": PCR[")?;
+        for (i, pcr_index) in bank.pcrs().enumerate() {
+            if i > 0 {
+                writer.write_char(',')?;
+            }
+            write!(writer, "{pcr_index}")?;
+        }
+        writer.write_char(']')?;
+    }
+    Ok(())
+}
+
+pub struct DisplayPcrSelectionList<'a>(&'a PcrSelectionList);
+
+impl fmt::Display for DisplayPcrSelectionList<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        format_pcr_selection_list(self.0, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::string::ToString;
+
+    use super::*;
+
+    fn list(selections: &[PcrSelection]) -> PcrSelectionList {
+        let mut list = PcrSelectionList::new();
+        for selection in selections {
+            list.push(*selection).unwrap();
+        }
+        list
+    }
+
+    #[test]
+    fn unallocated_banks_are_dropped() {
+        let sha1 = PcrSelection::new(AlgorithmId::SHA1)
+            .with_pcr(0)
+            .unwrap()
+            .with_pcr(7)
+            .unwrap();
+        let sha256 = PcrSelection::new(AlgorithmId::SHA256).with_pcr(7).unwrap();
+        let mut all = PcrSelection::new(AlgorithmId::SHA256);
+        for pcr_index in 0..24 {
+            all.select(pcr_index).unwrap();
+        }
+
+        let (effective, dropped) = list(&[sha1, sha256]).intersect(&list(&[all]));
+        assert_eq!(effective, list(&[sha256]));
+        assert_eq!(dropped, list(&[sha1]));
+        assert_eq!(dropped.display().to_string(), "SHA1: PCR[0,7]");
+    }
+
+    #[test]
+    fn unallocated_pcrs_are_dropped() {
+        let requested = PcrSelection::new(AlgorithmId::SHA256)
+            .with_pcr(7)
+            .unwrap()
+            .with_pcr(23)
+            .unwrap();
+        let allocated = PcrSelection::new(AlgorithmId::SHA256).with_pcr(7).unwrap();
+        let (effective, dropped) = list(&[requested]).intersect(&list(&[allocated]));
+        assert_eq!(effective, list(&[allocated]));
+        assert_eq!(dropped.display().to_string(), "SHA256: PCR[23]");
+    }
+
+    #[test]
+    fn pcrs_past_31_are_out_of_range() {
+        let mut selection = PcrSelection::new(AlgorithmId::SHA256);
+        assert_eq!(selection.select(31), Ok(()));
+        assert_eq!(selection.size_of_select, 4);
+        assert_eq!(selection.select(32), Err(TpmError::PcrOutOfRange(32)));
+        assert_eq!(
+            PcrSelection::new(AlgorithmId::SHA256).with_pcr(u32::MAX),
+            Err(TpmError::PcrOutOfRange(u32::MAX))
+        );
+        assert_eq!(selection.bitmap(), 1 << 31);
+    }
+}
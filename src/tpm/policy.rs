@@ -0,0 +1,578 @@
+use alloc::vec::Vec;
+
+use uefi::proto::tcg::AlgorithmId;
+
+use super::{
+    BUFFER_SIZE, CommandCode, TPM_ST_NO_SESSIONS, TPM_ST_SESSIONS, TpmError, TpmTransport,
+    alg::{self, TPM_ALG_ECDSA, TPM_ALG_NULL, TPM_ALG_RSAPSS, TPM_ALG_RSASSA},
+    auth::{AuthCommand, write_auth_area},
+    begin_command, finish_command,
+    marshal::{Reader, Writer},
+    pcr::TpmDigest,
+    pcr_selection::PcrSelectionList,
+    submit,
+    tpm2b::{Tpm2b, Tpm2bDigest},
+};
+
+/// `TPMT_SIGNATURE`
+#[derive(Debug, Clone, Copy)]
+pub enum Signature<'a> {
+    Rsassa {
+        hash: AlgorithmId,
+        signature: &'a [u8],
+    },
+    Rsapss {
+        hash: AlgorithmId,
+        signature: &'a [u8],
+    },
+    Ecdsa {
+        hash: AlgorithmId,
+        r: &'a [u8],
+        s: &'a [u8],
+    },
+    Null,
+}
+
+impl Signature<'_> {
+    pub fn write(&self, writer: &mut Writer<'_>) -> Result<(), TpmError> {
+        match *self {
+            Self::Rsassa { hash, signature } => {
+                writer.u16(TPM_ALG_RSASSA.0)?;
+                writer.u16(hash.0)?;
+                writer.tpm2b(signature)
+            }
+            Self::Rsapss { hash, signature } => {
+                writer.u16(TPM_ALG_RSAPSS.0)?;
+                writer.u16(hash.0)?;
+                writer.tpm2b(signature)
+            }
+            Self::Ecdsa { hash, r, s } => {
+                writer.u16(TPM_ALG_ECDSA.0)?;
+                writer.u16(hash.0)?;
+                writer.tpm2b(r)?;
+                writer.tpm2b(s)
+            }
+            Self::Null => writer.u16(TPM_ALG_NULL.0),
+        }
+    }
+}
+
+/// `TPMT_TK_AUTH`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AuthTicket {
+    pub tag: u16,
+    pub hierarchy: u32,
+    pub digest: Tpm2bDigest,
+}
+
+/// What `PolicySigned` and `PolicySecret` return. Both are empty unless `expiration` was negative.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PolicyTicket {
+    /// `TPM2B_TIMEOUT`, an implementation-specific value to pass to `PolicyTicket`
+    pub timeout: Tpm2b<8>,
+    pub policy_ticket: AuthTicket,
+}
+
+impl PolicyTicket {
+    fn read(reader: &mut Reader<'_>) -> Result<Self, TpmError> {
+        Ok(Self {
+            timeout: Tpm2b::read(reader)?,
+            policy_ticket: AuthTicket {
+                tag: reader.u16()?,
+                hierarchy: reader.u32()?,
+                digest: Tpm2b::read(reader)?,
+            },
+        })
+    }
+}
+
+/// The parameters `PolicySigned` and `PolicySecret` have in common
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PolicyAuthorization<'a> {
+    /// The session's `nonceTPM`, to limit the authorization to this session
+    pub nonce: &'a [u8],
+    /// Limits the authorization to a specific command and parameters
+    pub cp_hash: &'a [u8],
+    pub policy_ref: &'a [u8],
+    /// In seconds. Negative values ask the TPM for a ticket.
+    pub expiration: i32,
+}
+
+impl PolicyAuthorization<'_> {
+    fn write(&self, writer: &mut Writer<'_>) -> Result<(), TpmError> {
+        writer.tpm2b(self.nonce)?;
+        writer.tpm2b(self.cp_hash)?;
+        writer.tpm2b(self.policy_ref)?;
+        writer.u32(self.expiration as u32)
+    }
+}
+
+/// `TPM2_PolicySigned`: the policy requires `auth` to be a signature from the key loaded at
+/// `auth_object` over `nonce || expiration || cp_hash || policy_ref`
+pub fn policy_signed(
+    tcg: &mut dyn TpmTransport,
+    auth_object: u32,
+    session: u32,
+    authorization: &PolicyAuthorization<'_>,
+    auth: &Signature<'_>,
+) -> Result<PolicyTicket, TpmError> {
+    let mut command_buffer = [0; BUFFER_SIZE];
+    let mut writer = Writer::new(&mut command_buffer);
+    begin_command(&mut writer, TPM_ST_NO_SESSIONS, CommandCode::POLICY_SIGNED)?;
+    writer.u32(auth_object)?;
+    writer.u32(session)?;
+    authorization.write(&mut writer)?;
+    auth.write(&mut writer)?;
+    let mut response_buffer = [0; BUFFER_SIZE];
+    let response = submit(tcg, finish_command(writer), &mut response_buffer)
+        .map_err(|e| e.with_handle(auth_object))?;
+    PolicyTicket::read(&mut response.parameters()?)
+}
+
+/// `TPM2_PolicySecret`: the policy requires knowing the authorization value of `auth_handle`,
+/// proven by `auth`
+pub fn policy_secret(
+    tcg: &mut dyn TpmTransport,
+    auth_handle: u32,
+    auth: &AuthCommand<'_>,
+    session: u32,
+    authorization: &PolicyAuthorization<'_>,
+) -> Result<PolicyTicket, TpmError> {
+    let mut command_buffer = [0; BUFFER_SIZE];
+    let mut writer = Writer::new(&mut command_buffer);
+    begin_command(&mut writer, TPM_ST_SESSIONS, CommandCode::POLICY_SECRET)?;
+    writer.u32(auth_handle)?;
+    writer.u32(session)?;
+    write_auth_area(&mut writer, core::slice::from_ref(auth))?;
+    authorization.write(&mut writer)?;
+    let mut response_buffer = [0; BUFFER_SIZE];
+    let response = submit(tcg, finish_command(writer), &mut response_buffer)
+        .map_err(|e| e.with_handle(auth_handle))?;
+    PolicyTicket::read(&mut response.parameters()?)
+}
+
+/// `TPM2_PolicyNvWritten`: the policy requires the NV index it's used with to have been written
+/// (`written_set`) or never written. Like a one-time flag: a secret sealed with `written_set`
+/// false can only be unsealed until the index is first written.
+pub fn policy_nv_written(
+    tcg: &mut dyn TpmTransport,
+    session: u32,
+    written_set: bool,
+) -> Result<(), TpmError> {
+    let mut command_buffer = [0; 15];
+    let mut writer = Writer::new(&mut command_buffer);
+    begin_command(
+        &mut writer,
+        TPM_ST_NO_SESSIONS,
+        CommandCode::POLICY_NV_WRITTEN,
+    )?;
+    writer.u32(session)?;
+    writer.u8(written_set as u8)?;
+    let mut response_buffer = [0; BUFFER_SIZE];
+    submit(tcg, finish_command(writer), &mut response_buffer)
+        .map_err(|e| e.with_handle(session))?;
+    Ok(())
+}
+
+/// What [`policy_nv_written`] makes the session's digest, starting from `policy_digest`:
+/// `H(policyDigest || TPM_CC_PolicyNvWritten || writtenSet)`. For computing an index's or
+/// object's `authPolicy` without a TPM.
+pub fn policy_nv_written_digest(
+    policy_digest: &TpmDigest,
+    written_set: bool,
+) -> Result<TpmDigest, TpmError> {
+    TpmDigest::hash(
+        policy_digest.algorithm,
+        &[
+            policy_digest.digest.as_slice(),
+            &CommandCode::POLICY_NV_WRITTEN.0.to_be_bytes(),
+            &[written_set as u8],
+        ],
+    )
+}
+
+/// `TPM2_PolicyPCR`: the policy requires the PCRs in `pcrs` to hash to `pcr_digest`, the session's
+/// hash of their values concatenated (see [`pcr_values_digest`]). With an empty `pcr_digest` the
+/// TPM uses the current values, which only makes sense in a trial session.
+pub fn policy_pcr(
+    tcg: &mut dyn TpmTransport,
+    session: u32,
+    pcr_digest: &[u8],
+    pcrs: &PcrSelectionList,
+) -> Result<(), TpmError> {
+    let mut command_buffer = [0; BUFFER_SIZE];
+    let mut writer = Writer::new(&mut command_buffer);
+    begin_command(&mut writer, TPM_ST_NO_SESSIONS, CommandCode::POLICY_PCR)?;
+    writer.u32(session)?;
+    writer.tpm2b(pcr_digest)?;
+    pcrs.write(&mut writer)?;
+    let mut response_buffer = [0; BUFFER_SIZE];
+    submit(tcg, finish_command(writer), &mut response_buffer)
+        .map_err(|e| e.with_handle(session))?;
+    Ok(())
+}
+
+/// `TPM2_PolicyGetDigest`: the session's current policy digest
+pub fn policy_get_digest(
+    tcg: &mut dyn TpmTransport,
+    session: u32,
+) -> Result<Tpm2bDigest, TpmError> {
+    let mut command_buffer = [0; 14];
+    let mut writer = Writer::new(&mut command_buffer);
+    begin_command(
+        &mut writer,
+        TPM_ST_NO_SESSIONS,
+        CommandCode::POLICY_GET_DIGEST,
+    )?;
+    writer.u32(session)?;
+    let mut response_buffer = [0; BUFFER_SIZE];
+    let response = submit(tcg, finish_command(writer), &mut response_buffer)
+        .map_err(|e| e.with_handle(session))?;
+    Tpm2b::read(&mut response.parameters()?)
+}
+
+/// The `pcrDigest` for [`policy_pcr`]: the hash of the PCR values, in the order the selection
+/// lists them (bank by bank, then by increasing PCR index)
+pub fn pcr_values_digest(
+    algorithm: AlgorithmId,
+    values: &[TpmDigest],
+) -> Result<TpmDigest, TpmError> {
+    let parts = values
+        .iter()
+        .map(|value| value.digest.as_slice())
+        .collect::<Vec<_>>();
+    TpmDigest::hash(algorithm, &parts)
+}
+
+/// What [`policy_pcr`] makes the session's digest, starting from `policy_digest`:
+/// `H(policyDigest || TPM_CC_PolicyPCR || pcrs || pcrDigest)`
+pub fn policy_pcr_digest(
+    policy_digest: &TpmDigest,
+    pcrs: &PcrSelectionList,
+    pcr_digest: &[u8],
+) -> Result<TpmDigest, TpmError> {
+    let mut selection_buffer = [0; BUFFER_SIZE];
+    let mut writer = Writer::new(&mut selection_buffer);
+    pcrs.write(&mut writer)?;
+    TpmDigest::hash(
+        policy_digest.algorithm,
+        &[
+            policy_digest.digest.as_slice(),
+            &CommandCode::POLICY_PCR.0.to_be_bytes(),
+            writer.into_slice(),
+            pcr_digest,
+        ],
+    )
+}
+
+/// Sends [`policy_pcr`] and checks the session's digest changed to what [`policy_pcr_digest`]
+/// says it should. `session_hash` is the hash algorithm the session was started with, and
+/// `pcr_digest` has to be one of its digests: the empty one that has the TPM use the current
+/// values can't be predicted, so it fails with [`TpmError::DigestSize`] before anything is sent.
+/// `false` means our policy digests wouldn't match the TPM's, so nothing sealed with them could be
+/// unsealed.
+pub fn check_policy_pcr(
+    tcg: &mut dyn TpmTransport,
+    session: u32,
+    session_hash: AlgorithmId,
+    pcr_digest: &[u8],
+    pcrs: &PcrSelectionList,
+) -> Result<bool, TpmError> {
+    let expected_size =
+        alg::digest_size(session_hash).ok_or(TpmError::UnsupportedAlgorithm(session_hash))?;
+    if pcr_digest.len() != expected_size {
+        return Err(TpmError::DigestSize {
+            expected: expected_size,
+            actual: pcr_digest.len(),
+        });
+    }
+    let before = TpmDigest {
+        algorithm: session_hash,
+        digest: policy_get_digest(tcg, session)?,
+    };
+    let expected = policy_pcr_digest(&before, pcrs, pcr_digest)?;
+    policy_pcr(tcg, session, pcr_digest, pcrs)?;
+    Ok(policy_get_digest(tcg, session)? == expected.digest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tpm::{
+        ResponseCode,
+        mock::{MockTransport, success_response},
+        pcr_selection::PcrSelection,
+    };
+
+    const SESSION: u32 = 0x0300_0000;
+
+    fn sha1_pcr_0() -> PcrSelectionList {
+        let mut pcrs = PcrSelectionList::new();
+        pcrs.push(PcrSelection::new(AlgorithmId::SHA1).with_pcr(0).unwrap())
+            .unwrap();
+        pcrs
+    }
+
+    /// `pcrDigest` for SHA-1 PCR 0 holding zeros
+    const SHA1_PCR_DIGEST: [u8; 20] = [
+        0x67, 0x68, 0x03, 0x3e, 0x21, 0x64, 0x68, 0x24, 0x7b, 0xd0, 0x31, 0xa0, 0xa2, 0xd9, 0x87,
+        0x6d, 0x79, 0x81, 0x8f, 0x8f,
+    ];
+
+    /// `H(zeros || TPM_CC_PolicyPCR || pcrs || pcrDigest)` for SHA-1 PCR 0 holding zeros
+    const SHA1_POLICY: [u8; 20] = [
+        0x50, 0xdd, 0xb2, 0x7f, 0x89, 0x69, 0x68, 0x95, 0xa5, 0xcf, 0xe4, 0xd9, 0x15, 0xf4, 0x49,
+        0x28, 0x6a, 0xfc, 0x39, 0x19,
+    ];
+
+    fn policy_get_digest_response(digest: &[u8]) -> Vec<u8> {
+        let mut parameters = (digest.len() as u16).to_be_bytes().to_vec();
+        parameters.extend_from_slice(digest);
+        MockTransport::response_bytes(ResponseCode::SUCCESS, &parameters)
+    }
+
+    #[test]
+    fn pcr_values_digest_hashes_the_values_in_order() {
+        let zero = TpmDigest::zero(AlgorithmId::SHA1).unwrap();
+        let digest = pcr_values_digest(AlgorithmId::SHA1, &[zero]).unwrap();
+        assert_eq!(digest.digest.as_slice(), SHA1_PCR_DIGEST);
+    }
+
+    #[test]
+    fn policy_pcr_digest_sha1() {
+        let zero = TpmDigest::zero(AlgorithmId::SHA1).unwrap();
+        let digest = policy_pcr_digest(&zero, &sha1_pcr_0(), &SHA1_PCR_DIGEST).unwrap();
+        assert_eq!(digest.digest.as_slice(), SHA1_POLICY);
+    }
+
+    #[cfg(feature = "crypto")]
+    #[test]
+    fn policy_pcr_digest_sha256() {
+        let zero = TpmDigest::zero(AlgorithmId::SHA256).unwrap();
+        let mut pcrs = PcrSelectionList::new();
+        pcrs.push(
+            PcrSelection::new(AlgorithmId::SHA256)
+                .with_pcr(0)
+                .unwrap()
+                .with_pcr(1)
+                .unwrap()
+                .with_pcr(7)
+                .unwrap(),
+        )
+        .unwrap();
+        let pcr_digest = pcr_values_digest(AlgorithmId::SHA256, &[zero; 3]).unwrap();
+        assert_eq!(
+            pcr_digest.digest.as_slice(),
+            [
+                0x2e, 0xa9, 0xab, 0x91, 0x98, 0xd1, 0x63, 0x80, 0x07, 0x40, 0x0c, 0xd2, 0xc3, 0xbe,
+                0xf1, 0xcc, 0x74, 0x5b, 0x86, 0x4b, 0x76, 0x01, 0x1a, 0x0e, 0x1b, 0xc5, 0x21, 0x80,
+                0xac, 0x64, 0x52, 0xd4,
+            ]
+        );
+        let digest = policy_pcr_digest(&zero, &pcrs, pcr_digest.digest.as_slice()).unwrap();
+        assert_eq!(
+            digest.digest.as_slice(),
+            [
+                0x24, 0xfc, 0x94, 0x1c, 0xf5, 0x32, 0x01, 0xc2, 0x94, 0x7a, 0x72, 0x7b, 0x24, 0xc7,
+                0x37, 0x26, 0x0b, 0x06, 0xf4, 0x84, 0x75, 0xd9, 0x7b, 0x46, 0x1a, 0xf2, 0xa9, 0xba,
+                0xc2, 0xdd, 0xff, 0x78,
+            ]
+        );
+    }
+
+    #[test]
+    fn check_policy_pcr_matches_the_tpm() {
+        let mut tpm = MockTransport::default()
+            .expect(
+                [0x80, 0x01, 0x00, 0x00, 0x00, 0x0e, 0x00, 0x00, 0x01, 0x89],
+                policy_get_digest_response(&[0; 20]),
+            )
+            .expect(
+                [0x80, 0x01, 0x00, 0x00, 0x00, 0x2e, 0x00, 0x00, 0x01, 0x7f],
+                success_response(),
+            )
+            .expect(
+                [0x80, 0x01, 0x00, 0x00, 0x00, 0x0e, 0x00, 0x00, 0x01, 0x89],
+                policy_get_digest_response(&SHA1_POLICY),
+            );
+        let matches = check_policy_pcr(
+            &mut tpm,
+            SESSION,
+            AlgorithmId::SHA1,
+            &SHA1_PCR_DIGEST,
+            &sha1_pcr_0(),
+        )
+        .unwrap();
+        assert!(matches);
+        tpm.assert_done();
+    }
+
+    #[test]
+    fn check_policy_pcr_rejects_an_empty_pcr_digest() {
+        let mut tpm = MockTransport::default();
+        let result = check_policy_pcr(&mut tpm, SESSION, AlgorithmId::SHA1, &[], &sha1_pcr_0());
+        assert!(matches!(
+            result,
+            Err(TpmError::DigestSize {
+                expected: 20,
+                actual: 0
+            })
+        ));
+        assert!(tpm.commands.is_empty());
+    }
+
+    #[test]
+    fn policy_nv_written_encoding() {
+        let mut tpm = MockTransport::success(&[]);
+        policy_nv_written(&mut tpm, SESSION, true).unwrap();
+        policy_nv_written(&mut tpm, SESSION, false).unwrap();
+        assert_eq!(
+            tpm.commands,
+            [
+                // TPM_YES
+                [
+                    0x80, 0x01, 0x00, 0x00, 0x00, 0x0f, 0x00, 0x00, 0x01, 0x8f, 0x03, 0x00, 0x00,
+                    0x00, 0x01,
+                ],
+                // TPM_NO
+                [
+                    0x80, 0x01, 0x00, 0x00, 0x00, 0x0f, 0x00, 0x00, 0x01, 0x8f, 0x03, 0x00, 0x00,
+                    0x00, 0x00,
+                ],
+            ]
+        );
+    }
+
+    #[test]
+    fn policy_nv_written_digest_sha1() {
+        let zero = TpmDigest::zero(AlgorithmId::SHA1).unwrap();
+        assert_eq!(
+            policy_nv_written_digest(&zero, false)
+                .unwrap()
+                .digest
+                .as_slice(),
+            [
+                0x5a, 0x91, 0xe7, 0x10, 0x53, 0x86, 0xbd, 0x54, 0x7a, 0x15, 0xaa, 0xd4, 0x03, 0x69,
+                0xb1, 0xe2, 0x5e, 0x46, 0x28, 0x73,
+            ]
+        );
+        assert_eq!(
+            policy_nv_written_digest(&zero, true)
+                .unwrap()
+                .digest
+                .as_slice(),
+            [
+                0x30, 0x73, 0x48, 0xdf, 0x01, 0x17, 0x1a, 0x5f, 0x08, 0xeb, 0xed, 0x65, 0x94, 0xe6,
+                0xfd, 0xac, 0x84, 0x22, 0xe3, 0x09,
+            ]
+        );
+    }
+
+    #[cfg(feature = "crypto")]
+    #[test]
+    fn policy_nv_written_digest_sha256() {
+        let zero = TpmDigest::zero(AlgorithmId::SHA256).unwrap();
+        let digest = policy_nv_written_digest(&zero, true).unwrap();
+        assert_eq!(digest.algorithm, AlgorithmId::SHA256);
+        assert_eq!(
+            digest.digest.as_slice(),
+            [
+                0xf7, 0x88, 0x7d, 0x15, 0x8a, 0xe8, 0xd3, 0x8b, 0xe0, 0xac, 0x53, 0x19, 0xf3, 0x7a,
+                0x9e, 0x07, 0x61, 0x8b, 0xf5, 0x48, 0x85, 0x45, 0x3c, 0x7a, 0x54, 0xdd, 0xb0, 0xc6,
+                0xa6, 0x19, 0x3b, 0xeb,
+            ]
+        );
+        let digest = policy_nv_written_digest(&zero, false).unwrap();
+        assert_eq!(
+            digest.digest.as_slice(),
+            [
+                0x3c, 0x32, 0x63, 0x23, 0x67, 0x0e, 0x28, 0xad, 0x37, 0xbd, 0x57, 0xf6, 0x3b, 0x4c,
+                0xc3, 0x4d, 0x26, 0xab, 0x20, 0x5e, 0xf2, 0x2f, 0x27, 0x5c, 0x58, 0xd4, 0x7f, 0xab,
+                0x24, 0x85, 0x46, 0x6e,
+            ]
+        );
+    }
+
+    #[test]
+    fn policy_signed_encoding() {
+        let mut timeout_and_ticket = Vec::from([0x00, 0x08]);
+        timeout_and_ticket.extend_from_slice(&[0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x2c, 0x00]);
+        // TPM_ST_AUTH_SIGNED, TPM_RH_OWNER and a SHA-1 HMAC
+        timeout_and_ticket.extend_from_slice(&[0x80, 0x25, 0x40, 0x00, 0x00, 0x01, 0x00, 0x14]);
+        timeout_and_ticket.extend_from_slice(&[0x5a; 20]);
+        let mut tpm = MockTransport::success(&timeout_and_ticket);
+        let ticket = policy_signed(
+            &mut tpm,
+            0x8000_0001,
+            SESSION,
+            &PolicyAuthorization {
+                nonce: &[0x11, 0x22],
+                cp_hash: &[],
+                policy_ref: &[0x33],
+                expiration: -1,
+            },
+            &Signature::Rsassa {
+                hash: AlgorithmId::SHA256,
+                signature: &[0xaa, 0xbb],
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            tpm.commands,
+            [[
+                0x80, 0x01, 0x00, 0x00, 0x00, 0x27, 0x00, 0x00, 0x01, 0x60, 0x80, 0x00, 0x00, 0x01,
+                0x03, 0x00, 0x00, 0x00, 0x00, 0x02, 0x11, 0x22, 0x00, 0x00, 0x00, 0x01, 0x33, 0xff,
+                0xff, 0xff, 0xff, 0x00, 0x14, 0x00, 0x0b, 0x00, 0x02, 0xaa, 0xbb,
+            ]]
+        );
+        assert_eq!(
+            ticket.timeout.as_slice(),
+            [0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x2c, 0x00]
+        );
+        assert_eq!(ticket.policy_ticket.tag, 0x8025);
+        assert_eq!(ticket.policy_ticket.hierarchy, 0x4000_0001);
+        assert_eq!(ticket.policy_ticket.digest.as_slice(), [0x5a; 20]);
+    }
+
+    #[test]
+    fn policy_secret_encoding() {
+        // No timeout, and the NULL ticket: TPM_ST_AUTH_SECRET, TPM_RH_NULL and no digest
+        let mut tpm =
+            MockTransport::success(&[0x00, 0x00, 0x80, 0x23, 0x40, 0x00, 0x00, 0x07, 0x00, 0x00]);
+        let ticket = policy_secret(
+            &mut tpm,
+            0x4000_0001,
+            &AuthCommand::password(&[]),
+            SESSION,
+            &PolicyAuthorization::default(),
+        )
+        .unwrap();
+        assert_eq!(
+            tpm.commands,
+            [[
+                0x80, 0x02, 0x00, 0x00, 0x00, 0x29, 0x00, 0x00, 0x01, 0x51, 0x40, 0x00, 0x00, 0x01,
+                0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x09, 0x40, 0x00, 0x00, 0x09, 0x00, 0x00,
+                0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+            ]]
+        );
+        assert!(ticket.timeout.as_slice().is_empty());
+        assert_eq!(ticket.policy_ticket.tag, 0x8023);
+        assert_eq!(ticket.policy_ticket.hierarchy, 0x4000_0007);
+        assert!(ticket.policy_ticket.digest.as_slice().is_empty());
+    }
+
+    #[test]
+    fn policy_ticket_must_be_complete() {
+        let mut tpm = MockTransport::success(&[0x00, 0x00, 0x80, 0x23]);
+        assert!(matches!(
+            policy_secret(
+                &mut tpm,
+                0x4000_0001,
+                &AuthCommand::password(&[]),
+                SESSION,
+                &PolicyAuthorization::default(),
+            ),
+            Err(TpmError::UnexpectedEnd)
+        ));
+    }
+}
@@ -0,0 +1,314 @@
+//! First-boot provisioning: the EK, the SRK and an attestation identity key (AIK), each created
+//! once and kept at a persistent handle so later boots and the OS find the same keys.
+//!
+//! Every hierarchy is assumed to still have an empty password, like it does until someone takes
+//! ownership. Running it again reads back the keys that are already there instead of creating new
+//! ones.
+
+use core::fmt;
+
+use hex_slice::AsHex;
+use uefi::proto::tcg::AlgorithmId;
+
+use super::{
+    TPM_RH_ENDORSEMENT, TPM_RH_OWNER, TpmError, TpmHandle, TpmTransport,
+    alg::TPM_ALG_RSASSA,
+    auth::{AuthCommand, Password},
+    capability::{TpmPersistentHandle, list_persistent_handles},
+    ek::{EkAlgorithm, ek_template},
+    object::{create_primary, evict_control, flush_context, read_public},
+    public::{
+        PublicId, PublicParameters, Scheme, SymDefObject, TPMA_OBJECT_FIXED_PARENT,
+        TPMA_OBJECT_FIXED_TPM, TPMA_OBJECT_RESTRICTED, TPMA_OBJECT_SENSITIVE_DATA_ORIGIN,
+        TPMA_OBJECT_SIGN_ENCRYPT, TPMA_OBJECT_USER_WITH_AUTH, TpmtPublic,
+    },
+    self_test::self_test,
+    srk::make_persisted_srk,
+    tpm2b::Tpm2b,
+};
+
+/// Where the EK is kept, from the TCG provisioning guidance
+pub const EK_HANDLE: TpmPersistentHandle = TpmPersistentHandle(0x81010001);
+
+/// Where the AIK is kept, the next endorsement handle after the EK
+pub const AIK_HANDLE: TpmPersistentHandle = TpmPersistentHandle(0x81010002);
+
+/// `fixedTPM | fixedParent | sensitiveDataOrigin | userWithAuth | restricted | sign`
+pub const AIK_ATTRIBUTES: u32 = TPMA_OBJECT_FIXED_TPM
+    | TPMA_OBJECT_FIXED_PARENT
+    | TPMA_OBJECT_SENSITIVE_DATA_ORIGIN
+    | TPMA_OBJECT_USER_WITH_AUTH
+    | TPMA_OBJECT_RESTRICTED
+    | TPMA_OBJECT_SIGN_ENCRYPT;
+
+/// An RSA 2048 restricted signing key with RSASSA and SHA-256, so it can only sign quotes and
+/// other data the TPM produced itself
+pub fn aik_template_rsa2048() -> TpmtPublic {
+    TpmtPublic {
+        name_alg: AlgorithmId::SHA256,
+        object_attributes: AIK_ATTRIBUTES,
+        auth_policy: Tpm2b::default(),
+        parameters: PublicParameters::Rsa {
+            symmetric: SymDefObject::NULL,
+            scheme: Scheme::new(TPM_ALG_RSASSA, AlgorithmId::SHA256),
+            key_bits: 2048,
+            exponent: 0,
+        },
+        unique: PublicId::Rsa(Tpm2b::default()),
+    }
+}
+
+/// Creates a primary key from `template` under `hierarchy` and persists it at `handle`, unless
+/// something is already there. Returns the public area of whatever is at `handle`.
+fn persist_primary(
+    tcg: &mut dyn TpmTransport,
+    hierarchy: TpmHandle,
+    template: &TpmtPublic,
+    handle: TpmPersistentHandle,
+) -> Result<TpmtPublic, TpmError> {
+    if !list_persistent_handles(tcg)?.contains(&handle) {
+        let key = create_primary(tcg, hierarchy, &AuthCommand::password(&[]), &[], template)?;
+        // Persistent objects are always controlled by the owner, whichever hierarchy they're in
+        let persisted = evict_control(
+            tcg,
+            TPM_RH_OWNER,
+            &AuthCommand::password(&[]),
+            key.handle,
+            handle.0,
+        );
+        let flushed = flush_context(tcg, key.handle);
+        persisted?;
+        flushed?;
+    }
+    let (public, _name) = read_public(tcg, handle.0)?;
+    Ok(public)
+}
+
+/// The persistent keys and their public areas
+#[derive(Debug, Clone, Copy)]
+pub struct ProvisioningResult {
+    pub ek_handle: TpmPersistentHandle,
+    pub srk_handle: TpmPersistentHandle,
+    pub aik_handle: TpmPersistentHandle,
+    pub ek_public: TpmtPublic,
+    pub srk_public: TpmtPublic,
+    pub aik_public: TpmtPublic,
+}
+
+/// The RSA modulus or ECC point in hex
+struct PublicKey<'a>(&'a TpmtPublic);
+
+impl fmt::Display for PublicKey<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0.unique {
+            PublicId::Rsa(modulus) => write!(f, "n={:x}", modulus.as_slice().plain_hex(false)),
+            PublicId::Ecc { x, y } => write!(
+                f,
+                "x={:x} y={:x}",
+                x.as_slice().plain_hex(false),
+                y.as_slice().plain_hex(false)
+            ),
+            PublicId::Digest(digest) => write!(f, "{:x}", digest.as_slice().plain_hex(false)),
+        }
+    }
+}
+
+impl fmt::Display for ProvisioningResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "EK at {}: {}",
+            self.ek_handle,
+            PublicKey(&self.ek_public)
+        )?;
+        writeln!(
+            f,
+            "SRK at {}: {}",
+            self.srk_handle,
+            PublicKey(&self.srk_public)
+        )?;
+        write!(
+            f,
+            "AIK at {}: {}",
+            self.aik_handle,
+            PublicKey(&self.aik_public)
+        )
+    }
+}
+
+/// Runs the self test, then makes sure the EK, the SRK and the AIK are each persisted
+pub fn provision(tcg: &mut dyn TpmTransport) -> Result<ProvisioningResult, TpmError> {
+    self_test(tcg, false)?;
+    let ek_public = persist_primary(
+        tcg,
+        TPM_RH_ENDORSEMENT,
+        &ek_template(EkAlgorithm::Rsa2048),
+        EK_HANDLE,
+    )?;
+    let srk_handle = make_persisted_srk(tcg, &Password::default())?;
+    let (srk_public, _name) = read_public(tcg, srk_handle.0)?;
+    let aik_public = persist_primary(tcg, TPM_RH_ENDORSEMENT, &aik_template_rsa2048(), AIK_HANDLE)?;
+    Ok(ProvisioningResult {
+        ek_handle: EK_HANDLE,
+        srk_handle,
+        aik_handle: AIK_HANDLE,
+        ek_public,
+        srk_public,
+        aik_public,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::vec::Vec;
+
+    use super::*;
+    use crate::tpm::{
+        ResponseCode,
+        marshal::Writer,
+        mock::{
+            MockTransport, handles_response, list_handles_command, read_public_command,
+            read_public_name, read_public_response, success_response,
+        },
+        srk::{SRK_HANDLE, srk_template_rsa2048},
+    };
+
+    /// `TPM2_SelfTest(NO)`
+    const SELF_TEST: [u8; 11] = [0x80, 0x01, 0, 0, 0, 0x0b, 0, 0, 0x01, 0x43, 0x00];
+
+    fn ek_public() -> TpmtPublic {
+        TpmtPublic {
+            unique: PublicId::Rsa(Tpm2b::new(&[0xab, 0xcd]).unwrap()),
+            ..ek_template(EkAlgorithm::Rsa2048)
+        }
+    }
+
+    #[test]
+    fn provisioned_keys_are_read_back() {
+        let persisted = [SRK_HANDLE.0, EK_HANDLE.0, AIK_HANDLE.0];
+        let mut tcg = MockTransport::default()
+            .expect(SELF_TEST, success_response())
+            .expect(
+                list_handles_command(0x81000000),
+                handles_response(false, &persisted),
+            )
+            .expect(
+                read_public_command(EK_HANDLE.0),
+                read_public_response(&ek_public()),
+            )
+            .expect(
+                list_handles_command(0x81000000),
+                handles_response(false, &persisted),
+            )
+            .expect(
+                read_public_command(SRK_HANDLE.0),
+                read_public_response(&srk_template_rsa2048()),
+            )
+            .expect(
+                list_handles_command(0x81000000),
+                handles_response(false, &persisted),
+            )
+            .expect(
+                read_public_command(AIK_HANDLE.0),
+                read_public_response(&aik_template_rsa2048()),
+            );
+        let result = provision(&mut tcg).unwrap();
+        tcg.assert_done();
+        assert_eq!(result.ek_public, ek_public());
+        assert_eq!(result.srk_handle, SRK_HANDLE);
+        assert_eq!(result.srk_public, srk_template_rsa2048());
+        assert_eq!(result.aik_public, aik_template_rsa2048());
+        assert_eq!(
+            format!("{result}"),
+            "EK at 0x81010001: n=abcd\nSRK at 0x81000001: n=\nAIK at 0x81010002: n="
+        );
+    }
+
+    #[test]
+    fn missing_aik_is_created_and_persisted() {
+        let persisted = [SRK_HANDLE.0, EK_HANDLE.0];
+        let aik = aik_template_rsa2048();
+        let mut template = [0; 1024];
+        let mut writer = Writer::new(&mut template);
+        aik.write_tpm2b(&mut writer).unwrap();
+        // Handle, auth area, empty sensitive, template, outsideInfo and creationPCR
+        let create_size = 10 + 4 + 4 + 9 + 6 + writer.len() as u32 + 2 + 4;
+        let mut create_primary = Vec::from([0x80, 0x02]);
+        create_primary.extend_from_slice(&create_size.to_be_bytes());
+        create_primary.extend_from_slice(&[0, 0, 0x01, 0x31]);
+        create_primary.extend_from_slice(&TPM_RH_ENDORSEMENT.to_be_bytes());
+        let mut created = [0; 1024];
+        let mut writer = Writer::new(&mut created);
+        writer.u32(0x80000000).unwrap();
+        aik.write_tpm2b(&mut writer).unwrap();
+        // Empty creationData and creationHash, and a TPM_ST_CREATION ticket with no digest
+        writer
+            .bytes(&[0, 0, 0, 0, 0x80, 0x21, 0x40, 0, 0, 0x0b, 0, 0])
+            .unwrap();
+        writer.tpm2b(&read_public_name()).unwrap();
+        let created_len = writer.len();
+        let mut tcg = MockTransport::default()
+            .expect(SELF_TEST, success_response())
+            .expect(
+                list_handles_command(0x81000000),
+                handles_response(false, &persisted),
+            )
+            .expect(
+                read_public_command(EK_HANDLE.0),
+                read_public_response(&ek_public()),
+            )
+            .expect(
+                list_handles_command(0x81000000),
+                handles_response(false, &persisted),
+            )
+            .expect(
+                read_public_command(SRK_HANDLE.0),
+                read_public_response(&srk_template_rsa2048()),
+            )
+            .expect(
+                list_handles_command(0x81000000),
+                handles_response(false, &persisted),
+            )
+            .expect(
+                create_primary,
+                MockTransport::response_bytes(ResponseCode::SUCCESS, &created[..created_len]),
+            )
+            .expect(
+                [
+                    0x80, 0x02, 0, 0, 0, 0x23, 0, 0, 0x01, 0x20, 0x40, 0, 0, 0x01, 0x80, 0, 0, 0,
+                ],
+                success_response(),
+            )
+            .expect(
+                [0x80, 0x01, 0, 0, 0, 0x0e, 0, 0, 0x01, 0x65, 0x80, 0, 0, 0],
+                success_response(),
+            )
+            .expect(
+                read_public_command(AIK_HANDLE.0),
+                read_public_response(&aik),
+            );
+        let result = provision(&mut tcg).unwrap();
+        tcg.assert_done();
+        // EvictControl's persistentHandle comes after the auth area
+        assert!(tcg.commands[7].ends_with(&AIK_HANDLE.0.to_be_bytes()));
+        assert_eq!(result.aik_handle, AIK_HANDLE);
+        assert_eq!(result.aik_public, aik);
+    }
+
+    #[test]
+    fn aik_is_a_restricted_signing_key() {
+        let aik = aik_template_rsa2048();
+        assert_eq!(
+            aik.object_attributes & TPMA_OBJECT_RESTRICTED,
+            TPMA_OBJECT_RESTRICTED
+        );
+        assert_eq!(
+            aik.object_attributes & TPMA_OBJECT_SIGN_ENCRYPT,
+            TPMA_OBJECT_SIGN_ENCRYPT
+        );
+        let PublicParameters::Rsa { scheme, .. } = aik.parameters else {
+            panic!("the AIK should be RSA");
+        };
+        assert_eq!(scheme, Scheme::new(TPM_ALG_RSASSA, AlgorithmId::SHA256));
+    }
+}
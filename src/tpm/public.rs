@@ -0,0 +1,385 @@
+use core::fmt;
+
+use uefi::proto::tcg::AlgorithmId;
+
+use super::{
+    TpmError,
+    alg::{
+        TPM_ALG_ECC, TPM_ALG_ECDAA, TPM_ALG_KEYEDHASH, TPM_ALG_NULL, TPM_ALG_RSA,
+        TPM_ALG_SYMCIPHER, TPM_ALG_XOR,
+    },
+    marshal::{Reader, Writer},
+    tpm2b::{Tpm2b, Tpm2bDigest},
+};
+
+/// `TPMA_OBJECT` bits
+pub const TPMA_OBJECT_FIXED_TPM: u32 = 1 << 1;
+pub const TPMA_OBJECT_ST_CLEAR: u32 = 1 << 2;
+pub const TPMA_OBJECT_FIXED_PARENT: u32 = 1 << 4;
+pub const TPMA_OBJECT_SENSITIVE_DATA_ORIGIN: u32 = 1 << 5;
+pub const TPMA_OBJECT_USER_WITH_AUTH: u32 = 1 << 6;
+pub const TPMA_OBJECT_ADMIN_WITH_POLICY: u32 = 1 << 7;
+pub const TPMA_OBJECT_NO_DA: u32 = 1 << 10;
+pub const TPMA_OBJECT_ENCRYPTED_DUPLICATION: u32 = 1 << 11;
+pub const TPMA_OBJECT_RESTRICTED: u32 = 1 << 16;
+pub const TPMA_OBJECT_DECRYPT: u32 = 1 << 17;
+pub const TPMA_OBJECT_SIGN_ENCRYPT: u32 = 1 << 18;
+
+/// `TPMA_OBJECT` as the spec's attribute names, like `fixedTPM|restricted|decrypt`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ObjectAttributes(pub u32);
+
+impl fmt::Display for ObjectAttributes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = [
+            (TPMA_OBJECT_FIXED_TPM, "fixedTPM"),
+            (TPMA_OBJECT_ST_CLEAR, "stClear"),
+            (TPMA_OBJECT_FIXED_PARENT, "fixedParent"),
+            (TPMA_OBJECT_SENSITIVE_DATA_ORIGIN, "sensitiveDataOrigin"),
+            (TPMA_OBJECT_USER_WITH_AUTH, "userWithAuth"),
+            (TPMA_OBJECT_ADMIN_WITH_POLICY, "adminWithPolicy"),
+            (TPMA_OBJECT_NO_DA, "noDA"),
+            (TPMA_OBJECT_ENCRYPTED_DUPLICATION, "encryptedDuplication"),
+            (TPMA_OBJECT_RESTRICTED, "restricted"),
+            (TPMA_OBJECT_DECRYPT, "decrypt"),
+            (TPMA_OBJECT_SIGN_ENCRYPT, "sign"),
+        ];
+        let mut separator = "";
+        for (bit, name) in names {
+            if self.0 & bit != 0 {
+                write!(f, "{separator}{name}")?;
+                separator = "|";
+            }
+        }
+        if separator.is_empty() {
+            f.write_str("none")?;
+        }
+        Ok(())
+    }
+}
+
+/// `TPM_ECC_CURVE`
+pub const TPM_ECC_NIST_P256: u16 = 0x0003;
+pub const TPM_ECC_NIST_P384: u16 = 0x0004;
+
+/// `MAX_RSA_KEY_BYTES` for 4096-bit keys
+pub const MAX_RSA_KEY_BYTES: usize = 512;
+/// `MAX_ECC_KEY_BYTES` for P-521
+pub const MAX_ECC_KEY_BYTES: usize = 66;
+
+/// `TPMT_SYM_DEF_OBJECT`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SymDefObject {
+    pub algorithm: AlgorithmId,
+    pub key_bits: u16,
+    pub mode: AlgorithmId,
+}
+
+impl SymDefObject {
+    pub const NULL: Self = Self {
+        algorithm: TPM_ALG_NULL,
+        key_bits: 0,
+        mode: TPM_ALG_NULL,
+    };
+
+    fn read(reader: &mut Reader<'_>) -> Result<Self, TpmError> {
+        let algorithm = AlgorithmId(reader.u16()?);
+        if algorithm == TPM_ALG_NULL {
+            return Ok(Self::NULL);
+        }
+        Ok(Self {
+            algorithm,
+            key_bits: reader.u16()?,
+            mode: AlgorithmId(reader.u16()?),
+        })
+    }
+
+    fn write(&self, writer: &mut Writer<'_>) -> Result<(), TpmError> {
+        writer.u16(self.algorithm.0)?;
+        if self.algorithm != TPM_ALG_NULL {
+            writer.u16(self.key_bits)?;
+            writer.u16(self.mode.0)?;
+        }
+        Ok(())
+    }
+}
+
+/// Any of the `TPMT_*_SCHEME` structures
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Scheme {
+    pub scheme: AlgorithmId,
+    /// `TPM_ALG_NULL` for the null scheme
+    pub hash_alg: AlgorithmId,
+    /// ECDAA's `count` or XOR's `kdf`, otherwise unused
+    pub extra: u16,
+}
+
+impl Scheme {
+    pub const NULL: Self = Self {
+        scheme: TPM_ALG_NULL,
+        hash_alg: TPM_ALG_NULL,
+        extra: 0,
+    };
+
+    pub const fn new(scheme: AlgorithmId, hash_alg: AlgorithmId) -> Self {
+        Self {
+            scheme,
+            hash_alg,
+            extra: 0,
+        }
+    }
+
+    fn has_extra(&self) -> bool {
+        self.scheme == TPM_ALG_ECDAA || self.scheme == TPM_ALG_XOR
+    }
+
+    pub fn read(reader: &mut Reader<'_>) -> Result<Self, TpmError> {
+        let scheme = AlgorithmId(reader.u16()?);
+        if scheme == TPM_ALG_NULL {
+            return Ok(Self::NULL);
+        }
+        let mut result = Self::new(scheme, AlgorithmId(reader.u16()?));
+        if result.has_extra() {
+            result.extra = reader.u16()?;
+        }
+        Ok(result)
+    }
+
+    fn write(&self, writer: &mut Writer<'_>) -> Result<(), TpmError> {
+        writer.u16(self.scheme.0)?;
+        if self.scheme != TPM_ALG_NULL {
+            writer.u16(self.hash_alg.0)?;
+            if self.has_extra() {
+                writer.u16(self.extra)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `TPMU_PUBLIC_PARMS`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PublicParameters {
+    Rsa {
+        symmetric: SymDefObject,
+        scheme: Scheme,
+        key_bits: u16,
+        /// 0 means the default of 65537
+        exponent: u32,
+    },
+    Ecc {
+        symmetric: SymDefObject,
+        scheme: Scheme,
+        curve_id: u16,
+        kdf: Scheme,
+    },
+    KeyedHash {
+        scheme: Scheme,
+    },
+    SymCipher {
+        symmetric: SymDefObject,
+    },
+}
+
+/// The kind of key, like `RSA 2048` or `ECC NIST P-256`
+impl fmt::Display for PublicParameters {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Rsa { key_bits, .. } => write!(f, "RSA {key_bits}"),
+            Self::Ecc {
+                curve_id: TPM_ECC_NIST_P256,
+                ..
+            } => f.write_str("ECC NIST P-256"),
+            Self::Ecc {
+                curve_id: TPM_ECC_NIST_P384,
+                ..
+            } => f.write_str("ECC NIST P-384"),
+            Self::Ecc { curve_id, .. } => write!(f, "ECC curve {curve_id:#06x}"),
+            Self::KeyedHash { .. } => f.write_str("keyed hash"),
+            Self::SymCipher { .. } => f.write_str("symmetric cipher"),
+        }
+    }
+}
+
+/// `TPMU_PUBLIC_ID`
+// Not boxed so a public area can be copied around without allocating
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PublicId {
+    Rsa(Tpm2b<MAX_RSA_KEY_BYTES>),
+    Ecc {
+        x: Tpm2b<MAX_ECC_KEY_BYTES>,
+        y: Tpm2b<MAX_ECC_KEY_BYTES>,
+    },
+    /// For keyed hash and symmetric objects
+    Digest(Tpm2bDigest),
+}
+
+/// `TPMT_PUBLIC`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TpmtPublic {
+    pub name_alg: AlgorithmId,
+    pub object_attributes: u32,
+    pub auth_policy: Tpm2bDigest,
+    pub parameters: PublicParameters,
+    pub unique: PublicId,
+}
+
+impl TpmtPublic {
+    /// `TPMI_ALG_PUBLIC`
+    pub fn object_type(&self) -> AlgorithmId {
+        match self.parameters {
+            PublicParameters::Rsa { .. } => TPM_ALG_RSA,
+            PublicParameters::Ecc { .. } => TPM_ALG_ECC,
+            PublicParameters::KeyedHash { .. } => TPM_ALG_KEYEDHASH,
+            PublicParameters::SymCipher { .. } => TPM_ALG_SYMCIPHER,
+        }
+    }
+
+    pub fn read(reader: &mut Reader<'_>) -> Result<Self, TpmError> {
+        let object_type = AlgorithmId(reader.u16()?);
+        let name_alg = AlgorithmId(reader.u16()?);
+        let object_attributes = reader.u32()?;
+        let auth_policy = Tpm2b::read(reader)?;
+        let (parameters, unique) = match object_type {
+            TPM_ALG_RSA => (
+                PublicParameters::Rsa {
+                    symmetric: SymDefObject::read(reader)?,
+                    scheme: Scheme::read(reader)?,
+                    key_bits: reader.u16()?,
+                    exponent: reader.u32()?,
+                },
+                PublicId::Rsa(Tpm2b::read(reader)?),
+            ),
+            TPM_ALG_ECC => (
+                PublicParameters::Ecc {
+                    symmetric: SymDefObject::read(reader)?,
+                    scheme: Scheme::read(reader)?,
+                    curve_id: reader.u16()?,
+                    kdf: Scheme::read(reader)?,
+                },
+                PublicId::Ecc {
+                    x: Tpm2b::read(reader)?,
+                    y: Tpm2b::read(reader)?,
+                },
+            ),
+            TPM_ALG_KEYEDHASH => (
+                PublicParameters::KeyedHash {
+                    scheme: Scheme::read(reader)?,
+                },
+                PublicId::Digest(Tpm2b::read(reader)?),
+            ),
+            TPM_ALG_SYMCIPHER => (
+                PublicParameters::SymCipher {
+                    symmetric: SymDefObject::read(reader)?,
+                },
+                PublicId::Digest(Tpm2b::read(reader)?),
+            ),
+            _ => return Err(TpmError::Malformed),
+        };
+        Ok(Self {
+            name_alg,
+            object_attributes,
+            auth_policy,
+            parameters,
+            unique,
+        })
+    }
+
+    pub fn write(&self, writer: &mut Writer<'_>) -> Result<(), TpmError> {
+        writer.u16(self.object_type().0)?;
+        writer.u16(self.name_alg.0)?;
+        writer.u32(self.object_attributes)?;
+        self.auth_policy.write(writer)?;
+        match &self.parameters {
+            PublicParameters::Rsa {
+                symmetric,
+                scheme,
+                key_bits,
+                exponent,
+            } => {
+                symmetric.write(writer)?;
+                scheme.write(writer)?;
+                writer.u16(*key_bits)?;
+                writer.u32(*exponent)?;
+            }
+            PublicParameters::Ecc {
+                symmetric,
+                scheme,
+                curve_id,
+                kdf,
+            } => {
+                symmetric.write(writer)?;
+                scheme.write(writer)?;
+                writer.u16(*curve_id)?;
+                kdf.write(writer)?;
+            }
+            PublicParameters::KeyedHash { scheme } => scheme.write(writer)?,
+            PublicParameters::SymCipher { symmetric } => symmetric.write(writer)?,
+        }
+        match &self.unique {
+            PublicId::Rsa(n) => n.write(writer),
+            PublicId::Ecc { x, y } => {
+                x.write(writer)?;
+                y.write(writer)
+            }
+            PublicId::Digest(digest) => digest.write(writer),
+        }
+    }
+
+    /// `TPM2B_PUBLIC`
+    pub fn read_tpm2b(reader: &mut Reader<'_>) -> Result<Self, TpmError> {
+        let mut inner = Reader::new(reader.tpm2b()?);
+        let public = Self::read(&mut inner)?;
+        if !inner.is_empty() {
+            return Err(TpmError::Malformed);
+        }
+        Ok(public)
+    }
+
+    /// `TPM2B_PUBLIC`
+    pub fn write_tpm2b(&self, writer: &mut Writer<'_>) -> Result<(), TpmError> {
+        let size_offset = writer.len();
+        writer.u16(0)?;
+        self.write(writer)?;
+        let size = writer.len() - size_offset - 2;
+        writer.patch_u16(size_offset, size as u16);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tpm::ek::{EkAlgorithm, ek_template};
+
+    #[test]
+    fn object_attributes_use_the_spec_names() {
+        assert_eq!(
+            ObjectAttributes(TPMA_OBJECT_FIXED_TPM | TPMA_OBJECT_SIGN_ENCRYPT).to_string(),
+            "fixedTPM|sign"
+        );
+        assert_eq!(ObjectAttributes(0).to_string(), "none");
+        // Reserved bits aren't named
+        assert_eq!(ObjectAttributes(1 << 31).to_string(), "none");
+    }
+
+    #[test]
+    fn parameters_name_the_kind_of_key() {
+        assert_eq!(
+            ek_template(EkAlgorithm::Rsa2048).parameters.to_string(),
+            "RSA 2048"
+        );
+        assert_eq!(
+            ek_template(EkAlgorithm::EccNistP256).parameters.to_string(),
+            "ECC NIST P-256"
+        );
+        let sm2 = PublicParameters::Ecc {
+            symmetric: SymDefObject::NULL,
+            scheme: Scheme::NULL,
+            curve_id: 0x0020,
+            kdf: Scheme::NULL,
+        };
+        assert_eq!(sm2.to_string(), "ECC curve 0x0020");
+    }
+}
@@ -0,0 +1,225 @@
+use core::fmt;
+
+use hex_slice::AsHex;
+
+use super::{
+    CommandCode, TpmError, TpmTransport,
+    command::{TpmCommand, execute},
+    marshal::{Reader, Writer},
+    tpm2b::{Tpm2b, Tpm2bDigest},
+};
+
+/// `TPM2_GetRandom`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GetRandomCommand {
+    pub bytes_requested: u16,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct GetRandomResponse {
+    pub random_bytes: Tpm2bDigest,
+}
+
+impl TpmCommand for GetRandomCommand {
+    const CODE: CommandCode = CommandCode::GET_RANDOM;
+
+    type Response = GetRandomResponse;
+
+    fn write_parameters(&self, writer: &mut Writer<'_>) -> Result<(), TpmError> {
+        writer.u16(self.bytes_requested)
+    }
+
+    fn read_response(
+        _handles: &mut Reader<'_>,
+        parameters: &mut Reader<'_>,
+    ) -> Result<Self::Response, TpmError> {
+        Ok(GetRandomResponse {
+            random_bytes: Tpm2b::read(parameters)?,
+        })
+    }
+}
+
+/// `TPM2_GetRandom`. Fills the start of `bytes` and returns how much of it. The TPM gives at most
+/// the size of its largest digest per call.
+pub fn get_random(tcg: &mut dyn TpmTransport, bytes: &mut [u8]) -> Result<usize, TpmError> {
+    let command = GetRandomCommand {
+        bytes_requested: bytes.len().min(u16::MAX as usize) as u16,
+    };
+    let response = execute(tcg, &command)?;
+    let random_bytes = response.random_bytes.as_slice();
+    let len = random_bytes.len().min(bytes.len());
+    bytes[..len].copy_from_slice(&random_bytes[..len]);
+    Ok(len)
+}
+
+/// Fills all of `bytes`, asking again while the TPM gives fewer than were left
+pub fn fill_random(tcg: &mut dyn TpmTransport, bytes: &mut [u8]) -> Result<(), TpmError> {
+    let mut filled = 0;
+    while filled < bytes.len() {
+        match get_random(tcg, &mut bytes[filled..])? {
+            // It would never finish
+            0 => return Err(TpmError::Malformed),
+            len => filled += len,
+        }
+    }
+    Ok(())
+}
+
+/// `N` random bytes, like a nonce or a key
+pub fn get_random_array<const N: usize>(tcg: &mut dyn TpmTransport) -> Result<[u8; N], TpmError> {
+    let mut bytes = [0; N];
+    fill_random(tcg, &mut bytes)?;
+    Ok(bytes)
+}
+
+/// How many bytes [`drain_entropy_to`] asks for per command, the size of a SHA-256 digest, which
+/// every TPM can return at once
+const DRAIN_CHUNK_SIZE: usize = 32;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrainError {
+    Tpm(TpmError),
+    /// The sink failed
+    Write,
+}
+
+impl From<TpmError> for DrainError {
+    fn from(error: TpmError) -> Self {
+        Self::Tpm(error)
+    }
+}
+
+impl fmt::Display for DrainError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Tpm(error) => write!(f, "{error}"),
+            Self::Write => f.write_str("failed to write the random bytes"),
+        }
+    }
+}
+
+/// Draws `n` random bytes and writes them to `out` in lowercase hex as they come, so any amount
+/// can go to a log, the console or a `String` without holding all of it
+pub fn drain_entropy_to(
+    tcg: &mut dyn TpmTransport,
+    n: usize,
+    out: &mut impl fmt::Write,
+) -> Result<(), DrainError> {
+    let mut chunk = [0; DRAIN_CHUNK_SIZE];
+    let mut drained = 0;
+    while drained < n {
+        let chunk = &mut chunk[..(n - drained).min(DRAIN_CHUNK_SIZE)];
+        fill_random(tcg, chunk)?;
+        write!(out, "{:02x}", chunk.plain_hex(false)).map_err(|_| DrainError::Write)?;
+        drained += chunk.len();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::{string::String, vec::Vec};
+
+    use super::*;
+    use crate::tpm::{RESPONSE_HEADER_SIZE, ResponseCode, mock::MockTransport};
+
+    /// A successful `TPM2_GetRandom` response with `random_bytes` in its `TPM2B_DIGEST`
+    fn response(random_bytes: &[u8]) -> Vec<u8> {
+        let mut parameters = (random_bytes.len() as u16).to_be_bytes().to_vec();
+        parameters.extend_from_slice(random_bytes);
+        MockTransport::response_bytes(ResponseCode::SUCCESS, &parameters)
+    }
+
+    #[test]
+    fn get_random_returns_the_tpm2b_contents() {
+        let random_bytes = [0xDE, 0xAD, 0xBE, 0xEF];
+        let response = response(&random_bytes);
+        assert_eq!(response.len(), RESPONSE_HEADER_SIZE + 2 + 4);
+        let mut tpm = MockTransport::new(response);
+        let mut bytes = [0; 4];
+        assert_eq!(get_random(&mut tpm, &mut bytes), Ok(4));
+        assert_eq!(bytes, random_bytes);
+        assert_eq!(
+            tpm.commands,
+            [[0x80, 0x01, 0, 0, 0, 12, 0, 0, 0x01, 0x7B, 0, 4]]
+        );
+    }
+
+    #[test]
+    fn get_random_keeps_the_rest_of_the_buffer() {
+        let mut tpm = MockTransport::new(response(&[1, 2]));
+        let mut bytes = [0; 4];
+        assert_eq!(get_random(&mut tpm, &mut bytes), Ok(2));
+        assert_eq!(bytes, [1, 2, 0, 0]);
+    }
+
+    #[test]
+    fn fill_random_fails_when_the_tpm_gives_nothing() {
+        let mut tpm = MockTransport::new(response(&[]));
+        assert_eq!(fill_random(&mut tpm, &mut [0; 4]), Err(TpmError::Malformed));
+    }
+
+    #[test]
+    fn drain_entropy_writes_hex() {
+        let mut tpm = MockTransport::new(response(&[0x0F; 32]));
+        let mut out = String::new();
+        drain_entropy_to(&mut tpm, 33, &mut out).unwrap();
+        assert_eq!(out, "0f".repeat(33));
+        assert_eq!(tpm.commands.len(), 2);
+    }
+
+    #[test]
+    fn drain_entropy_of_nothing_sends_nothing() {
+        let mut tpm = MockTransport::new(response(&[0x0F; 32]));
+        let mut out = String::new();
+        drain_entropy_to(&mut tpm, 0, &mut out).unwrap();
+        assert_eq!(out, "");
+        assert!(tpm.commands.is_empty());
+    }
+
+    #[test]
+    fn drain_entropy_stops_when_the_sink_fails() {
+        struct FullSink;
+
+        impl fmt::Write for FullSink {
+            fn write_str(&mut self, _: &str) -> fmt::Result {
+                Err(fmt::Error)
+            }
+        }
+
+        let mut tpm = MockTransport::new(response(&[0x0F; 32]));
+        assert_eq!(
+            drain_entropy_to(&mut tpm, 64, &mut FullSink),
+            Err(DrainError::Write)
+        );
+        assert_eq!(tpm.commands.len(), 1);
+    }
+
+    #[test]
+    fn drain_entropy_passes_tpm_errors_on() {
+        let mut tpm = MockTransport::new(MockTransport::response_bytes(ResponseCode::FAILURE, &[]));
+        assert_eq!(
+            drain_entropy_to(&mut tpm, 8, &mut String::new()),
+            Err(DrainError::Tpm(TpmError::FailureMode {
+                command: Some(CommandCode::GET_RANDOM)
+            }))
+        );
+    }
+
+    #[test]
+    fn get_random_array_asks_again_for_the_rest() {
+        let mut tpm = MockTransport::default()
+            .expect(
+                [0x80, 0x01, 0, 0, 0, 12, 0, 0, 0x01, 0x7B, 0, 48],
+                response(&[0x11; 32]),
+            )
+            .expect(
+                [0x80, 0x01, 0, 0, 0, 12, 0, 0, 0x01, 0x7B, 0, 16],
+                response(&[0x22; 16]),
+            );
+        let bytes = get_random_array::<48>(&mut tpm).unwrap();
+        tpm.assert_done();
+        assert_eq!(bytes[..32], [0x11; 32]);
+        assert_eq!(bytes[32..], [0x22; 16]);
+    }
+}
@@ -0,0 +1,212 @@
+use core::fmt;
+
+/// `TPM_RC`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ResponseCode(pub u32);
+
+/// `RC_FMT1`: the code says which handle, session or parameter was wrong
+const RC_FMT1: u32 = 0x080;
+
+/// What a format-one response code points at
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResponsePosition {
+    Handle(u8),
+    Session(u8),
+    Parameter(u8),
+}
+
+impl fmt::Display for ResponsePosition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Handle(n) => write!(f, "handle {n}"),
+            Self::Session(n) => write!(f, "session {n}"),
+            Self::Parameter(n) => write!(f, "parameter {n}"),
+        }
+    }
+}
+
+impl ResponseCode {
+    pub const SUCCESS: Self = Self(0x000);
+    pub const INITIALIZE: Self = Self(0x100);
+    pub const FAILURE: Self = Self(0x101);
+    pub const HIERARCHY: Self = Self(0x085);
+    pub const HANDLE: Self = Self(0x08B);
+    pub const AUTH_FAIL: Self = Self(0x08E);
+    pub const POLICY_FAIL: Self = Self(0x09D);
+    pub const BAD_AUTH: Self = Self(0x0A2);
+    pub const TESTING: Self = Self(0x90A);
+    pub const LOCKOUT: Self = Self(0x921);
+
+    /// The code without the handle, session or parameter number
+    pub fn base(self) -> Self {
+        if self.0 & RC_FMT1 != 0 {
+            Self(RC_FMT1 | (self.0 & 0x3F))
+        } else {
+            self
+        }
+    }
+
+    /// Which handle, session or parameter a format-one code is about. `None` for other codes, and
+    /// for handle 0, which means the TPM didn't say.
+    pub fn position(self) -> Option<ResponsePosition> {
+        if self.0 & RC_FMT1 == 0 {
+            return None;
+        }
+        let n = ((self.0 >> 8) & 0xF) as u8;
+        if self.0 & (1 << 6) != 0 {
+            Some(ResponsePosition::Parameter(n))
+        } else if n >= 8 {
+            Some(ResponsePosition::Session(n - 8))
+        } else if n != 0 {
+            Some(ResponsePosition::Handle(n))
+        } else {
+            None
+        }
+    }
+
+    /// The name from the spec, without `TPM_RC_`, for the codes we're likely to see
+    pub fn name(self) -> Option<&'static str> {
+        Some(match self.base().0 {
+            0x000 => "SUCCESS",
+            0x01E => "BAD_TAG",
+            // Format zero
+            0x100 => "INITIALIZE",
+            0x101 => "FAILURE",
+            0x103 => "SEQUENCE",
+            0x120 => "DISABLED",
+            0x121 => "EXCLUSIVE",
+            0x124 => "AUTH_TYPE",
+            0x125 => "AUTH_MISSING",
+            0x126 => "POLICY",
+            0x127 => "PCR",
+            0x128 => "PCR_CHANGED",
+            0x12D => "UPGRADE",
+            0x130 => "REBOOT",
+            0x142 => "COMMAND_SIZE",
+            0x143 => "COMMAND_CODE",
+            0x144 => "AUTHSIZE",
+            0x145 => "AUTH_CONTEXT",
+            0x146 => "NV_RANGE",
+            0x147 => "NV_SIZE",
+            0x148 => "NV_LOCKED",
+            0x149 => "NV_AUTHORIZATION",
+            0x14A => "NV_UNINITIALIZED",
+            0x14B => "NV_SPACE",
+            0x14C => "NV_DEFINED",
+            0x150 => "BAD_CONTEXT",
+            0x151 => "CPHASH",
+            0x152 => "PARENT",
+            0x153 => "NEEDS_TEST",
+            0x154 => "NO_RESULT",
+            0x155 => "SENSITIVE",
+            // Format one
+            0x081 => "ASYMMETRIC",
+            0x082 => "ATTRIBUTES",
+            0x083 => "HASH",
+            0x084 => "VALUE",
+            0x085 => "HIERARCHY",
+            0x087 => "KEY_SIZE",
+            0x088 => "MGF",
+            0x089 => "MODE",
+            0x08A => "TYPE",
+            0x08B => "HANDLE",
+            0x08C => "KDF",
+            0x08D => "RANGE",
+            0x08E => "AUTH_FAIL",
+            0x08F => "NONCE",
+            0x090 => "PP",
+            0x092 => "SCHEME",
+            0x095 => "SIZE",
+            0x096 => "SYMMETRIC",
+            0x097 => "TAG",
+            0x098 => "SELECTOR",
+            0x09A => "INSUFFICIENT",
+            0x09B => "SIGNATURE",
+            0x09C => "KEY",
+            0x09D => "POLICY_FAIL",
+            0x09F => "INTEGRITY",
+            0x0A0 => "TICKET",
+            0x0A1 => "RESERVED_BITS",
+            0x0A2 => "BAD_AUTH",
+            0x0A3 => "EXPIRED",
+            0x0A4 => "POLICY_CC",
+            0x0A5 => "BINDING",
+            0x0A6 => "CURVE",
+            0x0A7 => "ECC_POINT",
+            // Warnings
+            0x901 => "CONTEXT_GAP",
+            0x902 => "OBJECT_MEMORY",
+            0x903 => "SESSION_MEMORY",
+            0x904 => "MEMORY",
+            0x905 => "SESSION_HANDLES",
+            0x906 => "OBJECT_HANDLES",
+            0x907 => "LOCALITY",
+            0x908 => "YIELDED",
+            0x909 => "CANCELED",
+            0x90A => "TESTING",
+            0x920 => "NV_RATE",
+            0x921 => "LOCKOUT",
+            0x922 => "RETRY",
+            0x923 => "NV_UNAVAILABLE",
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for ResponseCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "TPM_RC_{name}")?,
+            None => write!(f, "TPM_RC({:#x})", self.0)?,
+        }
+        if let Some(position) = self.position() {
+            write!(f, " ({position})")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use alloc::format;
+
+    use super::*;
+
+    #[test]
+    fn parameter_error() {
+        // `TPM_RC_VALUE` + `TPM_RC_P` + `TPM_RC_2`
+        let code = ResponseCode(0x084 | 0x040 | 0x200);
+        assert_eq!(code.base(), ResponseCode(0x084));
+        assert_eq!(code.position(), Some(ResponsePosition::Parameter(2)));
+        assert_eq!(format!("{code}"), "TPM_RC_VALUE (parameter 2)");
+    }
+
+    #[test]
+    fn handle_error() {
+        // `TPM_RC_HANDLE` + `TPM_RC_H` + `TPM_RC_1`
+        let code = ResponseCode(0x08B | 0x100);
+        assert_eq!(code.base(), ResponseCode::HANDLE);
+        assert_eq!(code.position(), Some(ResponsePosition::Handle(1)));
+        assert_eq!(format!("{code}"), "TPM_RC_HANDLE (handle 1)");
+    }
+
+    #[test]
+    fn session_error() {
+        // `TPM_RC_AUTH_FAIL` + `TPM_RC_S` + `TPM_RC_1`
+        let code = ResponseCode(0x08E | 0x800 | 0x100);
+        assert_eq!(code.base(), ResponseCode::AUTH_FAIL);
+        assert_eq!(code.position(), Some(ResponsePosition::Session(1)));
+        assert_eq!(format!("{code}"), "TPM_RC_AUTH_FAIL (session 1)");
+    }
+
+    #[test]
+    fn codes_without_a_position() {
+        // Format one, but the TPM didn't say which handle
+        assert_eq!(ResponseCode(0x08B).position(), None);
+        assert_eq!(format!("{}", ResponseCode(0x08B)), "TPM_RC_HANDLE");
+        // Format zero and warnings never have one
+        assert_eq!(format!("{}", ResponseCode::INITIALIZE), "TPM_RC_INITIALIZE");
+        assert_eq!(format!("{}", ResponseCode::LOCKOUT), "TPM_RC_LOCKOUT");
+        assert_eq!(format!("{}", ResponseCode(0x17F)), "TPM_RC(0x17f)");
+    }
+}
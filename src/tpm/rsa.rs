@@ -0,0 +1,234 @@
+use alloc::vec::Vec;
+
+use uefi::proto::tcg::AlgorithmId;
+
+use super::{
+    BUFFER_SIZE, CommandCode, TPM_ST_NO_SESSIONS, TPM_ST_SESSIONS, TpmError, TpmHandle,
+    TpmTransport,
+    alg::{TPM_ALG_NULL, TPM_ALG_OAEP, TPM_ALG_RSAES},
+    auth::{AuthCommand, write_auth_area},
+    begin_command, finish_command,
+    marshal::Writer,
+    submit,
+};
+
+/// `TPMT_RSA_DECRYPT`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RsaDecryptScheme {
+    /// PKCS#1 v1.5
+    Rsaes,
+    Oaep {
+        hash: AlgorithmId,
+    },
+    /// Use the key's scheme
+    Null,
+}
+
+impl RsaDecryptScheme {
+    fn write(&self, writer: &mut Writer<'_>) -> Result<(), TpmError> {
+        match *self {
+            Self::Rsaes => writer.u16(TPM_ALG_RSAES.0),
+            Self::Oaep { hash } => {
+                writer.u16(TPM_ALG_OAEP.0)?;
+                writer.u16(hash.0)
+            }
+            Self::Null => writer.u16(TPM_ALG_NULL.0),
+        }
+    }
+}
+
+/// `TPM2_RSA_Encrypt` with the public part of the RSA key at `key_handle`. For OAEP, a non-empty
+/// `label` has to include its terminating zero.
+pub fn rsa_encrypt(
+    tcg: &mut dyn TpmTransport,
+    key_handle: TpmHandle,
+    message: &[u8],
+    scheme: RsaDecryptScheme,
+    label: &[u8],
+) -> Result<Vec<u8>, TpmError> {
+    let mut command_buffer = [0; BUFFER_SIZE];
+    let mut writer = Writer::new(&mut command_buffer);
+    begin_command(&mut writer, TPM_ST_NO_SESSIONS, CommandCode::RSA_ENCRYPT)?;
+    writer.u32(key_handle)?;
+    writer.tpm2b(message)?;
+    scheme.write(&mut writer)?;
+    writer.tpm2b(label)?;
+    let mut response_buffer = [0; BUFFER_SIZE];
+    let response = submit(tcg, finish_command(writer), &mut response_buffer)
+        .map_err(|e| e.with_handle(key_handle))?;
+    Ok(response.parameters()?.tpm2b()?.to_vec())
+}
+
+/// `TPM2_RSA_Decrypt`, which needs the key's authorization in `session`. `scheme` and `label`
+/// have to match what the message was encrypted with.
+pub fn rsa_decrypt(
+    tcg: &mut dyn TpmTransport,
+    key_handle: TpmHandle,
+    cipher_text: &[u8],
+    scheme: RsaDecryptScheme,
+    label: &[u8],
+    session: &AuthCommand<'_>,
+) -> Result<Vec<u8>, TpmError> {
+    let mut command_buffer = [0; BUFFER_SIZE];
+    let mut writer = Writer::new(&mut command_buffer);
+    begin_command(&mut writer, TPM_ST_SESSIONS, CommandCode::RSA_DECRYPT)?;
+    writer.u32(key_handle)?;
+    write_auth_area(&mut writer, core::slice::from_ref(session))?;
+    writer.tpm2b(cipher_text)?;
+    scheme.write(&mut writer)?;
+    writer.tpm2b(label)?;
+    let mut response_buffer = [0; BUFFER_SIZE];
+    let response = submit(tcg, finish_command(writer), &mut response_buffer)
+        .map_err(|e| e.with_handle(key_handle))?;
+    Ok(response.parameters()?.tpm2b()?.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tpm::mock::MockTransport;
+
+    #[test]
+    fn encrypt_with_oaep_and_a_label() {
+        let mut tcg = MockTransport::success(&[0x00, 0x02, 0xaa, 0xbb]);
+        let cipher_text = rsa_encrypt(
+            &mut tcg,
+            0x80000001,
+            &[0x11, 0x22, 0x33],
+            RsaDecryptScheme::Oaep {
+                hash: AlgorithmId::SHA256,
+            },
+            b"label\0",
+        )
+        .unwrap();
+        assert_eq!(cipher_text, [0xaa, 0xbb]);
+        assert_eq!(
+            tcg.commands,
+            [[
+                0x80, 0x01, 0x00, 0x00, 0x00, 0x1f, 0x00, 0x00, 0x01, 0x74, 0x80, 0x00, 0x00,
+                0x01, // message
+                0x00, 0x03, 0x11, 0x22, 0x33, // TPM_ALG_OAEP, TPM_ALG_SHA256
+                0x00, 0x17, 0x00, 0x0b, // label
+                0x00, 0x06, 0x6c, 0x61, 0x62, 0x65, 0x6c, 0x00,
+            ]]
+        );
+    }
+
+    #[test]
+    fn encrypt_with_rsaes_and_an_empty_label() {
+        let mut tcg = MockTransport::success(&[0x00, 0x00]);
+        rsa_encrypt(
+            &mut tcg,
+            0x80000001,
+            &[0x11, 0x22, 0x33],
+            RsaDecryptScheme::Rsaes,
+            &[],
+        )
+        .unwrap();
+        assert_eq!(
+            tcg.commands,
+            [[
+                0x80, 0x01, 0x00, 0x00, 0x00, 0x17, 0x00, 0x00, 0x01, 0x74, 0x80, 0x00, 0x00,
+                0x01, // message
+                0x00, 0x03, 0x11, 0x22, 0x33, // TPM_ALG_RSAES, no hash
+                0x00, 0x15, // empty label
+                0x00, 0x00,
+            ]]
+        );
+    }
+
+    #[test]
+    fn decrypt_with_a_password_session() {
+        let mut tcg = MockTransport::new([
+            0x80, 0x02, 0x00, 0x00, 0x00, 0x19, 0x00, 0x00, 0x00, 0x00, // parameterSize
+            0x00, 0x00, 0x00, 0x06, // message
+            0x00, 0x04, 0xaa, 0xbb, 0xcc, 0xdd, // password session response
+            0x00, 0x00, 0x01, 0x00, 0x00,
+        ]);
+        let message = rsa_decrypt(
+            &mut tcg,
+            0x80000001,
+            &[0xaa, 0xbb],
+            RsaDecryptScheme::Rsaes,
+            &[],
+            &AuthCommand::password(&[]),
+        )
+        .unwrap();
+        assert_eq!(message, [0xaa, 0xbb, 0xcc, 0xdd]);
+        assert_eq!(
+            tcg.commands,
+            [[
+                0x80, 0x02, 0x00, 0x00, 0x00, 0x23, 0x00, 0x00, 0x01, 0x59, 0x80, 0x00, 0x00,
+                0x01, // authorizationSize, TPM_RS_PW with an empty password
+                0x00, 0x00, 0x00, 0x09, 0x40, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00, 0x00, 0x00,
+                // cipher text
+                0x00, 0x02, 0xaa, 0xbb, // TPM_ALG_RSAES
+                0x00, 0x15, // empty label
+                0x00, 0x00,
+            ]]
+        );
+    }
+
+    /// Against swtpm, see [`crate::tpm::swtpm`]
+    #[cfg(feature = "swtpm-tests")]
+    mod simulator {
+        use super::*;
+        use crate::tpm::{
+            TPM_RH_OWNER,
+            object::{create_primary, flush_context},
+            public::{
+                PublicId, PublicParameters, Scheme, SymDefObject, TPMA_OBJECT_DECRYPT,
+                TPMA_OBJECT_FIXED_PARENT, TPMA_OBJECT_FIXED_TPM, TPMA_OBJECT_SENSITIVE_DATA_ORIGIN,
+                TPMA_OBJECT_USER_WITH_AUTH, TpmtPublic,
+            },
+            swtpm::SwtpmTransport,
+            tpm2b::Tpm2b,
+        };
+
+        #[test]
+        fn encrypt_decrypt_round_trip() {
+            let mut tcg = SwtpmTransport::connect();
+            let key = create_primary(
+                &mut tcg,
+                TPM_RH_OWNER,
+                &AuthCommand::password(&[]),
+                &[],
+                &TpmtPublic {
+                    name_alg: AlgorithmId::SHA256,
+                    object_attributes: TPMA_OBJECT_FIXED_TPM
+                        | TPMA_OBJECT_FIXED_PARENT
+                        | TPMA_OBJECT_SENSITIVE_DATA_ORIGIN
+                        | TPMA_OBJECT_USER_WITH_AUTH
+                        | TPMA_OBJECT_DECRYPT,
+                    auth_policy: Tpm2b::default(),
+                    parameters: PublicParameters::Rsa {
+                        symmetric: SymDefObject::NULL,
+                        scheme: Scheme::NULL,
+                        key_bits: 2048,
+                        exponent: 0,
+                    },
+                    unique: PublicId::Rsa(Tpm2b::default()),
+                },
+            )
+            .unwrap();
+            let scheme = RsaDecryptScheme::Oaep {
+                hash: AlgorithmId::SHA256,
+            };
+            let message = b"round trip";
+            let round_trip = rsa_encrypt(&mut tcg, key.handle, message, scheme, b"label\0")
+                .and_then(|cipher_text| {
+                    assert_eq!(cipher_text.len(), 256);
+                    rsa_decrypt(
+                        &mut tcg,
+                        key.handle,
+                        &cipher_text,
+                        scheme,
+                        b"label\0",
+                        &AuthCommand::password(&[]),
+                    )
+                });
+            flush_context(&mut tcg, key.handle).unwrap();
+            assert_eq!(round_trip.unwrap(), message);
+        }
+    }
+}
@@ -0,0 +1,230 @@
+//! Making sure the TPM's algorithms work before trusting anything it computes. `TPM2_SelfTest`
+//! may return before the tests finish, so the result is polled with `TPM2_GetTestResult` until
+//! it's no longer `TPM_RC_TESTING`.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use super::{
+    BUFFER_SIZE, CommandCode, TPM_ST_NO_SESSIONS, TpmError, TpmTransport,
+    backoff::Backoff,
+    begin_command, finish_command,
+    marshal::Writer,
+    response_code::ResponseCode,
+    submit,
+    timeout::{FULL_SELF_TEST_TIMEOUT, TimeoutTransport},
+};
+
+/// How long [`selftest_and_report`] waits for the tests to finish. Polls start 10 ms apart and
+/// back off to 500 ms.
+pub const TEST_RESULT_BACKOFF: Backoff = Backoff::new(10_000, 500_000, 5_000_000);
+
+/// `TPM2_SelfTest`. With `full_test` false only the algorithms that haven't been tested yet are.
+/// A full test can keep the TPM busy long enough that it's sent with [`FULL_SELF_TEST_TIMEOUT`].
+pub fn self_test(tcg: &mut dyn TpmTransport, full_test: bool) -> Result<(), TpmError> {
+    let mut command_buffer = [0; 11];
+    let mut writer = Writer::new(&mut command_buffer);
+    begin_command(&mut writer, TPM_ST_NO_SESSIONS, CommandCode::SELF_TEST)?;
+    writer.u8(full_test as u8)?;
+    let mut response_buffer = [0; BUFFER_SIZE];
+    if full_test {
+        let mut tcg = TimeoutTransport {
+            inner: tcg,
+            timeout: FULL_SELF_TEST_TIMEOUT,
+        };
+        submit(&mut tcg, finish_command(writer), &mut response_buffer)?;
+    } else {
+        submit(tcg, finish_command(writer), &mut response_buffer)?;
+    }
+    Ok(())
+}
+
+/// What `TPM2_GetTestResult` says
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SelfTestReport {
+    /// `TPM_RC_SUCCESS` if every test passed, `TPM_RC_TESTING` if they're still running
+    pub result: ResponseCode,
+    /// Vendor-specific detail about what failed
+    pub out_data: Vec<u8>,
+}
+
+impl SelfTestReport {
+    pub fn passed(&self) -> bool {
+        self.result == ResponseCode::SUCCESS
+    }
+}
+
+impl fmt::Display for SelfTestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.passed() {
+            return f.write_str("passed");
+        }
+        write!(f, "failed with {}", self.result)?;
+        if !self.out_data.is_empty() {
+            write!(f, " ({} bytes of vendor data)", self.out_data.len())?;
+        }
+        Ok(())
+    }
+}
+
+/// `TPM2_GetTestResult`
+pub fn get_test_result(tcg: &mut dyn TpmTransport) -> Result<SelfTestReport, TpmError> {
+    let mut command_buffer = [0; 10];
+    let mut writer = Writer::new(&mut command_buffer);
+    begin_command(
+        &mut writer,
+        TPM_ST_NO_SESSIONS,
+        CommandCode::GET_TEST_RESULT,
+    )?;
+    let mut response_buffer = [0; BUFFER_SIZE];
+    let response = submit(tcg, finish_command(writer), &mut response_buffer)?;
+    let mut parameters = response.parameters()?;
+    let out_data = parameters.tpm2b()?.into();
+    let result = ResponseCode(parameters.u32()?);
+    Ok(SelfTestReport { result, out_data })
+}
+
+/// Tests whatever hasn't been tested yet and waits for the result. A TPM in failure mode refuses
+/// the test but still gives the result, which says what failed. Fails with
+/// [`TpmError::Timeout`] if the tests are still running after [`TEST_RESULT_BACKOFF`].
+pub fn selftest_and_report(tcg: &mut dyn TpmTransport) -> Result<SelfTestReport, TpmError> {
+    match self_test(tcg, false) {
+        // The tests started, and the result will say how they went
+        Err(e) if e.response_code() == Some(ResponseCode::TESTING) => {}
+        // The result says why
+        Err(TpmError::FailureMode { .. }) => {}
+        result => result?,
+    }
+    let mut backoff = TEST_RESULT_BACKOFF;
+    let mut report = get_test_result(tcg)?;
+    while report.result == ResponseCode::TESTING {
+        backoff.wait()?;
+        report = get_test_result(tcg)?;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tpm::mock::MockTransport;
+
+    const SELF_TEST: [u8; 11] = [
+        0x80, 0x01, 0x00, 0x00, 0x00, 0x0b, 0x00, 0x00, 0x01, 0x43, 0x00,
+    ];
+    const GET_TEST_RESULT: [u8; 10] = [0x80, 0x01, 0x00, 0x00, 0x00, 0x0a, 0x00, 0x00, 0x01, 0x7c];
+
+    fn test_result(out_data: &[u8], result: ResponseCode) -> Vec<u8> {
+        let mut parameters = Vec::new();
+        parameters.extend_from_slice(&(out_data.len() as u16).to_be_bytes());
+        parameters.extend_from_slice(out_data);
+        parameters.extend_from_slice(&result.0.to_be_bytes());
+        MockTransport::response_bytes(ResponseCode::SUCCESS, &parameters)
+    }
+
+    #[test]
+    fn failure_mode_still_reads_the_result() {
+        let mut tcg = MockTransport::default()
+            .expect(
+                SELF_TEST,
+                MockTransport::response_bytes(ResponseCode::FAILURE, &[]),
+            )
+            .expect(
+                GET_TEST_RESULT,
+                test_result(&[0xde, 0xad], ResponseCode::FAILURE),
+            );
+        let report = selftest_and_report(&mut tcg).unwrap();
+        tcg.assert_done();
+        assert_eq!(
+            report,
+            SelfTestReport {
+                result: ResponseCode::FAILURE,
+                out_data: [0xde, 0xad].into(),
+            }
+        );
+        assert!(!report.passed());
+    }
+
+    #[test]
+    fn polls_until_the_tests_finish() {
+        let mut tcg = MockTransport::default()
+            .expect(
+                SELF_TEST,
+                MockTransport::response_bytes(ResponseCode::TESTING, &[]),
+            )
+            .expect(GET_TEST_RESULT, test_result(&[], ResponseCode::TESTING))
+            .expect(GET_TEST_RESULT, test_result(&[], ResponseCode::SUCCESS));
+        let report = selftest_and_report(&mut tcg).unwrap();
+        tcg.assert_done();
+        assert!(report.passed());
+        assert_eq!(report.to_string(), "passed");
+    }
+
+    #[test]
+    fn other_errors_are_not_swallowed() {
+        let mut tcg = MockTransport::default().expect(
+            SELF_TEST,
+            MockTransport::response_bytes(ResponseCode::INITIALIZE, &[]),
+        );
+        assert_eq!(
+            selftest_and_report(&mut tcg).unwrap_err().response_code(),
+            Some(ResponseCode::INITIALIZE)
+        );
+    }
+
+    #[test]
+    fn gives_up_on_tests_that_never_finish() {
+        // Every command, the SelfTest included, gets a result saying the tests are still running
+        let mut tcg = MockTransport::new(test_result(&[], ResponseCode::TESTING));
+        assert_eq!(
+            selftest_and_report(&mut tcg),
+            Err(TpmError::Timeout {
+                waited_us: 5_000_000
+            })
+        );
+        // The SelfTest, the first poll and one after each wait
+        assert_eq!(tcg.commands.len(), 2 + TEST_RESULT_BACKOFF.count());
+    }
+
+    #[test]
+    fn get_test_result_reads_the_vendor_data_and_result() {
+        let mut tcg = MockTransport::new(test_result(&[1, 2, 3], ResponseCode::FAILURE));
+        let report = get_test_result(&mut tcg).unwrap();
+        assert_eq!(tcg.commands, [GET_TEST_RESULT]);
+        assert_eq!(report.out_data, [1, 2, 3]);
+        assert_eq!(report.result, ResponseCode::FAILURE);
+    }
+
+    #[test]
+    fn self_test_sends_full_test() {
+        let mut tcg = MockTransport::success(&[]);
+        self_test(&mut tcg, false).unwrap();
+        self_test(&mut tcg, true).unwrap();
+        let mut full = SELF_TEST;
+        full[10] = 1;
+        assert_eq!(tcg.commands, [SELF_TEST, full]);
+    }
+
+    #[test]
+    fn failed_report_says_how() {
+        let report = SelfTestReport {
+            result: ResponseCode::FAILURE,
+            out_data: [1, 2, 3].into(),
+        };
+        assert_eq!(
+            report.to_string(),
+            format!(
+                "failed with {} (3 bytes of vendor data)",
+                ResponseCode::FAILURE
+            )
+        );
+        let report = SelfTestReport {
+            out_data: Vec::new(),
+            ..report
+        };
+        assert_eq!(
+            report.to_string(),
+            format!("failed with {}", ResponseCode::FAILURE)
+        );
+    }
+}
@@ -0,0 +1,81 @@
+//! The storage root key (SRK): the primary key under the owner hierarchy that sealed data and
+//! other keys are created under, kept at the handle the TCG provisioning guidance gives it
+
+use uefi::proto::tcg::AlgorithmId;
+
+use super::{
+    TPM_RH_OWNER, TpmError, TpmTransport,
+    alg::{TPM_ALG_AES, TPM_ALG_CFB},
+    auth::TpmAuth,
+    capability::{TpmPersistentHandle, list_persistent_handles},
+    object::{create_primary, evict_control, flush_context},
+    public::{
+        PublicId, PublicParameters, Scheme, SymDefObject, TPMA_OBJECT_DECRYPT,
+        TPMA_OBJECT_FIXED_PARENT, TPMA_OBJECT_FIXED_TPM, TPMA_OBJECT_NO_DA, TPMA_OBJECT_RESTRICTED,
+        TPMA_OBJECT_SENSITIVE_DATA_ORIGIN, TPMA_OBJECT_USER_WITH_AUTH, TpmtPublic,
+    },
+    tpm2b::Tpm2b,
+};
+
+/// Where the SRK is kept
+pub const SRK_HANDLE: TpmPersistentHandle = TpmPersistentHandle(0x81000001);
+
+/// `fixedTPM | fixedParent | sensitiveDataOrigin | userWithAuth | noDA | restricted | decrypt`
+pub const SRK_ATTRIBUTES: u32 = TPMA_OBJECT_FIXED_TPM
+    | TPMA_OBJECT_FIXED_PARENT
+    | TPMA_OBJECT_SENSITIVE_DATA_ORIGIN
+    | TPMA_OBJECT_USER_WITH_AUTH
+    | TPMA_OBJECT_NO_DA
+    | TPMA_OBJECT_RESTRICTED
+    | TPMA_OBJECT_DECRYPT;
+
+/// The provisioning guidance's RSA 2048 SRK template, with an empty `unique` like other tools
+/// use, so they all derive the same key
+pub fn srk_template_rsa2048() -> TpmtPublic {
+    TpmtPublic {
+        name_alg: AlgorithmId::SHA256,
+        object_attributes: SRK_ATTRIBUTES,
+        auth_policy: Tpm2b::default(),
+        parameters: PublicParameters::Rsa {
+            symmetric: SymDefObject {
+                algorithm: TPM_ALG_AES,
+                key_bits: 128,
+                mode: TPM_ALG_CFB,
+            },
+            scheme: Scheme::NULL,
+            key_bits: 2048,
+            exponent: 0,
+        },
+        unique: PublicId::Rsa(Tpm2b::default()),
+    }
+}
+
+/// Makes sure there's an SRK at [`SRK_HANDLE`], creating it under the owner hierarchy and making
+/// it persistent if there isn't. Whatever is already at the handle is assumed to be the SRK.
+pub fn make_persisted_srk(
+    tcg: &mut dyn TpmTransport,
+    owner_auth: &dyn TpmAuth,
+) -> Result<TpmPersistentHandle, TpmError> {
+    if list_persistent_handles(tcg)?.contains(&SRK_HANDLE) {
+        return Ok(SRK_HANDLE);
+    }
+    let srk = create_primary(
+        tcg,
+        TPM_RH_OWNER,
+        &owner_auth.auth_command(),
+        &[],
+        &srk_template_rsa2048(),
+    )?;
+    let persisted = evict_control(
+        tcg,
+        TPM_RH_OWNER,
+        &owner_auth.auth_command(),
+        srk.handle,
+        SRK_HANDLE.0,
+    );
+    // The persistent copy is separate, and the transient one takes up an object slot
+    let flushed = flush_context(tcg, srk.handle);
+    persisted?;
+    flushed?;
+    Ok(SRK_HANDLE)
+}
@@ -0,0 +1,67 @@
+//! swtpm on the host, for the `swtpm-tests` unit tests. Its server socket takes raw TPM commands,
+//! like the firmware's `SubmitCommand`. Start it with the TPM already started up:
+//!
+//! ```text
+//! swtpm socket --tpm2 --tpmstate dir=/tmp/mytpm1 --server type=tcp,port=2321 \
+//!   --ctrl type=tcp,port=2322 --flags not-need-init,startup-clear
+//! ```
+//!
+//! `SWTPM_SERVER` overrides the address.
+
+use std::{
+    io::{self, Read, Write},
+    net::TcpStream,
+    sync::{Mutex, MutexGuard},
+};
+
+use uefi::Status;
+
+use super::{RESPONSE_HEADER_SIZE, TpmError, TpmTransport};
+
+/// Held by the test using the simulator, since the tests share its state
+static SIMULATOR: Mutex<()> = Mutex::new(());
+
+pub struct SwtpmTransport {
+    stream: TcpStream,
+    _simulator: MutexGuard<'static, ()>,
+}
+
+impl SwtpmTransport {
+    /// Waits for other tests to be done with the simulator, then connects to it. Panics if it
+    /// isn't running.
+    pub fn connect() -> Self {
+        let simulator = SIMULATOR
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let address = std::env::var("SWTPM_SERVER").unwrap_or_else(|_| "127.0.0.1:2321".into());
+        let stream = TcpStream::connect(&address)
+            .unwrap_or_else(|e| panic!("can't connect to swtpm at {address}: {e}"));
+        Self {
+            stream,
+            _simulator: simulator,
+        }
+    }
+
+    fn exchange(&mut self, command: &[u8], response: &mut [u8]) -> io::Result<()> {
+        self.stream.write_all(command)?;
+        let mut header = [0; RESPONSE_HEADER_SIZE];
+        self.stream.read_exact(&mut header)?;
+        let size = u32::from_be_bytes(header[2..6].try_into().unwrap()) as usize;
+        let mut rest = vec![0; size.saturating_sub(RESPONSE_HEADER_SIZE)];
+        self.stream.read_exact(&mut rest)?;
+        // Like the firmware, only as much as fits
+        for (to, from) in response.iter_mut().zip(header.iter().chain(&rest)) {
+            *to = *from;
+        }
+        Ok(())
+    }
+}
+
+impl TpmTransport for SwtpmTransport {
+    fn transmit(&mut self, command: &[u8], response: &mut [u8]) -> Result<(), TpmError> {
+        self.exchange(command, response).map_err(|e| {
+            log::error!("swtpm: {e}");
+            TpmError::Uefi(Status::DEVICE_ERROR)
+        })
+    }
+}
@@ -0,0 +1,87 @@
+//! A last line of defense against TPMs that never answer. Submitting a command is synchronous and
+//! most firmware has no way to cancel one, so instead of giving up on the command this arms the
+//! UEFI watchdog timer around it. If the TPM takes longer than the timeout, the watchdog fires
+//! and the firmware resets the platform (logging [`WATCHDOG_CODE`] where it supports that), which
+//! beats hanging forever on a machine nobody is looking at.
+
+use uefi::boot;
+
+use super::{TpmError, TpmTransport};
+
+/// Watchdog codes up to `0xFFFF` are reserved for the firmware. This one is `TPM2` in ASCII.
+pub const WATCHDOG_CODE: u64 = 0x5450_4D32;
+
+/// The watchdog the boot manager arms before starting an image, which is restored after each
+/// command
+pub const DEFAULT_WATCHDOG_SECONDS: usize = 5 * 60;
+
+/// For `TPM2_SelfTest` with `fullTest` set, which tests every algorithm the TPM has
+pub const FULL_SELF_TEST_TIMEOUT: TpmCommandTimeout = TpmCommandTimeout::new(60_000_000);
+
+/// For `TPM2_CreatePrimary`, which generates an RSA key from scratch the first time a template is
+/// used after the seed changes
+pub const CREATE_PRIMARY_TIMEOUT: TpmCommandTimeout = TpmCommandTimeout::new(120_000_000);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TpmCommandTimeout {
+    /// Rounded up to whole seconds, which is all the watchdog supports
+    pub timeout_us: u64,
+}
+
+impl TpmCommandTimeout {
+    pub const fn new(timeout_us: u64) -> Self {
+        Self { timeout_us }
+    }
+
+    fn timeout_seconds(&self) -> usize {
+        // 0 would disarm the watchdog instead
+        self.timeout_us.div_ceil(1_000_000).max(1) as usize
+    }
+
+    /// Sends `command` with the watchdog armed for the timeout. If the watchdog can't be armed,
+    /// nothing is sent, since the command wouldn't be protected. A command that times out doesn't
+    /// return `EFI_TIMEOUT`: the platform resets.
+    pub fn submit_with_timeout(
+        &self,
+        tcg: &mut dyn TpmTransport,
+        command: &[u8],
+        response: &mut [u8],
+    ) -> Result<(), TpmError> {
+        boot::set_watchdog_timer(self.timeout_seconds(), WATCHDOG_CODE, None)?;
+        let result = tcg.transmit(command, response);
+        // Restarts the boot manager's 5 minutes rather than continuing them, which is the best
+        // we can do since the time left can't be read back
+        let _ = boot::set_watchdog_timer(DEFAULT_WATCHDOG_SECONDS, 0, None);
+        result
+    }
+}
+
+/// Sends every command with the watchdog armed for `timeout`, so commands built with
+/// [`submit`](super::submit) get the same protection as [`TpmCommandTimeout::submit_with_timeout`]
+#[derive(Debug)]
+pub struct TimeoutTransport<T> {
+    pub inner: T,
+    pub timeout: TpmCommandTimeout,
+}
+
+impl<T: TpmTransport> TpmTransport for TimeoutTransport<T> {
+    fn transmit(&mut self, command: &[u8], response: &mut [u8]) -> Result<(), TpmError> {
+        self.timeout
+            .submit_with_timeout(&mut self.inner, command, response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeouts_round_up_to_whole_seconds() {
+        assert_eq!(TpmCommandTimeout::new(0).timeout_seconds(), 1);
+        assert_eq!(TpmCommandTimeout::new(1).timeout_seconds(), 1);
+        assert_eq!(TpmCommandTimeout::new(1_000_000).timeout_seconds(), 1);
+        assert_eq!(TpmCommandTimeout::new(1_000_001).timeout_seconds(), 2);
+        assert_eq!(FULL_SELF_TEST_TIMEOUT.timeout_seconds(), 60);
+        assert_eq!(CREATE_PRIMARY_TIMEOUT.timeout_seconds(), 120);
+    }
+}
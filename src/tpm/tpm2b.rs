@@ -0,0 +1,56 @@
+use core::fmt;
+
+use super::{
+    TpmError,
+    marshal::{Reader, Writer},
+};
+
+/// An owned `TPM2B_*` with room for up to `MAX` bytes
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Tpm2b<const MAX: usize> {
+    size: u16,
+    buffer: [u8; MAX],
+}
+
+/// `TPM2B_DIGEST` and `TPM2B_NONCE`, big enough for SHA-512
+pub type Tpm2bDigest = Tpm2b<64>;
+
+/// `TPM2B_NAME`: a hash algorithm followed by a digest, or just a handle
+pub type Tpm2bName = Tpm2b<66>;
+
+impl<const MAX: usize> Default for Tpm2b<MAX> {
+    fn default() -> Self {
+        Self {
+            size: 0,
+            buffer: [0; MAX],
+        }
+    }
+}
+
+impl<const MAX: usize> Tpm2b<MAX> {
+    /// `None` if `bytes` is longer than `MAX`
+    pub fn new(bytes: &[u8]) -> Option<Self> {
+        let mut tpm2b = Self::default();
+        tpm2b.buffer.get_mut(..bytes.len())?.copy_from_slice(bytes);
+        tpm2b.size = bytes.len() as u16;
+        Some(tpm2b)
+    }
+
+    pub fn as_slice(&self) -> &[u8] {
+        &self.buffer[..self.size as usize]
+    }
+
+    pub fn read(reader: &mut Reader<'_>) -> Result<Self, TpmError> {
+        Self::new(reader.tpm2b()?).ok_or(TpmError::Malformed)
+    }
+
+    pub fn write(&self, writer: &mut Writer<'_>) -> Result<(), TpmError> {
+        writer.tpm2b(self.as_slice())
+    }
+}
+
+impl<const MAX: usize> fmt::Debug for Tpm2b<MAX> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02x?}", self.as_slice())
+    }
+}
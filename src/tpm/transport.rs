@@ -0,0 +1,229 @@
+use alloc::vec::Vec;
+
+use uefi::{
+    boot::ScopedProtocol,
+    proto::{
+        ProtocolPointer,
+        tcg::{v1, v2::Tcg},
+    },
+};
+
+use super::{RESPONSE_HEADER_SIZE, TpmError};
+use crate::hexdump::HexDump;
+
+/// Something that can send a command to a TPM. Every command goes through this, so it's where
+/// mocks and wrappers plug in.
+pub trait TpmTransport {
+    /// Sends `command` and writes the response into `response`. Like `submit_command`, this
+    /// doesn't say how many bytes were written, so only trust `responseSize` from the header.
+    fn transmit(&mut self, command: &[u8], response: &mut [u8]) -> Result<(), TpmError>;
+}
+
+impl TpmTransport for Tcg {
+    fn transmit(&mut self, command: &[u8], response: &mut [u8]) -> Result<(), TpmError> {
+        self.submit_command(command, response)?;
+        Ok(())
+    }
+}
+
+/// For firmware that only has the TCG 1.2 protocol. A TPM 2.0 behind it works like it does behind
+/// the 2.0 protocol; a TPM 1.2 fails every command with [`TpmError::Tpm12`].
+impl TpmTransport for v1::Tcg {
+    fn transmit(&mut self, command: &[u8], response: &mut [u8]) -> Result<(), TpmError> {
+        self.pass_through_to_tpm(command, response)?;
+        Ok(())
+    }
+}
+
+impl<P: ProtocolPointer + TpmTransport + ?Sized> TpmTransport for ScopedProtocol<P> {
+    fn transmit(&mut self, command: &[u8], response: &mut [u8]) -> Result<(), TpmError> {
+        (**self).transmit(command, response)
+    }
+}
+
+/// A transport with the TCG2 protocol underneath, for code that needs the protocol itself as well
+/// as sending commands, like reading the event log. Wrappers hand the protocol through, so
+/// commands sent through the wrapper still go through everything it adds.
+pub trait TcgTransport: TpmTransport {
+    fn protocol(&mut self) -> &mut Tcg;
+}
+
+impl TcgTransport for Tcg {
+    fn protocol(&mut self) -> &mut Tcg {
+        self
+    }
+}
+
+impl TcgTransport for ScopedProtocol<Tcg> {
+    fn protocol(&mut self) -> &mut Tcg {
+        self
+    }
+}
+
+/// So a wrapper can borrow the transport it wraps
+impl<T: TpmTransport + ?Sized> TpmTransport for &mut T {
+    fn transmit(&mut self, command: &[u8], response: &mut [u8]) -> Result<(), TpmError> {
+        (**self).transmit(command, response)
+    }
+}
+
+impl<T: TcgTransport + ?Sized> TcgTransport for &mut T {
+    fn protocol(&mut self) -> &mut Tcg {
+        (**self).protocol()
+    }
+}
+
+/// How much of `response` the TPM says it wrote, going by `responseSize`
+fn response_len(response: &[u8]) -> usize {
+    match response.get(2..6) {
+        Some(size) => (u32::from_be_bytes(size.try_into().unwrap()) as usize)
+            .clamp(RESPONSE_HEADER_SIZE, response.len()),
+        None => response.len(),
+    }
+}
+
+/// Logs every command and response at trace level
+#[derive(Debug)]
+pub struct LoggingTransport<T> {
+    pub inner: T,
+}
+
+impl<T: TpmTransport> TpmTransport for LoggingTransport<T> {
+    fn transmit(&mut self, command: &[u8], response: &mut [u8]) -> Result<(), TpmError> {
+        log::trace!("TPM command:\n{}", HexDump(command));
+        let result = self.inner.transmit(command, response);
+        match result {
+            Ok(()) => log::trace!(
+                "TPM response:\n{}",
+                HexDump(&response[..response_len(response)])
+            ),
+            Err(e) => log::trace!("TPM command failed: {e}"),
+        }
+        result
+    }
+}
+
+impl<T: TcgTransport> TcgTransport for LoggingTransport<T> {
+    fn protocol(&mut self) -> &mut Tcg {
+        self.inner.protocol()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RecordedCommand {
+    pub command: Vec<u8>,
+    /// Empty if the command wasn't sent
+    pub response: Vec<u8>,
+    pub result: Result<(), TpmError>,
+}
+
+/// Everything that went through a [`RecordingTransport`], in order
+#[derive(Debug, Clone, Default)]
+pub struct TpmCommandRecord {
+    pub commands: Vec<RecordedCommand>,
+}
+
+/// Keeps a copy of every command and response, e.g. to replay them later with `MockTransport`
+#[derive(Debug)]
+pub struct RecordingTransport<T> {
+    pub inner: T,
+    pub record: TpmCommandRecord,
+}
+
+impl<T> RecordingTransport<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            record: TpmCommandRecord::default(),
+        }
+    }
+}
+
+impl<T: TpmTransport> TpmTransport for RecordingTransport<T> {
+    fn transmit(&mut self, command: &[u8], response: &mut [u8]) -> Result<(), TpmError> {
+        let result = self.inner.transmit(command, response);
+        self.record.commands.push(RecordedCommand {
+            command: command.to_vec(),
+            response: match result {
+                Ok(()) => response[..response_len(response)].to_vec(),
+                Err(_) => Vec::new(),
+            },
+            result,
+        });
+        result
+    }
+}
+
+impl<T: TcgTransport> TcgTransport for RecordingTransport<T> {
+    fn protocol(&mut self) -> &mut Tcg {
+        self.inner.protocol()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tpm::{
+        ResponseCode,
+        mock::{MockTransport, success_response},
+    };
+
+    /// Fails every command without answering
+    struct Unreachable;
+
+    impl TpmTransport for Unreachable {
+        fn transmit(&mut self, _command: &[u8], _response: &mut [u8]) -> Result<(), TpmError> {
+            Err(TpmError::Uefi(uefi::Status::DEVICE_ERROR))
+        }
+    }
+
+    const COMMAND: [u8; 12] = [0x80, 0x01, 0, 0, 0, 12, 0, 0, 0x01, 0x7B, 0, 2];
+
+    #[test]
+    fn recording_keeps_only_the_response_the_tpm_sent() {
+        let response = MockTransport::response_bytes(ResponseCode::SUCCESS, &[0, 2, 0xAB, 0xCD]);
+        let mut tpm = RecordingTransport::new(MockTransport::new(response.clone()));
+        let mut buffer = [0xFF; 64];
+        tpm.transmit(&COMMAND, &mut buffer).unwrap();
+        let [recorded] = &tpm.record.commands[..] else {
+            panic!("recorded {} commands", tpm.record.commands.len());
+        };
+        assert_eq!(recorded.command, COMMAND);
+        assert_eq!(recorded.response, response);
+        assert_eq!(recorded.result, Ok(()));
+    }
+
+    #[test]
+    fn recording_keeps_failures() {
+        let mut tpm = RecordingTransport::new(Unreachable);
+        let error = TpmError::Uefi(uefi::Status::DEVICE_ERROR);
+        assert_eq!(tpm.transmit(&COMMAND, &mut [0; 64]), Err(error));
+        let recorded = &tpm.record.commands[0];
+        assert!(recorded.response.is_empty());
+        assert_eq!(recorded.result, Err(error));
+    }
+
+    #[test]
+    fn logging_passes_commands_through() {
+        let response = success_response();
+        let mut tpm = LoggingTransport {
+            inner: MockTransport::new(response.clone()),
+        };
+        let mut buffer = [0; 64];
+        tpm.transmit(&COMMAND, &mut buffer).unwrap();
+        assert_eq!(buffer[..response.len()], response);
+        assert_eq!(tpm.inner.commands, [COMMAND]);
+    }
+
+    #[test]
+    fn response_len_trusts_response_size_within_the_buffer() {
+        let mut response = [0; 32];
+        response[2..6].copy_from_slice(&14u32.to_be_bytes());
+        assert_eq!(response_len(&response), 14);
+        response[2..6].copy_from_slice(&100u32.to_be_bytes());
+        assert_eq!(response_len(&response), 32);
+        response[2..6].copy_from_slice(&0u32.to_be_bytes());
+        assert_eq!(response_len(&response), RESPONSE_HEADER_SIZE);
+        assert_eq!(response_len(&[0x80]), 1);
+    }
+}
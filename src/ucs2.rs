@@ -0,0 +1,42 @@
+//! Helpers for the little-endian UCS-2 strings UEFI uses everywhere
+
+use alloc::string::String;
+use core::char::{REPLACEMENT_CHARACTER, decode_utf16};
+
+/// The UCS-2 code units in `bytes`, stopping at a null terminator if there is one
+pub fn units(bytes: &[u8]) -> impl Iterator<Item = u16> + '_ {
+    bytes
+        .as_chunks::<2>()
+        .0
+        .iter()
+        .map(|unit| u16::from_le_bytes(*unit))
+        .take_while(|unit| *unit != 0)
+}
+
+/// Decodes `bytes` up to the null terminator, replacing invalid characters
+pub fn decode_lossy(bytes: &[u8]) -> String {
+    decode_utf16(units(bytes))
+        .map(|c| c.unwrap_or(REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+/// The length in bytes of a null-terminated string at the start of `bytes`, including the
+/// terminator, or `None` if it isn't terminated
+pub fn terminated_len(bytes: &[u8]) -> Option<usize> {
+    bytes
+        .as_chunks::<2>()
+        .0
+        .iter()
+        .position(|unit| *unit == [0, 0])
+        .map(|i| (i + 1) * 2)
+}
+
+/// Whether `bytes` is plausibly printable UCS-2 text, optionally null-terminated
+pub fn is_text(bytes: &[u8]) -> bool {
+    if bytes.is_empty() || !bytes.len().is_multiple_of(2) {
+        return false;
+    }
+    let terminator = terminated_len(bytes).unwrap_or(bytes.len());
+    terminator == bytes.len()
+        && decode_utf16(units(bytes)).all(|c| c.is_ok_and(|c| !c.is_control() || c == '\t'))
+}
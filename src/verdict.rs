@@ -0,0 +1,161 @@
+//! The verdict the driver leaves in a volatile UEFI variable, so the app (`--show-verdict`) or the
+//! OS can see what the verification found earlier in the boot.
+//!
+//! All integers are big-endian, like [`crate::sealed_blob`]:
+//!
+//! | Size | Field |
+//! |------|-------|
+//! | 4 | Magic, `VRDT` in ASCII |
+//! | 1 | Format version, currently 1 |
+//! | 2 | Number of entries |
+//!
+//! Followed by each entry:
+//!
+//! | Size | Field |
+//! |------|-------|
+//! | 1 + n | Length of the finding code, followed by the code in ASCII |
+//! | 4 | PCR index, or `0xFFFFFFFF` if the finding isn't about a PCR |
+//!
+//! Messages are left out to keep the variable small. The codes are stable, so a newer driver's
+//! verdict can still be read, just without the severity of codes this version doesn't know.
+
+use alloc::{string::String, vec::Vec};
+use core::fmt;
+
+use uefi::{
+    CStr16, cstr16, guid,
+    runtime::{self, VariableAttributes, VariableVendor},
+};
+
+use crate::findings::{FindingCode, Findings, Severity, lookup};
+
+pub const MAGIC: [u8; 4] = *b"VRDT";
+pub const VERSION: u8 = 1;
+
+pub const VERDICT_VARIABLE_NAME: &CStr16 = cstr16!("UefiTpm2Verdict");
+pub const VERDICT_VARIABLE_VENDOR: VariableVendor =
+    VariableVendor(guid!("5d0b3c9e-8f3a-4c1e-9a57-2f6b1e0c7d42"));
+
+const NO_PCR: u32 = u32::MAX;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerdictEntry {
+    pub code: String,
+    pub pcr_index: Option<u32>,
+}
+
+impl VerdictEntry {
+    /// `None` for codes this version doesn't know
+    pub fn finding_code(&self) -> Option<&'static FindingCode> {
+        lookup(&self.code)
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Verdict {
+    pub entries: Vec<VerdictEntry>,
+}
+
+impl Verdict {
+    pub fn from_findings(findings: &Findings) -> Self {
+        Self {
+            entries: findings
+                .iter()
+                .map(|finding| VerdictEntry {
+                    code: finding.code.code().into(),
+                    pcr_index: finding.pcr_index,
+                })
+                .collect(),
+        }
+    }
+
+    /// Out of the entries with known codes
+    pub fn worst_severity(&self) -> Option<Severity> {
+        self.entries
+            .iter()
+            .filter_map(|entry| Some(entry.finding_code()?.severity()))
+            .max()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    BadMagic,
+    UnsupportedVersion(u8),
+    Truncated,
+    TrailingData,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BadMagic => f.write_str("not a verdict"),
+            Self::UnsupportedVersion(version) => write!(f, "unsupported verdict version {version}"),
+            Self::Truncated => f.write_str("verdict is truncated"),
+            Self::TrailingData => f.write_str("unexpected data after verdict"),
+        }
+    }
+}
+
+pub fn serialize_verdict(verdict: &Verdict) -> Vec<u8> {
+    let mut data = Vec::new();
+    data.extend_from_slice(&MAGIC);
+    data.push(VERSION);
+    let count = u16::try_from(verdict.entries.len()).unwrap_or(u16::MAX);
+    data.extend_from_slice(&count.to_be_bytes());
+    for entry in &verdict.entries[..count as usize] {
+        let code = &entry.code.as_bytes()[..entry.code.len().min(u8::MAX as usize)];
+        data.push(code.len() as u8);
+        data.extend_from_slice(code);
+        data.extend_from_slice(&entry.pcr_index.unwrap_or(NO_PCR).to_be_bytes());
+    }
+    data
+}
+
+pub fn deserialize_verdict(data: &[u8]) -> Result<Verdict, ParseError> {
+    let (magic, rest) = data.split_first_chunk::<4>().ok_or(ParseError::Truncated)?;
+    if *magic != MAGIC {
+        return Err(ParseError::BadMagic);
+    }
+    let (version, rest) = rest.split_first().ok_or(ParseError::Truncated)?;
+    if *version != VERSION {
+        return Err(ParseError::UnsupportedVersion(*version));
+    }
+    let (count, mut rest) = rest.split_first_chunk::<2>().ok_or(ParseError::Truncated)?;
+    let mut entries = Vec::new();
+    for _ in 0..u16::from_be_bytes(*count) {
+        let (len, tail) = rest.split_first().ok_or(ParseError::Truncated)?;
+        let (code, tail) = tail
+            .split_at_checked(*len as usize)
+            .ok_or(ParseError::Truncated)?;
+        let (pcr_index, tail) = tail.split_first_chunk::<4>().ok_or(ParseError::Truncated)?;
+        rest = tail;
+        let pcr_index = u32::from_be_bytes(*pcr_index);
+        entries.push(VerdictEntry {
+            code: String::from_utf8_lossy(code).into(),
+            pcr_index: (pcr_index != NO_PCR).then_some(pcr_index),
+        });
+    }
+    if !rest.is_empty() {
+        return Err(ParseError::TrailingData);
+    }
+    Ok(Verdict { entries })
+}
+
+/// Saves the verdict for the rest of this boot. It's volatile so a stale verdict from an earlier
+/// boot can't be mistaken for this one's.
+pub fn save_verdict(verdict: &Verdict) -> uefi::Result {
+    runtime::set_variable(
+        VERDICT_VARIABLE_NAME,
+        &VERDICT_VARIABLE_VENDOR,
+        VariableAttributes::BOOTSERVICE_ACCESS | VariableAttributes::RUNTIME_ACCESS,
+        &serialize_verdict(verdict),
+    )
+}
+
+/// The raw verdict variable, if the driver ran this boot
+pub fn load_verdict() -> uefi::Result<Vec<u8>> {
+    let (data, _attributes) =
+        runtime::get_variable_boxed(VERDICT_VARIABLE_NAME, &VERDICT_VARIABLE_VENDOR)?;
+    Ok(data.into_vec())
+}
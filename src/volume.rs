@@ -0,0 +1,155 @@
+//! Picking the volume files are read from and written to, so the app can be booted from read-only
+//! media and still use a USB stick. A volume is selected by its index in [`list_volumes`], its
+//! label, or the start of its device path as text, like `PciRoot(0x0)/Pci(0x1D,0x0)/USB(0x1,0x0)`.
+
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt;
+
+use uefi::{
+    Handle, Identify, Status,
+    boot::{self, OpenProtocolAttributes, OpenProtocolParams, ScopedProtocol, SearchType},
+    fs::FileSystem,
+    proto::{
+        device_path::{
+            DevicePath,
+            text::{AllowShortcuts, DisplayOnly},
+        },
+        media::{
+            file::{File, FileSystemVolumeLabel},
+            fs::SimpleFileSystem,
+        },
+    },
+};
+
+/// A volume as listed for the user
+#[derive(Debug, Clone)]
+pub struct VolumeInfo {
+    pub handle: Handle,
+    /// `None` if there's no media in the drive, or the label couldn't be read
+    pub label: Option<String>,
+    pub device_path: Option<String>,
+}
+
+impl fmt::Display for VolumeInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.label {
+            Some(label) if label.is_empty() => f.write_str("(no label)")?,
+            Some(label) => write!(f, "{label:?}")?,
+            None => f.write_str("(no media)")?,
+        }
+        match &self.device_path {
+            Some(device_path) => write!(f, " {device_path}"),
+            None => f.write_str(" (unknown device path)"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VolumeError {
+    /// Nothing matched the selector
+    NotFound,
+    /// The volume matched, but there's no media in the drive
+    NoMedia,
+    Uefi(Status),
+}
+
+impl fmt::Display for VolumeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotFound => f.write_str("no volume matches"),
+            Self::NoMedia => f.write_str("the drive has no media in it"),
+            Self::Uefi(status) => write!(f, "UEFI error: {status:?}"),
+        }
+    }
+}
+
+impl From<uefi::Error> for VolumeError {
+    fn from(error: uefi::Error) -> Self {
+        match error.status() {
+            Status::NO_MEDIA | Status::MEDIA_CHANGED => Self::NoMedia,
+            status => Self::Uefi(status),
+        }
+    }
+}
+
+fn open_file_system(handle: Handle) -> Result<ScopedProtocol<SimpleFileSystem>, VolumeError> {
+    Ok(boot::open_protocol_exclusive::<SimpleFileSystem>(handle)?)
+}
+
+fn read_label(handle: Handle) -> Result<String, VolumeError> {
+    let mut root = open_file_system(handle)?.open_volume()?;
+    let label = root.get_boxed_info::<FileSystemVolumeLabel>()?;
+    Ok(label.volume_label().to_string())
+}
+
+fn read_device_path(handle: Handle) -> Option<String> {
+    // Opening it exclusively would disconnect the drivers using it
+    // SAFETY: the handle and its device path outlive this function
+    let device_path = unsafe {
+        boot::open_protocol::<DevicePath>(
+            OpenProtocolParams {
+                handle,
+                agent: boot::image_handle(),
+                controller: None,
+            },
+            OpenProtocolAttributes::GetProtocol,
+        )
+    }
+    .ok()?;
+    let text = device_path
+        .to_string(DisplayOnly(false), AllowShortcuts(false))
+        .ok()?;
+    Some(text.to_string())
+}
+
+/// Every handle with a file system, in the order the firmware returns them
+pub fn list_volumes() -> Result<Vec<VolumeInfo>, VolumeError> {
+    let handles = boot::locate_handle_buffer(SearchType::ByProtocol(&SimpleFileSystem::GUID))?;
+    Ok(handles
+        .iter()
+        .map(|handle| VolumeInfo {
+            handle: *handle,
+            label: read_label(*handle).ok(),
+            device_path: read_device_path(*handle),
+        })
+        .collect())
+}
+
+/// A number is an index into [`list_volumes`]. Otherwise labels are compared case-insensitively,
+/// then device paths by prefix.
+pub fn find_volume<'v>(volumes: &'v [VolumeInfo], selector: &str) -> Option<&'v VolumeInfo> {
+    if let Ok(index) = selector.parse::<usize>() {
+        return volumes.get(index);
+    }
+    volumes
+        .iter()
+        .find(|volume| {
+            volume
+                .label
+                .as_deref()
+                .is_some_and(|label| label.eq_ignore_ascii_case(selector))
+        })
+        .or_else(|| {
+            volumes.iter().find(|volume| {
+                volume.device_path.as_deref().is_some_and(|device_path| {
+                    device_path
+                        .get(..selector.len())
+                        .is_some_and(|prefix| prefix.eq_ignore_ascii_case(selector))
+                })
+            })
+        })
+}
+
+/// Opens the volume `selector` picks. On [`VolumeError::NotFound`], show the user
+/// [`list_volumes`].
+pub fn open_volume(selector: &str) -> Result<FileSystem, VolumeError> {
+    let volumes = list_volumes()?;
+    let volume = find_volume(&volumes, selector).ok_or(VolumeError::NotFound)?;
+    let mut file_system = open_file_system(volume.handle)?;
+    // Fails here rather than on the first file access if the drive is empty
+    file_system.open_volume()?;
+    Ok(FileSystem::new(file_system))
+}